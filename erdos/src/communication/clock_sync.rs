@@ -0,0 +1,374 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::node::{Clock, NodeId};
+
+use super::{ControlMessage, ControlMessageHandler, SharedChannels};
+
+/// Number of recent probes among which the probe with the shortest round trip determines the
+/// offset.
+const FILTER_SAMPLES: usize = 8;
+/// Number of recent probes from which the skew is estimated.
+const MAX_SAMPLES: usize = 64;
+
+/// Estimated offset of the clock of another node from the clock of this node.
+///
+/// Both clocks are the system's wall clocks, which are never virtual.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClockOffset {
+    /// Nanoseconds to add to a time read from this node's clock to obtain the time on the other
+    /// node's clock.
+    pub offset_nanos: i64,
+    /// Round trip time of the probe from which the offset was estimated. The offset is off by
+    /// at most half of the round trip time.
+    pub round_trip: Duration,
+    /// Rate at which the other node's clock drifts from this node's clock, in parts per million.
+    /// Positive if the other node's clock runs faster.
+    pub skew_ppm: f64,
+    /// The time on this node's clock at which the offset was measured.
+    pub measured_at: SystemTime,
+}
+
+impl ClockOffset {
+    /// Estimates the offset at `local_time`, accounting for the drift since the offset was
+    /// measured.
+    pub fn offset_at(&self, local_time: SystemTime) -> i64 {
+        let elapsed_nanos = unix_nanos(local_time) as f64 - unix_nanos(self.measured_at) as f64;
+        self.offset_nanos + (elapsed_nanos * self.skew_ppm / 1e6) as i64
+    }
+
+    /// Converts a time read from the other node's clock to the clock of this node.
+    pub fn to_local(&self, remote_time: SystemTime) -> SystemTime {
+        // The drift over the duration of the offset itself is negligible.
+        shift(remote_time, -self.offset_at(remote_time))
+    }
+
+    /// Converts a time read from this node's clock to the clock of the other node.
+    pub fn to_remote(&self, local_time: SystemTime) -> SystemTime {
+        shift(local_time, self.offset_at(local_time))
+    }
+}
+
+/// The estimated [`ClockOffset`] of each other node.
+pub(crate) type ClockOffsets = Arc<Mutex<HashMap<NodeId, ClockOffset>>>;
+
+/// Returns the time in nanoseconds since the UNIX epoch, as sent to other nodes.
+pub(crate) fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+pub(crate) fn from_unix_nanos(nanos: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_nanos(nanos)
+}
+
+/// Converts the time `sent_at` at which `node_id` sent a message, in nanoseconds since the UNIX
+/// epoch on its clock, to the clock of this node. Returns `None` if the offset of the node's
+/// clock is not yet estimated.
+pub(crate) fn to_local_time(
+    clock_offsets: &ClockOffsets,
+    node_id: NodeId,
+    sent_at: u64,
+) -> Option<SystemTime> {
+    let offset = *clock_offsets.lock().unwrap().get(&node_id)?;
+    Some(offset.to_local(from_unix_nanos(sent_at)))
+}
+
+fn shift(time: SystemTime, nanos: i64) -> SystemTime {
+    if nanos >= 0 {
+        time + Duration::from_nanos(nanos as u64)
+    } else {
+        time - Duration::from_nanos(nanos.unsigned_abs())
+    }
+}
+
+/// Offset measured by one probe.
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    /// Time at which the reply was received, in nanoseconds since the UNIX epoch.
+    received_at: u64,
+    offset_nanos: i64,
+    round_trip_nanos: u64,
+}
+
+impl Sample {
+    /// Computes the offset from the times at which the probe was sent (`t1`) and received (`t2`),
+    /// and the reply was sent (`t3`) and received (`t4`).
+    fn new(t1: u64, t2: u64, t3: u64, t4: u64) -> Self {
+        let (t1, t2, t3, t4) = (t1 as i128, t2 as i128, t3 as i128, t4 as i128);
+        Self {
+            received_at: t4 as u64,
+            offset_nanos: (((t2 - t1) + (t3 - t4)) / 2) as i64,
+            round_trip_nanos: ((t4 - t1) - (t3 - t2)).max(0) as u64,
+        }
+    }
+}
+
+/// Estimates the offsets of the clocks of other nodes by periodically exchanging probes over the
+/// control connections, as done by NTP.
+///
+/// The offset is taken from the recent probe with the shortest round trip, which is least
+/// affected by queueing delays. The skew is the slope of a least-squares fit of the offsets
+/// measured by the probes whose round trip is at most the median round trip.
+pub(crate) struct ClockSynchronizer {
+    /// The id of the node on which the synchronizer runs.
+    node_id: NodeId,
+    /// Period with which probes are sent. Probes are not sent if zero.
+    interval: Duration,
+    /// Clock with which probes are timed.
+    clock: Clock,
+    /// Recent samples for each other node, from oldest to newest.
+    samples: HashMap<NodeId, VecDeque<Sample>>,
+    /// The estimated offsets, shared with the [`NodeHandle`](crate::node::NodeHandle).
+    offsets: ClockOffsets,
+    /// Channels to the control senders, used to send probes and replies to other nodes.
+    channels_to_nodes: SharedChannels,
+    /// Channel on which control receivers forward probes and replies, along with the time at
+    /// which they were received.
+    rx: UnboundedReceiver<(ControlMessage, SystemTime)>,
+}
+
+impl ClockSynchronizer {
+    pub(crate) fn new(
+        node_id: NodeId,
+        interval: Duration,
+        clock: Clock,
+        offsets: ClockOffsets,
+        rx: UnboundedReceiver<(ControlMessage, SystemTime)>,
+        control_handler: &ControlMessageHandler,
+    ) -> Self {
+        Self {
+            node_id,
+            interval,
+            clock,
+            samples: HashMap::new(),
+            offsets,
+            channels_to_nodes: control_handler.get_channels_to_nodes(),
+            rx,
+        }
+    }
+
+    /// Sends probes and answers the probes of other nodes until the control receivers shut
+    /// down.
+    pub(crate) async fn run(&mut self) {
+        let mut interval = if self.interval.is_zero() {
+            None
+        } else {
+            Some(self.clock.interval(self.interval))
+        };
+        loop {
+            tokio::select! {
+                _ = async { interval.as_mut().unwrap().tick().await }, if interval.is_some() => {
+                    self.send_probes();
+                }
+                msg = self.rx.recv() => match msg {
+                    Some((ControlMessage::ClockProbe(node_id, sent_at), received_at)) => {
+                        if let Some(tx) = self.channels_to_nodes.lock().unwrap().get(&node_id) {
+                            tx.send(ControlMessage::ClockReply(
+                                self.node_id,
+                                sent_at,
+                                unix_nanos(received_at),
+                                unix_nanos(self.clock.system_time()),
+                            ))
+                            .ok();
+                        }
+                    }
+                    Some((ControlMessage::ClockReply(node_id, t1, t2, t3), received_at)) => {
+                        self.on_reply(node_id, Sample::new(t1, t2, t3, unix_nanos(received_at)));
+                    }
+                    Some((msg, _)) => tracing::warn!(
+                        "Node {}: clock synchronizer received unexpected control message {:?}",
+                        self.node_id,
+                        msg
+                    ),
+                    None => return,
+                },
+            }
+        }
+    }
+
+    fn send_probes(&mut self) {
+        let sent_at = unix_nanos(self.clock.system_time());
+        for tx in self.channels_to_nodes.lock().unwrap().values() {
+            // Failed and finished nodes are not probed successfully, and keep their last offset.
+            tx.send(ControlMessage::ClockProbe(self.node_id, sent_at))
+                .ok();
+        }
+    }
+
+    fn on_reply(&mut self, node_id: NodeId, sample: Sample) {
+        let samples = self.samples.entry(node_id).or_default();
+        if samples.len() == MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+
+        let best = *samples
+            .iter()
+            .rev()
+            .take(FILTER_SAMPLES)
+            .min_by_key(|sample| sample.round_trip_nanos)
+            .unwrap();
+        let offset = ClockOffset {
+            offset_nanos: best.offset_nanos,
+            round_trip: Duration::from_nanos(best.round_trip_nanos),
+            skew_ppm: estimate_skew(samples) * 1e6,
+            measured_at: from_unix_nanos(best.received_at),
+        };
+        tracing::trace!(
+            "Node {}: estimated clock offset of node {}: {:?}",
+            self.node_id,
+            node_id,
+            offset
+        );
+        self.offsets.lock().unwrap().insert(node_id, offset);
+    }
+}
+
+/// Returns the rate at which the offset changes, estimated from the samples whose round trip is
+/// at most the median round trip. Returns 0 if there are not enough samples.
+fn estimate_skew(samples: &VecDeque<Sample>) -> f64 {
+    let mut round_trips: Vec<u64> = samples.iter().map(|s| s.round_trip_nanos).collect();
+    round_trips.sort_unstable();
+    let median_round_trip = round_trips[round_trips.len() / 2];
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .filter(|sample| sample.round_trip_nanos <= median_round_trip)
+        .map(|sample| {
+            (
+                sample.received_at.saturating_sub(samples[0].received_at) as f64,
+                sample.offset_nanos as f64,
+            )
+        })
+        .collect();
+    if points.len() < 2 {
+        return 0.0;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    if variance == 0.0 {
+        0.0
+    } else {
+        covariance / variance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the sample of a probe sent at `t1` to a node whose clock is ahead by `offset`,
+    /// which took `delay` in each direction and was answered after 10ns.
+    fn probe(t1: u64, offset: i64, delay: u64) -> Sample {
+        let t2 = (t1 + delay) as i64 + offset;
+        let t3 = t2 + 10;
+        let t4 = t1 + delay + 10 + delay;
+        Sample::new(t1, t2 as u64, t3 as u64, t4)
+    }
+
+    #[test]
+    fn test_sample() {
+        let sample = Sample::new(0, 150, 160, 20);
+        assert_eq!(sample.offset_nanos, 145);
+        assert_eq!(sample.round_trip_nanos, 10);
+        assert_eq!(sample.received_at, 20);
+
+        let sample = probe(1_000, -500, 40);
+        assert_eq!(sample.offset_nanos, -500);
+        assert_eq!(sample.round_trip_nanos, 80);
+    }
+
+    #[test]
+    fn test_sample_negative_round_trip() {
+        // The reply took less time than the other node took to answer, which happens if a clock
+        // jumps.
+        let sample = Sample::new(0, 100, 200, 50);
+        assert_eq!(sample.round_trip_nanos, 0);
+    }
+
+    #[test]
+    fn test_estimate_skew() {
+        // The other clock runs 100 ppm faster.
+        let samples: VecDeque<Sample> = (0..10)
+            .map(|i| probe(i * 1_000_000, 5_000 + (i * 100) as i64, 50))
+            .collect();
+        let skew = estimate_skew(&samples);
+        assert!((skew - 1e-4).abs() < 1e-6, "skew {}", skew);
+    }
+
+    #[test]
+    fn test_estimate_skew_ignores_slow_probes() {
+        let mut samples: VecDeque<Sample> =
+            (0..10).map(|i| probe(i * 1_000_000, 5_000, 50)).collect();
+        // A probe delayed on the way to the other node measures a larger offset.
+        samples[9] = Sample::new(9_000_000, 9_100_000 + 5_000, 9_100_010 + 5_000, 9_100_060);
+        assert_eq!(estimate_skew(&samples), 0.0);
+    }
+
+    #[test]
+    fn test_estimate_skew_not_enough_samples() {
+        let samples: VecDeque<Sample> = vec![probe(0, 5_000, 50)].into_iter().collect();
+        assert_eq!(estimate_skew(&samples), 0.0);
+
+        // Probes received at the same time do not determine a slope.
+        let samples: VecDeque<Sample> = vec![probe(0, 5_000, 50), probe(0, 6_000, 50)]
+            .into_iter()
+            .collect();
+        assert_eq!(estimate_skew(&samples), 0.0);
+    }
+
+    #[test]
+    fn test_clock_offset() {
+        let measured_at = from_unix_nanos(1_000_000_000);
+        let offset = ClockOffset {
+            offset_nanos: -2_000,
+            round_trip: Duration::from_nanos(100),
+            skew_ppm: 0.0,
+            measured_at,
+        };
+        let remote_time = from_unix_nanos(1_000_000_000 - 2_000);
+        assert_eq!(offset.to_remote(measured_at), remote_time);
+        assert_eq!(offset.to_local(remote_time), measured_at);
+
+        // The other clock gains 1µs per second, so it catches up after 2 seconds.
+        let offset = ClockOffset {
+            skew_ppm: 1.0,
+            ..offset
+        };
+        let later = measured_at + Duration::from_secs(2);
+        assert_eq!(offset.offset_at(later), 0);
+        assert_eq!(offset.to_remote(later), later);
+    }
+
+    #[test]
+    fn test_to_local_time() {
+        let clock_offsets = ClockOffsets::default();
+        assert_eq!(to_local_time(&clock_offsets, 1, 5_000), None);
+
+        clock_offsets.lock().unwrap().insert(
+            1,
+            ClockOffset {
+                offset_nanos: 1_000,
+                round_trip: Duration::from_nanos(100),
+                skew_ppm: 0.0,
+                measured_at: from_unix_nanos(0),
+            },
+        );
+        assert_eq!(
+            to_local_time(&clock_offsets, 1, 5_000),
+            Some(from_unix_nanos(4_000))
+        );
+    }
+}
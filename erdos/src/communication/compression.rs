@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::{BufMut, BytesMut};
+use serde::{Deserialize, Serialize};
+
+use crate::dataflow::stream::StreamId;
+
+use super::CodecError;
+
+/// Compression applied to the messages sent on a stream to other nodes.
+///
+/// Messages between operators on the same node are never compressed. The compression of a stream
+/// defaults to the [`OperatorConfig::compression`](crate::dataflow::OperatorConfig::compression)
+/// of the operator writing to it, and can be overridden with
+/// [`Stream::set_compression`](crate::dataflow::Stream::set_compression).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    None,
+    /// Fast compression suited for data which must arrive with low latency.
+    Lz4,
+    /// Zstandard compression with a level from 1 (fastest) to 22 (smallest); `0` selects the
+    /// default level. Suited for highly compressible data such as occupancy grids and logs.
+    Zstd(i32),
+}
+
+impl Compression {
+    pub(crate) fn is_none(&self) -> bool {
+        *self == Compression::None
+    }
+
+    pub(crate) fn compress(&self, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+            Compression::Zstd(level) => Ok(zstd::bulk::compress(data, *level)?),
+        }
+    }
+
+    /// Decompresses a message, failing if it would exceed `max_size` bytes once decompressed.
+    ///
+    /// The limit is enforced while decompressing, so that a small frame cannot expand into an
+    /// arbitrarily large allocation.
+    pub(crate) fn decompress(
+        &self,
+        data: &[u8],
+        max_size: Option<usize>,
+    ) -> Result<BytesMut, CodecError> {
+        let max_size = max_size.unwrap_or(usize::MAX);
+        match self {
+            Compression::None => {
+                check_decompressed_size(data.len(), max_size)?;
+                Ok(BytesMut::from(data))
+            }
+            Compression::Lz4 => {
+                // `compress_prepend_size` prefixes the data with its size as a little-endian u32.
+                if data.len() < 4 {
+                    return Err(CodecError::CompressionError(
+                        "lz4 message is missing its size".to_string(),
+                    ));
+                }
+                let (size, compressed) = data.split_at(4);
+                let size = u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize;
+                check_decompressed_size(size, max_size)?;
+                let mut decompressed = BytesMut::new();
+                decompressed.resize(size, 0);
+                let decompressed_size = lz4_flex::decompress_into(compressed, &mut decompressed)
+                    .map_err(|e| CodecError::CompressionError(e.to_string()))?;
+                if decompressed_size != size {
+                    return Err(CodecError::CompressionError(format!(
+                        "lz4 message decompressed to {} bytes, expected {} bytes",
+                        decompressed_size, size
+                    )));
+                }
+                Ok(decompressed)
+            }
+            Compression::Zstd(_) => {
+                let decoder = zstd::stream::read::Decoder::new(data)?;
+                let mut writer = BytesMut::new().writer();
+                io::copy(
+                    &mut decoder.take(max_size.saturating_add(1) as u64),
+                    &mut writer,
+                )?;
+                let decompressed = writer.into_inner();
+                check_decompressed_size(decompressed.len(), max_size)?;
+                Ok(decompressed)
+            }
+        }
+    }
+}
+
+fn check_decompressed_size(size: usize, max_size: usize) -> Result<(), CodecError> {
+    if size > max_size {
+        Err(CodecError::CompressionError(format!(
+            "message decompresses to more than the maximum frame size of {} bytes",
+            max_size
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::None
+    }
+}
+
+/// Statistics on the messages sent and received on a stream over connections to other nodes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StreamStatistics {
+    pub messages_sent: u64,
+    /// The size of the sent messages once serialized.
+    pub bytes_sent: u64,
+    /// The size of the sent messages once serialized and compressed.
+    pub compressed_bytes_sent: u64,
+    pub messages_received: u64,
+    /// The size of the received messages once decompressed.
+    pub bytes_received: u64,
+    /// The size of the received messages as received from the network.
+    pub compressed_bytes_received: u64,
+    /// The number of received messages whose latency was measured. Latencies are only measured
+    /// once the offset of the sending node's clock is estimated.
+    pub latency_samples: u64,
+    /// The sum of the times the received messages took from being sent to being received,
+    /// corrected for the offset of the sending node's clock.
+    pub total_latency: Duration,
+    /// The longest time a received message took from being sent to being received.
+    pub max_latency: Duration,
+    /// The number of messages of a [best-effort](crate::communication::Delivery) stream which
+    /// were not received, because they were lost or arrived after a newer message.
+    pub messages_lost: u64,
+}
+
+impl StreamStatistics {
+    /// Returns the ratio between the serialized and the compressed size of the sent messages,
+    /// or `None` if no messages were sent.
+    pub fn send_compression_ratio(&self) -> Option<f64> {
+        ratio(self.bytes_sent, self.compressed_bytes_sent)
+    }
+
+    /// Returns the ratio between the decompressed and the compressed size of the received
+    /// messages, or `None` if no messages were received.
+    pub fn receive_compression_ratio(&self) -> Option<f64> {
+        ratio(self.bytes_received, self.compressed_bytes_received)
+    }
+
+    /// Returns the average time the received messages took from being sent to being received,
+    /// or `None` if no latency was measured.
+    pub fn mean_latency(&self) -> Option<Duration> {
+        if self.latency_samples == 0 {
+            None
+        } else {
+            Some(Duration::from_nanos(
+                (self.total_latency.as_nanos() / self.latency_samples as u128) as u64,
+            ))
+        }
+    }
+}
+
+fn ratio(bytes: u64, compressed_bytes: u64) -> Option<f64> {
+    if compressed_bytes == 0 {
+        None
+    } else {
+        Some(bytes as f64 / compressed_bytes as f64)
+    }
+}
+
+/// The [`StreamStatistics`] of each stream, shared by the codecs of all connections of a node.
+pub(crate) type StreamStatisticsMap = Arc<Mutex<HashMap<StreamId, StreamStatistics>>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPRESSIONS: [Compression; 5] = [
+        Compression::None,
+        Compression::Lz4,
+        Compression::Zstd(0),
+        Compression::Zstd(1),
+        Compression::Zstd(22),
+    ];
+
+    /// Returns data which compresses well, like an occupancy grid.
+    fn repetitive_data() -> Vec<u8> {
+        (0..10_000).map(|i| (i / 100 % 3) as u8).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for compression in COMPRESSIONS.iter() {
+            for data in [repetitive_data(), vec![7], vec![]].iter() {
+                let compressed = compression.compress(data).unwrap();
+                assert_eq!(
+                    &compression.decompress(&compressed, None).unwrap()[..],
+                    &data[..],
+                    "{:?}",
+                    compression
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_compresses_repetitive_data() {
+        let data = repetitive_data();
+        for compression in COMPRESSIONS.iter().filter(|c| !c.is_none()) {
+            let compressed = compression.compress(&data).unwrap();
+            assert!(
+                compressed.len() < data.len() / 10,
+                "{:?} compressed {} bytes to {} bytes",
+                compression,
+                data.len(),
+                compressed.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_corrupted_data() {
+        let data = repetitive_data();
+        for compression in COMPRESSIONS.iter().filter(|c| !c.is_none()) {
+            let mut compressed = compression.compress(&data).unwrap();
+            compressed.truncate(compressed.len() / 2);
+            assert!(
+                compression.decompress(&compressed, None).is_err(),
+                "{:?}",
+                compression
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_size() {
+        let data = repetitive_data();
+        for compression in COMPRESSIONS.iter() {
+            let compressed = compression.compress(&data).unwrap();
+            assert!(
+                compression
+                    .decompress(&compressed, Some(data.len()))
+                    .is_ok(),
+                "{:?}",
+                compression
+            );
+            assert!(
+                compression
+                    .decompress(&compressed, Some(data.len() - 1))
+                    .is_err(),
+                "{:?}",
+                compression
+            );
+        }
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        let mut stats = StreamStatistics::default();
+        assert_eq!(stats.send_compression_ratio(), None);
+        assert_eq!(stats.receive_compression_ratio(), None);
+
+        stats.bytes_sent = 1000;
+        stats.compressed_bytes_sent = 250;
+        stats.bytes_received = 300;
+        stats.compressed_bytes_received = 300;
+        assert_eq!(stats.send_compression_ratio(), Some(4.0));
+        assert_eq!(stats.receive_compression_ratio(), Some(1.0));
+    }
+}
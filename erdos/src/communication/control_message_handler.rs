@@ -1,11 +1,18 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
 
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
-use crate::node::NodeId;
+use crate::{dataflow::stream::StreamId, node::NodeId, OperatorId};
 
 use super::{CommunicationError, ControlMessage};
 
+/// Channels to a task per other node, shared with the tasks which must also reach nodes that
+/// join later.
+pub(crate) type SharedChannels = Arc<Mutex<HashMap<NodeId, UnboundedSender<ControlMessage>>>>;
+
 // TODO: update `channels_to_nodes` for fault tolerance in case nodes to go down.
 pub struct ControlMessageHandler {
     /// Sender to clone so other tasks can send messages to `self.rx`.
@@ -15,8 +22,9 @@ pub struct ControlMessageHandler {
     channels_to_control_senders: HashMap<NodeId, UnboundedSender<ControlMessage>>,
     channels_to_control_receivers: HashMap<NodeId, UnboundedSender<ControlMessage>>,
     channels_to_data_senders: HashMap<NodeId, UnboundedSender<ControlMessage>>,
-    channels_to_data_receivers: HashMap<NodeId, UnboundedSender<ControlMessage>>,
-    channels_to_nodes: HashMap<NodeId, UnboundedSender<ControlMessage>>,
+    channels_to_data_receivers: SharedChannels,
+    channels_to_nodes: SharedChannels,
+    channels_to_operators: HashMap<OperatorId, UnboundedSender<ControlMessage>>,
 }
 
 #[allow(dead_code)]
@@ -29,8 +37,9 @@ impl ControlMessageHandler {
             channels_to_control_senders: HashMap::new(),
             channels_to_control_receivers: HashMap::new(),
             channels_to_data_senders: HashMap::new(),
-            channels_to_data_receivers: HashMap::new(),
-            channels_to_nodes: HashMap::new(),
+            channels_to_data_receivers: SharedChannels::default(),
+            channels_to_nodes: SharedChannels::default(),
+            channels_to_operators: HashMap::new(),
         }
     }
 
@@ -151,6 +160,8 @@ impl ControlMessageHandler {
     ) {
         if self
             .channels_to_data_receivers
+            .lock()
+            .unwrap()
             .insert(node_id, tx)
             .is_some()
         {
@@ -166,7 +177,12 @@ impl ControlMessageHandler {
         node_id: NodeId,
         msg: ControlMessage,
     ) -> Result<(), CommunicationError> {
-        match self.channels_to_data_receivers.get_mut(&node_id) {
+        match self
+            .channels_to_data_receivers
+            .lock()
+            .unwrap()
+            .get(&node_id)
+        {
             Some(tx) => tx.send(msg).map_err(CommunicationError::from),
             None => Err(CommunicationError::Disconnected),
         }
@@ -176,14 +192,14 @@ impl ControlMessageHandler {
         &mut self,
         msg: ControlMessage,
     ) -> Result<(), CommunicationError> {
-        for tx in self.channels_to_data_receivers.values_mut() {
+        for tx in self.channels_to_data_receivers.lock().unwrap().values() {
             tx.send(msg.clone()).map_err(CommunicationError::from)?;
         }
         Ok(())
     }
 
     pub fn add_channel_to_node(&mut self, node_id: NodeId, tx: UnboundedSender<ControlMessage>) {
-        self.channels_to_nodes.insert(node_id, tx);
+        self.channels_to_nodes.lock().unwrap().insert(node_id, tx);
     }
 
     pub fn send_to_node(
@@ -191,23 +207,59 @@ impl ControlMessageHandler {
         node_id: NodeId,
         msg: ControlMessage,
     ) -> Result<(), CommunicationError> {
-        match self.channels_to_nodes.get_mut(&node_id) {
+        match self.channels_to_nodes.lock().unwrap().get(&node_id) {
             Some(tx) => tx.send(msg).map_err(CommunicationError::from),
             None => Err(CommunicationError::Disconnected),
         }
     }
 
     pub fn broadcast_to_nodes(&mut self, msg: ControlMessage) -> Result<(), CommunicationError> {
-        for tx in self.channels_to_nodes.values_mut() {
+        for tx in self.channels_to_nodes.lock().unwrap().values() {
             tx.send(msg.clone()).map_err(CommunicationError::from)?;
         }
         Ok(())
     }
 
+    pub fn add_channel_to_operator(
+        &mut self,
+        operator_id: OperatorId,
+        tx: UnboundedSender<ControlMessage>,
+    ) {
+        if self.channels_to_operators.insert(operator_id, tx).is_some() {
+            tracing::error!(
+                "ControlMessageHandler: overwrote channel to operator {}",
+                operator_id
+            );
+        }
+    }
+
+    pub fn send_to_operator(
+        &mut self,
+        operator_id: OperatorId,
+        msg: ControlMessage,
+    ) -> Result<(), CommunicationError> {
+        match self.channels_to_operators.get_mut(&operator_id) {
+            Some(tx) => tx.send(msg).map_err(CommunicationError::from),
+            None => Err(CommunicationError::Disconnected),
+        }
+    }
+
     pub fn get_channel_to_handler(&self) -> UnboundedSender<ControlMessage> {
         self.tx.clone()
     }
 
+    /// Returns the channels used to send control messages to other nodes, including the nodes
+    /// which join later.
+    pub(crate) fn get_channels_to_nodes(&self) -> SharedChannels {
+        Arc::clone(&self.channels_to_nodes)
+    }
+
+    /// Returns the channels used to send control messages to data receivers, including the
+    /// receivers of the nodes which join later.
+    pub(crate) fn get_channels_to_data_receivers(&self) -> SharedChannels {
+        Arc::clone(&self.channels_to_data_receivers)
+    }
+
     pub async fn read(&mut self) -> Result<ControlMessage, CommunicationError> {
         self.rx.recv().await.ok_or(CommunicationError::Disconnected)
     }
@@ -218,13 +270,37 @@ impl ControlMessageHandler {
     /// Note: this may affect message order.
     pub async fn read_all_operators_initialized_on_node_msg(
         &mut self,
-    ) -> Result<NodeId, CommunicationError> {
+    ) -> Result<(NodeId, HashMap<OperatorId, usize>), CommunicationError> {
+        let mut read_msgs = Vec::new();
+        let mut result = None;
+        while result.is_none() {
+            match self.read().await {
+                Ok(ControlMessage::AllOperatorsInitializedOnNode(node_id, num_deadlines)) => {
+                    result = Some(Ok((node_id, num_deadlines)))
+                }
+                Ok(msg) => read_msgs.push(msg),
+                Err(e) => result = Some(Err(e)),
+            };
+        }
+        // Re-enqueue read messages.
+        for msg in read_msgs {
+            self.tx.send(msg).map_err(CommunicationError::from)?;
+        }
+        result.unwrap()
+    }
+
+    /// Reads messages until a `ControlMessage::OperatorInitialized` is received
+    /// without consuming any other messages types.
+    /// Note: this may affect message order.
+    pub async fn read_operator_initialized_msg(
+        &mut self,
+    ) -> Result<(OperatorId, usize), CommunicationError> {
         let mut read_msgs = Vec::new();
         let mut result = None;
         while result.is_none() {
             match self.read().await {
-                Ok(ControlMessage::AllOperatorsInitializedOnNode(node_id)) => {
-                    result = Some(Ok(node_id))
+                Ok(ControlMessage::OperatorInitialized(operator_id, num_deadlines)) => {
+                    result = Some(Ok((operator_id, num_deadlines)))
                 }
                 Ok(msg) => read_msgs.push(msg),
                 Err(e) => result = Some(Err(e)),
@@ -237,6 +313,34 @@ impl ControlMessageHandler {
         result.unwrap()
     }
 
+    /// Reads messages until a `ControlMessage::StreamTypes` is received
+    /// without consuming any other messages types.
+    /// Note: this may affect message order.
+    pub async fn read_stream_types_msg(
+        &mut self,
+    ) -> Result<(NodeId, BTreeMap<StreamId, String>), CommunicationError> {
+        let mut read_msgs = Vec::new();
+        let mut result = None;
+        while result.is_none() {
+            match self.read().await {
+                Ok(ControlMessage::StreamTypes(node_id, stream_types)) => {
+                    result = Some(Ok((node_id, stream_types)))
+                }
+                Ok(msg) => read_msgs.push(msg),
+                Err(e) => result = Some(Err(e)),
+            };
+        }
+        // Re-enqueue read messages.
+        for msg in read_msgs {
+            self.tx.send(msg).map_err(CommunicationError::from)?;
+        }
+        result.unwrap()
+    }
+
+    /// Reads messages until a message exchanged while setting up the communication layer
+    /// (e.g., `ControlMessage::DataSenderInitialized`) is received without consuming any other
+    /// messages types.
+    /// Note: this may affect message order.
     pub async fn read_sender_or_receiver_initialized(
         &mut self,
     ) -> Result<ControlMessage, CommunicationError> {
@@ -248,7 +352,8 @@ impl ControlMessageHandler {
                     ControlMessage::ControlSenderInitialized(_)
                     | ControlMessage::ControlReceiverInitialized(_)
                     | ControlMessage::DataSenderInitialized(_)
-                    | ControlMessage::DataReceiverInitialized(_) => result = Some(Ok(control_msg)),
+                    | ControlMessage::DataReceiverInitialized(_)
+                    | ControlMessage::GraphFingerprint(_, _) => result = Some(Ok(control_msg)),
                     _ => read_msgs.push(control_msg),
                 },
                 Err(e) => result = Some(Err(e)),
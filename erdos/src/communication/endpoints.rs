@@ -1,45 +1,112 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{fmt::Debug, sync::Arc, time::SystemTime};
 
 use futures::FutureExt;
 use tokio::{sync::mpsc, task::unconstrained};
 
 use crate::{
-    communication::{CommunicationError, InterProcessMessage, Serializable, TryRecvError},
+    communication::{
+        CommunicationError, Compression, Delivery, InterProcessMessage, Serializable, TryRecvError,
+        UdpSender,
+    },
     dataflow::stream::StreamId,
 };
 
+/// Priority with which the messages of a stream are sent to other nodes.
+///
+/// Each node keeps a queue per priority for each other node, and sends the pending messages of
+/// higher priority first, so that latency-critical streams do not queue behind bulk data sent on
+/// the same connection. The messages of a stream are always sent in order. A message of higher
+/// priority still waits for the message which is being written to the connection, while large
+/// bulk messages are written in fragments between which other messages are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// For large messages, such as images or point clouds.
+    Bulk,
+    Normal,
+    /// For small latency-critical messages, such as control commands. Messages are flushed
+    /// without waiting to be batched with other messages.
+    High,
+}
+
+impl Priority {
+    /// All priorities, from highest to lowest.
+    pub(crate) const ALL: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Bulk];
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::Normal
+    }
+}
+
+/// Options for sending the messages of a stream to other nodes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SendOptions {
+    pub compression: Compression,
+    /// Whether messages are flushed immediately instead of being batched with other messages.
+    pub low_latency: bool,
+    pub priority: Priority,
+    pub delivery: Delivery,
+}
+
+/// A message sent to an operator running in the same process, along with the time at which
+/// another node sent it on the clock of this node if the message was received from another node.
+pub(crate) type InterThreadMessage<D> = (D, Option<SystemTime>);
+
 /// Endpoint to be used to send messages between operators.
 #[derive(Clone)]
 pub enum SendEndpoint<D: Clone + Send + Debug> {
     /// Send messages to an operator running in the same process.
-    InterThread(mpsc::UnboundedSender<D>),
+    InterThread(mpsc::UnboundedSender<InterThreadMessage<D>>),
     /// Send messages to operators running on a different node.
     /// Data is first sent to [`DataSender`](crate::communication::senders::DataSender)
-    /// which encodes and sends the message on a TCP stream.
-    InterProcess(StreamId, mpsc::UnboundedSender<InterProcessMessage>),
+    /// which compresses, encodes and sends the message on a TCP stream.
+    InterProcess(
+        StreamId,
+        SendOptions,
+        mpsc::UnboundedSender<InterProcessMessage>,
+    ),
+    /// Send messages to operators running on other nodes as UDP datagrams.
+    BestEffort(Arc<UdpSender>),
 }
 
 /// Zero-copy implementation of the endpoint.
 /// Because we [`Arc`], the message isn't copied when sent between endpoints within the node.
 impl<D: 'static + Serializable + Send + Sync + Debug> SendEndpoint<Arc<D>> {
     pub fn send(&mut self, msg: Arc<D>) -> Result<(), CommunicationError> {
+        self.send_received(msg, None)
+    }
+
+    /// Sends a message which was received from another node at `sent_at`. Only operators on
+    /// this node learn when the message was sent, as it is sent anew to other nodes.
+    pub(crate) fn send_received(
+        &mut self,
+        msg: Arc<D>,
+        sent_at: Option<SystemTime>,
+    ) -> Result<(), CommunicationError> {
         match self {
-            Self::InterThread(sender) => sender.send(msg).map_err(CommunicationError::from),
-            Self::InterProcess(stream_id, sender) => sender
-                .send(InterProcessMessage::new_deserialized(msg, *stream_id))
+            Self::InterThread(sender) => sender
+                .send((msg, sent_at))
+                .map_err(CommunicationError::from),
+            Self::InterProcess(stream_id, options, sender) => sender
+                .send(InterProcessMessage::new_deserialized(
+                    msg, *stream_id, *options,
+                ))
                 .map_err(CommunicationError::from),
+            Self::BestEffort(sender) => sender.send(&*msg),
         }
     }
 }
 
 /// Endpoint to be used to receive messages.
 pub enum RecvEndpoint<D: Clone + Send + Debug> {
-    InterThread(mpsc::UnboundedReceiver<D>),
+    InterThread(mpsc::UnboundedReceiver<InterThreadMessage<D>>),
 }
 
 impl<D: Clone + Send + Debug> RecvEndpoint<D> {
-    /// Async read of a new message.
-    pub async fn read(&mut self) -> Result<D, CommunicationError> {
+    /// Async read of a new message, along with the time at which another node sent it on the
+    /// clock of this node, if the message was received from another node.
+    pub async fn read(&mut self) -> Result<InterThreadMessage<D>, CommunicationError> {
         match self {
             Self::InterThread(receiver) => receiver
                 .recv()
@@ -53,7 +120,7 @@ impl<D: Clone + Send + Debug> RecvEndpoint<D> {
         match self {
             // See https://github.com/tokio-rs/tokio/issues/3350.
             Self::InterThread(rx) => match unconstrained(rx.recv()).now_or_never() {
-                Some(Some(msg)) => Ok(msg),
+                Some(Some((msg, _))) => Ok(msg),
                 Some(None) => Err(TryRecvError::Disconnected),
                 None => Err(TryRecvError::Empty),
             },
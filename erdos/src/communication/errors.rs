@@ -18,6 +18,8 @@ pub enum CommunicationError {
     BincodeError(bincode::Error),
     /// Failed to read/write data from/to the TCP stream.
     IoError(io::Error),
+    /// Failed to compress/decompress a message.
+    CompressionError(String),
 }
 
 impl From<bincode::Error> for CommunicationError {
@@ -58,6 +60,7 @@ impl From<CodecError> for CommunicationError {
         match e {
             CodecError::IoError(e) => CommunicationError::IoError(e),
             CodecError::BincodeError(e) => CommunicationError::BincodeError(e),
+            CodecError::CompressionError(e) => CommunicationError::CompressionError(e),
         }
     }
 }
@@ -70,6 +73,8 @@ pub enum CodecError {
     /// Bincode serialization/deserialization error. It is raised when the `MessageMetadata` serialization
     /// fails. This should not ever happen.
     BincodeError(bincode::Error),
+    /// Raised when a message cannot be decompressed.
+    CompressionError(String),
 }
 
 impl From<io::Error> for CodecError {
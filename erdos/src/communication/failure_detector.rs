@@ -0,0 +1,173 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::mpsc as std_mpsc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::node::{NodeEvent, NodeId};
+
+use super::{ControlMessage, ControlMessageHandler, SharedChannels};
+
+/// Detects failures of other nodes using heartbeats sent over the control connections.
+///
+/// The [`FailureDetector`] periodically broadcasts a [`ControlMessage::Heartbeat`] to all
+/// other nodes. A node is considered failed if no heartbeat has been received from it within
+/// the heartbeat timeout, or if a [`ControlReceiver`](super::receivers::ControlReceiver)
+/// reports that its control connection was closed. Nodes which announced that they finished
+/// executing via [`ControlMessage::NodeFinished`] are no longer monitored. Nodes which join
+/// later are monitored once they connect.
+pub(crate) struct FailureDetector {
+    /// The id of the node on which the detector runs.
+    node_id: NodeId,
+    /// Period with which heartbeats are sent.
+    heartbeat_interval: Duration,
+    /// Time after which a silent node is considered failed.
+    heartbeat_timeout: Duration,
+    /// Time at which the last heartbeat was received from each node that is alive.
+    last_heartbeats: HashMap<NodeId, Instant>,
+    /// Nodes which have been detected as failed.
+    failed_nodes: HashSet<NodeId>,
+    /// Nodes which finished executing.
+    finished_nodes: HashSet<NodeId>,
+    /// Channels to the control senders, used to send heartbeats to other nodes.
+    channels_to_nodes: SharedChannels,
+    /// Channels to the data receivers, used to notify them of node failures and completions.
+    channels_to_data_receivers: SharedChannels,
+    /// Channel on which control receivers forward heartbeats and connection failures.
+    rx: UnboundedReceiver<ControlMessage>,
+    /// Channel on which node failures are reported to the driver.
+    events_tx: std_mpsc::Sender<NodeEvent>,
+}
+
+impl FailureDetector {
+    pub(crate) fn new(
+        node_id: NodeId,
+        heartbeat_interval: Duration,
+        heartbeat_timeout: Duration,
+        rx: UnboundedReceiver<ControlMessage>,
+        events_tx: std_mpsc::Sender<NodeEvent>,
+        control_handler: &ControlMessageHandler,
+    ) -> Self {
+        Self {
+            node_id,
+            heartbeat_interval,
+            heartbeat_timeout,
+            last_heartbeats: HashMap::new(),
+            failed_nodes: HashSet::new(),
+            finished_nodes: HashSet::new(),
+            channels_to_nodes: control_handler.get_channels_to_nodes(),
+            channels_to_data_receivers: control_handler.get_channels_to_data_receivers(),
+            rx,
+            events_tx,
+        }
+    }
+
+    /// Sends heartbeats and checks for failed nodes until the control receivers shut down.
+    ///
+    /// Heartbeats are timed on the system clock even if the node runs on a
+    /// [`VirtualClock`](crate::node::VirtualClock), as other nodes keep sending them in real
+    /// time while the virtual clock is advanced.
+    pub(crate) async fn run(&mut self) {
+        let mut interval = tokio::time::interval(self.heartbeat_interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    self.monitor_connected_nodes();
+                    self.send_heartbeats();
+                    self.check_timeouts();
+                }
+                msg = self.rx.recv() => match msg {
+                    Some(ControlMessage::Heartbeat(node_id)) => {
+                        if let Some(last_heartbeat) = self.last_heartbeats.get_mut(&node_id) {
+                            *last_heartbeat = Instant::now();
+                        }
+                    }
+                    Some(ControlMessage::NodeFailure(node_id)) => self.on_node_failure(node_id),
+                    Some(ControlMessage::NodeFinished(node_id)) => {
+                        tracing::debug!("Node {}: node {} finished", self.node_id, node_id);
+                        self.last_heartbeats.remove(&node_id);
+                        self.finished_nodes.insert(node_id);
+                        // The data receiver closes its streams unless the node finished.
+                        if let Some(tx) = self.channels_to_data_receivers.lock().unwrap().get(&node_id)
+                        {
+                            tx.send(ControlMessage::NodeFinished(node_id)).ok();
+                        }
+                    }
+                    Some(msg) => tracing::warn!(
+                        "Node {}: failure detector received unexpected control message {:?}",
+                        self.node_id,
+                        msg
+                    ),
+                    None => return,
+                },
+            }
+        }
+    }
+
+    /// Starts monitoring the nodes which connected since the last check, giving them a full
+    /// timeout to send their first heartbeat.
+    fn monitor_connected_nodes(&mut self) {
+        let now = Instant::now();
+        for &node_id in self.channels_to_nodes.lock().unwrap().keys() {
+            if !self.failed_nodes.contains(&node_id) && !self.finished_nodes.contains(&node_id) {
+                self.last_heartbeats.entry(node_id).or_insert(now);
+            }
+        }
+    }
+
+    fn send_heartbeats(&mut self) {
+        for (node_id, tx) in self.channels_to_nodes.lock().unwrap().iter() {
+            if !self.failed_nodes.contains(node_id) && !self.finished_nodes.contains(node_id) {
+                // Errors are handled when the heartbeat timeout expires.
+                tx.send(ControlMessage::Heartbeat(self.node_id)).ok();
+            }
+        }
+    }
+
+    fn check_timeouts(&mut self) {
+        let now = Instant::now();
+        let timed_out_nodes: Vec<NodeId> = self
+            .last_heartbeats
+            .iter()
+            .filter(|(_, &last_heartbeat)| {
+                now.duration_since(last_heartbeat) > self.heartbeat_timeout
+            })
+            .map(|(&node_id, _)| node_id)
+            .collect();
+        for node_id in timed_out_nodes {
+            tracing::error!(
+                "Node {}: did not receive a heartbeat from node {} within {:?}",
+                self.node_id,
+                node_id,
+                self.heartbeat_timeout
+            );
+            self.on_node_failure(node_id);
+        }
+    }
+
+    fn on_node_failure(&mut self, node_id: NodeId) {
+        // Finished nodes close their connections.
+        if self.finished_nodes.contains(&node_id) || !self.failed_nodes.insert(node_id) {
+            return;
+        }
+        self.last_heartbeats.remove(&node_id);
+        tracing::error!(
+            "Node {}: detected failure of node {}",
+            self.node_id,
+            node_id
+        );
+        // Allow the data receiver to close streams that originate on the failed node.
+        if let Some(tx) = self
+            .channels_to_data_receivers
+            .lock()
+            .unwrap()
+            .get(&node_id)
+        {
+            tx.send(ControlMessage::NodeFailure(node_id)).ok();
+        }
+        // The driver may have dropped its handle to the node.
+        self.events_tx.send(NodeEvent::NodeFailure(node_id)).ok();
+    }
+}
@@ -0,0 +1,225 @@
+use std::{io, time::Duration};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::node::NodeId;
+
+use super::Connection;
+
+/// Bytes with which each connection between nodes starts.
+const MAGIC: &[u8; 4] = b"ERDS";
+/// Version of the handshake and of the framing of messages. Bump when either changes.
+///
+/// The magic bytes, the protocol version, and the ERDOS version must keep their layout across
+/// protocol versions, so that mismatching nodes can report each other's versions.
+pub(crate) const PROTOCOL_VERSION: u16 = 3;
+/// Version of ERDOS running on the node.
+const ERDOS_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Time after which a peer which has not completed the handshake is considered not to be an
+/// ERDOS node.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const CHECKSUMS_FLAG: u8 = 0b01;
+const LONG_LENGTHS_FLAG: u8 = 0b10;
+
+/// Options for framing messages on the data connections between nodes. All nodes must use the
+/// same `checksums` and `long_lengths`, which is checked when the nodes connect.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameOptions {
+    /// Appends a CRC32 checksum to each message, which the receiver verifies.
+    pub checksums: bool,
+    /// Encodes the sizes of messages with 64 bits instead of 32 bits, which is required to send
+    /// messages larger than 4 GiB.
+    pub long_lengths: bool,
+    /// The largest message, in bytes, which the node accepts from other nodes. Larger messages
+    /// close the connection before memory is allocated for them, and compressed messages may not
+    /// exceed this size once decompressed. If `None`, messages are only limited by the sizes
+    /// which the lengths can encode.
+    pub max_frame_size: Option<usize>,
+}
+
+impl FrameOptions {
+    fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.checksums {
+            flags |= CHECKSUMS_FLAG;
+        }
+        if self.long_lengths {
+            flags |= LONG_LENGTHS_FLAG;
+        }
+        flags
+    }
+
+    fn from_flags(flags: u8) -> Self {
+        Self {
+            checksums: flags & CHECKSUMS_FLAG != 0,
+            long_lengths: flags & LONG_LENGTHS_FLAG != 0,
+            max_frame_size: None,
+        }
+    }
+}
+
+/// Identifies a node to its peer when a connection is established.
+///
+/// Both ends of a connection send their handshake, and check that the peer is an ERDOS node
+/// which speaks the same protocol version and frames messages the same way.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Handshake {
+    pub protocol_version: u16,
+    pub erdos_version: String,
+    pub node_id: NodeId,
+    pub frame_options: FrameOptions,
+}
+
+impl Handshake {
+    pub fn new(node_id: NodeId, frame_options: FrameOptions) -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            erdos_version: ERDOS_VERSION.to_string(),
+            node_id,
+            frame_options,
+        }
+    }
+
+    /// Sends the handshake on `stream`, and returns the handshake of the peer once checked.
+    pub async fn exchange(&self, stream: &mut Box<dyn Connection>) -> io::Result<Handshake> {
+        stream.write_all(&self.encode()).await?;
+        stream.flush().await?;
+        let peer = tokio::time::timeout(HANDSHAKE_TIMEOUT, Self::read(stream))
+            .await
+            .map_err(|_| {
+                invalid_data(format!(
+                    "the peer did not complete the handshake within {:?}; it may not be an \
+                    ERDOS node",
+                    HANDSHAKE_TIMEOUT
+                ))
+            })??;
+        self.check(&peer)?;
+        Ok(peer)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let erdos_version = self.erdos_version.as_bytes();
+        let mut buffer = Vec::with_capacity(MAGIC.len() + 8 + erdos_version.len());
+        buffer.extend_from_slice(MAGIC);
+        buffer.extend_from_slice(&self.protocol_version.to_be_bytes());
+        buffer.push(erdos_version.len() as u8);
+        buffer.extend_from_slice(erdos_version);
+        buffer.extend_from_slice(&(self.node_id as u32).to_be_bytes());
+        buffer.push(self.frame_options.flags());
+        buffer
+    }
+
+    async fn read(stream: &mut Box<dyn Connection>) -> io::Result<Handshake> {
+        let mut magic = [0u8; 4];
+        stream.read_exact(&mut magic).await?;
+        if &magic != MAGIC {
+            return Err(invalid_data(format!(
+                "the peer sent {:02x?} instead of the ERDOS magic bytes; it is not an ERDOS node, \
+                or runs a version of ERDOS older than 0.4 (is another service using the port?)",
+                magic
+            )));
+        }
+        let protocol_version = stream.read_u16().await?;
+        let version_len = stream.read_u8().await? as usize;
+        let mut erdos_version = vec![0u8; version_len];
+        stream.read_exact(&mut erdos_version).await?;
+        let erdos_version = String::from_utf8_lossy(&erdos_version).into_owned();
+        if protocol_version != PROTOCOL_VERSION {
+            return Err(invalid_data(format!(
+                "the peer runs ERDOS {} with wire protocol version {}, but this node runs \
+                ERDOS {} with wire protocol version {}",
+                erdos_version, protocol_version, ERDOS_VERSION, PROTOCOL_VERSION
+            )));
+        }
+        let node_id = stream.read_u32().await? as NodeId;
+        let frame_options = FrameOptions::from_flags(stream.read_u8().await?);
+        Ok(Handshake {
+            protocol_version,
+            erdos_version,
+            node_id,
+            frame_options,
+        })
+    }
+
+    fn check(&self, peer: &Handshake) -> io::Result<()> {
+        // The maximum frame size only applies to the messages which each node receives.
+        if peer.frame_options.flags() != self.frame_options.flags() {
+            return Err(invalid_data(format!(
+                "node {} frames messages with {:?}, but this node frames messages with {:?}",
+                peer.node_id,
+                peer.frame_options,
+                FrameOptions::from_flags(self.frame_options.flags())
+            )));
+        }
+        if peer.erdos_version != self.erdos_version {
+            tracing::warn!(
+                "Node {}: node {} runs ERDOS {}, but this node runs ERDOS {}",
+                self.node_id,
+                peer.node_id,
+                peer.erdos_version,
+                self.erdos_version
+            );
+        }
+        Ok(())
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_handshake_round_trip() {
+        let (client, server) = tokio::io::duplex(1024);
+        let mut client: Box<dyn Connection> = Box::new(client);
+        let mut server: Box<dyn Connection> = Box::new(server);
+        let frame_options = FrameOptions {
+            checksums: true,
+            long_lengths: true,
+            max_frame_size: None,
+        };
+        let handshake = Handshake::new(3, frame_options);
+        client.write_all(&handshake.encode()).await.unwrap();
+        assert_eq!(Handshake::read(&mut server).await.unwrap(), handshake);
+    }
+
+    #[tokio::test]
+    async fn test_protocol_version_mismatch() {
+        let (client, server) = tokio::io::duplex(1024);
+        let mut client: Box<dyn Connection> = Box::new(client);
+        let mut server: Box<dyn Connection> = Box::new(server);
+        let mut handshake = Handshake::new(1, FrameOptions::default());
+        handshake.protocol_version = PROTOCOL_VERSION + 1;
+        client.write_all(&handshake.encode()).await.unwrap();
+
+        let error = Handshake::read(&mut server).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("wire protocol version"));
+    }
+
+    #[test]
+    fn test_frame_options_mismatch() {
+        let frame_options = FrameOptions {
+            checksums: true,
+            ..Default::default()
+        };
+        let handshake = Handshake::new(0, frame_options);
+        assert!(handshake
+            .check(&Handshake::new(1, FrameOptions::default()))
+            .is_err());
+
+        // Nodes may limit the size of the messages they receive differently.
+        let peer_frame_options = FrameOptions {
+            max_frame_size: Some(1024),
+            ..frame_options
+        };
+        assert!(handshake
+            .check(&Handshake::new(1, peer_frame_options))
+            .is_ok());
+    }
+}
@@ -0,0 +1,417 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use futures::{stream::FuturesUnordered, SinkExt, StreamExt};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    time::{sleep, timeout},
+};
+use tokio_util::codec::Framed;
+
+use crate::node::NodeId;
+
+use super::{
+    CodecError, Connection, ControlMessage, ControlMessageCodec, FrameOptions, Handshake, TlsConfig,
+};
+
+/// The node which hosts the registry of nodes.
+pub(crate) const COORDINATOR_NODE_ID: NodeId = 0;
+/// Time within which a node which connected to the coordinator must register.
+const REGISTRATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+type RegistrationStream = Framed<Box<dyn Connection>, ControlMessageCodec>;
+
+/// Learns the data and control addresses of the nodes from the coordinator.
+///
+/// Node 0 acts as the coordinator: it listens for registrations at `coordinator_address` until
+/// `min_nodes` nodes, including itself, registered their addresses, and then sends the table of
+/// addresses to these nodes. Other nodes register with the coordinator, and wait until the table
+/// arrives, so nodes can start in any order. The coordinator keeps listening until all
+/// `num_nodes` nodes joined, and sends each node which joins later the table of the nodes which
+/// joined before it.
+///
+/// Returns the data and control addresses of the nodes, indexed by node, and the nodes which
+/// joined before this node or along with it, in the order in which they joined. The addresses of
+/// the other nodes are unspecified.
+pub(crate) async fn discover_nodes(
+    coordinator_address: SocketAddr,
+    node_id: NodeId,
+    num_nodes: usize,
+    min_nodes: usize,
+    data_address: SocketAddr,
+    control_address: SocketAddr,
+    tls: Option<&TlsConfig>,
+    frame_options: FrameOptions,
+) -> io::Result<(Vec<SocketAddr>, Vec<SocketAddr>, Vec<NodeId>)> {
+    let handshake = Handshake::new(node_id, frame_options);
+    if node_id == COORDINATOR_NODE_ID {
+        coordinate(
+            coordinator_address,
+            handshake,
+            num_nodes,
+            min_nodes,
+            data_address,
+            control_address,
+            tls.cloned(),
+        )
+        .await
+    } else {
+        register(
+            coordinator_address,
+            &handshake,
+            num_nodes,
+            data_address,
+            control_address,
+            tls,
+        )
+        .await
+    }
+}
+
+/// Collects the addresses of the nodes which register, and sends the table of addresses to all
+/// nodes once `min_nodes` nodes registered. Nodes which register afterwards are answered by a
+/// task which runs until all nodes joined.
+async fn coordinate(
+    coordinator_address: SocketAddr,
+    handshake: Handshake,
+    num_nodes: usize,
+    min_nodes: usize,
+    data_address: SocketAddr,
+    control_address: SocketAddr,
+    tls: Option<TlsConfig>,
+) -> io::Result<(Vec<SocketAddr>, Vec<SocketAddr>, Vec<NodeId>)> {
+    let node_id = handshake.node_id;
+    let listener = TcpListener::bind(coordinator_address).await?;
+    tracing::info!(
+        "Node {}: waiting for {} nodes to join at {}",
+        node_id,
+        min_nodes.saturating_sub(1),
+        coordinator_address
+    );
+
+    let unknown = SocketAddr::from(([0, 0, 0, 0], 0));
+    let mut data_addresses = vec![unknown; num_nodes];
+    let mut control_addresses = vec![unknown; num_nodes];
+    data_addresses[node_id] = data_address;
+    control_addresses[node_id] = control_address;
+    let mut members: HashMap<NodeId, RegistrationStream> = HashMap::new();
+    // Registrations proceed concurrently, so that a node which stalls during its registration
+    // does not delay the other nodes.
+    let mut registrations = FuturesUnordered::new();
+    while members.len() + 1 < min_nodes {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer_address) = accepted?;
+                registrations.push(accept_registration(
+                    stream,
+                    peer_address,
+                    handshake.clone(),
+                    num_nodes,
+                    tls.clone(),
+                ));
+            }
+            Some((peer_address, registration)) = registrations.next() => match registration {
+                Ok((other_node_id, other_data_address, other_control_address, stream)) => {
+                    if members.insert(other_node_id, stream).is_some() {
+                        tracing::warn!(
+                            "Node {}: node {} registered again from {}; replacing its registration",
+                            node_id,
+                            other_node_id,
+                            peer_address
+                        );
+                    }
+                    data_addresses[other_node_id] = other_data_address;
+                    control_addresses[other_node_id] = other_control_address;
+                    tracing::info!(
+                        "Node {}: node {} joined from {} ({}/{} nodes)",
+                        node_id,
+                        other_node_id,
+                        peer_address,
+                        members.len() + 1,
+                        min_nodes
+                    );
+                }
+                Err(e) => tracing::warn!(
+                    "Node {}: rejected registration from {}; error {}",
+                    node_id,
+                    peer_address,
+                    e
+                ),
+            },
+        }
+    }
+
+    // The nodes which join together connect to each other in the order of their ids.
+    let mut joined: Vec<NodeId> = members.keys().copied().collect();
+    joined.push(node_id);
+    joined.sort_unstable();
+    for stream in members.values_mut() {
+        stream
+            .send(ControlMessage::NodeTable(
+                data_addresses.clone(),
+                control_addresses.clone(),
+                joined.clone(),
+            ))
+            .await
+            .map_err(io_error)?;
+    }
+
+    let table = (
+        data_addresses.clone(),
+        control_addresses.clone(),
+        joined.clone(),
+    );
+    if joined.len() < num_nodes {
+        tokio::spawn(async move {
+            let mut data_addresses = data_addresses;
+            let mut control_addresses = control_addresses;
+            let mut joined = joined;
+            while joined.len() < num_nodes {
+                tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, peer_address)) => registrations.push(accept_registration(
+                            stream,
+                            peer_address,
+                            handshake.clone(),
+                            num_nodes,
+                            tls.clone(),
+                        )),
+                        Err(e) => {
+                            tracing::error!(
+                                "Node {}: stopped accepting nodes at {}; error {}",
+                                node_id,
+                                coordinator_address,
+                                e
+                            );
+                            return;
+                        }
+                    },
+                    Some((peer_address, registration)) = registrations.next() => {
+                        let (other_node_id, other_data_address, other_control_address, mut stream) =
+                            match registration {
+                                Ok(registration) => registration,
+                                Err(e) => {
+                                    tracing::warn!(
+                                        "Node {}: rejected registration from {}; error {}",
+                                        node_id,
+                                        peer_address,
+                                        e
+                                    );
+                                    continue;
+                                }
+                            };
+                        if joined.contains(&other_node_id) {
+                            tracing::warn!(
+                                "Node {}: rejected registration of node {} from {}, which \
+                                already joined",
+                                node_id,
+                                other_node_id,
+                                peer_address
+                            );
+                            continue;
+                        }
+                        data_addresses[other_node_id] = other_data_address;
+                        control_addresses[other_node_id] = other_control_address;
+                        joined.push(other_node_id);
+                        let table = ControlMessage::NodeTable(
+                            data_addresses.clone(),
+                            control_addresses.clone(),
+                            joined.clone(),
+                        );
+                        if let Err(e) = stream.send(table).await {
+                            // The node connects to the nodes which joined before it, so it
+                            // cannot register again under the same id.
+                            tracing::error!(
+                                "Node {}: could not send the table of nodes to node {}; error {:?}",
+                                node_id,
+                                other_node_id,
+                                e
+                            );
+                        }
+                        tracing::info!(
+                            "Node {}: node {} joined the running cluster from {} ({}/{} nodes)",
+                            node_id,
+                            other_node_id,
+                            peer_address,
+                            joined.len(),
+                            num_nodes
+                        );
+                    },
+                }
+            }
+        });
+    }
+    Ok(table)
+}
+
+/// Reads the registration of a node which connected from `peer_address`, and returns it along
+/// with the address.
+async fn accept_registration(
+    stream: TcpStream,
+    peer_address: SocketAddr,
+    handshake: Handshake,
+    num_nodes: usize,
+    tls: Option<TlsConfig>,
+) -> (
+    SocketAddr,
+    io::Result<(NodeId, SocketAddr, SocketAddr, RegistrationStream)>,
+) {
+    let registration = read_registration(stream, peer_address, &handshake, num_nodes, tls).await;
+    (peer_address, registration)
+}
+
+/// Authenticates a node which connected to the coordinator, and reads its registration.
+///
+/// Unspecified IP addresses in the registration are replaced with the IP address from which the
+/// node connected.
+async fn read_registration(
+    stream: TcpStream,
+    peer_address: SocketAddr,
+    handshake: &Handshake,
+    num_nodes: usize,
+    tls: Option<TlsConfig>,
+) -> io::Result<(NodeId, SocketAddr, SocketAddr, RegistrationStream)> {
+    stream.set_nodelay(true)?;
+    let mut stream: Box<dyn Connection> = Box::new(stream);
+    let node_id = match tls {
+        Some(tls) => {
+            let (node_id, tls_stream) = tls.accept(stream, handshake).await?;
+            stream = tls_stream;
+            node_id
+        }
+        None => handshake.exchange(&mut stream).await?.node_id,
+    };
+    if node_id == COORDINATOR_NODE_ID || node_id >= num_nodes {
+        return Err(invalid_data(format!(
+            "node {} is not a node of the cluster of {} nodes",
+            node_id, num_nodes
+        )));
+    }
+
+    let mut stream = Framed::new(stream, ControlMessageCodec::new());
+    let registration = timeout(REGISTRATION_TIMEOUT, stream.next())
+        .await
+        .map_err(|_| invalid_data("node did not register in time".to_string()))?;
+    match registration {
+        Some(Ok(ControlMessage::RegisterNode(registered_node_id, mut data, mut control)))
+            if registered_node_id == node_id =>
+        {
+            for address in [&mut data, &mut control] {
+                if address.ip().is_unspecified() {
+                    address.set_ip(peer_address.ip());
+                }
+            }
+            Ok((node_id, data, control, stream))
+        }
+        Some(Ok(msg)) => Err(invalid_data(format!(
+            "node {} sent {:?} instead of registering",
+            node_id, msg
+        ))),
+        Some(Err(e)) => Err(io_error(e)),
+        None => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+    }
+}
+
+/// Registers the node's addresses with the coordinator, and waits for the table of addresses.
+async fn register(
+    coordinator_address: SocketAddr,
+    handshake: &Handshake,
+    num_nodes: usize,
+    data_address: SocketAddr,
+    control_address: SocketAddr,
+    tls: Option<&TlsConfig>,
+) -> io::Result<(Vec<SocketAddr>, Vec<SocketAddr>, Vec<NodeId>)> {
+    let node_id = handshake.node_id;
+    // Keep on retrying until the coordinator starts.
+    let mut last_err_msg_time = Instant::now();
+    let stream = loop {
+        match TcpStream::connect(coordinator_address).await {
+            Ok(stream) => break stream,
+            Err(e) => {
+                // Only print connection errors every 1s.
+                let now = Instant::now();
+                if now.duration_since(last_err_msg_time) >= Duration::from_secs(1) {
+                    tracing::warn!(
+                        "Node {}: could not connect to the coordinator at {}; error {}; retrying",
+                        node_id,
+                        coordinator_address,
+                        e
+                    );
+                    last_err_msg_time = now;
+                }
+                sleep(Duration::from_millis(100)).await;
+            }
+        }
+    };
+    stream.set_nodelay(true)?;
+    let mut stream: Box<dyn Connection> = Box::new(stream);
+    if let Some(tls) = tls {
+        stream = tls.connect(stream, COORDINATOR_NODE_ID).await?;
+    }
+    let coordinator = handshake.exchange(&mut stream).await?;
+    if coordinator.node_id != COORDINATOR_NODE_ID {
+        return Err(invalid_data(format!(
+            "expected the coordinator at {}, but node {} answered",
+            coordinator_address, coordinator.node_id
+        )));
+    }
+
+    let mut stream = Framed::new(stream, ControlMessageCodec::new());
+    stream
+        .send(ControlMessage::RegisterNode(
+            node_id,
+            data_address,
+            control_address,
+        ))
+        .await
+        .map_err(io_error)?;
+    tracing::info!(
+        "Node {}: registered with the coordinator at {}; waiting for the other nodes to join",
+        node_id,
+        coordinator_address
+    );
+    match stream.next().await {
+        Some(Ok(ControlMessage::NodeTable(data_addresses, control_addresses, joined)))
+            if data_addresses.len() == num_nodes
+                && control_addresses.len() == num_nodes
+                && joined.contains(&node_id) =>
+        {
+            Ok((data_addresses, control_addresses, joined))
+        }
+        Some(Ok(ControlMessage::NodeTable(data_addresses, control_addresses, _)))
+            if data_addresses.len() != num_nodes || control_addresses.len() != num_nodes =>
+        {
+            Err(invalid_data(format!(
+                "the coordinator expects {} nodes, but this node expects {} nodes",
+                data_addresses.len(),
+                num_nodes
+            )))
+        }
+        Some(Ok(ControlMessage::NodeTable(..))) => Err(invalid_data(
+            "the coordinator did not list this node among the nodes which joined".to_string(),
+        )),
+        Some(Ok(msg)) => Err(invalid_data(format!(
+            "the coordinator sent {:?} instead of the table of nodes",
+            msg
+        ))),
+        Some(Err(e)) => Err(io_error(e)),
+        None => Err(invalid_data(
+            "the coordinator closed the connection before all nodes joined".to_string(),
+        )),
+    }
+}
+
+fn io_error(e: CodecError) -> io::Error {
+    match e {
+        CodecError::IoError(e) => e,
+        e => invalid_data(format!("{:?}", e)),
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
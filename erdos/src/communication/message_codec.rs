@@ -1,11 +1,18 @@
 use byteorder::{ByteOrder, NetworkEndian, WriteBytesExt};
 use bytes::{BufMut, BytesMut};
-use std::fmt::Debug;
+use std::{collections::HashMap, convert::TryFrom, fmt::Debug, io, time::Duration};
 use tokio_util::codec::{Decoder, Encoder};
 
-use crate::communication::{CodecError, InterProcessMessage, MessageMetadata};
+use crate::{
+    communication::{
+        clock_sync, ClockOffsets, CodecError, FrameOptions, InterProcessMessage, MessageMetadata,
+        StreamStatistics, StreamStatisticsMap,
+    },
+    dataflow::stream::StreamId,
+    node::{Clock, NodeId},
+};
 
-const HEADER_SIZE: usize = 8;
+const CHECKSUM_SIZE: usize = 4;
 
 #[derive(Debug)]
 enum DecodeStatus {
@@ -16,18 +23,34 @@ enum DecodeStatus {
     },
     Data {
         data_size: usize,
+        /// Checksum of the metadata, which the checksum of the data continues.
+        metadata_checksum: u32,
     },
 }
 
 /// Encodes messages into bytes, and decodes bytes into an [`InterProcessMessage`].
 ///
-/// For each message, the codec first writes the size of its message header,
+/// For each message, the codec first writes the sizes of its message header and content,
 /// then the message header, and finally the content of the message.
+/// The content is compressed as specified in the message header.
+/// Depending on the [`FrameOptions`], the sizes are written as 32-bit or 64-bit integers,
+/// and a CRC32 checksum of the message header and content follows the message.
+/// The fragments of a message which is split are reassembled once its last fragment is decoded.
 #[derive(Debug)]
 pub struct MessageCodec {
     /// Current part of the message to decode.
     status: DecodeStatus,
     msg_metadata: Option<MessageMetadata>,
+    /// The data of the fragments decoded so far of the messages which are split, by stream.
+    fragments: HashMap<StreamId, BytesMut>,
+    frame_options: FrameOptions,
+    /// Statistics updated for each encoded and decoded message.
+    statistics: StreamStatisticsMap,
+    /// The node which sends the decoded messages, and the offsets of the clocks of other nodes.
+    clock_offsets: Option<(NodeId, ClockOffsets)>,
+    /// Clock with which messages are timestamped when encoded, and their latency measured when
+    /// decoded.
+    clock: Clock,
 }
 
 impl MessageCodec {
@@ -35,7 +58,157 @@ impl MessageCodec {
         MessageCodec {
             status: DecodeStatus::Header,
             msg_metadata: None,
+            fragments: HashMap::new(),
+            frame_options: FrameOptions::default(),
+            statistics: StreamStatisticsMap::default(),
+            clock_offsets: None,
+            clock: Clock::System,
+        }
+    }
+
+    /// Frames messages as specified by `frame_options`.
+    pub(crate) fn with_frame_options(mut self, frame_options: FrameOptions) -> Self {
+        self.frame_options = frame_options;
+        self
+    }
+
+    /// Records the statistics of the encoded and decoded messages in `statistics`.
+    pub(crate) fn with_statistics(mut self, statistics: StreamStatisticsMap) -> Self {
+        self.statistics = statistics;
+        self
+    }
+
+    /// Measures the latency of the messages decoded from `node_id`, corrected with the
+    /// estimated offset of its clock in `clock_offsets`.
+    pub(crate) fn with_clock_offsets(
+        mut self,
+        node_id: NodeId,
+        clock_offsets: ClockOffsets,
+    ) -> Self {
+        self.clock_offsets = Some((node_id, clock_offsets));
+        self
+    }
+
+    /// Timestamps and measures the latency of messages on `clock`.
+    pub(crate) fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the time a message took from being sent to being received, or `None` if the
+    /// offset of the sender's clock is not yet estimated.
+    fn latency(&self, metadata: &MessageMetadata) -> Option<Duration> {
+        let (node_id, clock_offsets) = self.clock_offsets.as_ref()?;
+        let sent_at = clock_sync::to_local_time(clock_offsets, *node_id, metadata.sent_at)?;
+        Some(
+            self.clock
+                .system_time()
+                .duration_since(sent_at)
+                .unwrap_or_default(),
+        )
+    }
+
+    fn update_statistics(&self, stream_id: StreamId, update: impl FnOnce(&mut StreamStatistics)) {
+        update(
+            self.statistics
+                .lock()
+                .unwrap()
+                .entry(stream_id)
+                .or_default(),
+        );
+    }
+
+    fn header_size(&self) -> usize {
+        if self.frame_options.long_lengths {
+            16
+        } else {
+            8
+        }
+    }
+
+    fn checksum_size(&self) -> usize {
+        if self.frame_options.checksums {
+            CHECKSUM_SIZE
+        } else {
+            0
+        }
+    }
+
+    /// Returns the size of a message with the given header and content sizes, including its
+    /// checksum, if the message may be received.
+    ///
+    /// The sizes are read from the connection, so they are checked before memory is reserved
+    /// for the message.
+    fn check_frame_size(&self, metadata_size: u64, data_size: u64) -> Result<usize, CodecError> {
+        let frame_size = metadata_size
+            .checked_add(data_size)
+            .and_then(|size| size.checked_add(self.checksum_size() as u64))
+            .and_then(|size| usize::try_from(size).ok());
+        let error = match (frame_size, self.frame_options.max_frame_size) {
+            (Some(frame_size), Some(max_frame_size)) if frame_size > max_frame_size => format!(
+                "received a message of {} bytes, which exceeds the maximum frame size of {} bytes",
+                frame_size, max_frame_size
+            ),
+            (Some(frame_size), _) => return Ok(frame_size),
+            (None, _) => format!(
+                "received a message with a {} byte header and {} bytes of content, which is \
+                too large to receive",
+                metadata_size, data_size
+            ),
+        };
+        Err(CodecError::IoError(io::Error::new(
+            io::ErrorKind::InvalidData,
+            error,
+        )))
+    }
+
+    /// Appends the data of a fragment to the message of `stream_id` which is being reassembled,
+    /// failing if the message would exceed the maximum frame size.
+    fn add_fragment(&mut self, stream_id: StreamId, bytes: BytesMut) -> Result<(), CodecError> {
+        let message = self.fragments.entry(stream_id).or_default();
+        if let Some(max_frame_size) = self.frame_options.max_frame_size {
+            if message.len() + bytes.len() > max_frame_size {
+                return Err(CodecError::IoError(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "received a fragmented message of more than {} bytes on stream {}, which \
+                        exceeds the maximum frame size",
+                        max_frame_size, stream_id
+                    ),
+                )));
+            }
         }
+        message.extend_from_slice(&bytes);
+        Ok(())
+    }
+
+    /// Writes the sizes of the message header and content.
+    fn write_header(
+        &self,
+        buf: &mut BytesMut,
+        metadata: &MessageMetadata,
+        metadata_size: usize,
+        data_size: usize,
+    ) -> Result<(), CodecError> {
+        let mut writer = buf.writer();
+        if self.frame_options.long_lengths {
+            writer.write_u64::<NetworkEndian>(metadata_size as u64)?;
+            writer.write_u64::<NetworkEndian>(data_size as u64)?;
+        } else {
+            if data_size > u32::MAX as usize {
+                return Err(CodecError::IoError(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "message of {} bytes on stream {} exceeds the 4 GiB frame size limit; \
+                        enable 64-bit frame lengths on all nodes",
+                        data_size, metadata.stream_id
+                    ),
+                )));
+            }
+            writer.write_u32::<NetworkEndian>(metadata_size as u32)?;
+            writer.write_u32::<NetworkEndian>(data_size as u32)?;
+        }
+        Ok(())
     }
 }
 
@@ -52,16 +225,27 @@ impl Decoder for MessageCodec {
         match self.status {
             // Decode the header and reserve
             DecodeStatus::Header => {
-                if buf.len() >= HEADER_SIZE {
-                    let header = buf.split_to(HEADER_SIZE);
-                    let metadata_size = NetworkEndian::read_u32(&header[0..4]) as usize;
-                    let data_size = NetworkEndian::read_u32(&header[4..8]) as usize;
+                let header_size = self.header_size();
+                if buf.len() >= header_size {
+                    let header = buf.split_to(header_size);
+                    let (metadata_size, data_size) = if self.frame_options.long_lengths {
+                        (
+                            NetworkEndian::read_u64(&header[0..8]),
+                            NetworkEndian::read_u64(&header[8..16]),
+                        )
+                    } else {
+                        (
+                            NetworkEndian::read_u32(&header[0..4]) as u64,
+                            NetworkEndian::read_u32(&header[4..8]) as u64,
+                        )
+                    };
+                    let frame_size = self.check_frame_size(metadata_size, data_size)?;
                     self.status = DecodeStatus::Metadata {
-                        metadata_size,
-                        data_size,
+                        metadata_size: metadata_size as usize,
+                        data_size: data_size as usize,
                     };
                     // Reserve space in the buffer for the rest of the message and the next header.
-                    buf.reserve(metadata_size + data_size + HEADER_SIZE);
+                    buf.reserve(frame_size.saturating_add(header_size));
                     self.decode(buf)
                 } else {
                     Ok(None)
@@ -74,24 +258,78 @@ impl Decoder for MessageCodec {
             } => {
                 if buf.len() >= metadata_size {
                     let metadata_bytes = buf.split_to(metadata_size);
+                    let metadata_checksum = if self.frame_options.checksums {
+                        crc32fast::hash(&metadata_bytes)
+                    } else {
+                        0
+                    };
                     let metadata: MessageMetadata =
                         bincode::deserialize(&metadata_bytes).map_err(CodecError::BincodeError)?;
                     self.msg_metadata = Some(metadata);
-                    self.status = DecodeStatus::Data { data_size };
+                    self.status = DecodeStatus::Data {
+                        data_size,
+                        metadata_checksum,
+                    };
                     self.decode(buf)
                 } else {
                     Ok(None)
                 }
             }
             // Decode the data.
-            DecodeStatus::Data { data_size } => {
-                if buf.len() >= data_size {
-                    let bytes = buf.split_to(data_size);
-                    let msg = InterProcessMessage::new_serialized(
-                        bytes,
-                        self.msg_metadata.take().unwrap(),
-                    );
+            DecodeStatus::Data {
+                data_size,
+                metadata_checksum,
+            } => {
+                if buf.len() >= data_size + self.checksum_size() {
+                    let mut bytes = buf.split_to(data_size);
+                    let metadata = self.msg_metadata.take().unwrap();
                     self.status = DecodeStatus::Header;
+                    if self.frame_options.checksums {
+                        let expected = NetworkEndian::read_u32(&buf.split_to(CHECKSUM_SIZE));
+                        let mut hasher = crc32fast::Hasher::new_with_initial(metadata_checksum);
+                        hasher.update(&bytes);
+                        let actual = hasher.finalize();
+                        if actual != expected {
+                            return Err(CodecError::IoError(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!(
+                                    "corrupted message on stream {}: checksum is {:#010x}, \
+                                    expected {:#010x}",
+                                    metadata.stream_id, actual, expected
+                                ),
+                            )));
+                        }
+                    }
+                    if !metadata.compression.is_none() {
+                        bytes = metadata
+                            .compression
+                            .decompress(&bytes, self.frame_options.max_frame_size)?;
+                    }
+                    let decompressed_size = bytes.len();
+                    if metadata.more_fragments {
+                        self.update_statistics(metadata.stream_id, |stats| {
+                            stats.bytes_received += decompressed_size as u64;
+                            stats.compressed_bytes_received += data_size as u64;
+                        });
+                        self.add_fragment(metadata.stream_id, bytes)?;
+                        return self.decode(buf);
+                    }
+                    if self.fragments.contains_key(&metadata.stream_id) {
+                        self.add_fragment(metadata.stream_id, bytes)?;
+                        bytes = self.fragments.remove(&metadata.stream_id).unwrap();
+                    }
+                    let latency = self.latency(&metadata);
+                    self.update_statistics(metadata.stream_id, |stats| {
+                        stats.messages_received += 1;
+                        stats.bytes_received += decompressed_size as u64;
+                        stats.compressed_bytes_received += data_size as u64;
+                        if let Some(latency) = latency {
+                            stats.latency_samples += 1;
+                            stats.total_latency += latency;
+                            stats.max_latency = stats.max_latency.max(latency);
+                        }
+                    });
+                    let msg = InterProcessMessage::new_serialized(bytes, metadata);
                     Ok(Some(msg))
                 } else {
                     Ok(None)
@@ -106,30 +344,65 @@ impl Encoder<InterProcessMessage> for MessageCodec {
 
     /// Encodes a InterProcessMessage into a buffer.
     ///
-    /// First writes the header_size, then the header, and finally the
-    /// serialized message.
+    /// First writes the header_size and the message size, then the header, and finally the
+    /// serialized message, which is compressed if the header specifies a compression.
+    /// Appends a checksum if checksums are enabled. Serialized messages are the fragments of a
+    /// message, and are compressed separately.
     fn encode(&mut self, msg: InterProcessMessage, buf: &mut BytesMut) -> Result<(), CodecError> {
         // Serialize and write the header.
-        let (metadata, data) = match msg {
-            InterProcessMessage::Deserialized { metadata, data } => (metadata, data),
-            InterProcessMessage::Serialized {
-                metadata: _,
-                bytes: _,
-            } => unreachable!(),
+        let (mut metadata, data, serialized_data) = match msg {
+            InterProcessMessage::Deserialized { metadata, data, .. } => {
+                (metadata, Some(data), None)
+            }
+            InterProcessMessage::Serialized { metadata, bytes } => (metadata, None, Some(bytes)),
         };
+        metadata.sent_at = clock_sync::unix_nanos(self.clock.system_time());
 
-        // Allocate memory in the buffer for serialized metadata and data
-        // to reduce memory allocations.
-        let metadata_size = bincode::serialized_size(&metadata).map_err(CodecError::from)?;
-        let data_size = data.serialized_size().unwrap();
-        buf.reserve(HEADER_SIZE + metadata_size as usize + data_size);
+        let metadata_size = bincode::serialized_size(&metadata).map_err(CodecError::from)? as usize;
+        let frame_overhead = self.header_size() + metadata_size + self.checksum_size();
+        let start;
+        let (data_size, compressed_data_size) = match data {
+            Some(data) if metadata.compression.is_none() => {
+                // Allocate memory in the buffer for serialized metadata and data
+                // to reduce memory allocations.
+                let data_size = data.serialized_size().unwrap();
+                buf.reserve(frame_overhead + data_size);
 
-        // Serialize directly into the buffer.
-        let mut writer = buf.writer();
-        writer.write_u32::<NetworkEndian>(metadata_size as u32)?;
-        writer.write_u32::<NetworkEndian>(data_size as u32)?;
-        bincode::serialize_into(&mut writer, &metadata).map_err(CodecError::from)?;
-        data.encode_into(buf).unwrap();
+                // Serialize directly into the buffer.
+                self.write_header(buf, &metadata, metadata_size, data_size)?;
+                start = buf.len();
+                bincode::serialize_into(buf.writer(), &metadata).map_err(CodecError::from)?;
+                data.encode_into(buf).unwrap();
+                (data_size, data_size)
+            }
+            data => {
+                // The compressed size is only known once the data is serialized and compressed.
+                let serialized_data = match data {
+                    Some(data) => data.encode().unwrap(),
+                    None => serialized_data.unwrap(),
+                };
+                let compressed_data = metadata.compression.compress(&serialized_data)?;
+                buf.reserve(frame_overhead + compressed_data.len());
+
+                self.write_header(buf, &metadata, metadata_size, compressed_data.len())?;
+                start = buf.len();
+                bincode::serialize_into(buf.writer(), &metadata).map_err(CodecError::from)?;
+                buf.put_slice(&compressed_data);
+                (serialized_data.len(), compressed_data.len())
+            }
+        };
+        if self.frame_options.checksums {
+            // The checksum covers the message header and content.
+            let checksum = crc32fast::hash(&buf[start..]);
+            buf.put_u32(checksum);
+        }
+        self.update_statistics(metadata.stream_id, |stats| {
+            if !metadata.more_fragments {
+                stats.messages_sent += 1;
+            }
+            stats.bytes_sent += data_size as u64;
+            stats.compressed_bytes_sent += compressed_data_size as u64;
+        });
 
         Ok(())
     }
@@ -140,3 +413,228 @@ impl Default for MessageCodec {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::communication::{
+        serializable::{Deserializable, DeserializedMessage},
+        Compression, SendOptions,
+    };
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct TestData(Vec<u32>);
+
+    fn encode(codec: &mut MessageCodec, data: TestData, buf: &mut BytesMut) {
+        encode_with(codec, data, SendOptions::default(), buf);
+    }
+
+    fn encode_with(
+        codec: &mut MessageCodec,
+        data: TestData,
+        options: SendOptions,
+        buf: &mut BytesMut,
+    ) {
+        let msg = InterProcessMessage::new_deserialized(Arc::new(data), StreamId::nil(), options);
+        codec.encode(msg, buf).unwrap();
+    }
+
+    fn decode(
+        codec: &mut MessageCodec,
+        buf: &mut BytesMut,
+    ) -> Result<Option<TestData>, CodecError> {
+        match codec.decode(buf)? {
+            Some(InterProcessMessage::Serialized { mut bytes, .. }) => {
+                let data = match Deserializable::decode(&mut bytes).unwrap() {
+                    DeserializedMessage::Owned(data) => data,
+                    DeserializedMessage::Ref(data) => TestData::clone(data),
+                };
+                Ok(Some(data))
+            }
+            Some(InterProcessMessage::Deserialized { .. }) => unreachable!(),
+            None => Ok(None),
+        }
+    }
+
+    #[test]
+    fn test_frame_options_round_trip() {
+        for &checksums in [false, true].iter() {
+            for &long_lengths in [false, true].iter() {
+                let frame_options = FrameOptions {
+                    checksums,
+                    long_lengths,
+                    max_frame_size: None,
+                };
+                let mut codec = MessageCodec::new().with_frame_options(frame_options);
+                let mut buf = BytesMut::new();
+                encode(&mut codec, TestData(vec![1, 2, 3]), &mut buf);
+                encode(&mut codec, TestData(vec![]), &mut buf);
+
+                // Messages are decoded once they were received completely.
+                let mut received = buf.split_to(buf.len() / 2);
+                assert_eq!(decode(&mut codec, &mut received).unwrap(), None);
+                received.unsplit(buf);
+                assert_eq!(
+                    decode(&mut codec, &mut received).unwrap(),
+                    Some(TestData(vec![1, 2, 3]))
+                );
+                assert_eq!(
+                    decode(&mut codec, &mut received).unwrap(),
+                    Some(TestData(vec![]))
+                );
+                assert_eq!(decode(&mut codec, &mut received).unwrap(), None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compression_round_trip() {
+        let data = TestData((0..1000).map(|i| i / 100).collect());
+        for &compression in [Compression::Lz4, Compression::Zstd(3)].iter() {
+            let statistics = StreamStatisticsMap::default();
+            let mut codec = MessageCodec::new().with_statistics(Arc::clone(&statistics));
+            let options = SendOptions {
+                compression,
+                ..SendOptions::default()
+            };
+            let mut buf = BytesMut::new();
+            encode_with(&mut codec, data.clone(), options, &mut buf);
+            assert_eq!(decode(&mut codec, &mut buf).unwrap(), Some(data.clone()));
+
+            let stats = statistics.lock().unwrap()[&StreamId::nil()];
+            assert_eq!(stats.messages_sent, 1);
+            assert_eq!(stats.messages_received, 1);
+            assert_eq!(stats.bytes_sent, stats.bytes_received);
+            assert_eq!(stats.compressed_bytes_sent, stats.compressed_bytes_received);
+            assert!(
+                stats.compressed_bytes_sent < stats.bytes_sent,
+                "{:?}: {:?}",
+                compression,
+                stats
+            );
+        }
+    }
+
+    #[test]
+    fn test_fragments_round_trip() {
+        let data = TestData((0..1000).collect());
+        let other_data = TestData(vec![1, 2, 3]);
+        let statistics = StreamStatisticsMap::default();
+        let mut codec = MessageCodec::new().with_statistics(Arc::clone(&statistics));
+        let options = SendOptions {
+            compression: Compression::Lz4,
+            ..SendOptions::default()
+        };
+        let msg =
+            InterProcessMessage::new_deserialized(Arc::new(data.clone()), StreamId::nil(), options);
+        let mut fragments = msg.into_fragments(1000);
+        assert_eq!(fragments.len(), 5);
+
+        // A message of another stream is sent between the fragments.
+        let mut buf = BytesMut::new();
+        codec
+            .encode(fragments.pop_front().unwrap(), &mut buf)
+            .unwrap();
+        let other_stream_id = StreamId::new_deterministic();
+        let other_msg = InterProcessMessage::new_deserialized(
+            Arc::new(other_data.clone()),
+            other_stream_id,
+            options,
+        );
+        codec.encode(other_msg, &mut buf).unwrap();
+        assert_eq!(decode(&mut codec, &mut buf).unwrap(), Some(other_data));
+        for fragment in fragments {
+            codec.encode(fragment, &mut buf).unwrap();
+        }
+        assert_eq!(decode(&mut codec, &mut buf).unwrap(), Some(data));
+        assert!(codec.fragments.is_empty());
+
+        let stats = statistics.lock().unwrap()[&StreamId::nil()];
+        assert_eq!(stats.messages_sent, 1);
+        assert_eq!(stats.messages_received, 1);
+        assert_eq!(stats.bytes_sent, stats.bytes_received);
+        assert_eq!(stats.compressed_bytes_sent, stats.compressed_bytes_received);
+    }
+
+    #[test]
+    fn test_oversized_fragmented_message() {
+        let mut codec = MessageCodec::new().with_frame_options(FrameOptions {
+            max_frame_size: Some(1024),
+            ..FrameOptions::default()
+        });
+        let msg = InterProcessMessage::new_deserialized(
+            Arc::new(TestData(vec![0; 1000])),
+            StreamId::nil(),
+            SendOptions::default(),
+        );
+        // Each fragment fits in a frame, but the reassembled message does not.
+        let mut buf = BytesMut::new();
+        for fragment in msg.into_fragments(512) {
+            codec.encode(fragment, &mut buf).unwrap();
+        }
+        assert!(decode(&mut codec, &mut buf).is_err());
+    }
+
+    #[test]
+    fn test_oversized_compressed_frame() {
+        // Compresses to well below the maximum frame size, but decompresses to 4 KB.
+        let data = TestData(vec![0; 1000]);
+        for &compression in [Compression::Lz4, Compression::Zstd(3)].iter() {
+            let options = SendOptions {
+                compression,
+                ..SendOptions::default()
+            };
+            let mut buf = BytesMut::new();
+            encode_with(&mut MessageCodec::new(), data.clone(), options, &mut buf);
+            let mut codec = MessageCodec::new().with_frame_options(FrameOptions {
+                max_frame_size: Some(1024),
+                ..FrameOptions::default()
+            });
+            assert!(buf.len() < 1024, "{:?}", compression);
+            assert!(decode(&mut codec, &mut buf).is_err(), "{:?}", compression);
+        }
+    }
+
+    #[test]
+    fn test_corrupted_frame() {
+        let frame_options = FrameOptions {
+            checksums: true,
+            long_lengths: true,
+            max_frame_size: None,
+        };
+        let mut codec = MessageCodec::new().with_frame_options(frame_options);
+        let mut buf = BytesMut::new();
+        encode(&mut codec, TestData(vec![1, 2, 3]), &mut buf);
+        // Flip a bit of the content, which precedes the checksum.
+        let index = buf.len() - CHECKSUM_SIZE - 1;
+        buf[index] ^= 1;
+        assert!(decode(&mut codec, &mut buf).is_err());
+    }
+
+    #[test]
+    fn test_max_frame_size() {
+        let frame_options = FrameOptions {
+            checksums: false,
+            long_lengths: true,
+            max_frame_size: Some(64),
+        };
+        let mut buf = BytesMut::new();
+        let mut codec = MessageCodec::new().with_frame_options(frame_options);
+        encode(&mut codec, TestData(vec![0; 64]), &mut buf);
+        assert!(decode(&mut codec, &mut buf).is_err());
+
+        // Sizes whose sum overflows are rejected without a maximum frame size.
+        let mut codec = MessageCodec::new().with_frame_options(FrameOptions {
+            max_frame_size: None,
+            ..frame_options
+        });
+        let mut buf = BytesMut::new();
+        buf.put_u64(u64::MAX);
+        buf.put_u64(u64::MAX);
+        assert!(decode(&mut codec, &mut buf).is_err());
+    }
+}
@@ -1,29 +1,43 @@
 use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     fmt::Debug,
     net::SocketAddr,
     sync::Arc,
     time::{Duration, Instant},
 };
 
-use byteorder::{ByteOrder, NetworkEndian, WriteBytesExt};
 use bytes::BytesMut;
-use futures::future;
+use futures::{future, stream::FuturesUnordered, StreamExt};
 use serde::{Deserialize, Serialize};
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, UnboundedReceiver},
+    task::JoinHandle,
     time::sleep,
 };
 
-use crate::{dataflow::stream::StreamId, node::NodeId, OperatorId};
+use crate::{
+    dataflow::{graph::GraphFingerprint, stream::StreamId},
+    node::NodeId,
+    OperatorId,
+};
 
 // Private submodules
+mod clock_sync;
+mod compression;
 mod control_message_codec;
 mod control_message_handler;
 mod endpoints;
 mod errors;
+mod failure_detector;
+mod handshake;
+mod membership;
 mod message_codec;
 mod serializable;
+#[cfg(target_os = "linux")]
+mod shared_memory;
+mod tls;
+mod transport;
+mod udp;
 
 // Crate-wide visible submodules
 pub(crate) mod pusher;
@@ -31,33 +45,90 @@ pub(crate) mod receivers;
 pub(crate) mod senders;
 
 // Private imports
+use handshake::Handshake;
 use serializable::Serializable;
 
 // Module-wide exports
+pub(crate) use clock_sync::{ClockOffsets, ClockSynchronizer};
+pub(crate) use compression::StreamStatisticsMap;
 pub(crate) use control_message_codec::ControlMessageCodec;
-pub(crate) use control_message_handler::ControlMessageHandler;
+pub(crate) use control_message_handler::{ControlMessageHandler, SharedChannels};
 pub(crate) use errors::{CodecError, CommunicationError, TryRecvError};
+pub(crate) use failure_detector::FailureDetector;
+pub(crate) use membership::discover_nodes;
 pub(crate) use message_codec::MessageCodec;
 pub(crate) use pusher::{Pusher, PusherT};
+pub(crate) use udp::{UdpReceiver, UdpSender};
 
 // Crate-wide exports
-pub(crate) use endpoints::{RecvEndpoint, SendEndpoint};
+pub(crate) use endpoints::{RecvEndpoint, SendEndpoint, SendOptions};
+
+// Public exports
+pub use clock_sync::ClockOffset;
+pub use compression::{Compression, StreamStatistics};
+pub use endpoints::Priority;
+pub use handshake::FrameOptions;
+#[cfg(target_os = "linux")]
+pub use shared_memory::SharedMemoryTransport;
+pub use tls::TlsConfig;
+#[cfg(unix)]
+pub use transport::UnixSocketTransport;
+pub use transport::{Connection, TcpTransport, Transport, TransportListener};
+pub use udp::Delivery;
 
 /// Message sent between nodes in order to coordinate node and operator initialization.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ControlMessage {
-    AllOperatorsInitializedOnNode(NodeId),
-    OperatorInitialized(OperatorId),
+    /// Sent by a node once its operators are set up, along with the number of deadlines each
+    /// operator registered.
+    AllOperatorsInitializedOnNode(NodeId, HashMap<OperatorId, usize>),
+    /// Sent by an operator once set up, along with the number of deadlines it registered.
+    OperatorInitialized(OperatorId, usize),
     RunOperator(OperatorId),
     DataSenderInitialized(NodeId),
     DataReceiverInitialized(NodeId),
     ControlSenderInitialized(NodeId),
     ControlReceiverInitialized(NodeId),
+    /// Sent by a node once connected in order to check that all nodes execute the same graph.
+    GraphFingerprint(NodeId, GraphFingerprint),
+    /// Sent by a node while setting up channels. Maps each stream which crosses process
+    /// boundaries at the node to a fingerprint of the type of data sent on the stream.
+    StreamTypes(NodeId, BTreeMap<StreamId, String>),
+    /// Periodically sent by a node to signal that it is alive.
+    Heartbeat(NodeId),
+    /// Signals that a node has failed. Only sent between tasks within a node.
+    NodeFailure(NodeId),
+    /// Sent by a node once all of its operators completed.
+    NodeFinished(NodeId),
+    /// Periodically sent by a node to estimate the offsets of the clocks of other nodes, along
+    /// with the time at which it was sent.
+    ClockProbe(NodeId, u64),
+    /// Answers a [`ControlMessage::ClockProbe`] with the time at which the probe was sent, the
+    /// time at which it was received, and the time at which the reply was sent.
+    ClockReply(NodeId, u64, u64, u64),
+    /// Instructs a sender to flush pending messages and close its connection.
+    /// Only sent between tasks within a node.
+    CloseConnection,
+    /// Sent by a node to the coordinator in order to join the cluster, along with the node's
+    /// data and control addresses.
+    RegisterNode(NodeId, SocketAddr, SocketAddr),
+    /// Sent by the coordinator to the nodes which join before the dataflow starts once enough
+    /// nodes joined, and to each node which joins later. Lists the data and control addresses of
+    /// the nodes, indexed by node, and the nodes which joined so far in the order they joined.
+    NodeTable(Vec<SocketAddr>, Vec<SocketAddr>, Vec<NodeId>),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageMetadata {
     pub stream_id: StreamId,
+    /// The compression applied to the message's data.
+    pub compression: Compression,
+    /// Time at which the message was written to the connection, in nanoseconds since the UNIX
+    /// epoch on the clock of the sending node.
+    pub sent_at: u64,
+    /// Whether the message continues in the next message on the stream. Large bulk messages are
+    /// split into fragments, so that messages of higher priority can be sent in between.
+    pub more_fragments: bool,
 }
 
 #[derive(Clone)]
@@ -69,6 +140,8 @@ pub enum InterProcessMessage {
     Deserialized {
         metadata: MessageMetadata,
         data: Arc<dyn Serializable + Send + Sync>,
+        /// Whether the message should be flushed immediately.
+        low_latency: bool,
     },
 }
 
@@ -77,108 +150,278 @@ impl InterProcessMessage {
         Self::Serialized { metadata, bytes }
     }
 
-    pub fn new_deserialized(
+    pub(crate) fn new_deserialized(
         data: Arc<dyn Serializable + Send + Sync>,
         stream_id: StreamId,
+        options: SendOptions,
     ) -> Self {
         Self::Deserialized {
-            metadata: MessageMetadata { stream_id },
+            metadata: MessageMetadata {
+                stream_id,
+                compression: options.compression,
+                // Set when the message is encoded.
+                sent_at: 0,
+                more_fragments: false,
+            },
             data,
+            low_latency: options.low_latency || options.priority == Priority::High,
+        }
+    }
+
+    /// Whether the message should be sent without waiting for other messages to batch with.
+    pub fn is_low_latency(&self) -> bool {
+        match self {
+            Self::Serialized { .. } => false,
+            Self::Deserialized { low_latency, .. } => *low_latency,
         }
     }
+
+    /// Splits the message into serialized fragments of at most `fragment_size` bytes if its
+    /// serialized data is larger, and otherwise returns the message.
+    pub(crate) fn into_fragments(self, fragment_size: usize) -> VecDeque<Self> {
+        let (metadata, data) = match self {
+            Self::Deserialized { metadata, data, .. }
+                if data.serialized_size().unwrap() > fragment_size =>
+            {
+                (metadata, data)
+            }
+            msg => return vec![msg].into(),
+        };
+        let mut bytes = data.encode().unwrap();
+        let mut fragments = VecDeque::new();
+        while !bytes.is_empty() {
+            let fragment = bytes.split_to(fragment_size.min(bytes.len()));
+            let metadata = MessageMetadata {
+                more_fragments: !bytes.is_empty(),
+                ..metadata.clone()
+            };
+            fragments.push_back(Self::new_serialized(fragment, metadata));
+        }
+        fragments
+    }
 }
 
-/// Returns a vec of TCPStreams; one for each node pair.
+/// Returns a connection to each other node in `joined`, and a channel on which the connections
+/// to the other nodes are received as they join.
+///
+/// `joined` lists the nodes which joined before the node or along with it in the order they
+/// joined, and the node connects to the nodes which precede it, and waits for the connections of
+/// the nodes which follow it. Nodes which are not in `joined` join later, and connect to the node.
 ///
-/// The function creates a TCPStream to each node address. The node address vector stores
-/// the network address of each node, and is indexed by node id.
-pub async fn create_tcp_streams(
+/// The function connects to each node address using the transport configured for the node pair,
+/// or via shared memory if `shared_memory` is set and the node runs on the same machine, or via
+/// TCP otherwise. It then establishes TLS on the connections if `tls` is set. The nodes then exchange
+/// [handshakes](Handshake) to check that they run compatible versions of ERDOS and frame
+/// messages with the same `frame_options`. The node address vector stores the network address
+/// of each node, and is indexed by node id.
+pub async fn create_connections(
     node_addrs: Vec<SocketAddr>,
     node_id: NodeId,
-) -> Vec<(NodeId, TcpStream)> {
+    joined: &[NodeId],
+    transports: &HashMap<NodeId, Arc<dyn Transport>>,
+    shared_memory: bool,
+    tls: Option<&TlsConfig>,
+    frame_options: FrameOptions,
+) -> (
+    Vec<(NodeId, Box<dyn Connection>)>,
+    UnboundedReceiver<(NodeId, Box<dyn Connection>)>,
+) {
     let node_addr = node_addrs[node_id];
-    // Connect to the nodes that have a lower id than the node.
-    let connect_streams_fut = connect_to_nodes(node_addrs[..node_id].to_vec(), node_id);
-    // Wait for connections from the nodes that have a higher id than the node.
-    let stream_fut = await_node_connections(node_addr, node_addrs.len() - node_id - 1);
+    let position = joined
+        .iter()
+        .position(|&other_node_id| other_node_id == node_id)
+        .unwrap_or_else(|| panic!("Node {}: the node has not joined", node_id));
+    let late_node_ids = (0..node_addrs.len())
+        .filter(|other_node_id| !joined.contains(other_node_id))
+        .collect();
+    let transports = &select_transports(&node_addrs, node_id, transports, shared_memory);
+    let handshake = Handshake::new(node_id, frame_options);
+    // Connect to the nodes that joined before the node.
+    let connect_streams_fut = connect_to_nodes(
+        joined[..position]
+            .iter()
+            .map(|&other_node_id| (other_node_id, node_addrs[other_node_id]))
+            .collect(),
+        &handshake,
+        transports,
+        tls,
+    );
+    // Wait for connections from the nodes that joined after the node.
+    let stream_fut = await_node_connections(
+        node_addr,
+        &handshake,
+        joined[position + 1..].to_vec(),
+        late_node_ids,
+        transports,
+        shared_memory,
+        tls,
+    );
     // Wait until all connections are established.
     match future::try_join(connect_streams_fut, stream_fut).await {
-        Ok((mut streams, await_streams)) => {
-            // Streams contains a TCP stream for each other node.
+        Ok((mut streams, (await_streams, late_streams))) => {
+            // Streams contains a connection to each other node which joined.
             streams.extend(await_streams);
-            streams
+            (streams, late_streams)
         }
         Err(e) => {
             tracing::error!(
-                "Node {}: creating TCP streams errored with {:?}",
+                "Node {}: creating connections errored with {:?}",
                 node_id,
                 e
             );
             panic!(
-                "Node {}: creating TCP streams errored with {:?}",
+                "Node {}: creating connections errored with {:?}",
                 node_id, e
             )
         }
     }
 }
 
-/// Connects to all addresses and sends node id.
+/// Adds a shared memory transport for the nodes which run on the same machine as `node_id` and
+/// have no configured transport, if `shared_memory` is set and supported.
+fn select_transports(
+    node_addrs: &[SocketAddr],
+    node_id: NodeId,
+    transports: &HashMap<NodeId, Arc<dyn Transport>>,
+    shared_memory: bool,
+) -> HashMap<NodeId, Arc<dyn Transport>> {
+    let mut transports = transports.clone();
+    if let Some(transport) = shared_memory_transport().filter(|_| shared_memory) {
+        for (other_node_id, &other_addr) in node_addrs.iter().enumerate() {
+            if other_node_id != node_id && same_host(node_addrs[node_id], other_addr) {
+                transports
+                    .entry(other_node_id)
+                    .or_insert_with(|| Arc::clone(&transport));
+            }
+        }
+    }
+    transports
+}
+
+#[cfg(target_os = "linux")]
+fn shared_memory_transport() -> Option<Arc<dyn Transport>> {
+    // All nodes must place their sockets in the same directory, so it does not depend on the
+    // environment of the process.
+    Some(Arc::new(SharedMemoryTransport::new("/tmp")))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn shared_memory_transport() -> Option<Arc<dyn Transport>> {
+    None
+}
+
+/// Whether the nodes listening at `a` and `b` run on the same machine. Both nodes of a pair
+/// must come to the same conclusion, so only the addresses are considered.
+fn same_host(a: SocketAddr, b: SocketAddr) -> bool {
+    (a.ip().is_loopback() && b.ip().is_loopback()) || (a.ip() == b.ip() && !a.ip().is_unspecified())
+}
+
+/// Returns the transport used to connect to `node_id`, which defaults to TCP.
+fn transport(
+    transports: &HashMap<NodeId, Arc<dyn Transport>>,
+    node_id: NodeId,
+) -> Arc<dyn Transport> {
+    transports
+        .get(&node_id)
+        .cloned()
+        .unwrap_or_else(|| Arc::new(TcpTransport))
+}
+
+/// Connects to all node addresses and exchanges handshakes.
 ///
-/// The function returns a vector of `(NodeId, TcpStream)` for each connection.
+/// The function returns a vector of `(NodeId, Connection)` for each connection.
 async fn connect_to_nodes(
-    addrs: Vec<SocketAddr>,
-    node_id: NodeId,
-) -> Result<Vec<(NodeId, TcpStream)>, std::io::Error> {
+    addrs: Vec<(NodeId, SocketAddr)>,
+    handshake: &Handshake,
+    transports: &HashMap<NodeId, Arc<dyn Transport>>,
+    tls: Option<&TlsConfig>,
+) -> Result<Vec<(NodeId, Box<dyn Connection>)>, std::io::Error> {
     let mut connect_futures = Vec::new();
-    // For each node address, launch a task that tries to create a TCP stream to the node.
-    for addr in addrs.iter() {
-        connect_futures.push(connect_to_node(addr, node_id));
+    // For each node address, launch a task that tries to connect to the node.
+    for (other_node_id, addr) in addrs.iter() {
+        connect_futures.push(connect_to_node(
+            addr,
+            handshake,
+            *other_node_id,
+            transport(transports, *other_node_id),
+            tls,
+        ));
     }
     // Wait for all tasks to complete successfully.
-    let tcp_results = future::try_join_all(connect_futures).await?;
-    let streams: Vec<(NodeId, TcpStream)> = (0..tcp_results.len()).zip(tcp_results).collect();
+    let results = future::try_join_all(connect_futures).await?;
+    let streams: Vec<(NodeId, Box<dyn Connection>)> = addrs
+        .into_iter()
+        .map(|(other_node_id, _)| other_node_id)
+        .zip(results)
+        .collect();
     Ok(streams)
 }
 
-/// Creates TCP stream connection to an address and writes the node id on the TCP stream.
+/// Connects to the node `dst_node_id` at an address and exchanges handshakes with it.
 ///
-/// The function keeps on retrying until it connects successfully.
+/// The function keeps on retrying until it connects successfully. Fails if TLS cannot be
+/// established, e.g. because the other node does not present its listed certificate, or if the
+/// handshake fails, e.g. because another service or another node listens at the address.
 async fn connect_to_node(
     dst_addr: &SocketAddr,
-    node_id: NodeId,
-) -> Result<TcpStream, std::io::Error> {
+    handshake: &Handshake,
+    dst_node_id: NodeId,
+    transport: Arc<dyn Transport>,
+    tls: Option<&TlsConfig>,
+) -> Result<Box<dyn Connection>, std::io::Error> {
+    let node_id = handshake.node_id;
     // Keeps on reatying to connect to `dst_addr` until it succeeds.
     let mut last_err_msg_time = Instant::now();
     loop {
-        match TcpStream::connect(dst_addr).await {
-            Ok(mut stream) => {
-                stream.set_nodelay(true).expect("couldn't disable Nagle");
-                // Send the node id so that the TCP server knows with which
+        match transport.connect(*dst_addr).await {
+            Ok(stream) => {
+                let mut stream = match tls {
+                    Some(tls) => tls.connect(stream, dst_node_id).await.map_err(|e| {
+                        tracing::error!(
+                            "Node {}: could not establish TLS with node {} at {}; error {}",
+                            node_id,
+                            dst_node_id,
+                            dst_addr,
+                            e
+                        );
+                        e
+                    })?,
+                    None => stream,
+                };
+                // Exchange handshakes so that the listening node knows with which
                 // node the connection was established.
-                let mut buffer: Vec<u8> = Vec::new();
-                WriteBytesExt::write_u32::<NetworkEndian>(&mut buffer, node_id as u32)?;
-                loop {
-                    match stream.write(&buffer[..]).await {
-                        Ok(_) => return Ok(stream),
-                        Err(e) => {
-                            tracing::error!(
-                                "Node {}: could not send node id to {}; error {}; retrying in 100 ms",
-                                node_id,
-                                dst_addr,
-                                e
-                            );
-                        }
-                    }
+                let peer = handshake.exchange(&mut stream).await.map_err(|e| {
+                    tracing::error!(
+                        "Node {}: handshake with node {} at {} failed; error {}",
+                        node_id,
+                        dst_node_id,
+                        dst_addr,
+                        e
+                    );
+                    e
+                })?;
+                if peer.node_id != dst_node_id {
+                    let message = format!(
+                        "expected node {} at {}, but node {} answered",
+                        dst_node_id, dst_addr, peer.node_id
+                    );
+                    tracing::error!("Node {}: {}", node_id, message);
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        message,
+                    ));
                 }
+                return Ok(stream);
             }
             Err(e) => {
                 // Only print connection errors every 1s.
                 let now = Instant::now();
                 if now.duration_since(last_err_msg_time) >= Duration::from_secs(1) {
                     tracing::error!(
-                        "Node {}: could not connect to {}; error {}; retrying",
+                        "Node {}: could not connect to {} via {}; error {}; retrying",
                         node_id,
                         dst_addr,
+                        transport.kind(),
                         e
                     );
                     last_err_msg_time = now;
@@ -190,39 +433,252 @@ async fn connect_to_node(
     }
 }
 
-/// Awaiting for connections from `expected_conns` other nodes.
+/// Awaiting for connections from the nodes `node_ids`, and from the nodes `late_node_ids` which
+/// join later.
+///
+/// The node listens at `addr` once for each kind of transport used by the nodes. The transport
+/// a late node selects depends on its address, which is unknown until it joins, so the node
+/// accepts late nodes via all transports they may select. Upon a new connection, the node
+/// exchanges handshakes to learn the id of the node that initiated the connection. Connections
+/// from peers which claim the id of a node that is not expected or already connected, which
+/// connect via another transport than expected, which fail to authenticate with TLS, or whose
+/// handshake fails, are rejected.
 ///
-/// Upon a new connection, the function reads from the stream the id of the node that initiated
-/// the connection.
+/// Returns the connections of `node_ids` once all of them connected, and a channel on which the
+/// connections of the late nodes are received. The node keeps listening until all nodes
+/// connected.
 async fn await_node_connections(
     addr: SocketAddr,
-    expected_conns: usize,
-) -> Result<Vec<(NodeId, TcpStream)>, std::io::Error> {
-    let mut await_futures = Vec::new();
-    let listener = TcpListener::bind(&addr).await?;
-    // Awaiting for `expected_conns` conections.
-    for _ in 0..expected_conns {
-        let (stream, _) = listener.accept().await?;
-        stream.set_nodelay(true).expect("couldn't disable Nagle");
-        // Launch a task that reads the node id from the TCP stream.
-        await_futures.push(read_node_id(stream));
-    }
-    // Await until we've received `expected_conns` node ids.
-    future::try_join_all(await_futures).await
-}
-
-/// Reads a node id from a TCP stream.
+    handshake: &Handshake,
+    node_ids: Vec<NodeId>,
+    late_node_ids: Vec<NodeId>,
+    transports: &HashMap<NodeId, Arc<dyn Transport>>,
+    shared_memory: bool,
+    tls: Option<&TlsConfig>,
+) -> Result<
+    (
+        Vec<(NodeId, Box<dyn Connection>)>,
+        UnboundedReceiver<(NodeId, Box<dyn Connection>)>,
+    ),
+    std::io::Error,
+> {
+    // The kinds of transport via which each node may connect, and a transport of each kind.
+    let mut expected_nodes: HashMap<NodeId, Vec<&'static str>> = HashMap::new();
+    let mut kinds: BTreeMap<&'static str, Arc<dyn Transport>> = BTreeMap::new();
+    for &other_node_id in node_ids.iter() {
+        let transport = transport(transports, other_node_id);
+        expected_nodes.insert(other_node_id, vec![transport.kind()]);
+        kinds.entry(transport.kind()).or_insert(transport);
+    }
+    for &other_node_id in late_node_ids.iter() {
+        let mut candidates = vec![transport(transports, other_node_id)];
+        if !transports.contains_key(&other_node_id) {
+            candidates.extend(shared_memory_transport().filter(|_| shared_memory));
+        }
+        for transport in candidates {
+            expected_nodes
+                .entry(other_node_id)
+                .or_default()
+                .push(transport.kind());
+            kinds.entry(transport.kind()).or_insert(transport);
+        }
+    }
+
+    let (streams_tx, mut streams_rx) = mpsc::unbounded_channel();
+    let (late_streams_tx, late_streams_rx) = mpsc::unbounded_channel();
+    if !expected_nodes.is_empty() {
+        // Accept connections in separate tasks, as accepting may not be cancellation safe.
+        let (accepted_tx, accepted_rx) = mpsc::unbounded_channel();
+        let mut accept_tasks = Vec::new();
+        for (kind, transport) in kinds {
+            let mut listener = transport.bind(addr).await?;
+            let accepted_tx = accepted_tx.clone();
+            accept_tasks.push(tokio::spawn(async move {
+                loop {
+                    let result = listener.accept().await;
+                    let failed = result.is_err();
+                    if accepted_tx.send((kind, result)).is_err() || failed {
+                        break;
+                    }
+                }
+            }));
+        }
+        let initial_node_ids: HashSet<NodeId> = node_ids.iter().copied().collect();
+        tokio::spawn(accept_node_connections(
+            handshake.clone(),
+            accepted_rx,
+            expected_nodes,
+            tls.cloned(),
+            move |other_node_id, stream| {
+                let tx = if initial_node_ids.contains(&other_node_id) {
+                    &streams_tx
+                } else {
+                    &late_streams_tx
+                };
+                // The receiver was dropped if the node stopped.
+                tx.send((other_node_id, stream)).ok();
+            },
+            accept_tasks,
+        ));
+    }
+
+    let mut streams = Vec::with_capacity(node_ids.len());
+    while streams.len() < node_ids.len() {
+        match streams_rx.recv().await {
+            Some(stream) => streams.push(stream),
+            None => return Err(std::io::Error::from(std::io::ErrorKind::BrokenPipe)),
+        }
+    }
+    Ok((streams, late_streams_rx))
+}
+
+/// Exchanges handshakes on the connections which the listeners accept, and passes the
+/// connections of the `expected_nodes` to `on_connection` until each of them connected.
+async fn accept_node_connections(
+    handshake: Handshake,
+    mut accepted_rx: UnboundedReceiver<(&'static str, std::io::Result<Box<dyn Connection>>)>,
+    mut expected_nodes: HashMap<NodeId, Vec<&'static str>>,
+    tls: Option<TlsConfig>,
+    on_connection: impl Fn(NodeId, Box<dyn Connection>),
+    accept_tasks: Vec<JoinHandle<()>>,
+) {
+    let node_id = handshake.node_id;
+    let handshake = &handshake;
+    let tls = tls.as_ref();
+    // Connections which are exchanging handshakes with the node that initiated them.
+    let mut pending = FuturesUnordered::new();
+    while !expected_nodes.is_empty() {
+        tokio::select! {
+            accepted = accepted_rx.recv() => {
+                let (kind, stream) = match accepted {
+                    Some((kind, Ok(stream))) => (kind, stream),
+                    Some((kind, Err(e))) => {
+                        tracing::error!(
+                            "Node {}: stopped accepting connections via {}; error {}",
+                            node_id,
+                            kind,
+                            e
+                        );
+                        break;
+                    }
+                    None => break,
+                };
+                pending.push(async move {
+                    let result = match tls {
+                        Some(tls) => tls.accept(stream, handshake).await,
+                        None => read_handshake(stream, handshake).await,
+                    };
+                    (kind, result)
+                });
+            }
+            Some((kind, result)) = pending.next(), if !pending.is_empty() => match result {
+                Ok((other_node_id, stream))
+                    if matches!(
+                        expected_nodes.get(&other_node_id),
+                        Some(kinds) if kinds.contains(&kind)
+                    ) =>
+                {
+                    expected_nodes.remove(&other_node_id);
+                    on_connection(other_node_id, stream);
+                }
+                Ok((other_node_id, _)) => tracing::warn!(
+                    "Node {}: rejected connection via {} from a peer claiming to be node {}, \
+                    which is not expected or already connected",
+                    node_id,
+                    kind,
+                    other_node_id
+                ),
+                Err(e) => tracing::warn!("Node {}: rejected connection; error {}", node_id, e),
+            },
+        }
+    }
+    for accept_task in accept_tasks {
+        accept_task.abort();
+    }
+}
+
+/// Exchanges handshakes on a connection.
 ///
 /// The method is used to discover the id of the node that initiated the connection.
-async fn read_node_id(mut stream: TcpStream) -> Result<(NodeId, TcpStream), std::io::Error> {
-    let mut buffer = [0u8; 4];
-    match stream.read_exact(&mut buffer).await {
-        Ok(n) => n,
-        Err(e) => {
-            tracing::error!("failed to read from socket; err = {:?}", e);
-            return Err(e);
-        }
-    };
-    let node_id: u32 = NetworkEndian::read_u32(&buffer);
-    Ok((node_id as NodeId, stream))
+async fn read_handshake(
+    mut stream: Box<dyn Connection>,
+    handshake: &Handshake,
+) -> Result<(NodeId, Box<dyn Connection>), std::io::Error> {
+    let peer = handshake.exchange(&mut stream).await?;
+    Ok((peer.node_id, stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_host() {
+        let addr = |addr: &str| addr.parse::<SocketAddr>().unwrap();
+        assert!(same_host(addr("127.0.0.1:9000"), addr("127.0.0.2:9001")));
+        assert!(same_host(addr("10.0.0.1:9000"), addr("10.0.0.1:9001")));
+        assert!(!same_host(addr("10.0.0.1:9000"), addr("10.0.0.2:9000")));
+        assert!(!same_host(addr("127.0.0.1:9000"), addr("10.0.0.1:9001")));
+        assert!(!same_host(addr("0.0.0.0:9000"), addr("0.0.0.0:9001")));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_select_transports() {
+        let node_addrs: Vec<SocketAddr> = ["127.0.0.1:9000", "127.0.0.1:9001", "10.0.0.2:9000"]
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect();
+        let transports = select_transports(&node_addrs, 0, &HashMap::new(), true);
+        assert_eq!(transports.len(), 1);
+        assert_eq!(transports[&1].kind(), "shared-memory");
+
+        // Configured transports take precedence.
+        let mut configured: HashMap<NodeId, Arc<dyn Transport>> = HashMap::new();
+        configured.insert(1, Arc::new(TcpTransport));
+        let transports = select_transports(&node_addrs, 0, &configured, true);
+        assert_eq!(transports[&1].kind(), "tcp");
+
+        assert!(select_transports(&node_addrs, 0, &HashMap::new(), false).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_connections_late_node() {
+        let node_addrs: Vec<SocketAddr> = (0..3)
+            .map(|_| {
+                let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+                listener.local_addr().unwrap()
+            })
+            .collect();
+        let connect = |node_id: NodeId, joined: Vec<NodeId>| {
+            let node_addrs = node_addrs.clone();
+            tokio::spawn(async move {
+                create_connections(
+                    node_addrs,
+                    node_id,
+                    &joined,
+                    &HashMap::new(),
+                    false,
+                    None,
+                    FrameOptions::default(),
+                )
+                .await
+            })
+        };
+        // Nodes 0 and 1 start without node 2.
+        let node_0 = connect(0, vec![0, 1]);
+        let node_1 = connect(1, vec![0, 1]);
+        let (streams_0, mut late_streams_0) = node_0.await.unwrap();
+        let (streams_1, mut late_streams_1) = node_1.await.unwrap();
+        assert_eq!(streams_0.iter().map(|(id, _)| *id).collect::<Vec<_>>(), [1]);
+        assert_eq!(streams_1.iter().map(|(id, _)| *id).collect::<Vec<_>>(), [0]);
+
+        // Node 2 connects to both nodes once it joins.
+        let (streams_2, _) = connect(2, vec![0, 1, 2]).await.unwrap();
+        let mut node_ids: Vec<NodeId> = streams_2.iter().map(|(id, _)| *id).collect();
+        node_ids.sort_unstable();
+        assert_eq!(node_ids, [0, 1]);
+        assert_eq!(late_streams_0.recv().await.unwrap().0, 2);
+        assert_eq!(late_streams_1.recv().await.unwrap().0, 2);
+    }
 }
@@ -2,6 +2,7 @@ use std::{
     any::Any,
     fmt::{self, Debug},
     sync::Arc,
+    time::SystemTime,
 };
 
 use bytes::BytesMut;
@@ -22,7 +23,15 @@ pub trait PusherT: Send {
     /// To be used to clone a boxed pusher.
     fn box_clone(&self) -> Box<dyn PusherT>;
     /// Creates message from bytes and sends it to endpoints.
-    fn send_from_bytes(&mut self, buf: BytesMut) -> Result<(), CommunicationError>;
+    ///
+    /// `sent_at` is the time at which another node sent the message, on the clock of this node.
+    fn send_from_bytes(
+        &mut self,
+        buf: BytesMut,
+        sent_at: Option<SystemTime>,
+    ) -> Result<(), CommunicationError>;
+    /// Closes the endpoints which should be closed when the node sending the data fails.
+    fn close_on_failure(&mut self) -> Result<(), CommunicationError>;
 }
 
 /// Internal structure used to send data on a collection of [`SendEndpoint`]s.
@@ -30,6 +39,10 @@ pub trait PusherT: Send {
 pub struct Pusher<D: Debug + Clone + Send> {
     // TODO: We might want to order the endpoints by the priority of their tasks.
     endpoints: Vec<SendEndpoint<D>>,
+    /// Indices of the endpoints to which `close_msg` is sent if the sending node fails.
+    failure_endpoints: Vec<usize>,
+    /// Message which closes the stream (i.e. a top watermark).
+    close_msg: Option<D>,
 }
 
 /// Zero-copy implementation of the pusher.
@@ -37,6 +50,8 @@ impl<D: 'static + Serializable + Send + Sync + Debug> Pusher<Arc<D>> {
     pub fn new() -> Self {
         Self {
             endpoints: Vec::new(),
+            failure_endpoints: Vec::new(),
+            close_msg: None,
         }
     }
 
@@ -44,6 +59,17 @@ impl<D: 'static + Serializable + Send + Sync + Debug> Pusher<Arc<D>> {
         self.endpoints.push(endpoint);
     }
 
+    /// Adds an endpoint which receives `close_msg` if the node sending the data fails.
+    pub fn add_endpoint_closed_on_failure(
+        &mut self,
+        endpoint: SendEndpoint<Arc<D>>,
+        close_msg: Arc<D>,
+    ) {
+        self.failure_endpoints.push(self.endpoints.len());
+        self.endpoints.push(endpoint);
+        self.close_msg = Some(close_msg);
+    }
+
     pub fn send(&mut self, msg: Arc<D>) -> Result<(), CommunicationError> {
         for endpoint in self.endpoints.iter_mut() {
             endpoint.send(Arc::clone(&msg))?;
@@ -72,14 +98,29 @@ where
         Box::new((*self).clone())
     }
 
-    fn send_from_bytes(&mut self, mut buf: BytesMut) -> Result<(), CommunicationError> {
+    fn send_from_bytes(
+        &mut self,
+        mut buf: BytesMut,
+        sent_at: Option<SystemTime>,
+    ) -> Result<(), CommunicationError> {
         if !self.endpoints.is_empty() {
             let msg = match Deserializable::decode(&mut buf)? {
                 DeserializedMessage::<D>::Owned(msg) => msg,
                 DeserializedMessage::<D>::Ref(msg) => msg.clone(),
             };
             let msg_arc = Arc::new(msg);
-            self.send(msg_arc)?;
+            for endpoint in self.endpoints.iter_mut() {
+                endpoint.send_received(Arc::clone(&msg_arc), sent_at)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn close_on_failure(&mut self) -> Result<(), CommunicationError> {
+        if let Some(close_msg) = self.close_msg.as_ref() {
+            for &i in self.failure_endpoints.iter() {
+                self.endpoints[i].send(Arc::clone(close_msg))?;
+            }
         }
         Ok(())
     }
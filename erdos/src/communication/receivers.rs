@@ -1,33 +1,30 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::SystemTime};
 
 use futures::{future, stream::SplitStream, FutureExt};
 use futures_util::stream::StreamExt;
-use tokio::{
-    net::TcpStream,
-    sync::{
-        mpsc::{self, UnboundedReceiver, UnboundedSender},
-        Mutex,
-    },
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Mutex,
 };
 use tokio_util::codec::Framed;
 
 use crate::{
     communication::{
-        CommunicationError, ControlMessage, ControlMessageCodec, ControlMessageHandler,
-        InterProcessMessage, MessageCodec, PusherT,
+        clock_sync, ClockOffsets, CommunicationError, Connection, ControlMessage,
+        ControlMessageCodec, ControlMessageHandler, InterProcessMessage, MessageCodec, PusherT,
     },
     dataflow::stream::StreamId,
-    node::NodeId,
+    node::{Clock, NodeId},
     scheduler::endpoints_manager::ChannelsToReceivers,
 };
 
-/// Listens on a TCP stream, and pushes messages it receives to operator executors.
+/// Listens on a connection, and pushes messages it receives to operator executors.
 #[allow(dead_code)]
 pub(crate) struct DataReceiver {
-    /// The id of the node the TCP stream is receiving data from.
+    /// The id of the node the connection is receiving data from.
     node_id: NodeId,
-    /// Framed TCP read stream.
-    stream: SplitStream<Framed<TcpStream, MessageCodec>>,
+    /// Framed read stream.
+    stream: SplitStream<Framed<Box<dyn Connection>, MessageCodec>>,
     /// Channel receiver on which new pusher updates are received.
     rx: UnboundedReceiver<(StreamId, Box<dyn PusherT>)>,
     /// Mapping between stream id to [`PusherT`] trait objects.
@@ -38,14 +35,20 @@ pub(crate) struct DataReceiver {
     control_tx: UnboundedSender<ControlMessage>,
     /// Tokio channel receiver from `ControlMessageHandler`.
     control_rx: UnboundedReceiver<ControlMessage>,
+    /// Whether the failure detector reported that the other node finished.
+    node_finished: bool,
+    /// Estimated clock offsets, used to convert the times at which messages were sent to the
+    /// clock of this node.
+    clock_offsets: ClockOffsets,
 }
 
 impl DataReceiver {
     pub(crate) async fn new(
         node_id: NodeId,
-        stream: SplitStream<Framed<TcpStream, MessageCodec>>,
+        stream: SplitStream<Framed<Box<dyn Connection>, MessageCodec>>,
         channels_to_receivers: Arc<Mutex<ChannelsToReceivers>>,
         control_handler: &mut ControlMessageHandler,
+        clock_offsets: ClockOffsets,
     ) -> Self {
         // Create a channel for this stream.
         let (tx, rx) = mpsc::unbounded_channel();
@@ -61,6 +64,8 @@ impl DataReceiver {
             stream_id_to_pusher: HashMap::new(),
             control_tx: control_handler.get_channel_to_handler(),
             control_rx,
+            node_finished: false,
+            clock_offsets,
         }
     }
 
@@ -69,7 +74,25 @@ impl DataReceiver {
         self.control_tx
             .send(ControlMessage::DataReceiverInitialized(self.node_id))
             .map_err(CommunicationError::from)?;
-        while let Some(res) = self.stream.next().await {
+        loop {
+            let res = tokio::select! {
+                res = self.stream.next() => match res {
+                    Some(res) => res,
+                    None => return self.on_connection_closed(None).await,
+                },
+                Some(control_msg) = self.control_rx.recv() => {
+                    match control_msg {
+                        ControlMessage::NodeFailure(node_id) if node_id == self.node_id => {
+                            return self.close_streams_on_failure().await;
+                        }
+                        ControlMessage::NodeFinished(node_id) if node_id == self.node_id => {
+                            self.node_finished = true;
+                        }
+                        _ => (),
+                    }
+                    continue;
+                }
+            };
             match res {
                 // Push the message to the listening operator executors.
                 Ok(msg) => {
@@ -79,14 +102,16 @@ impl DataReceiver {
                     // Send the message.
                     let (metadata, bytes) = match msg {
                         InterProcessMessage::Serialized { metadata, bytes } => (metadata, bytes),
-                        InterProcessMessage::Deserialized {
-                            metadata: _,
-                            data: _,
-                        } => unreachable!(),
+                        InterProcessMessage::Deserialized { .. } => unreachable!(),
                     };
+                    let sent_at = clock_sync::to_local_time(
+                        &self.clock_offsets,
+                        self.node_id,
+                        metadata.sent_at,
+                    );
                     match self.stream_id_to_pusher.get_mut(&metadata.stream_id) {
                         Some(pusher) => {
-                            if let Err(e) = pusher.send_from_bytes(bytes) {
+                            if let Err(e) = pusher.send_from_bytes(bytes, sent_at) {
                                 return Err(e);
                             }
                         }
@@ -96,9 +121,54 @@ impl DataReceiver {
                         ),
                     }
                 }
-                Err(e) => return Err(CommunicationError::from(e)),
+                Err(e) => {
+                    return self
+                        .on_connection_closed(Some(CommunicationError::from(e)))
+                        .await
+                }
             }
         }
+    }
+
+    /// Handles the connection closing, or failing with `error`.
+    ///
+    /// A crashed node's connection usually closes before its failure is detected, so this waits
+    /// for the failure detector to report whether the other node finished or failed, and closes
+    /// the streams originating on the node if it failed.
+    async fn on_connection_closed(
+        &mut self,
+        error: Option<CommunicationError>,
+    ) -> Result<(), CommunicationError> {
+        while !self.node_finished {
+            match self.control_rx.recv().await {
+                Some(ControlMessage::NodeFailure(node_id)) if node_id == self.node_id => {
+                    self.close_streams_on_failure().await?;
+                    break;
+                }
+                Some(ControlMessage::NodeFinished(node_id)) if node_id == self.node_id => {
+                    self.node_finished = true;
+                }
+                Some(_) => (),
+                None => break,
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Closes the streams originating on the failed node for operators which opted in via
+    /// [`OperatorConfig::close_streams_on_node_failure`](crate::dataflow::OperatorConfig::close_streams_on_node_failure).
+    async fn close_streams_on_failure(&mut self) -> Result<(), CommunicationError> {
+        tracing::warn!(
+            "DataReceiver: closing streams from failed node {}",
+            self.node_id
+        );
+        self.update_pushers().await;
+        for pusher in self.stream_id_to_pusher.values_mut() {
+            pusher.close_on_failure()?;
+        }
         Ok(())
     }
 
@@ -111,9 +181,9 @@ impl DataReceiver {
     }
 }
 
-/// Receives TCP messages, and pushes them to operators endpoints.
-/// The function receives a vector of framed TCP receiver halves.
-/// It launches a task that listens for new messages for each TCP connection.
+/// Receives messages, and pushes them to operators endpoints.
+/// The function receives a vector of framed receiver halves.
+/// It launches a task that listens for new messages for each connection.
 pub(crate) async fn run_receivers(
     mut receivers: Vec<DataReceiver>,
 ) -> Result<(), CommunicationError> {
@@ -122,24 +192,35 @@ pub(crate) async fn run_receivers(
     Ok(())
 }
 
-/// Listens on a TCP stream, and pushes control messages it receives to the node.
+/// Listens on a connection, and pushes control messages it receives to the node.
 #[allow(dead_code)]
 pub(crate) struct ControlReceiver {
     /// The id of the node the stream is receiving data from.
     node_id: NodeId,
-    /// Framed TCP read stream.
-    stream: SplitStream<Framed<TcpStream, ControlMessageCodec>>,
+    /// Framed read stream.
+    stream: SplitStream<Framed<Box<dyn Connection>, ControlMessageCodec>>,
     /// Tokio channel sender to `ControlMessageHandler`.
     control_tx: UnboundedSender<ControlMessage>,
     /// Tokio channel receiver from `ControlMessageHandler`.
     control_rx: UnboundedReceiver<ControlMessage>,
+    /// Tokio channel sender to the `FailureDetector`.
+    failure_detector_tx: UnboundedSender<ControlMessage>,
+    /// Tokio channel sender to the `ClockSynchronizer`.
+    clock_sync_tx: UnboundedSender<(ControlMessage, SystemTime)>,
+    /// Clock with which clock probes are timestamped.
+    clock: Clock,
+    /// Whether the other node announced that it finished, and will close the connection.
+    node_finished: bool,
 }
 
 impl ControlReceiver {
     pub(crate) fn new(
         node_id: NodeId,
-        stream: SplitStream<Framed<TcpStream, ControlMessageCodec>>,
+        stream: SplitStream<Framed<Box<dyn Connection>, ControlMessageCodec>>,
         control_handler: &mut ControlMessageHandler,
+        failure_detector_tx: UnboundedSender<ControlMessage>,
+        clock_sync_tx: UnboundedSender<(ControlMessage, SystemTime)>,
+        clock: Clock,
     ) -> Self {
         // Set up control channel.
         let (tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
@@ -149,6 +230,10 @@ impl ControlReceiver {
             stream,
             control_tx: control_handler.get_channel_to_handler(),
             control_rx,
+            failure_detector_tx,
+            clock_sync_tx,
+            clock,
+            node_finished: false,
         }
     }
 
@@ -161,21 +246,52 @@ impl ControlReceiver {
             .map_err(CommunicationError::from)?;
         while let Some(res) = self.stream.next().await {
             match res {
+                // Heartbeats and completion notices are handled by the failure detector.
+                Ok(msg @ ControlMessage::Heartbeat(_))
+                | Ok(msg @ ControlMessage::NodeFinished(_)) => {
+                    if let ControlMessage::NodeFinished(_) = msg {
+                        self.node_finished = true;
+                    }
+                    self.failure_detector_tx
+                        .send(msg)
+                        .map_err(CommunicationError::from)?;
+                }
+                // Clock probes are timestamped upon receipt for an accurate offset estimate.
+                Ok(msg @ ControlMessage::ClockProbe(..))
+                | Ok(msg @ ControlMessage::ClockReply(..)) => {
+                    self.clock_sync_tx
+                        .send((msg, self.clock.system_time()))
+                        .map_err(CommunicationError::from)?;
+                }
                 Ok(msg) => {
                     self.control_tx
                         .send(msg)
                         .map_err(CommunicationError::from)?;
                 }
-                Err(e) => return Err(CommunicationError::from(e)),
+                Err(e) => {
+                    self.notify_connection_lost();
+                    return Err(CommunicationError::from(e));
+                }
             }
         }
+        if !self.node_finished {
+            self.notify_connection_lost();
+        }
         Ok(())
     }
+
+    /// Notifies the failure detector that the control connection to the other node was lost.
+    fn notify_connection_lost(&self) {
+        tracing::warn!("Lost control connection to node {}", self.node_id);
+        self.failure_detector_tx
+            .send(ControlMessage::NodeFailure(self.node_id))
+            .ok();
+    }
 }
 
-/// Receives TCP messages, and pushes them to the ControlHandler
-/// The function receives a vector of framed TCP receiver halves.
-/// It launches a task that listens for new messages for each TCP connection.
+/// Receives messages, and pushes them to the ControlHandler
+/// The function receives a vector of framed receiver halves.
+/// It launches a task that listens for new messages for each connection.
 pub(crate) async fn run_control_receivers(
     mut receivers: Vec<ControlReceiver>,
 ) -> Result<(), CommunicationError> {
@@ -1,60 +1,76 @@
-use futures::{future, stream::SplitSink};
+use futures::{future, stream::SplitSink, FutureExt};
 use futures_util::sink::SinkExt;
-use std::sync::Arc;
+use std::{collections::VecDeque, sync::Arc, time::Duration};
 use tokio::{
     self,
-    net::TcpStream,
     sync::{
         mpsc::{self, UnboundedReceiver, UnboundedSender},
         Mutex,
     },
+    time::{timeout_at, Instant},
 };
 use tokio_util::codec::Framed;
 
 use crate::communication::{
-    CommunicationError, ControlMessage, ControlMessageCodec, ControlMessageHandler,
+    CommunicationError, Connection, ControlMessage, ControlMessageCodec, ControlMessageHandler,
     InterProcessMessage, MessageCodec,
 };
 use crate::node::NodeId;
 use crate::scheduler::endpoints_manager::ChannelsToSenders;
 
+/// The largest fragment into which bulk messages are split, so that messages of higher
+/// priority do not wait until a large bulk message is written to the connection.
+const BULK_FRAGMENT_SIZE: usize = 64 * 1024;
+
 #[allow(dead_code)]
-/// The [`DataSender`] pulls messages from a FIFO inter-thread channel.
+/// The [`DataSender`] pulls messages from a FIFO inter-thread channel per
+/// [`Priority`](crate::communication::Priority),
+/// and sends pending messages of higher priority first.
 /// The [`DataSender`] services all operators sending messages to a particular
 /// node which may result in congestion.
+///
+/// Pending messages are written to the connection in batches, which is flushed once per batch
+/// in order to reduce the overhead of sending small high-rate messages.
 pub(crate) struct DataSender {
     /// The id of the node the sink is sending data to.
     node_id: NodeId,
-    /// Framed TCP write sink.
-    sink: SplitSink<Framed<TcpStream, MessageCodec>, InterProcessMessage>,
-    /// Tokio channel receiver on which to receive data from worker threads.
-    rx: UnboundedReceiver<InterProcessMessage>,
+    /// Framed write sink.
+    sink: SplitSink<Framed<Box<dyn Connection>, MessageCodec>, InterProcessMessage>,
+    /// The messages to send to the node.
+    queues: PriorityQueues,
     /// Tokio channel sender to `ControlMessageHandler`.
     control_tx: UnboundedSender<ControlMessage>,
     /// Tokio channel receiver from `ControlMessageHandler`.
     control_rx: UnboundedReceiver<ControlMessage>,
+    /// The maximum number of messages written before flushing the connection.
+    max_batch_size: usize,
+    /// The maximum time to wait for more messages before flushing a batch which is not full.
+    max_batch_linger: Duration,
 }
 
 impl DataSender {
     pub(crate) async fn new(
         node_id: NodeId,
-        sink: SplitSink<Framed<TcpStream, MessageCodec>, InterProcessMessage>,
+        sink: SplitSink<Framed<Box<dyn Connection>, MessageCodec>, InterProcessMessage>,
         channels_to_senders: Arc<Mutex<ChannelsToSenders>>,
         control_handler: &mut ControlMessageHandler,
+        max_batch_size: usize,
+        max_batch_linger: Duration,
     ) -> Self {
-        // Create a channel for this stream.
-        let (tx, rx) = mpsc::unbounded_channel();
-        // Add entry in the shared state map.
-        channels_to_senders.lock().await.add_sender(node_id, tx);
+        // Take the channel for each priority, which holds the messages queued before the node
+        // connected.
+        let rxs = channels_to_senders.lock().await.take_receivers(node_id);
         // Set up control channel.
         let (control_tx, control_rx) = mpsc::unbounded_channel();
         control_handler.add_channel_to_data_sender(node_id, control_tx);
         Self {
             node_id,
             sink,
-            rx,
+            queues: PriorityQueues::new(rxs, BULK_FRAGMENT_SIZE),
             control_tx: control_handler.get_channel_to_handler(),
             control_rx,
+            max_batch_size,
+            max_batch_linger,
         }
     }
 
@@ -63,24 +79,128 @@ impl DataSender {
         self.control_tx
             .send(ControlMessage::DataSenderInitialized(self.node_id))
             .map_err(CommunicationError::from)?;
-        // TODO: listen on control_rx?
         loop {
-            match self.rx.recv().await {
-                Some(msg) => {
-                    if let Err(e) = self.sink.send(msg).await.map_err(CommunicationError::from) {
-                        return Err(e);
+            tokio::select! {
+                msg = self.queues.recv() => match msg {
+                    Some(msg) => self.send_batch(msg).await?,
+                    None => return Err(CommunicationError::Disconnected),
+                },
+                Some(control_msg) = self.control_rx.recv() => {
+                    if let ControlMessage::CloseConnection = control_msg {
+                        return self.close().await;
                     }
                 }
-                None => return Err(CommunicationError::Disconnected),
             }
         }
     }
+
+    /// Writes `msg` and the messages which arrive within the linger time to the connection,
+    /// and flushes the connection once the batch is full or a low-latency message is written.
+    async fn send_batch(&mut self, msg: InterProcessMessage) -> Result<(), CommunicationError> {
+        let deadline = Instant::now() + self.max_batch_linger;
+        let mut flush_now = msg.is_low_latency();
+        self.sink
+            .feed(msg)
+            .await
+            .map_err(CommunicationError::from)?;
+        let mut batch_size = 1;
+        while batch_size < self.max_batch_size && !flush_now {
+            let msg = match self.queues.try_recv() {
+                Some(msg) => msg,
+                None if self.max_batch_linger.is_zero() => break,
+                None => match timeout_at(deadline, self.queues.recv()).await {
+                    Ok(Some(msg)) => msg,
+                    _ => break,
+                },
+            };
+            flush_now = msg.is_low_latency();
+            self.sink
+                .feed(msg)
+                .await
+                .map_err(CommunicationError::from)?;
+            batch_size += 1;
+        }
+        self.sink.flush().await.map_err(CommunicationError::from)
+    }
+
+    /// Sends all pending messages, and closes the connection.
+    async fn close(&mut self) -> Result<(), CommunicationError> {
+        while let Some(msg) = self.queues.try_recv() {
+            self.sink
+                .feed(msg)
+                .await
+                .map_err(CommunicationError::from)?;
+        }
+        tracing::debug!("DataSender: closing connection to node {}", self.node_id);
+        self.sink.close().await.map_err(CommunicationError::from)
+    }
+}
+
+/// The queues of messages to a node, from the highest to the lowest priority.
+///
+/// Bulk messages larger than the fragment size are split into fragments, which are sent after
+/// the pending messages of higher priority. The fragments of a message are sent before other
+/// bulk messages, so the messages of a stream stay in order.
+struct PriorityQueues {
+    rxs: Vec<UnboundedReceiver<InterProcessMessage>>,
+    /// The remaining fragments of the bulk message which is being sent.
+    fragments: VecDeque<InterProcessMessage>,
+    fragment_size: usize,
+}
+
+impl PriorityQueues {
+    fn new(rxs: Vec<UnboundedReceiver<InterProcessMessage>>, fragment_size: usize) -> Self {
+        Self {
+            rxs,
+            fragments: VecDeque::new(),
+            fragment_size,
+        }
+    }
+
+    /// Returns the pending message with the highest priority, if any.
+    fn try_recv(&mut self) -> Option<InterProcessMessage> {
+        let (bulk_rx, rxs) = self.rxs.split_last_mut().unwrap();
+        if let Some(msg) = rxs
+            .iter_mut()
+            .find_map(|rx| rx.recv().now_or_never().flatten())
+        {
+            return Some(msg);
+        }
+        if let Some(fragment) = self.fragments.pop_front() {
+            return Some(fragment);
+        }
+        let msg = bulk_rx.recv().now_or_never().flatten()?;
+        self.fragment(msg)
+    }
+
+    /// Waits for the next message, preferring pending messages of higher priority.
+    /// Returns `None` if the channels are closed.
+    async fn recv(&mut self) -> Option<InterProcessMessage> {
+        if let Some(msg) = self.try_recv() {
+            return Some(msg);
+        }
+        // Receiving is cancellation safe, so no message is lost when the other receives are
+        // dropped.
+        let (msg, index, _) =
+            future::select_all(self.rxs.iter_mut().map(|rx| Box::pin(rx.recv()))).await;
+        if index == self.rxs.len() - 1 {
+            self.fragment(msg?)
+        } else {
+            msg
+        }
+    }
+
+    /// Splits a bulk message into fragments, and returns the first one.
+    fn fragment(&mut self, msg: InterProcessMessage) -> Option<InterProcessMessage> {
+        self.fragments = msg.into_fragments(self.fragment_size);
+        self.fragments.pop_front()
+    }
 }
 
 /// Sends messages received from operator executors to other nodes.
-/// The function launches a task for each TCP sink. Each task listens
+/// The function launches a task for each sink. Each task listens
 /// on a mpsc channel for new `InterProcessMessages` messages, which it
-/// forwards on the TCP stream.
+/// forwards on the connection.
 pub(crate) async fn run_senders(senders: Vec<DataSender>) -> Result<(), CommunicationError> {
     // Waits until all futures complete. This code will only be reached
     // when all the mpsc channels are closed.
@@ -98,8 +218,8 @@ pub(crate) async fn run_senders(senders: Vec<DataSender>) -> Result<(), Communic
 pub(crate) struct ControlSender {
     /// The id of the node the sink is sending data to.
     node_id: NodeId,
-    /// Framed TCP write sink.
-    sink: SplitSink<Framed<TcpStream, ControlMessageCodec>, ControlMessage>,
+    /// Framed write sink.
+    sink: SplitSink<Framed<Box<dyn Connection>, ControlMessageCodec>, ControlMessage>,
     /// Tokio channel receiver on which to receive data from worker threads.
     rx: UnboundedReceiver<ControlMessage>,
     /// Tokio channel sender to `ControlMessageHandler`.
@@ -111,7 +231,7 @@ pub(crate) struct ControlSender {
 impl ControlSender {
     pub(crate) fn new(
         node_id: NodeId,
-        sink: SplitSink<Framed<TcpStream, ControlMessageCodec>, ControlMessage>,
+        sink: SplitSink<Framed<Box<dyn Connection>, ControlMessageCodec>, ControlMessage>,
         control_handler: &mut ControlMessageHandler,
     ) -> Self {
         // Set up channel to other node.
@@ -134,26 +254,44 @@ impl ControlSender {
         self.control_tx
             .send(ControlMessage::ControlSenderInitialized(self.node_id))
             .map_err(CommunicationError::from)?;
-        // TODO: listen on control_rx
         loop {
-            match self.rx.recv().await {
-                Some(msg) => {
-                    if let Err(e) = self.sink.send(msg).await.map_err(CommunicationError::from) {
-                        return Err(e);
+            tokio::select! {
+                msg = self.rx.recv() => match msg {
+                    Some(msg) => {
+                        if let Err(e) = self.sink.send(msg).await.map_err(CommunicationError::from) {
+                            return Err(e);
+                        }
+                    }
+                    None => {
+                        return Err(CommunicationError::Disconnected);
+                    }
+                },
+                Some(control_msg) = self.control_rx.recv() => {
+                    if let ControlMessage::CloseConnection = control_msg {
+                        return self.close().await;
                     }
-                }
-                None => {
-                    return Err(CommunicationError::Disconnected);
                 }
             }
         }
     }
+
+    /// Sends all pending control messages, and closes the connection.
+    async fn close(&mut self) -> Result<(), CommunicationError> {
+        while let Some(Some(msg)) = self.rx.recv().now_or_never() {
+            self.sink
+                .send(msg)
+                .await
+                .map_err(CommunicationError::from)?;
+        }
+        tracing::debug!("ControlSender: closing connection to node {}", self.node_id);
+        self.sink.close().await.map_err(CommunicationError::from)
+    }
 }
 
 /// Sends messages received from the control handler other nodes.
-/// The function launches a task for each TCP sink. Each task listens
+/// The function launches a task for each sink. Each task listens
 /// on a mpsc channel for new `ControlMessage`s, which it
-/// forwards on the TCP stream.
+/// forwards on the connection.
 pub(crate) async fn run_control_senders(
     mut senders: Vec<ControlSender>,
 ) -> Result<(), CommunicationError> {
@@ -162,3 +300,58 @@ pub(crate) async fn run_control_senders(
     future::join_all(senders.iter_mut().map(|sender| sender.run())).await;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        communication::{MessageMetadata, Priority, SendOptions},
+        dataflow::stream::StreamId,
+    };
+
+    use super::*;
+
+    fn message(size: usize, priority: Priority) -> InterProcessMessage {
+        let options = SendOptions {
+            priority,
+            ..SendOptions::default()
+        };
+        InterProcessMessage::new_deserialized(Arc::new(vec![0u8; size]), StreamId::nil(), options)
+    }
+
+    fn fragment_metadata(msg: &InterProcessMessage) -> Option<&MessageMetadata> {
+        match msg {
+            InterProcessMessage::Serialized { metadata, .. } => Some(metadata),
+            InterProcessMessage::Deserialized { .. } => None,
+        }
+    }
+
+    #[test]
+    fn test_high_priority_messages_overtake_bulk_fragments() {
+        let channels: Vec<_> = Priority::ALL
+            .iter()
+            .map(|_| mpsc::unbounded_channel())
+            .collect();
+        let (txs, rxs): (Vec<_>, Vec<_>) = channels.into_iter().unzip();
+        let mut queues = PriorityQueues::new(rxs, 1024);
+
+        // A bulk message of 4 fragments is being written when a high priority message arrives.
+        txs[2].send(message(3 * 1024 + 1, Priority::Bulk)).unwrap();
+        let first_fragment = queues.try_recv().unwrap();
+        assert!(fragment_metadata(&first_fragment).unwrap().more_fragments);
+        txs[0].send(message(8, Priority::High)).unwrap();
+        txs[2].send(message(8, Priority::Bulk)).unwrap();
+
+        let high_msg = queues.try_recv().unwrap();
+        assert!(high_msg.is_low_latency());
+        let more_fragments: Vec<bool> = (0..3)
+            .map(|_| {
+                let fragment = queues.try_recv().unwrap();
+                fragment_metadata(&fragment).unwrap().more_fragments
+            })
+            .collect();
+        assert_eq!(more_fragments, vec![true, true, false]);
+        // The next bulk message is small enough to be sent whole.
+        assert!(fragment_metadata(&queues.try_recv().unwrap()).is_none());
+        assert!(queues.try_recv().is_none());
+    }
+}
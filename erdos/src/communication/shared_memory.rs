@@ -0,0 +1,690 @@
+//! A transport which exchanges data between processes on the same machine through ring buffers
+//! in shared memory.
+//!
+//! Each direction of a connection uses a single-producer single-consumer ring buffer created
+//! with `shm_open`, and a Unix domain socket on which the writer signals that it wrote data and
+//! the reader signals that it freed space. The sockets only carry these 1-byte notifications,
+//! so messages are copied once into the ring buffer by the sender and once out of it by the
+//! receiver, instead of passing through the kernel's socket buffers.
+//!
+//! The ring buffers carry the same byte stream as other transports, framed by the
+//! `MessageCodec`. Messages are therefore serialized before they are copied into the ring
+//! buffer, and decoded after they are copied out of it. They are not decoded in place in shared
+//! memory: the receivers decode messages from `BytesMut` buffers which they own, and messages
+//! decoded in place would have to pin their part of the ring buffer until every operator
+//! dropped them, which would stall the writer.
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    io,
+    mem::MaybeUninit,
+    net::SocketAddr,
+    path::PathBuf,
+    pin::Pin,
+    ptr,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf},
+    net::{UnixListener, UnixStream},
+    sync::mpsc,
+    time::timeout,
+};
+
+use crate::Uuid;
+
+use super::{Connection, Transport, TransportListener};
+
+/// Bytes at the start of the shared memory segment which hold the ring buffer's positions.
+/// The positions are kept on separate cache lines.
+const RING_HEADER_SIZE: usize = 128;
+/// Offset of the position up to which the writer wrote.
+const HEAD_OFFSET: usize = 0;
+/// Offset of the position up to which the reader read.
+const TAIL_OFFSET: usize = 64;
+/// Default capacity of each ring buffer.
+const DEFAULT_CAPACITY: usize = 16 * 1024 * 1024;
+/// Size of the handshake identifying the role of a notification socket: a role byte followed by
+/// the connection's UUID.
+const HANDSHAKE_SIZE: usize = 17;
+/// Time within which a connecting node must send the handshake of a notification socket.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Role of the socket which notifies about the ring buffer written by the connecting node.
+const CONNECTOR_TO_LISTENER: u8 = 0;
+/// Role of the socket which notifies about the ring buffer written by the listening node.
+const LISTENER_TO_CONNECTOR: u8 = 1;
+
+/// Connects nodes on the same machine through ring buffers in shared memory, which avoids
+/// copying large messages through sockets.
+///
+/// Nodes on the same machine use this transport with sockets in `/tmp` unless shared memory is
+/// disabled with [`Configuration::disable_shared_memory`](crate::Configuration::disable_shared_memory)
+/// or another transport is configured.
+///
+/// The nodes exchange notifications over Unix domain sockets in `directory`, whose files are
+/// named after the nodes' addresses (see
+/// [`UnixSocketTransport`](crate::communication::UnixSocketTransport)).
+#[derive(Clone, Debug)]
+pub struct SharedMemoryTransport {
+    directory: PathBuf,
+    capacity: usize,
+}
+
+impl SharedMemoryTransport {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+
+    /// Sets the capacity in bytes of the ring buffer used for each direction of a connection.
+    /// Defaults to 16 MiB.
+    ///
+    /// Messages larger than the ring buffer are written in several parts.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    fn path(&self, address: SocketAddr) -> PathBuf {
+        self.directory.join(format!(
+            "erdos-shm-{}-{}.sock",
+            address.ip(),
+            address.port()
+        ))
+    }
+}
+
+#[async_trait]
+impl Transport for SharedMemoryTransport {
+    fn kind(&self) -> &'static str {
+        "shared-memory"
+    }
+
+    async fn bind(&self, address: SocketAddr) -> io::Result<Box<dyn TransportListener>> {
+        let path = self.path(address);
+        // Remove the socket file left behind by a previous run.
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => (),
+        }
+        Ok(Box::new(SharedMemoryListener::new(UnixListener::bind(
+            path,
+        )?)))
+    }
+
+    async fn connect(&self, address: SocketAddr) -> io::Result<Box<dyn Connection>> {
+        let path = self.path(address);
+        // Connect before creating the ring buffers, as connecting is retried until the other
+        // node listens.
+        let mut write_socket = UnixStream::connect(&path).await?;
+        let mut read_socket = UnixStream::connect(&path).await?;
+        let id = Uuid::new_v4();
+        let write_name = ring_name(&id, CONNECTOR_TO_LISTENER);
+        let read_name = ring_name(&id, LISTENER_TO_CONNECTOR);
+        let write_ring = Ring::create(&write_name, self.capacity)?;
+        let read_ring = match Ring::create(&read_name, self.capacity) {
+            Ok(ring) => ring,
+            Err(e) => {
+                Ring::unlink(&write_name);
+                return Err(e);
+            }
+        };
+        // The listener opens and unlinks the ring buffers once it receives the handshakes.
+        let handshakes = async {
+            write_socket
+                .write_all(&handshake(CONNECTOR_TO_LISTENER, &id))
+                .await?;
+            read_socket
+                .write_all(&handshake(LISTENER_TO_CONNECTOR, &id))
+                .await
+        };
+        if let Err(e) = handshakes.await {
+            Ring::unlink(&write_name);
+            Ring::unlink(&read_name);
+            return Err(e);
+        }
+        Ok(Box::new(SharedMemoryConnection::new(
+            read_ring,
+            read_socket,
+            write_ring,
+            write_socket,
+        )))
+    }
+}
+
+fn ring_name(id: &Uuid, role: u8) -> String {
+    format!("/erdos-{}-{}", id, role)
+}
+
+fn handshake(role: u8, id: &Uuid) -> [u8; HANDSHAKE_SIZE] {
+    let mut buffer = [0u8; HANDSHAKE_SIZE];
+    buffer[0] = role;
+    buffer[1..].copy_from_slice(&id.0);
+    buffer
+}
+
+/// Reads the handshake identifying the role of a notification socket and its connection.
+async fn read_handshake(mut socket: UnixStream) -> io::Result<(u8, Uuid, UnixStream)> {
+    let mut buffer = [0u8; HANDSHAKE_SIZE];
+    socket.read_exact(&mut buffer).await?;
+    match buffer[0] {
+        CONNECTOR_TO_LISTENER | LISTENER_TO_CONNECTOR => {
+            let mut id = Uuid::nil();
+            id.0.copy_from_slice(&buffer[1..]);
+            Ok((buffer[0], id, socket))
+        }
+        role => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid shared memory socket role {}", role),
+        )),
+    }
+}
+
+/// Accepts connections, each of which consists of two notification sockets.
+struct SharedMemoryListener {
+    listener: UnixListener,
+    /// Sends the notification sockets whose handshake was read.
+    handshakes_tx: mpsc::UnboundedSender<(u8, Uuid, UnixStream)>,
+    handshakes_rx: mpsc::UnboundedReceiver<(u8, Uuid, UnixStream)>,
+    /// Notification sockets of connections whose other socket was not accepted yet.
+    pending: HashMap<Uuid, (Option<UnixStream>, Option<UnixStream>)>,
+}
+
+impl SharedMemoryListener {
+    fn new(listener: UnixListener) -> Self {
+        let (handshakes_tx, handshakes_rx) = mpsc::unbounded_channel();
+        Self {
+            listener,
+            handshakes_tx,
+            handshakes_rx,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Reads the handshake of `socket` in a separate task, so that a peer which is slow to send
+    /// its handshake does not delay other connections. Sockets whose handshake fails are
+    /// dropped.
+    fn read_handshake(&self, socket: UnixStream) {
+        let handshakes_tx = self.handshakes_tx.clone();
+        tokio::spawn(async move {
+            match timeout(HANDSHAKE_TIMEOUT, read_handshake(socket)).await {
+                Ok(Ok(handshake)) => {
+                    let _ = handshakes_tx.send(handshake);
+                }
+                Ok(Err(e)) => tracing::warn!(
+                    "Rejected shared memory connection; reading its handshake failed with {}",
+                    e
+                ),
+                Err(_) => tracing::warn!(
+                    "Rejected shared memory connection; no handshake received within {:?}",
+                    HANDSHAKE_TIMEOUT
+                ),
+            }
+        });
+    }
+
+    /// Registers a notification socket. Returns the connection once both of its sockets were
+    /// accepted.
+    fn add_socket(
+        &mut self,
+        role: u8,
+        id: Uuid,
+        socket: UnixStream,
+    ) -> Option<io::Result<SharedMemoryConnection>> {
+        let sockets = self.pending.entry(id).or_insert((None, None));
+        if role == CONNECTOR_TO_LISTENER {
+            sockets.0 = Some(socket);
+        } else {
+            sockets.1 = Some(socket);
+        }
+        if sockets.0.is_none() || sockets.1.is_none() {
+            return None;
+        }
+        let (read_socket, write_socket) = match self.pending.remove(&id) {
+            Some((Some(read_socket), Some(write_socket))) => (read_socket, write_socket),
+            _ => unreachable!(),
+        };
+        let read_name = ring_name(&id, CONNECTOR_TO_LISTENER);
+        let write_name = ring_name(&id, LISTENER_TO_CONNECTOR);
+        let connection = Ring::open(&read_name).and_then(|read_ring| {
+            let write_ring = Ring::open(&write_name)?;
+            Ok(SharedMemoryConnection::new(
+                read_ring,
+                read_socket,
+                write_ring,
+                write_socket,
+            ))
+        });
+        if connection.is_err() {
+            Ring::unlink(&read_name);
+            Ring::unlink(&write_name);
+        }
+        Some(connection)
+    }
+}
+
+#[async_trait]
+impl TransportListener for SharedMemoryListener {
+    async fn accept(&mut self) -> io::Result<Box<dyn Connection>> {
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (socket, _) = accepted?;
+                    self.read_handshake(socket);
+                }
+                Some((role, id, socket)) = self.handshakes_rx.recv() => {
+                    match self.add_socket(role, id, socket) {
+                        Some(Ok(connection)) => return Ok(Box::new(connection)),
+                        Some(Err(e)) => tracing::warn!(
+                            "Rejected shared memory connection; could not open its ring buffers; \
+                            error {}",
+                            e
+                        ),
+                        None => (),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A single-producer single-consumer ring buffer in shared memory.
+///
+/// `head` and `tail` count the bytes written and read since the ring buffer was created. They
+/// are accessed with sequentially consistent ordering, so that a reader which frees space in a
+/// full ring buffer always observes that it was full, and notifies the writer.
+struct Ring {
+    memory: *mut u8,
+    size: usize,
+}
+
+// The memory is only accessed through the ring buffer's atomic positions.
+unsafe impl Send for Ring {}
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    /// Creates a ring buffer which holds `capacity` bytes.
+    fn create(name: &str, capacity: usize) -> io::Result<Self> {
+        let size = RING_HEADER_SIZE + capacity;
+        let name = CString::new(name)?;
+        unsafe {
+            let fd = libc::shm_open(
+                name.as_ptr(),
+                libc::O_CREAT | libc::O_EXCL | libc::O_RDWR,
+                0o600,
+            );
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::ftruncate(fd, size as libc::off_t) < 0 {
+                let e = io::Error::last_os_error();
+                libc::close(fd);
+                libc::shm_unlink(name.as_ptr());
+                return Err(e);
+            }
+            Self::map(fd, size)
+        }
+    }
+
+    /// Removes the name of a ring buffer which the other node did not open.
+    fn unlink(name: &str) {
+        if let Ok(name) = CString::new(name) {
+            unsafe {
+                libc::shm_unlink(name.as_ptr());
+            }
+        }
+    }
+
+    /// Opens the ring buffer created by the other node, and unlinks its name.
+    fn open(name: &str) -> io::Result<Self> {
+        let name = CString::new(name)?;
+        unsafe {
+            let fd = libc::shm_open(name.as_ptr(), libc::O_RDWR, 0o600);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            libc::shm_unlink(name.as_ptr());
+            let mut stat: libc::stat = std::mem::zeroed();
+            if libc::fstat(fd, &mut stat) < 0 {
+                let e = io::Error::last_os_error();
+                libc::close(fd);
+                return Err(e);
+            }
+            Self::map(fd, stat.st_size as usize)
+        }
+    }
+
+    /// Maps the shared memory segment `fd` of `size` bytes, and closes `fd`.
+    unsafe fn map(fd: libc::c_int, size: usize) -> io::Result<Self> {
+        let memory = libc::mmap(
+            ptr::null_mut(),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            fd,
+            0,
+        );
+        libc::close(fd);
+        if memory == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        if size <= RING_HEADER_SIZE {
+            libc::munmap(memory, size);
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "shared memory segment is too small",
+            ));
+        }
+        Ok(Self {
+            memory: memory as *mut u8,
+            size,
+        })
+    }
+
+    fn capacity(&self) -> usize {
+        self.size - RING_HEADER_SIZE
+    }
+
+    fn position(&self, offset: usize) -> &AtomicU64 {
+        unsafe { &*(self.memory.add(offset) as *const AtomicU64) }
+    }
+
+    fn head(&self) -> &AtomicU64 {
+        self.position(HEAD_OFFSET)
+    }
+
+    fn tail(&self) -> &AtomicU64 {
+        self.position(TAIL_OFFSET)
+    }
+
+    /// Copies `len` bytes between `buf` and the ring buffer starting at `position`, wrapping
+    /// around the end of the ring buffer.
+    unsafe fn copy(&self, position: u64, buf: *mut u8, len: usize, to_ring: bool) {
+        let capacity = self.capacity();
+        let start = (position % capacity as u64) as usize;
+        let first = len.min(capacity - start);
+        let data = self.memory.add(RING_HEADER_SIZE);
+        if to_ring {
+            ptr::copy_nonoverlapping(buf, data.add(start), first);
+            ptr::copy_nonoverlapping(buf.add(first), data, len - first);
+        } else {
+            ptr::copy_nonoverlapping(data.add(start), buf, first);
+            ptr::copy_nonoverlapping(data, buf.add(first), len - first);
+        }
+    }
+
+    /// Returns the number of bytes between `tail` and `head`. The positions are shared with the
+    /// other node, so they are checked against the capacity before they are used to access the
+    /// ring buffer.
+    fn len(&self, head: u64, tail: u64) -> io::Result<usize> {
+        head.checked_sub(tail)
+            .filter(|&len| len <= self.capacity() as u64)
+            .map(|len| len as usize)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "shared memory ring buffer of {} bytes is corrupted: head is {}, tail is {}",
+                        self.capacity(),
+                        head,
+                        tail
+                    ),
+                )
+            })
+    }
+
+    /// Writes as much of `buf` as fits. Returns the number of bytes written, and whether the
+    /// ring buffer was empty, in which case the reader may be waiting for a notification.
+    fn write(&self, buf: &[u8]) -> io::Result<(usize, bool)> {
+        let head = self.head().load(Ordering::SeqCst);
+        let tail = self.tail().load(Ordering::SeqCst);
+        let len = buf.len().min(self.capacity() - self.len(head, tail)?);
+        if len == 0 {
+            return Ok((0, false));
+        }
+        unsafe { self.copy(head, buf.as_ptr() as *mut u8, len, true) };
+        self.head().store(head + len as u64, Ordering::SeqCst);
+        Ok((len, self.tail().load(Ordering::SeqCst) == head))
+    }
+
+    /// Reads as many bytes as are available into `buf`, which may be uninitialized. Returns
+    /// the number of bytes read, and whether the ring buffer was full, in which case the writer
+    /// may be waiting for a notification.
+    fn read(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<(usize, bool)> {
+        let tail = self.tail().load(Ordering::SeqCst);
+        let head = self.head().load(Ordering::SeqCst);
+        let len = buf.len().min(self.len(head, tail)?);
+        if len == 0 {
+            return Ok((0, false));
+        }
+        unsafe { self.copy(tail, buf.as_mut_ptr() as *mut u8, len, false) };
+        self.tail().store(tail + len as u64, Ordering::SeqCst);
+        let was_full =
+            self.head().load(Ordering::SeqCst).checked_sub(tail) == Some(self.capacity() as u64);
+        Ok((len, was_full))
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.memory as *mut libc::c_void, self.size);
+        }
+    }
+}
+
+/// One direction of a [`SharedMemoryConnection`].
+struct Channel {
+    ring: Ring,
+    /// Carries notifications from the other node about the ring buffer.
+    socket: UnixStream,
+    /// Whether the other node closed its notification socket.
+    closed: bool,
+}
+
+impl Channel {
+    /// Sends a notification to the other node. The notification is dropped if the socket's
+    /// buffer is full, as the other node then has notifications pending.
+    fn notify(&self) -> io::Result<()> {
+        match self.socket.try_write(&[0]) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Waits until the other node sends notifications, and discards them.
+    fn poll_notified(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        futures::ready!(self.socket.poll_read_ready(cx))?;
+        let mut buffer = [0u8; 64];
+        loop {
+            match self.socket.try_read(&mut buffer) {
+                Ok(0) => {
+                    self.closed = true;
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(_) => (),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Poll::Ready(Ok(())),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+    }
+}
+
+/// A [`Connection`] which reads from and writes to ring buffers in shared memory.
+struct SharedMemoryConnection {
+    reader: Channel,
+    writer: Channel,
+}
+
+impl SharedMemoryConnection {
+    fn new(
+        read_ring: Ring,
+        read_socket: UnixStream,
+        write_ring: Ring,
+        write_socket: UnixStream,
+    ) -> Self {
+        Self {
+            reader: Channel {
+                ring: read_ring,
+                socket: read_socket,
+                closed: false,
+            },
+            writer: Channel {
+                ring: write_ring,
+                socket: write_socket,
+                closed: false,
+            },
+        }
+    }
+}
+
+impl AsyncRead for SharedMemoryConnection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let reader = &mut self.get_mut().reader;
+        loop {
+            // Copy directly into the unfilled part of the buffer without zeroing it first.
+            let (len, was_full) = reader.ring.read(unsafe { buf.unfilled_mut() })?;
+            if len > 0 {
+                unsafe { buf.assume_init(len) };
+                buf.advance(len);
+                if was_full {
+                    reader.notify()?;
+                }
+                return Poll::Ready(Ok(()));
+            }
+            if reader.closed || buf.remaining() == 0 {
+                // Signal the end of the stream by reading no bytes.
+                return Poll::Ready(Ok(()));
+            }
+            futures::ready!(reader.poll_notified(cx))?;
+        }
+    }
+}
+
+impl AsyncWrite for SharedMemoryConnection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let writer = &mut self.get_mut().writer;
+        loop {
+            if writer.closed {
+                return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+            }
+            let (len, was_empty) = writer.ring.write(buf)?;
+            if len > 0 || buf.is_empty() {
+                if was_empty {
+                    writer.notify()?;
+                }
+                return Poll::Ready(Ok(len));
+            }
+            futures::ready!(writer.poll_notified(cx))?;
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Data is visible to the reader as soon as it is written.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Closing the socket signals the end of the stream once the reader emptied the ring.
+        Pin::new(&mut self.get_mut().writer.socket).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a ring buffer which is not shared with another process.
+    fn ring(capacity: usize) -> Ring {
+        let name = ring_name(&Uuid::new_v4(), CONNECTOR_TO_LISTENER);
+        let ring = Ring::create(&name, capacity).unwrap();
+        Ring::unlink(&name);
+        ring
+    }
+
+    fn read(ring: &Ring, len: usize) -> (Vec<u8>, bool) {
+        let mut buf = vec![MaybeUninit::uninit(); len];
+        let (len, was_full) = ring.read(&mut buf).unwrap();
+        let bytes = buf[..len]
+            .iter()
+            .map(|byte| unsafe { byte.assume_init() })
+            .collect();
+        (bytes, was_full)
+    }
+
+    #[test]
+    fn test_ring_wraparound() {
+        let ring = ring(8);
+        assert_eq!(ring.write(&[1, 2, 3, 4, 5, 6]).unwrap(), (6, true));
+        assert_eq!(read(&ring, 4), (vec![1, 2, 3, 4], false));
+
+        // Only 6 bytes fit, which wrap around the end of the ring buffer.
+        assert_eq!(ring.write(&[7, 8, 9, 10, 11, 12, 13]).unwrap(), (6, false));
+        assert_eq!(ring.write(&[13]).unwrap(), (0, false));
+        assert_eq!(read(&ring, 16), (vec![5, 6, 7, 8, 9, 10, 11, 12], true));
+        assert_eq!(read(&ring, 16), (vec![], false));
+
+        assert_eq!(ring.write(&[13]).unwrap(), (1, true));
+        assert_eq!(read(&ring, 16), (vec![13], false));
+    }
+
+    #[tokio::test]
+    async fn test_accept_skips_bad_handshakes() {
+        let directory = std::env::temp_dir().join(format!("erdos-test-{}", Uuid::new_v4()));
+        std::fs::create_dir(&directory).unwrap();
+        let address: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let transport = SharedMemoryTransport::new(&directory).with_capacity(64);
+        let mut listener = transport.bind(address).await.unwrap();
+        let accept = tokio::spawn(async move { listener.accept().await.map(|_| ()) });
+
+        // A peer which never sends its handshake, and a peer which sends an invalid role.
+        let path = transport.path(address);
+        let _silent = UnixStream::connect(&path).await.unwrap();
+        let mut invalid = UnixStream::connect(&path).await.unwrap();
+        invalid
+            .write_all(&handshake(2, &Uuid::new_v4()))
+            .await
+            .unwrap();
+
+        let mut connection = transport.connect(address).await.unwrap();
+        timeout(Duration::from_secs(1), accept)
+            .await
+            .expect("accepting was blocked by a bad handshake")
+            .unwrap()
+            .unwrap();
+        connection.shutdown().await.unwrap();
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_ring_rejects_corrupted_positions() {
+        let ring = ring(8);
+        let mut buf = vec![MaybeUninit::uninit(); 16];
+
+        // The head is ahead of the tail by more than the capacity.
+        ring.head().store(9, Ordering::SeqCst);
+        assert!(ring.read(&mut buf).is_err());
+        assert!(ring.write(&[1]).is_err());
+
+        // The tail is ahead of the head.
+        ring.head().store(2, Ordering::SeqCst);
+        ring.tail().store(3, Ordering::SeqCst);
+        assert!(ring.read(&mut buf).is_err());
+        assert!(ring.write(&[1]).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+    sync::Arc,
+};
+
+use rustls::{
+    internal::pemfile, Certificate, ClientCertVerified, ClientCertVerifier, ClientConfig,
+    DistinguishedNames, PrivateKey, RootCertStore, ServerCertVerified, ServerCertVerifier,
+    ServerConfig, Session, TLSError,
+};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use webpki::{DNSName, DNSNameRef};
+
+use crate::node::NodeId;
+
+use super::{Connection, Handshake};
+
+/// Certificates and the private key used to encrypt and mutually authenticate connections
+/// between nodes with TLS.
+///
+/// Each node presents its own certificate, and only accepts connections from peers which
+/// present the certificate listed for the node index they claim. Self-signed certificates can
+/// be used, as certificates are compared with the listed ones instead of verified against a
+/// certificate authority.
+#[derive(Clone)]
+pub struct TlsConfig {
+    /// The node's certificate chain, starting with the node's certificate.
+    certificate_chain: Vec<Certificate>,
+    private_key: PrivateKey,
+    /// The certificate of each node, indexed by node.
+    node_certificates: Arc<Vec<Certificate>>,
+}
+
+impl TlsConfig {
+    /// Creates a TLS configuration from DER-encoded certificates and a DER-encoded PKCS#8 or
+    /// RSA private key.
+    ///
+    /// `node_certificates` lists the certificate of each node, indexed by node.
+    pub fn new(
+        certificate_chain: Vec<Vec<u8>>,
+        private_key: Vec<u8>,
+        node_certificates: Vec<Vec<u8>>,
+    ) -> Self {
+        Self {
+            certificate_chain: certificate_chain.into_iter().map(Certificate).collect(),
+            private_key: PrivateKey(private_key),
+            node_certificates: Arc::new(node_certificates.into_iter().map(Certificate).collect()),
+        }
+    }
+
+    /// Reads the node's certificate chain and private key, and the certificate of each node,
+    /// from PEM files.
+    pub fn from_pem_files(
+        certificate_chain: impl AsRef<Path>,
+        private_key: impl AsRef<Path>,
+        node_certificates: &[impl AsRef<Path>],
+    ) -> io::Result<Self> {
+        let certificate_chain = read_certificates(certificate_chain.as_ref())?;
+        let private_key = read_private_key(private_key.as_ref())?;
+        let node_certificates = node_certificates
+            .iter()
+            .map(|path| {
+                read_certificates(path.as_ref())?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| invalid_data(path.as_ref(), "no certificate found"))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self {
+            certificate_chain,
+            private_key,
+            node_certificates: Arc::new(node_certificates),
+        })
+    }
+
+    /// Establishes TLS on a connection to `node_id`, and checks that the node presents its
+    /// listed certificate.
+    pub(crate) async fn connect(
+        &self,
+        stream: Box<dyn Connection>,
+        node_id: NodeId,
+    ) -> io::Result<Box<dyn Connection>> {
+        let expected_certificate = self.node_certificate(node_id)?;
+        let mut config = ClientConfig::new();
+        config
+            .set_single_client_cert(self.certificate_chain.clone(), self.private_key.clone())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(PinnedServerCertVerifier {
+                certificate: expected_certificate,
+            }));
+        // Certificates are pinned, so the name is only used for server name indication.
+        let name = DNSNameRef::try_from_ascii_str("erdos-node").unwrap();
+        let stream = TlsConnector::from(Arc::new(config))
+            .connect(name, stream)
+            .await?;
+        Ok(Box::new(stream))
+    }
+
+    /// Establishes TLS on a connection from another node. Exchanges handshakes to learn the
+    /// index of the node, and checks that the node presented the certificate listed for it.
+    pub(crate) async fn accept(
+        &self,
+        stream: Box<dyn Connection>,
+        handshake: &Handshake,
+    ) -> io::Result<(NodeId, Box<dyn Connection>)> {
+        let mut config = ServerConfig::new(Arc::new(NodeClientCertVerifier {
+            node_certificates: Arc::clone(&self.node_certificates),
+        }));
+        config
+            .set_single_cert(self.certificate_chain.clone(), self.private_key.clone())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let stream = TlsAcceptor::from(Arc::new(config)).accept(stream).await?;
+        let presented_certificate = stream
+            .get_ref()
+            .1
+            .get_peer_certificates()
+            .and_then(|certificates| certificates.into_iter().next());
+
+        let mut stream: Box<dyn Connection> = Box::new(stream);
+        let node_id = handshake.exchange(&mut stream).await?.node_id;
+        if presented_certificate != Some(self.node_certificate(node_id)?) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "peer claiming to be node {} did not present its certificate",
+                    node_id
+                ),
+            ));
+        }
+        Ok((node_id, stream))
+    }
+
+    fn node_certificate(&self, node_id: NodeId) -> io::Result<Certificate> {
+        self.node_certificates.get(node_id).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("no certificate is listed for node {}", node_id),
+            )
+        })
+    }
+}
+
+fn invalid_data(path: &Path, error: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {}", path.display(), error),
+    )
+}
+
+fn read_certificates(path: &Path) -> io::Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    pemfile::certs(&mut reader).map_err(|_| invalid_data(path, "invalid certificate"))
+}
+
+fn read_private_key(path: &Path) -> io::Result<PrivateKey> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut keys = pemfile::pkcs8_private_keys(&mut reader)
+        .map_err(|_| invalid_data(path, "invalid private key"))?;
+    if keys.is_empty() {
+        let mut reader = BufReader::new(File::open(path)?);
+        keys = pemfile::rsa_private_keys(&mut reader)
+            .map_err(|_| invalid_data(path, "invalid private key"))?;
+    }
+    keys.into_iter()
+        .next()
+        .ok_or_else(|| invalid_data(path, "no private key found"))
+}
+
+/// Accepts the server only if it presents the expected certificate.
+struct PinnedServerCertVerifier {
+    certificate: Certificate,
+}
+
+impl ServerCertVerifier for PinnedServerCertVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        _dns_name: DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        match presented_certs.first() {
+            Some(certificate) if *certificate == self.certificate => {
+                Ok(ServerCertVerified::assertion())
+            }
+            _ => Err(TLSError::General(
+                "node did not present its listed certificate".to_string(),
+            )),
+        }
+    }
+}
+
+/// Requires clients to present the certificate of one of the nodes. Whether the certificate
+/// belongs to the node the client claims to be is checked once the client sent its index.
+struct NodeClientCertVerifier {
+    node_certificates: Arc<Vec<Certificate>>,
+}
+
+impl ClientCertVerifier for NodeClientCertVerifier {
+    fn client_auth_root_subjects(&self, _sni: Option<&DNSName>) -> Option<DistinguishedNames> {
+        Some(DistinguishedNames::new())
+    }
+
+    fn verify_client_cert(
+        &self,
+        presented_certs: &[Certificate],
+        _sni: Option<&DNSName>,
+    ) -> Result<ClientCertVerified, TLSError> {
+        match presented_certs.first() {
+            Some(certificate) if self.node_certificates.contains(certificate) => {
+                Ok(ClientCertVerified::assertion())
+            }
+            _ => Err(TLSError::General(
+                "peer did not present the certificate of a node".to_string(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::{io, net::SocketAddr};
+
+#[cfg(unix)]
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream},
+};
+
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+/// A bidirectional byte stream connecting two nodes.
+///
+/// Messages are framed on the connection by the same codecs regardless of the transport.
+pub trait Connection: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Connection for T {}
+
+/// Establishes [`Connection`]s between nodes.
+///
+/// Nodes are identified by their data and control addresses in the
+/// [`Configuration`](crate::Configuration); transports which do not use IP networking map the
+/// addresses to their own endpoints. The transport used between a pair of nodes is set with
+/// [`Configuration::with_transport`](crate::Configuration::with_transport), and must be set
+/// on both nodes. Nodes without a configured transport are connected via shared memory if they
+/// run on the same machine, and via TCP otherwise.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Identifies the transport in logs. A node accepts connections on one listener for each
+    /// kind of transport it uses.
+    fn kind(&self) -> &'static str;
+
+    /// Listens for connections at `address`.
+    async fn bind(&self, address: SocketAddr) -> io::Result<Box<dyn TransportListener>>;
+
+    /// Connects to the node listening at `address`.
+    async fn connect(&self, address: SocketAddr) -> io::Result<Box<dyn Connection>>;
+}
+
+/// Accepts [`Connection`]s from other nodes.
+#[async_trait]
+pub trait TransportListener: Send {
+    async fn accept(&mut self) -> io::Result<Box<dyn Connection>>;
+}
+
+/// Connects nodes via TCP. Used unless another transport is configured, or the nodes are
+/// connected via shared memory.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TcpTransport;
+
+#[async_trait]
+impl Transport for TcpTransport {
+    fn kind(&self) -> &'static str {
+        "tcp"
+    }
+
+    async fn bind(&self, address: SocketAddr) -> io::Result<Box<dyn TransportListener>> {
+        Ok(Box::new(TcpListener::bind(address).await?))
+    }
+
+    async fn connect(&self, address: SocketAddr) -> io::Result<Box<dyn Connection>> {
+        let stream = TcpStream::connect(address).await?;
+        stream.set_nodelay(true)?;
+        Ok(Box::new(stream))
+    }
+}
+
+#[async_trait]
+impl TransportListener for TcpListener {
+    async fn accept(&mut self) -> io::Result<Box<dyn Connection>> {
+        let (stream, _) = TcpListener::accept(self).await?;
+        stream.set_nodelay(true)?;
+        Ok(Box::new(stream))
+    }
+}
+
+/// Connects nodes on the same machine via Unix domain sockets, which avoids the overhead of the
+/// TCP loopback interface.
+///
+/// Each address is mapped to a socket file in `directory`, e.g. `127.0.0.1:9000` is mapped to
+/// `erdos-127.0.0.1-9000.sock`. The socket file is replaced when a node starts listening.
+#[cfg(unix)]
+#[derive(Clone, Debug)]
+pub struct UnixSocketTransport {
+    directory: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixSocketTransport {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// Returns the path of the socket file to which `address` is mapped.
+    pub fn path(&self, address: SocketAddr) -> PathBuf {
+        self.directory
+            .join(format!("erdos-{}-{}.sock", address.ip(), address.port()))
+    }
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl Transport for UnixSocketTransport {
+    fn kind(&self) -> &'static str {
+        "unix"
+    }
+
+    async fn bind(&self, address: SocketAddr) -> io::Result<Box<dyn TransportListener>> {
+        let path = self.path(address);
+        // Remove the socket file left behind by a previous run.
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => (),
+        }
+        Ok(Box::new(UnixListener::bind(path)?))
+    }
+
+    async fn connect(&self, address: SocketAddr) -> io::Result<Box<dyn Connection>> {
+        Ok(Box::new(UnixStream::connect(self.path(address)).await?))
+    }
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl TransportListener for UnixListener {
+    async fn accept(&mut self) -> io::Result<Box<dyn Connection>> {
+        let (stream, _) = UnixListener::accept(self).await?;
+        Ok(Box::new(stream))
+    }
+}
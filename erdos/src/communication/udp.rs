@@ -0,0 +1,749 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket as StdUdpSocket},
+    sync::{Arc, Mutex as StdMutex, Weak},
+    time::Duration,
+};
+
+use bytes::BytesMut;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    net::UdpSocket,
+    sync::{
+        mpsc::{self, UnboundedReceiver},
+        Mutex,
+    },
+};
+
+use crate::{
+    dataflow::{stream::StreamId, Data, Message},
+    node::{Clock, NodeId},
+    scheduler::endpoints_manager::ChannelsToReceivers,
+};
+
+use super::{CommunicationError, PusherT, Serializable, StreamStatistics, StreamStatisticsMap};
+
+/// Maximum size of a datagram, which fits into an Ethernet frame without IP fragmentation.
+const MAX_DATAGRAM_SIZE: usize = 1472;
+/// Period with which the last watermark sent on a stream is sent again.
+const WATERMARK_RESEND_INTERVAL: Duration = Duration::from_millis(10);
+/// Number of times a watermark is sent again.
+const WATERMARK_RESENDS: usize = 10;
+
+/// How the messages of a stream are delivered to other nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Messages are sent over the data connections between nodes, and are delivered in order
+    /// and without loss.
+    Reliable,
+    /// Messages are sent as UDP datagrams to each receiving node, at the node's data address.
+    ///
+    /// Messages may be lost. Messages which arrive after a newer message of the stream are
+    /// dropped, so operators only receive messages in order. Watermarks are sent again
+    /// shortly after being sent, and are piggybacked on subsequent messages, so that progress
+    /// is preserved when datagrams are lost.
+    BestEffort,
+    /// Like [`Delivery::BestEffort`], except that messages are sent once to the multicast group
+    /// at the address, which the receiving nodes join.
+    ///
+    /// Receiving nodes must run on different machines, as each of them binds the group's port.
+    Multicast(SocketAddr),
+}
+
+impl Default for Delivery {
+    fn default() -> Self {
+        Delivery::Reliable
+    }
+}
+
+/// Distinguishes watermarks from data, so that watermarks are sent again.
+pub(crate) trait Progress {
+    fn is_watermark(&self) -> bool;
+}
+
+impl<D> Progress for D {
+    default fn is_watermark(&self) -> bool {
+        false
+    }
+}
+
+impl<D: Data> Progress for Message<D> {
+    fn is_watermark(&self) -> bool {
+        matches!(self, Message::Watermark(_))
+    }
+}
+
+/// Header of each datagram, which is followed by a fragment of the message.
+#[derive(Debug, Serialize, Deserialize)]
+struct DatagramHeader {
+    stream_id: StreamId,
+    /// Position of the message among the messages sent on the stream.
+    sequence: u64,
+    fragment: u16,
+    num_fragments: u16,
+    /// The last watermark sent on the stream before the message, along with its sequence
+    /// number. Only piggybacked on the first fragment of data messages.
+    watermark: Option<(u64, Vec<u8>)>,
+}
+
+/// The last watermark sent on a stream.
+#[derive(Debug, Clone)]
+struct SentWatermark {
+    sequence: u64,
+    bytes: Vec<u8>,
+    /// Number of times the watermark is still to be sent again.
+    resends_left: usize,
+}
+
+#[derive(Debug, Default)]
+struct SendState {
+    next_sequence: u64,
+    watermark: Option<SentWatermark>,
+}
+
+/// Sends the messages of a best-effort stream as UDP datagrams to a node or a multicast group.
+#[derive(Debug)]
+pub struct UdpSender {
+    stream_id: StreamId,
+    destination: SocketAddr,
+    socket: StdUdpSocket,
+    state: StdMutex<SendState>,
+    statistics: StreamStatisticsMap,
+}
+
+impl UdpSender {
+    /// Creates a sender, and a task which sends watermarks again until the sender is dropped.
+    ///
+    /// Must be called within the node's runtime.
+    pub(crate) fn new(
+        stream_id: StreamId,
+        destination: SocketAddr,
+        statistics: StreamStatisticsMap,
+        clock: Clock,
+    ) -> io::Result<Arc<Self>> {
+        let unspecified = match destination {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let socket = StdUdpSocket::bind(SocketAddr::new(unspecified, 0))?;
+        // Datagrams are dropped instead of blocking the operator when the socket buffer is full.
+        socket.set_nonblocking(true)?;
+        let sender = Arc::new(Self {
+            stream_id,
+            destination,
+            socket,
+            state: StdMutex::new(SendState::default()),
+            statistics,
+        });
+        let weak_sender = Arc::downgrade(&sender);
+        tokio::spawn(async move { Self::resend_watermarks(weak_sender, clock).await });
+        Ok(sender)
+    }
+
+    /// Sends `msg` in as many datagrams as needed. Data messages carry the last watermark.
+    pub(crate) fn send<D: Serializable + Progress>(
+        &self,
+        msg: &D,
+    ) -> Result<(), CommunicationError> {
+        let bytes = msg.encode()?;
+        let is_watermark = msg.is_watermark();
+        let mut state = self.state.lock().unwrap();
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        let piggybacked_watermark = match (&state.watermark, is_watermark) {
+            (Some(watermark), false) => Some((watermark.sequence, watermark.bytes.clone())),
+            _ => None,
+        };
+        self.send_datagrams(sequence, &bytes, piggybacked_watermark)?;
+        if is_watermark {
+            state.watermark = Some(SentWatermark {
+                sequence,
+                bytes: bytes.to_vec(),
+                resends_left: WATERMARK_RESENDS,
+            });
+        }
+        let mut statistics = self.statistics.lock().unwrap();
+        let stats = statistics.entry(self.stream_id).or_default();
+        stats.messages_sent += 1;
+        stats.bytes_sent += bytes.len() as u64;
+        stats.compressed_bytes_sent += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Splits the message into fragments which fit into a datagram, and sends them.
+    fn send_datagrams(
+        &self,
+        sequence: u64,
+        bytes: &[u8],
+        watermark: Option<(u64, Vec<u8>)>,
+    ) -> Result<(), CommunicationError> {
+        for datagram in encode_datagrams(self.stream_id, sequence, bytes, watermark)? {
+            self.send_datagram(&datagram);
+        }
+        Ok(())
+    }
+
+    fn send_datagram(&self, datagram: &[u8]) {
+        // Datagrams which cannot be sent count as lost on the receiving node.
+        if let Err(e) = self.socket.send_to(datagram, self.destination) {
+            tracing::trace!(
+                "UdpSender: dropped datagram of stream {} to {}; error {}",
+                self.stream_id,
+                self.destination,
+                e
+            );
+        }
+    }
+
+    /// Periodically sends the last watermark again, so that the receiving nodes make progress
+    /// even if the watermark and all subsequent messages were lost.
+    async fn resend_watermarks(weak_sender: Weak<Self>, clock: Clock) {
+        let mut interval = clock.interval(WATERMARK_RESEND_INTERVAL);
+        loop {
+            interval.tick().await;
+            let sender = match weak_sender.upgrade() {
+                Some(sender) => sender,
+                None => return,
+            };
+            let mut state = sender.state.lock().unwrap();
+            if let Some(watermark) = state.watermark.as_mut() {
+                if watermark.resends_left > 0 {
+                    watermark.resends_left -= 1;
+                    let (sequence, bytes) = (watermark.sequence, watermark.bytes.clone());
+                    if let Err(e) = sender.send_datagrams(sequence, &bytes, None) {
+                        tracing::warn!(
+                            "UdpSender: failed to send watermark of stream {} again; error {:?}",
+                            sender.stream_id,
+                            e
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Splits the message into datagrams, each of which starts with a [`DatagramHeader`] followed by
+/// a fragment of the message. The watermark is piggybacked on the first datagram.
+fn encode_datagrams(
+    stream_id: StreamId,
+    sequence: u64,
+    bytes: &[u8],
+    watermark: Option<(u64, Vec<u8>)>,
+) -> Result<Vec<Vec<u8>>, CommunicationError> {
+    let mut header = DatagramHeader {
+        stream_id,
+        sequence,
+        fragment: 0,
+        num_fragments: 0,
+        watermark,
+    };
+    let mut header_size = bincode::serialized_size(&header)? as usize;
+    if header_size >= MAX_DATAGRAM_SIZE / 2 {
+        // The piggybacked watermark is too large; it is only sent again instead.
+        header.watermark = None;
+        header_size = bincode::serialized_size(&header)? as usize;
+    }
+    let fragment_size = MAX_DATAGRAM_SIZE - header_size;
+    let num_fragments = ((bytes.len() + fragment_size - 1) / fragment_size).max(1);
+    if num_fragments > u16::MAX as usize {
+        return Err(CommunicationError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "message of {} bytes on best-effort stream {} exceeds the maximum size of \
+                {} bytes",
+                bytes.len(),
+                stream_id,
+                u16::MAX as usize * fragment_size
+            ),
+        )));
+    }
+    header.num_fragments = num_fragments as u16;
+
+    let mut datagrams = Vec::with_capacity(num_fragments);
+    for (fragment, chunk) in bytes.chunks(fragment_size).enumerate() {
+        header.fragment = fragment as u16;
+        let mut datagram = Vec::with_capacity(header_size + chunk.len());
+        bincode::serialize_into(&mut datagram, &header)?;
+        datagram.extend_from_slice(chunk);
+        datagrams.push(datagram);
+        // Only the first fragment carries the watermark.
+        header.watermark = None;
+    }
+    if bytes.is_empty() {
+        datagrams.push(bincode::serialize(&header)?);
+    }
+    Ok(datagrams)
+}
+
+/// A message which is being reassembled from its fragments.
+#[derive(Debug)]
+struct PartialMessage {
+    sequence: u64,
+    fragments: Vec<Option<Vec<u8>>>,
+    missing_fragments: usize,
+    /// Size of the datagrams received so far, including their headers.
+    wire_size: usize,
+}
+
+/// State of a best-effort stream received from a node.
+#[derive(Debug, Default)]
+struct ReceiveState {
+    /// Sequence number of the last message delivered to operators.
+    delivered: Option<u64>,
+    partial: Option<PartialMessage>,
+}
+
+impl ReceiveState {
+    /// Whether the message is older than the last delivered message.
+    fn is_late(&self, sequence: u64) -> bool {
+        matches!(self.delivered, Some(delivered) if sequence <= delivered)
+    }
+}
+
+/// Receives the datagrams of best-effort streams on a UDP socket, reassembles messages, and
+/// pushes them to operator executors.
+pub(crate) struct UdpReceiver {
+    /// The id of the node on which the receiver runs.
+    node_id: NodeId,
+    socket: UdpSocket,
+    /// The IP addresses of the other nodes. Datagrams from other addresses are dropped.
+    sources: HashSet<IpAddr>,
+    /// Channel receiver on which new pusher updates are received.
+    rx: UnboundedReceiver<(StreamId, Box<dyn PusherT>)>,
+    stream_id_to_pusher: HashMap<StreamId, Box<dyn PusherT>>,
+    /// State of each stream, by sending address.
+    streams: HashMap<(SocketAddr, StreamId), ReceiveState>,
+    statistics: StreamStatisticsMap,
+}
+
+impl UdpReceiver {
+    /// Binds a socket at `address`. If `address` is a multicast address, the socket binds the
+    /// port of the multicast group, and joins the group.
+    ///
+    /// Only datagrams sent from the IP addresses in `sources` are received.
+    pub(crate) async fn bind(
+        node_id: NodeId,
+        address: SocketAddr,
+        sources: HashSet<IpAddr>,
+        channels_to_receivers: Arc<Mutex<ChannelsToReceivers>>,
+        statistics: StreamStatisticsMap,
+    ) -> io::Result<Self> {
+        let socket = match address.ip() {
+            IpAddr::V4(group) if group.is_multicast() => {
+                let socket = UdpSocket::bind(SocketAddr::new(
+                    Ipv4Addr::UNSPECIFIED.into(),
+                    address.port(),
+                ))
+                .await?;
+                socket.join_multicast_v4(group, Ipv4Addr::UNSPECIFIED)?;
+                socket
+            }
+            IpAddr::V6(group) if group.is_multicast() => {
+                let socket = UdpSocket::bind(SocketAddr::new(
+                    Ipv6Addr::UNSPECIFIED.into(),
+                    address.port(),
+                ))
+                .await?;
+                socket.join_multicast_v6(&group, 0)?;
+                socket
+            }
+            _ => UdpSocket::bind(address).await?,
+        };
+        let (tx, rx) = mpsc::unbounded_channel();
+        channels_to_receivers.lock().await.add_sender(tx);
+        Ok(Self {
+            node_id,
+            socket,
+            sources,
+            rx,
+            stream_id_to_pusher: HashMap::new(),
+            streams: HashMap::new(),
+            statistics,
+        })
+    }
+
+    /// Receives datagrams until the socket errors. Datagrams which cannot be delivered are
+    /// dropped.
+    pub(crate) async fn run(&mut self) -> Result<(), CommunicationError> {
+        let mut buffer = vec![0u8; u16::MAX as usize];
+        loop {
+            let (size, source) = self.socket.recv_from(&mut buffer).await?;
+            self.on_datagram(source, &buffer[..size]);
+        }
+    }
+
+    fn on_datagram(&mut self, source: SocketAddr, datagram: &[u8]) {
+        // Streams are tracked by source, so datagrams from unknown sources would grow the state
+        // without bound.
+        if !self.sources.contains(&source.ip()) {
+            tracing::trace!(
+                "Node {}: dropped datagram from {}, which is not a node",
+                self.node_id,
+                source
+            );
+            return;
+        }
+        let mut payload = datagram;
+        let header: DatagramHeader = match bincode::deserialize_from(&mut payload) {
+            Ok(header) => header,
+            Err(e) => {
+                tracing::warn!(
+                    "Node {}: dropped malformed datagram from {}; error {}",
+                    self.node_id,
+                    source,
+                    e
+                );
+                return;
+            }
+        };
+        let key = (source, header.stream_id);
+        let state = self.streams.entry(key).or_default();
+        // The piggybacked watermark precedes the message, and is counted as its part of the
+        // datagram.
+        let mut wire_size = datagram.len();
+        if let Some((sequence, bytes)) = header.watermark {
+            wire_size -= bytes.len();
+            if !state.is_late(sequence) {
+                let watermark_size = bytes.len();
+                self.deliver(key, sequence, bytes, watermark_size);
+            }
+        }
+        let state = self.streams.get_mut(&key).unwrap();
+        if state.is_late(header.sequence) || header.fragment >= header.num_fragments {
+            return;
+        }
+        let partial = match state.partial.as_mut() {
+            Some(partial) if partial.sequence > header.sequence => return,
+            Some(partial) if partial.sequence == header.sequence => partial,
+            // Newer messages replace incomplete older messages, which are lost.
+            _ => state.partial.insert(PartialMessage {
+                sequence: header.sequence,
+                fragments: vec![None; header.num_fragments as usize],
+                missing_fragments: header.num_fragments as usize,
+                wire_size: 0,
+            }),
+        };
+        let fragment = match partial.fragments.get_mut(header.fragment as usize) {
+            Some(fragment) => fragment,
+            None => return,
+        };
+        if fragment.is_none() {
+            *fragment = Some(payload.to_vec());
+            partial.missing_fragments -= 1;
+            partial.wire_size += wire_size;
+        }
+        if partial.missing_fragments == 0 {
+            let partial = state.partial.take().unwrap();
+            let bytes = partial.fragments.into_iter().flatten().flatten().collect();
+            self.deliver(key, partial.sequence, bytes, partial.wire_size);
+        }
+    }
+
+    /// Pushes a message received in datagrams of `wire_size` bytes to the operators, and counts
+    /// the messages skipped since the last delivered message as lost. Messages which cannot be
+    /// pushed, e.g. because they fail to deserialize, are dropped and also counted as lost.
+    fn deliver(
+        &mut self,
+        (source, stream_id): (SocketAddr, StreamId),
+        sequence: u64,
+        bytes: Vec<u8>,
+        wire_size: usize,
+    ) {
+        let state = self.streams.get_mut(&(source, stream_id)).unwrap();
+        let lost = match state.delivered {
+            Some(delivered) => sequence - delivered - 1,
+            None => sequence,
+        };
+        state.delivered = Some(sequence);
+        if matches!(&state.partial, Some(partial) if partial.sequence <= sequence) {
+            state.partial = None;
+        }
+
+        self.update_pushers();
+        let pushed = match self.stream_id_to_pusher.get_mut(&stream_id) {
+            Some(pusher) => match pusher.send_from_bytes(BytesMut::from(&bytes[..]), None) {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::warn!(
+                        "Node {}: dropped message from {} on stream {}; error {:?}",
+                        self.node_id,
+                        source,
+                        stream_id,
+                        e
+                    );
+                    false
+                }
+            },
+            None => {
+                tracing::warn!(
+                    "Node {}: dropped message from {} on stream {}, which is not received by \
+                    the node",
+                    self.node_id,
+                    source,
+                    stream_id
+                );
+                true
+            }
+        };
+        self.update_statistics(stream_id, |stats| {
+            if pushed {
+                stats.messages_received += 1;
+                stats.bytes_received += bytes.len() as u64;
+                stats.compressed_bytes_received += wire_size as u64;
+                stats.messages_lost += lost;
+            } else {
+                stats.messages_lost += lost + 1;
+            }
+        });
+    }
+
+    fn update_statistics(&self, stream_id: StreamId, update: impl FnOnce(&mut StreamStatistics)) {
+        update(
+            self.statistics
+                .lock()
+                .unwrap()
+                .entry(stream_id)
+                .or_default(),
+        );
+    }
+
+    fn update_pushers(&mut self) {
+        while let Some(Some((stream_id, pusher))) = self.rx.recv().now_or_never() {
+            self.stream_id_to_pusher.insert(stream_id, pusher);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        communication::{Pusher, RecvEndpoint, SendEndpoint},
+        dataflow::Timestamp,
+    };
+
+    type TestMessage = Message<Vec<u8>>;
+
+    fn source() -> SocketAddr {
+        "127.0.0.1:5000".parse().unwrap()
+    }
+
+    /// Returns a receiver which accepts datagrams from [`source`], and the endpoint to which it
+    /// pushes the messages of `stream_id`.
+    async fn receiver(stream_id: StreamId) -> (UdpReceiver, RecvEndpoint<Arc<TestMessage>>) {
+        let mut receiver = UdpReceiver::bind(
+            0,
+            "127.0.0.1:0".parse().unwrap(),
+            vec![source().ip()].into_iter().collect(),
+            Arc::new(Mutex::new(ChannelsToReceivers::new())),
+            StreamStatisticsMap::default(),
+        )
+        .await
+        .unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut pusher = Pusher::<Arc<TestMessage>>::new();
+        pusher.add_endpoint(SendEndpoint::InterThread(tx));
+        receiver
+            .stream_id_to_pusher
+            .insert(stream_id, Box::new(pusher));
+        (receiver, RecvEndpoint::InterThread(rx))
+    }
+
+    fn message(time: u64, size: usize) -> TestMessage {
+        Message::new_message(Timestamp::Time(vec![time]), vec![time as u8; size])
+    }
+
+    fn datagrams(stream_id: StreamId, sequence: u64, msg: &TestMessage) -> Vec<Vec<u8>> {
+        encode_datagrams(stream_id, sequence, &msg.encode().unwrap(), None).unwrap()
+    }
+
+    fn received(rx: &mut RecvEndpoint<Arc<TestMessage>>) -> Vec<TestMessage> {
+        let mut messages = Vec::new();
+        while let Ok(msg) = rx.try_read() {
+            messages.push(TestMessage::clone(&msg));
+        }
+        messages
+    }
+
+    fn statistics(receiver: &UdpReceiver, stream_id: StreamId) -> StreamStatistics {
+        receiver
+            .statistics
+            .lock()
+            .unwrap()
+            .get(&stream_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_fragmentation() {
+        let stream_id = StreamId::new_deterministic();
+        let bytes = message(1, 4000).encode().unwrap();
+        let watermark = Message::<Vec<u8>>::new_watermark(Timestamp::Time(vec![0]))
+            .encode()
+            .unwrap()
+            .to_vec();
+        let datagrams =
+            encode_datagrams(stream_id, 3, &bytes, Some((2, watermark.clone()))).unwrap();
+        assert!(datagrams.len() > 1);
+
+        let mut payload = Vec::new();
+        for (i, datagram) in datagrams.iter().enumerate() {
+            assert!(datagram.len() <= MAX_DATAGRAM_SIZE);
+            let mut fragment = &datagram[..];
+            let header: DatagramHeader = bincode::deserialize_from(&mut fragment).unwrap();
+            assert_eq!(header.stream_id, stream_id);
+            assert_eq!(header.sequence, 3);
+            assert_eq!(header.fragment as usize, i);
+            assert_eq!(header.num_fragments as usize, datagrams.len());
+            // Only the first fragment carries the watermark.
+            let expected_watermark = if i == 0 {
+                Some((2, watermark.clone()))
+            } else {
+                None
+            };
+            assert_eq!(header.watermark, expected_watermark);
+            payload.extend_from_slice(fragment);
+        }
+        assert_eq!(payload, bytes.to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_reassembly() {
+        let stream_id = StreamId::new_deterministic();
+        let (mut receiver, mut rx) = receiver(stream_id).await;
+        let msg = message(1, 4000);
+        let mut datagrams = datagrams(stream_id, 0, &msg);
+        let wire_size: usize = datagrams.iter().map(Vec::len).sum();
+
+        // Fragments arrive out of order, and some are duplicated.
+        datagrams.reverse();
+        let last = datagrams.pop().unwrap();
+        for datagram in datagrams.iter().chain(datagrams.iter()) {
+            receiver.on_datagram(source(), datagram);
+        }
+        assert!(received(&mut rx).is_empty());
+
+        receiver.on_datagram(source(), &last);
+        assert_eq!(received(&mut rx), vec![msg.clone()]);
+
+        let stats = statistics(&receiver, stream_id);
+        assert_eq!(stats.messages_received, 1);
+        assert_eq!(stats.bytes_received, msg.encode().unwrap().len() as u64);
+        assert_eq!(stats.compressed_bytes_received, wire_size as u64);
+        assert_eq!(stats.messages_lost, 0);
+    }
+
+    #[tokio::test]
+    async fn test_piggybacked_watermark() {
+        let stream_id = StreamId::new_deterministic();
+        let (mut receiver, mut rx) = receiver(stream_id).await;
+        let watermark = Message::new_watermark(Timestamp::Time(vec![0]));
+        let msg = message(1, 10);
+        // The watermark was lost, but is piggybacked on the next message.
+        let datagrams = encode_datagrams(
+            stream_id,
+            1,
+            &msg.encode().unwrap(),
+            Some((0, watermark.encode().unwrap().to_vec())),
+        )
+        .unwrap();
+        let wire_size: usize = datagrams.iter().map(Vec::len).sum();
+        for datagram in datagrams.iter() {
+            receiver.on_datagram(source(), datagram);
+        }
+        assert_eq!(received(&mut rx), vec![watermark, msg]);
+
+        let stats = statistics(&receiver, stream_id);
+        assert_eq!(stats.messages_received, 2);
+        assert_eq!(stats.compressed_bytes_received, wire_size as u64);
+        assert_eq!(stats.messages_lost, 0);
+    }
+
+    #[tokio::test]
+    async fn test_loss_counting() {
+        let stream_id = StreamId::new_deterministic();
+        let (mut receiver, mut rx) = receiver(stream_id).await;
+        let messages: Vec<TestMessage> = (0..6).map(|i| message(i, 2000)).collect();
+        let datagrams: Vec<Vec<Vec<u8>>> = messages
+            .iter()
+            .enumerate()
+            .map(|(i, msg)| datagrams(stream_id, i as u64, msg))
+            .collect();
+        let deliver = |receiver: &mut UdpReceiver, sequence: usize| {
+            for datagram in datagrams[sequence].iter() {
+                receiver.on_datagram(source(), datagram);
+            }
+        };
+
+        // Messages 1 and 2 are lost.
+        deliver(&mut receiver, 0);
+        deliver(&mut receiver, 3);
+        assert_eq!(statistics(&receiver, stream_id).messages_lost, 2);
+
+        // Message 2 arrives after a newer message, and is dropped.
+        deliver(&mut receiver, 2);
+        assert_eq!(statistics(&receiver, stream_id).messages_lost, 2);
+
+        // Message 4 is incomplete when message 5 arrives, and is lost.
+        receiver.on_datagram(source(), &datagrams[4][0]);
+        deliver(&mut receiver, 5);
+        receiver.on_datagram(source(), &datagrams[4][1]);
+
+        assert_eq!(
+            received(&mut rx),
+            vec![
+                messages[0].clone(),
+                messages[3].clone(),
+                messages[5].clone()
+            ]
+        );
+        let stats = statistics(&receiver, stream_id);
+        assert_eq!(stats.messages_received, 3);
+        assert_eq!(stats.messages_lost, 3);
+    }
+
+    #[tokio::test]
+    async fn test_undeserializable_message() {
+        let stream_id = StreamId::new_deterministic();
+        let (mut receiver, mut rx) = receiver(stream_id).await;
+        // The datagram has a valid header, but its payload is too short to be a message.
+        for datagram in encode_datagrams(stream_id, 0, &[0xff; 4], None)
+            .unwrap()
+            .iter()
+        {
+            receiver.on_datagram(source(), datagram);
+        }
+        assert!(received(&mut rx).is_empty());
+        assert_eq!(statistics(&receiver, stream_id).messages_lost, 1);
+
+        // The receiver keeps delivering the messages that follow.
+        let msg = message(1, 10);
+        for datagram in datagrams(stream_id, 1, &msg).iter() {
+            receiver.on_datagram(source(), datagram);
+        }
+        assert_eq!(received(&mut rx), vec![msg]);
+        let stats = statistics(&receiver, stream_id);
+        assert_eq!(stats.messages_received, 1);
+        assert_eq!(stats.messages_lost, 1);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_source() {
+        let stream_id = StreamId::new_deterministic();
+        let (mut receiver, mut rx) = receiver(stream_id).await;
+        let unknown_source = "10.0.0.1:5000".parse().unwrap();
+        for datagram in datagrams(stream_id, 0, &message(0, 10)).iter() {
+            receiver.on_datagram(unknown_source, datagram);
+        }
+        assert!(received(&mut rx).is_empty());
+        assert!(receiver.streams.is_empty());
+        assert_eq!(
+            statistics(&receiver, stream_id),
+            StreamStatistics::default()
+        );
+    }
+}
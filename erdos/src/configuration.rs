@@ -1,8 +1,23 @@
-use std::net::SocketAddr;
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
 
 use tracing::Level;
 
-use crate::node::NodeId;
+use crate::{
+    communication::{FrameOptions, TlsConfig, Transport},
+    dataflow::graph::PlacementStrategy,
+    node::{Clock, NodeId, VirtualClock},
+};
+
+/// Default period between heartbeats sent to other nodes.
+const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+/// Default time after which a node that has not sent a heartbeat is considered failed.
+const DEFAULT_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Default time operators are given to exit once the node shuts down.
+const DEFAULT_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+/// Default period between probes of the clocks of other nodes.
+const DEFAULT_CLOCK_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+/// Default maximum number of messages sent to another node in one write.
+const DEFAULT_MAX_BATCH_SIZE: usize = 64;
 
 /// Stores the configuration parameters of a [`node`](crate::node::Node).
 #[derive(Clone)]
@@ -15,8 +30,47 @@ pub struct Configuration {
     pub data_addresses: Vec<SocketAddr>,
     /// Mapping between node indices and control socket addresses.
     pub control_addresses: Vec<SocketAddr>,
-    /// DOT file to export dataflow graph.
+    /// Address of the coordinator from which the node learns the addresses of the other nodes.
+    /// If set, only the node's own entries in `data_addresses` and `control_addresses` are used.
+    /// Node 0 is the coordinator, and listens on this address. Defaults to `None`.
+    pub coordinator_address: Option<SocketAddr>,
+    /// With a coordinator, the number of nodes, including the coordinator, which must join
+    /// before the dataflow starts. The other nodes join the running dataflow, and their
+    /// operators start once they join. Only read by the coordinator. If `None`, all nodes must
+    /// join. Defaults to `None`.
+    pub min_nodes: Option<usize>,
+    /// Mapping between node indices and the transports used to connect to the nodes.
+    /// Nodes without a transport are connected via shared memory if they run on the same
+    /// machine and `shared_memory` is set, and via [TCP](crate::communication::TcpTransport)
+    /// otherwise.
+    pub transports: HashMap<NodeId, Arc<dyn Transport>>,
+    /// Whether nodes on the same machine are connected via
+    /// [shared memory](crate::communication::SharedMemoryTransport) unless another transport is
+    /// configured for them. Nodes are on the same machine if their addresses are both loopback
+    /// addresses or the same IP address. Must be the same on all nodes. Defaults to `true` on
+    /// Linux, the only platform which supports shared memory connections.
+    pub shared_memory: bool,
+    /// Encrypts and mutually authenticates the data and control connections between nodes.
+    /// If `None`, connections are not encrypted. Defaults to `None`.
+    pub tls: Option<TlsConfig>,
+    /// The maximum number of messages to another node which are written to the connection
+    /// before it is flushed. Defaults to 64.
+    pub max_batch_size: usize,
+    /// The maximum time to wait for more messages to another node before flushing a batch
+    /// which is not full. If zero, only messages which are already pending are batched.
+    /// Defaults to 0.
+    pub max_batch_linger: Duration,
+    /// How messages are framed on the data connections between nodes. Must be the same on all
+    /// nodes, except for the maximum frame size. Defaults to 32-bit lengths without checksums,
+    /// and no maximum frame size.
+    pub frame_options: FrameOptions,
+    /// File to which the dataflow graph is exported. The format depends on the extension:
+    /// JSON (*.json), YAML (*.yaml, *.yml), or DOT otherwise.
     pub graph_filename: Option<String>,
+    /// Whether the exported DOT file draws each composite operator, i.e. the operators of a
+    /// top-level [subgraph](crate::dataflow::Graph::subgraph), as a single operator.
+    /// Defaults to `false`.
+    pub collapse_composite_operators: bool,
     /// The logging level of the logger initialized by ERDOS.
     /// If `None`, ERDOS will not initialize a logger.
     /// Defaults to [`Level::DEBUG`] when compiling in debug mode,
@@ -25,6 +79,29 @@ pub struct Configuration {
     /// While [`tracing`] provides extensions for connecting additional
     /// subscribers, note that these may impact performance.
     pub logging_level: Option<Level>,
+    /// The period with which the node sends heartbeats to other nodes over the
+    /// control connections. Defaults to 500 ms.
+    pub heartbeat_interval: Duration,
+    /// The time after which a node that has not sent a heartbeat is considered failed.
+    /// Defaults to 5 s.
+    pub heartbeat_timeout: Duration,
+    /// The period with which the node probes the clocks of other nodes over the control
+    /// connections to estimate their offsets. If zero, the node does not estimate offsets.
+    /// Defaults to 1 s.
+    pub clock_sync_interval: Duration,
+    /// The time operators are given to exit after their
+    /// [`CancellationToken`](crate::dataflow::CancellationToken) is cancelled during shutdown.
+    /// Defaults to 5 s.
+    pub shutdown_grace_period: Duration,
+    /// Assigns operators which are not pinned to a node when the dataflow graph is compiled.
+    /// If `None`, such operators run on node `0`. Defaults to `None`.
+    pub placement_strategy: Option<Arc<dyn PlacementStrategy>>,
+    /// The clock on which deadlines are timed, and with which messages to other nodes are
+    /// timestamped. Defaults to [`Clock::System`].
+    ///
+    /// Heartbeats, connection setup, batching, draining and the shutdown grace period always use
+    /// the system clock, so advancing a [`VirtualClock`] does not expire them.
+    pub clock: Clock,
 }
 
 impl Configuration {
@@ -45,11 +122,70 @@ impl Configuration {
             num_threads,
             data_addresses,
             control_addresses,
+            coordinator_address: None,
+            min_nodes: None,
+            transports: HashMap::new(),
+            shared_memory: cfg!(target_os = "linux"),
+            tls: None,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            max_batch_linger: Duration::ZERO,
+            frame_options: FrameOptions::default(),
             graph_filename: None,
+            collapse_composite_operators: false,
             logging_level: log_level,
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            heartbeat_timeout: DEFAULT_HEARTBEAT_TIMEOUT,
+            clock_sync_interval: DEFAULT_CLOCK_SYNC_INTERVAL,
+            shutdown_grace_period: DEFAULT_SHUTDOWN_GRACE_PERIOD,
+            placement_strategy: None,
+            clock: Clock::System,
         }
     }
 
+    /// Creates the configuration of a node which learns the addresses of the other nodes from a
+    /// coordinator, so that the addresses need not be known up front.
+    ///
+    /// Node 0 is the coordinator, and waits at `coordinator_address` until all `num_nodes` nodes
+    /// registered their addresses, or as many as set with
+    /// [`with_min_nodes`](Configuration::with_min_nodes). Nodes may start in any order;
+    /// operators start running once these nodes joined. The remaining nodes join the running
+    /// cluster, and their operators start once they join. Meanwhile, messages to them are
+    /// queued, and the other nodes do not complete the dataflow until all nodes joined.
+    pub fn with_coordinator(
+        node_index: NodeId,
+        num_nodes: usize,
+        data_address: SocketAddr,
+        control_address: SocketAddr,
+        coordinator_address: SocketAddr,
+        num_threads: usize,
+    ) -> Self {
+        assert!(
+            node_index < num_nodes,
+            "Node index is larger than number of available nodes"
+        );
+        let (data_addresses, control_addresses) =
+            Self::own_addresses(node_index, num_nodes, data_address, control_address);
+        let mut config = Self::new(node_index, data_addresses, control_addresses, num_threads);
+        config.coordinator_address = Some(coordinator_address);
+        config
+    }
+
+    /// Lists the node's own addresses at its index, and placeholders for the addresses of the
+    /// other nodes, which are learned from the coordinator.
+    fn own_addresses(
+        node_index: NodeId,
+        num_nodes: usize,
+        data_address: SocketAddr,
+        control_address: SocketAddr,
+    ) -> (Vec<SocketAddr>, Vec<SocketAddr>) {
+        let unknown = SocketAddr::from(([0, 0, 0, 0], 0));
+        let mut data_addresses = vec![unknown; num_nodes];
+        let mut control_addresses = vec![unknown; num_nodes];
+        data_addresses[node_index] = data_address;
+        control_addresses[node_index] = control_address;
+        (data_addresses, control_addresses)
+    }
+
     /// Creates a node configuration from command line arguments.
     pub fn from_args(args: &clap::ArgMatches) -> Self {
         let num_threads = args
@@ -78,6 +214,36 @@ impl Configuration {
             .unwrap()
             .parse()
             .expect("Unable to parse node index");
+        let coordinator_address: Option<SocketAddr> = args
+            .value_of("coordinator")
+            .map(|addr| addr.parse().expect("Unable to parse coordinator address"));
+        let min_nodes: Option<usize> = args
+            .value_of("min-nodes")
+            .map(|n| n.parse().expect("Unable to parse minimum number of nodes"));
+        if coordinator_address.is_some() {
+            assert_eq!(
+                data_addresses.len(),
+                1,
+                "With a coordinator, each node only lists its own data and control address"
+            );
+            let num_nodes = args
+                .value_of("num-nodes")
+                .expect("The number of nodes is required with a coordinator")
+                .parse()
+                .expect("Unable to parse number of nodes");
+            assert!(
+                node_index < num_nodes,
+                "Node index is larger than number of available nodes"
+            );
+            let (data, control) = Self::own_addresses(
+                node_index,
+                num_nodes,
+                data_addresses[0],
+                control_addresses[0],
+            );
+            data_addresses = data;
+            control_addresses = control;
+        }
         assert!(
             node_index < data_addresses.len(),
             "Node index is larger than number of available nodes"
@@ -101,24 +267,166 @@ impl Configuration {
             num_threads,
             data_addresses,
             control_addresses,
+            coordinator_address,
+            min_nodes,
+            transports: HashMap::new(),
+            shared_memory: cfg!(target_os = "linux"),
+            tls: None,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            max_batch_linger: Duration::ZERO,
+            frame_options: FrameOptions::default(),
             graph_filename,
+            collapse_composite_operators: false,
             logging_level: log_level,
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            heartbeat_timeout: DEFAULT_HEARTBEAT_TIMEOUT,
+            clock_sync_interval: DEFAULT_CLOCK_SYNC_INTERVAL,
+            shutdown_grace_period: DEFAULT_SHUTDOWN_GRACE_PERIOD,
+            placement_strategy: None,
+            clock: Clock::System,
         }
     }
 
     /// Upon executing, exports the dataflow graph as a
     /// [DOT file](https://en.wikipedia.org/wiki/DOT_(graph_description_language)).
+    ///
+    /// If `filename` ends in `.json`, `.yaml`, or `.yml`, the graph is instead exported as a
+    /// [`GraphDescription`](crate::dataflow::graph::GraphDescription) in JSON or YAML.
     pub fn export_dataflow_graph(mut self, filename: &str) -> Self {
         self.graph_filename = Some(filename.to_string());
         self
     }
 
+    /// Draws each composite operator as a single operator in the exported DOT file.
+    pub fn collapse_composite_operators(mut self) -> Self {
+        self.collapse_composite_operators = true;
+        self
+    }
+
+    /// Starts the dataflow once `min_nodes` nodes, including the coordinator, joined the
+    /// coordinator. The other nodes join the running dataflow.
+    ///
+    /// Best-effort streams must not connect the nodes which join later to other nodes, as
+    /// datagrams are only exchanged between nodes which know each other's addresses when they set
+    /// up their streams.
+    pub fn with_min_nodes(mut self, min_nodes: usize) -> Self {
+        self.min_nodes = Some(min_nodes);
+        self
+    }
+
+    /// Connects to the node `node_id` using `transport`, e.g. a
+    /// [`UnixSocketTransport`](crate::communication::UnixSocketTransport) if the nodes run on
+    /// the same machine.
+    ///
+    /// The other node must also be configured to use the transport to connect to this node.
+    pub fn with_transport(mut self, node_id: NodeId, transport: impl Transport + 'static) -> Self {
+        self.transports.insert(node_id, Arc::new(transport));
+        self
+    }
+
+    /// Connects to nodes on the same machine via TCP instead of shared memory, unless another
+    /// transport is configured for them.
+    ///
+    /// The other nodes must also be configured not to use shared memory.
+    pub fn disable_shared_memory(mut self) -> Self {
+        self.shared_memory = false;
+        self
+    }
+
+    /// Establishes TLS on the connections to other nodes. Peers which do not present the
+    /// certificate listed for the node index they claim are rejected.
+    ///
+    /// All nodes must be configured with TLS.
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Sets the maximum number of messages to another node sent in one write. Set to `1` to
+    /// disable batching.
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size.max(1);
+        self
+    }
+
+    /// Sets the maximum time to wait for more messages before flushing a batch which is not
+    /// full. Increases throughput for small high-rate messages at the cost of latency.
+    pub fn with_max_batch_linger(mut self, max_batch_linger: Duration) -> Self {
+        self.max_batch_linger = max_batch_linger;
+        self
+    }
+
+    /// Appends a CRC32 checksum to each message sent to other nodes, so that corrupted messages
+    /// are detected. All nodes must enable checksums.
+    pub fn with_frame_checksums(mut self) -> Self {
+        self.frame_options.checksums = true;
+        self
+    }
+
+    /// Encodes the sizes of messages sent to other nodes with 64 bits, which is required to
+    /// send messages larger than 4 GiB. All nodes must enable 64-bit lengths.
+    pub fn with_64_bit_frame_lengths(mut self) -> Self {
+        self.frame_options.long_lengths = true;
+        self
+    }
+
+    /// Closes connections on which another node sends a message larger than `max_frame_size`
+    /// bytes, before memory is allocated for the message. Recommended with 64-bit lengths,
+    /// which allow messages of any size.
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.frame_options.max_frame_size = Some(max_frame_size);
+        self
+    }
+
     /// Sets the logging level.
     pub fn with_logging_level(mut self, level: Level) -> Self {
         self.logging_level = Some(level);
         self
     }
 
+    /// Sets the period with which heartbeats are sent to other nodes.
+    pub fn with_heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = interval;
+        self
+    }
+
+    /// Sets the time after which a node that has not sent a heartbeat is considered failed.
+    pub fn with_heartbeat_timeout(mut self, timeout: Duration) -> Self {
+        self.heartbeat_timeout = timeout;
+        self
+    }
+
+    /// Sets the period with which the clocks of other nodes are probed to estimate their
+    /// offsets. Set to zero to disable the estimation.
+    pub fn with_clock_sync_interval(mut self, interval: Duration) -> Self {
+        self.clock_sync_interval = interval;
+        self
+    }
+
+    /// Sets the time operators are given to exit once the node shuts down.
+    pub fn with_shutdown_grace_period(mut self, grace_period: Duration) -> Self {
+        self.shutdown_grace_period = grace_period;
+        self
+    }
+
+    /// Places operators which are not pinned to a node using `strategy`, e.g.
+    /// [`RoundRobin`](crate::dataflow::graph::RoundRobin). The placement is logged at the
+    /// `INFO` level.
+    ///
+    /// All nodes must use the same strategy.
+    pub fn with_placement_strategy(mut self, strategy: impl PlacementStrategy + 'static) -> Self {
+        self.placement_strategy = Some(Arc::new(strategy));
+        self
+    }
+
+    /// Runs the node on a [`VirtualClock`], which only advances when the caller advances it.
+    ///
+    /// Useful for reproducing the behavior of deadlines in tests.
+    pub fn with_virtual_clock(mut self, clock: VirtualClock) -> Self {
+        self.clock = Clock::Virtual(clock);
+        self
+    }
+
     /// ERDOS will not initialize a logger if this method is called.
     pub fn disable_logger(mut self) -> Self {
         self.logging_level = None;
@@ -0,0 +1,147 @@
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+use tokio::sync::Notify;
+
+/// Signals long-running [operators](crate::dataflow::operator) to stop.
+///
+/// ERDOS cancels the token when the [`Node`](crate::node::Node) shuts down. Operators which
+/// override `run` (e.g. a [`Source`](crate::dataflow::operator::Source) which loops forever)
+/// should periodically check [`CancellationToken::is_cancelled`], or block on
+/// [`CancellationToken::wait_timeout`], and return once the token is cancelled.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// #
+/// # use erdos::dataflow::{
+/// #     operator::{Source, OperatorConfig}, stream::WriteStreamT, Message, Timestamp, WriteStream
+/// # };
+/// #
+/// struct CounterOperator {}
+///
+/// impl Source<usize> for CounterOperator {
+///     fn run(&mut self, config: &OperatorConfig, write_stream: &mut WriteStream<usize>) {
+///         let mut t = 0;
+///         // Send a message every 100 ms until the node shuts down.
+///         while !config.cancellation_token.wait_timeout(Duration::from_millis(100)) {
+///             write_stream
+///                 .send(Message::new_message(Timestamp::Time(vec![t as u64]), t))
+///                 .unwrap();
+///             t += 1;
+///         }
+///     }
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<CancellationTokenInner>,
+}
+
+#[derive(Default)]
+struct CancellationTokenInner {
+    cancelled: Mutex<bool>,
+    /// Wakes up threads blocked in `wait_timeout`.
+    cvar: Condvar,
+    /// Wakes up tasks awaiting `cancelled`.
+    notify: Notify,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` if the token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        *self.inner.cancelled.lock().unwrap()
+    }
+
+    /// Blocks the current thread for up to `timeout` until the token is cancelled.
+    ///
+    /// Returns `true` if the token was cancelled.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let cancelled = self.inner.cancelled.lock().unwrap();
+        let (cancelled, _) = self
+            .inner
+            .cvar
+            .wait_timeout_while(cancelled, timeout, |cancelled| !*cancelled)
+            .unwrap();
+        *cancelled
+    }
+
+    /// Completes once the token is cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Cancels the token, and wakes up all threads and tasks waiting on it.
+    pub(crate) fn cancel(&self) {
+        *self.inner.cancelled.lock().unwrap() = true;
+        self.inner.cvar.notify_all();
+        self.inner.notify.notify_waiters();
+    }
+}
+
+impl std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "CancellationToken {{ cancelled: {} }}",
+            self.is_cancelled()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use futures::{executor::block_on, poll, FutureExt};
+
+    use super::*;
+
+    #[test]
+    fn test_cancel() {
+        let token = CancellationToken::new();
+        assert!(!token.is_cancelled());
+        assert!(!token.wait_timeout(Duration::from_millis(1)));
+
+        // Clones share the same state.
+        token.clone().cancel();
+        assert!(token.is_cancelled());
+        assert!(token.wait_timeout(Duration::from_secs(3600)));
+        assert_eq!(token.cancelled().now_or_never(), Some(()));
+    }
+
+    #[test]
+    fn test_wait_timeout_wakes_up_on_cancel() {
+        let token = CancellationToken::new();
+        let token_copy = token.clone();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            token_copy.cancel();
+        });
+        assert!(token.wait_timeout(Duration::from_secs(3600)));
+        canceller.join().unwrap();
+    }
+
+    #[test]
+    fn test_cancelled_wakes_up_on_cancel() {
+        let token = CancellationToken::new();
+        block_on(async {
+            let mut cancelled = Box::pin(token.cancelled());
+            assert!(poll!(&mut cancelled).is_pending());
+            token.cancel();
+            assert!(poll!(&mut cancelled).is_ready());
+        });
+    }
+}
@@ -3,7 +3,11 @@ use std::sync::{Arc, Mutex};
 use serde::Deserialize;
 
 use crate::{
-    dataflow::{graph::default_graph, operator::*, AppendableState, Data, State, Stream},
+    dataflow::{
+        graph::{default_graph, Graph},
+        operator::*,
+        AppendableState, Data, State, Stream,
+    },
     node::operator_executors::{
         OneInExecutor, OneInOneOutMessageProcessor, OneInTwoOutMessageProcessor, OperatorExecutorT,
         ParallelOneInOneOutMessageProcessor, ParallelOneInTwoOutMessageProcessor,
@@ -11,57 +15,524 @@ use crate::{
         SourceExecutor, TwoInExecutor, TwoInOneOutMessageProcessor,
     },
     scheduler::channel_manager::ChannelManager,
-    OperatorId,
 };
 
-use super::stream::OperatorStream;
+use super::stream::{OperatorStream, StreamId};
+
+impl Graph {
+    /// Adds a [`Source`] operator, which has no read streams, but introduces data into the dataflow
+    /// graph by interacting with external data sources (e.g., other systems, sensor data).
+    pub fn connect_source<O, T>(
+        &self,
+        operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
+        mut config: OperatorConfig,
+    ) -> OperatorStream<T>
+    where
+        O: 'static + Source<T>,
+        T: Data + for<'a> Deserialize<'a>,
+    {
+        self.configure_operator(&mut config, "source", &[]);
+        let write_stream = OperatorStream::new(self, StreamId::new_v5(config.id, "write-stream"));
+
+        let write_stream_id = write_stream.id();
+        let op_runner = move |config: OperatorConfig,
+                              channel_manager: Arc<Mutex<ChannelManager>>|
+              -> Box<dyn OperatorExecutorT> {
+            let mut channel_manager = channel_manager.lock().unwrap();
+
+            let write_stream = channel_manager.write_stream(write_stream_id).unwrap();
+
+            let executor = SourceExecutor::new(config.clone(), operator_fn.clone(), write_stream);
+
+            Box::new(executor)
+        };
+
+        self.lock().add_operator::<_, (), (), T, ()>(
+            config,
+            op_runner,
+            None,
+            None,
+            Some(&write_stream),
+            None,
+        );
+
+        write_stream
+    }
+
+    /// Adds a [`ParallelSink`] operator, which receives data on input read streams and directly
+    /// interacts with external systems.
+    pub fn connect_parallel_sink<O, S, T, U>(
+        &self,
+        operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
+        // Add state as an explicit argument to support future features such as state sharing.
+        state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
+        mut config: OperatorConfig,
+        read_stream: &dyn Stream<T>,
+    ) where
+        O: 'static + ParallelSink<S, T, U>,
+        S: AppendableState<U>,
+        T: Data + for<'a> Deserialize<'a>,
+        U: 'static + Send + Sync,
+    {
+        self.configure_operator(&mut config, "parallel_sink", &[read_stream.id()]);
+
+        let read_stream_id = read_stream.id();
+        let op_runner = move |config: OperatorConfig,
+                              channel_manager: Arc<Mutex<ChannelManager>>|
+              -> Box<dyn OperatorExecutorT> {
+            let mut channel_manager = channel_manager.lock().unwrap();
+
+            let read_stream = channel_manager.take_read_stream(read_stream_id).unwrap();
+
+            Box::new(OneInExecutor::new(
+                config.clone(),
+                Box::new(ParallelSinkMessageProcessor::new(
+                    config.clone(),
+                    operator_fn.clone(),
+                    state_fn.clone(),
+                )),
+                read_stream,
+            ))
+        };
+
+        self.lock().add_operator::<_, T, (), (), ()>(
+            config,
+            op_runner,
+            Some(read_stream),
+            None,
+            None,
+            None,
+        );
+    }
+
+    /// Adds a [`Sink`] operator, which receives data on input read streams and directly interacts
+    /// with external systems.
+    pub fn connect_sink<O, S, T>(
+        &self,
+        operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
+        // Add state as an explicit argument to support future features such as state sharing.
+        state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
+        mut config: OperatorConfig,
+        read_stream: &dyn Stream<T>,
+    ) where
+        O: 'static + Sink<S, T>,
+        S: State,
+        T: Data + for<'a> Deserialize<'a>,
+    {
+        self.configure_operator(&mut config, "sink", &[read_stream.id()]);
+
+        let read_stream_id = read_stream.id();
+        let op_runner = move |config: OperatorConfig,
+                              channel_manager: Arc<Mutex<ChannelManager>>|
+              -> Box<dyn OperatorExecutorT> {
+            let mut channel_manager = channel_manager.lock().unwrap();
+
+            let read_stream = channel_manager.take_read_stream(read_stream_id).unwrap();
+
+            Box::new(OneInExecutor::new(
+                config.clone(),
+                Box::new(SinkMessageProcessor::new(
+                    config.clone(),
+                    operator_fn.clone(),
+                    state_fn.clone(),
+                )),
+                read_stream,
+            ))
+        };
+
+        self.lock().add_operator::<_, T, (), (), ()>(
+            config,
+            op_runner,
+            Some(read_stream),
+            None,
+            None,
+            None,
+        );
+    }
+
+    /// Adds a [`ParallelOneInOneOut`] operator that has one input read stream and one output
+    /// write stream.
+    pub fn connect_parallel_one_in_one_out<O, S, T, U, V>(
+        &self,
+        operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
+        // Add state as an explicit argument to support future features such as state sharing.
+        state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
+        mut config: OperatorConfig,
+        read_stream: &dyn Stream<T>,
+    ) -> OperatorStream<U>
+    where
+        O: 'static + ParallelOneInOneOut<S, T, U, V>,
+        S: AppendableState<V>,
+        T: Data + for<'a> Deserialize<'a>,
+        U: Data + for<'a> Deserialize<'a>,
+        V: 'static + Send + Sync,
+    {
+        self.configure_operator(&mut config, "parallel_one_in_one_out", &[read_stream.id()]);
+        let write_stream = OperatorStream::new(self, StreamId::new_v5(config.id, "write-stream"));
+
+        let read_stream_id = read_stream.id();
+        let write_stream_id = write_stream.id();
+        let op_runner = move |config: OperatorConfig,
+                              channel_manager: Arc<Mutex<ChannelManager>>|
+              -> Box<dyn OperatorExecutorT> {
+            let mut channel_manager = channel_manager.lock().unwrap();
+
+            let read_stream = channel_manager.take_read_stream(read_stream_id).unwrap();
+            let write_stream = channel_manager.write_stream(write_stream_id).unwrap();
+
+            Box::new(OneInExecutor::new(
+                config.clone(),
+                Box::new(ParallelOneInOneOutMessageProcessor::new(
+                    config.clone(),
+                    operator_fn.clone(),
+                    state_fn.clone(),
+                    write_stream,
+                )),
+                read_stream,
+            ))
+        };
+
+        self.lock().add_operator::<_, T, (), U, ()>(
+            config,
+            op_runner,
+            Some(read_stream),
+            None,
+            Some(&write_stream),
+            None,
+        );
+
+        write_stream
+    }
+
+    /// Adds a [`OneInOneOut`] operator that has one input read stream and one output write stream.
+    pub fn connect_one_in_one_out<O, S, T, U>(
+        &self,
+        operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
+        // Add state as an explicit argument to support future features such as state sharing.
+        state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
+        mut config: OperatorConfig,
+        read_stream: &dyn Stream<T>,
+    ) -> OperatorStream<U>
+    where
+        O: 'static + OneInOneOut<S, T, U>,
+        S: State,
+        T: Data + for<'a> Deserialize<'a>,
+        U: Data + for<'a> Deserialize<'a>,
+    {
+        self.configure_operator(&mut config, "one_in_one_out", &[read_stream.id()]);
+        let write_stream = OperatorStream::new(self, StreamId::new_v5(config.id, "write-stream"));
+
+        let read_stream_id = read_stream.id();
+        let write_stream_id = write_stream.id();
+        let op_runner = move |config: OperatorConfig,
+                              channel_manager: Arc<Mutex<ChannelManager>>|
+              -> Box<dyn OperatorExecutorT> {
+            let mut channel_manager = channel_manager.lock().unwrap();
+
+            let read_stream = channel_manager.take_read_stream(read_stream_id).unwrap();
+            let write_stream = channel_manager.write_stream(write_stream_id).unwrap();
+
+            Box::new(OneInExecutor::new(
+                config.clone(),
+                Box::new(OneInOneOutMessageProcessor::new(
+                    config.clone(),
+                    operator_fn.clone(),
+                    state_fn.clone(),
+                    write_stream,
+                )),
+                read_stream,
+            ))
+        };
+
+        self.lock().add_operator::<_, T, (), U, ()>(
+            config,
+            op_runner,
+            Some(read_stream),
+            None,
+            Some(&write_stream),
+            None,
+        );
+
+        write_stream
+    }
+
+    /// Adds a [`ParallelTwoInOneOut`] operator that has two input read streams and one output
+    /// write stream.
+    pub fn connect_parallel_two_in_one_out<O, S, T, U, V, W>(
+        &self,
+        operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
+        // Add state as an explicit argument to support future features such as state sharing.
+        state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
+        mut config: OperatorConfig,
+        left_read_stream: &dyn Stream<T>,
+        right_read_stream: &dyn Stream<U>,
+    ) -> OperatorStream<V>
+    where
+        O: 'static + ParallelTwoInOneOut<S, T, U, V, W>,
+        S: AppendableState<W>,
+        T: Data + for<'a> Deserialize<'a>,
+        U: Data + for<'a> Deserialize<'a>,
+        V: Data + for<'a> Deserialize<'a>,
+        W: 'static + Send + Sync,
+    {
+        self.configure_operator(
+            &mut config,
+            "parallel_two_in_one_out",
+            &[left_read_stream.id(), right_read_stream.id()],
+        );
+        let write_stream = OperatorStream::new(self, StreamId::new_v5(config.id, "write-stream"));
+
+        let left_read_stream_id = left_read_stream.id();
+        let right_read_stream_id = right_read_stream.id();
+        let write_stream_id = write_stream.id();
+        let op_runner = move |config: OperatorConfig,
+                              channel_manager: Arc<Mutex<ChannelManager>>|
+              -> Box<dyn OperatorExecutorT> {
+            let mut channel_manager = channel_manager.lock().unwrap();
+
+            let left_read_stream = channel_manager
+                .take_read_stream(left_read_stream_id)
+                .unwrap();
+            let right_read_stream = channel_manager
+                .take_read_stream(right_read_stream_id)
+                .unwrap();
+            let write_stream = channel_manager.write_stream(write_stream_id).unwrap();
+
+            Box::new(TwoInExecutor::new(
+                config.clone(),
+                Box::new(ParallelTwoInOneOutMessageProcessor::new(
+                    config.clone(),
+                    operator_fn.clone(),
+                    state_fn.clone(),
+                    write_stream,
+                )),
+                left_read_stream,
+                right_read_stream,
+            ))
+        };
+
+        self.lock().add_operator::<_, T, U, V, ()>(
+            config,
+            op_runner,
+            Some(left_read_stream),
+            Some(right_read_stream),
+            Some(&write_stream),
+            None,
+        );
+
+        write_stream
+    }
+
+    /// Adds a [`TwoInOneOut`] operator that has two input read streams and one output write stream.
+    pub fn connect_two_in_one_out<O, S, T, U, V>(
+        &self,
+        operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
+        // Add state as an explicit argument to support future features such as state sharing.
+        state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
+        mut config: OperatorConfig,
+        left_read_stream: &dyn Stream<T>,
+        right_read_stream: &dyn Stream<U>,
+    ) -> OperatorStream<V>
+    where
+        O: 'static + TwoInOneOut<S, T, U, V>,
+        S: State,
+        T: Data + for<'a> Deserialize<'a>,
+        U: Data + for<'a> Deserialize<'a>,
+        V: Data + for<'a> Deserialize<'a>,
+    {
+        self.configure_operator(
+            &mut config,
+            "two_in_one_out",
+            &[left_read_stream.id(), right_read_stream.id()],
+        );
+        let write_stream = OperatorStream::new(self, StreamId::new_v5(config.id, "write-stream"));
+
+        let left_read_stream_id = left_read_stream.id();
+        let right_read_stream_id = right_read_stream.id();
+        let write_stream_id = write_stream.id();
+        let op_runner = move |config: OperatorConfig,
+                              channel_manager: Arc<Mutex<ChannelManager>>|
+              -> Box<dyn OperatorExecutorT> {
+            let mut channel_manager = channel_manager.lock().unwrap();
+
+            let left_read_stream = channel_manager
+                .take_read_stream(left_read_stream_id)
+                .unwrap();
+            let right_read_stream = channel_manager
+                .take_read_stream(right_read_stream_id)
+                .unwrap();
+            let write_stream = channel_manager.write_stream(write_stream_id).unwrap();
+
+            Box::new(TwoInExecutor::new(
+                config.clone(),
+                Box::new(TwoInOneOutMessageProcessor::new(
+                    config.clone(),
+                    operator_fn.clone(),
+                    state_fn.clone(),
+                    write_stream,
+                )),
+                left_read_stream,
+                right_read_stream,
+            ))
+        };
+
+        self.lock().add_operator::<_, T, U, V, ()>(
+            config,
+            op_runner,
+            Some(left_read_stream),
+            Some(right_read_stream),
+            Some(&write_stream),
+            None,
+        );
+
+        write_stream
+    }
+
+    /// Adds a [`ParallelOneInTwoOut`] operator that has one input read stream and two output
+    /// write streams.
+    pub fn connect_parallel_one_in_two_out<O, S, T, U, V, W>(
+        &self,
+        operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
+        // Add state as an explicit argument to support future features such as state sharing.
+        state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
+        mut config: OperatorConfig,
+        read_stream: &dyn Stream<T>,
+    ) -> (OperatorStream<U>, OperatorStream<V>)
+    where
+        O: 'static + ParallelOneInTwoOut<S, T, U, V, W>,
+        S: AppendableState<W>,
+        T: Data + for<'a> Deserialize<'a>,
+        U: Data + for<'a> Deserialize<'a>,
+        V: Data + for<'a> Deserialize<'a>,
+        W: 'static + Send + Sync,
+    {
+        self.configure_operator(&mut config, "parallel_one_in_two_out", &[read_stream.id()]);
+        let left_write_stream =
+            OperatorStream::new(self, StreamId::new_v5(config.id, "left-write-stream"));
+        let right_write_stream =
+            OperatorStream::new(self, StreamId::new_v5(config.id, "right-write-stream"));
+
+        let read_stream_id = read_stream.id();
+        let left_write_stream_id = left_write_stream.id();
+        let right_write_stream_id = right_write_stream.id();
+        let op_runner = move |config: OperatorConfig,
+                              channel_manager: Arc<Mutex<ChannelManager>>|
+              -> Box<dyn OperatorExecutorT> {
+            let mut channel_manager = channel_manager.lock().unwrap();
+
+            let read_stream = channel_manager.take_read_stream(read_stream_id).unwrap();
+            let left_write_stream = channel_manager.write_stream(left_write_stream_id).unwrap();
+            let right_write_stream = channel_manager.write_stream(right_write_stream_id).unwrap();
+
+            Box::new(OneInExecutor::new(
+                config.clone(),
+                Box::new(ParallelOneInTwoOutMessageProcessor::new(
+                    config.clone(),
+                    operator_fn.clone(),
+                    state_fn.clone(),
+                    left_write_stream,
+                    right_write_stream,
+                )),
+                read_stream,
+            ))
+        };
+
+        self.lock().add_operator::<_, T, (), U, V>(
+            config,
+            op_runner,
+            Some(read_stream),
+            None,
+            Some(&left_write_stream),
+            Some(&right_write_stream),
+        );
+
+        (left_write_stream, right_write_stream)
+    }
+
+    /// Adds a [`OneInTwoOut`] operator that has one input read stream and two output write streams.
+    pub fn connect_one_in_two_out<O, S, T, U, V>(
+        &self,
+        operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
+        // Add state as an explicit argument to support future features such as state sharing.
+        state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
+        mut config: OperatorConfig,
+        read_stream: &dyn Stream<T>,
+    ) -> (OperatorStream<U>, OperatorStream<V>)
+    where
+        O: 'static + OneInTwoOut<S, T, U, V>,
+        S: State,
+        T: Data + for<'a> Deserialize<'a>,
+        U: Data + for<'a> Deserialize<'a>,
+        V: Data + for<'a> Deserialize<'a>,
+    {
+        self.configure_operator(&mut config, "one_in_two_out", &[read_stream.id()]);
+        let left_write_stream =
+            OperatorStream::new(self, StreamId::new_v5(config.id, "left-write-stream"));
+        let right_write_stream =
+            OperatorStream::new(self, StreamId::new_v5(config.id, "right-write-stream"));
+
+        let read_stream_id = read_stream.id();
+        let left_write_stream_id = left_write_stream.id();
+        let right_write_stream_id = right_write_stream.id();
+        let op_runner = move |config: OperatorConfig,
+                              channel_manager: Arc<Mutex<ChannelManager>>|
+              -> Box<dyn OperatorExecutorT> {
+            let mut channel_manager = channel_manager.lock().unwrap();
+
+            let read_stream = channel_manager.take_read_stream(read_stream_id).unwrap();
+            let left_write_stream = channel_manager.write_stream(left_write_stream_id).unwrap();
+            let right_write_stream = channel_manager.write_stream(right_write_stream_id).unwrap();
+
+            Box::new(OneInExecutor::new(
+                config.clone(),
+                Box::new(OneInTwoOutMessageProcessor::new(
+                    config.clone(),
+                    operator_fn.clone(),
+                    state_fn.clone(),
+                    left_write_stream,
+                    right_write_stream,
+                )),
+                read_stream,
+            ))
+        };
+
+        self.lock().add_operator::<_, T, (), U, V>(
+            config,
+            op_runner,
+            Some(read_stream),
+            None,
+            Some(&left_write_stream),
+            Some(&right_write_stream),
+        );
+
+        (left_write_stream, right_write_stream)
+    }
+}
 
 /// Adds a [`Source`] operator, which has no read streams, but introduces data into the dataflow
 /// graph by interacting with external data sources (e.g., other systems, sensor data).
+///
+/// The operator is added to the default graph (see [`Graph::connect_source`]).
 pub fn connect_source<O, T>(
     operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
-    mut config: OperatorConfig,
+    config: OperatorConfig,
 ) -> OperatorStream<T>
 where
     O: 'static + Source<T>,
     T: Data + for<'a> Deserialize<'a>,
 {
-    config.id = OperatorId::new_deterministic();
-    let write_stream = OperatorStream::new();
-
-    let config_copy = config.clone();
-    let write_stream_id = write_stream.id();
-    let op_runner =
-        move |channel_manager: Arc<Mutex<ChannelManager>>| -> Box<dyn OperatorExecutorT> {
-            let mut channel_manager = channel_manager.lock().unwrap();
-
-            let write_stream = channel_manager.write_stream(write_stream_id).unwrap();
-
-            let executor =
-                SourceExecutor::new(config_copy.clone(), operator_fn.clone(), write_stream);
-
-            Box::new(executor)
-        };
-
-    default_graph::add_operator::<_, (), (), T, ()>(
-        config,
-        op_runner,
-        None,
-        None,
-        Some(&write_stream),
-        None,
-    );
-
-    write_stream
+    default_graph::get().connect_source(operator_fn, config)
 }
 
 /// Adds a [`ParallelSink`] operator, which receives data on input read streams and directly
 /// interacts with external systems.
+///
+/// The operator is added to the default graph (see [`Graph::connect_parallel_sink`]).
 pub fn connect_parallel_sink<O, S, T, U>(
     operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
     // Add state as an explicit argument to support future features such as state sharing.
     state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
-    mut config: OperatorConfig,
+    config: OperatorConfig,
     read_stream: &dyn Stream<T>,
 ) where
     O: 'static + ParallelSink<S, T, U>,
@@ -69,92 +540,36 @@ pub fn connect_parallel_sink<O, S, T, U>(
     T: Data + for<'a> Deserialize<'a>,
     U: 'static + Send + Sync,
 {
-    config.id = OperatorId::new_deterministic();
-
-    let config_copy = config.clone();
-    let read_stream_id = read_stream.id();
-    let op_runner =
-        move |channel_manager: Arc<Mutex<ChannelManager>>| -> Box<dyn OperatorExecutorT> {
-            let mut channel_manager = channel_manager.lock().unwrap();
-
-            let read_stream = channel_manager
-                .take_read_stream(default_graph::resolve_stream_id(&read_stream_id).unwrap())
-                .unwrap();
-
-            Box::new(OneInExecutor::new(
-                config_copy.clone(),
-                Box::new(ParallelSinkMessageProcessor::new(
-                    config_copy.clone(),
-                    operator_fn.clone(),
-                    state_fn.clone(),
-                )),
-                read_stream,
-            ))
-        };
-
-    default_graph::add_operator::<_, T, (), (), ()>(
-        config,
-        op_runner,
-        Some(read_stream),
-        None,
-        None,
-        None,
-    );
+    default_graph::get().connect_parallel_sink(operator_fn, state_fn, config, read_stream)
 }
 
 /// Adds a [`Sink`] operator, which receives data on input read streams and directly interacts
 /// with external systems.
+///
+/// The operator is added to the default graph (see [`Graph::connect_sink`]).
 pub fn connect_sink<O, S, T>(
     operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
     // Add state as an explicit argument to support future features such as state sharing.
     state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
-    mut config: OperatorConfig,
+    config: OperatorConfig,
     read_stream: &dyn Stream<T>,
 ) where
     O: 'static + Sink<S, T>,
     S: State,
     T: Data + for<'a> Deserialize<'a>,
 {
-    config.id = OperatorId::new_deterministic();
-
-    let config_copy = config.clone();
-    let read_stream_id = read_stream.id();
-    let op_runner =
-        move |channel_manager: Arc<Mutex<ChannelManager>>| -> Box<dyn OperatorExecutorT> {
-            let mut channel_manager = channel_manager.lock().unwrap();
-
-            let read_stream = channel_manager
-                .take_read_stream(default_graph::resolve_stream_id(&read_stream_id).unwrap())
-                .unwrap();
-
-            Box::new(OneInExecutor::new(
-                config_copy.clone(),
-                Box::new(SinkMessageProcessor::new(
-                    config_copy.clone(),
-                    operator_fn.clone(),
-                    state_fn.clone(),
-                )),
-                read_stream,
-            ))
-        };
-
-    default_graph::add_operator::<_, T, (), (), ()>(
-        config,
-        op_runner,
-        Some(read_stream),
-        None,
-        None,
-        None,
-    );
+    default_graph::get().connect_sink(operator_fn, state_fn, config, read_stream)
 }
 
 /// Adds a [`ParallelOneInOneOut`] operator that has one input read stream and one output
 /// write stream.
+///
+/// The operator is added to the default graph (see [`Graph::connect_parallel_one_in_one_out`]).
 pub fn connect_parallel_one_in_one_out<O, S, T, U, V>(
     operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
     // Add state as an explicit argument to support future features such as state sharing.
     state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
-    mut config: OperatorConfig,
+    config: OperatorConfig,
     read_stream: &dyn Stream<T>,
 ) -> OperatorStream<U>
 where
@@ -164,51 +579,17 @@ where
     U: Data + for<'a> Deserialize<'a>,
     V: 'static + Send + Sync,
 {
-    config.id = OperatorId::new_deterministic();
-    let write_stream = OperatorStream::new();
-
-    let config_copy = config.clone();
-    let read_stream_id = read_stream.id();
-    let write_stream_id = write_stream.id();
-    let op_runner =
-        move |channel_manager: Arc<Mutex<ChannelManager>>| -> Box<dyn OperatorExecutorT> {
-            let mut channel_manager = channel_manager.lock().unwrap();
-
-            let read_stream = channel_manager
-                .take_read_stream(default_graph::resolve_stream_id(&read_stream_id).unwrap())
-                .unwrap();
-            let write_stream = channel_manager.write_stream(write_stream_id).unwrap();
-
-            Box::new(OneInExecutor::new(
-                config_copy.clone(),
-                Box::new(ParallelOneInOneOutMessageProcessor::new(
-                    config_copy.clone(),
-                    operator_fn.clone(),
-                    state_fn.clone(),
-                    write_stream,
-                )),
-                read_stream,
-            ))
-        };
-
-    default_graph::add_operator::<_, T, (), U, ()>(
-        config,
-        op_runner,
-        Some(read_stream),
-        None,
-        Some(&write_stream),
-        None,
-    );
-
-    write_stream
+    default_graph::get().connect_parallel_one_in_one_out(operator_fn, state_fn, config, read_stream)
 }
 
 /// Adds a [`OneInOneOut`] operator that has one input read stream and one output write stream.
+///
+/// The operator is added to the default graph (see [`Graph::connect_one_in_one_out`]).
 pub fn connect_one_in_one_out<O, S, T, U>(
     operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
     // Add state as an explicit argument to support future features such as state sharing.
     state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
-    mut config: OperatorConfig,
+    config: OperatorConfig,
     read_stream: &dyn Stream<T>,
 ) -> OperatorStream<U>
 where
@@ -217,52 +598,18 @@ where
     T: Data + for<'a> Deserialize<'a>,
     U: Data + for<'a> Deserialize<'a>,
 {
-    config.id = OperatorId::new_deterministic();
-    let write_stream = OperatorStream::new();
-
-    let config_copy = config.clone();
-    let read_stream_id = read_stream.id();
-    let write_stream_id = write_stream.id();
-    let op_runner =
-        move |channel_manager: Arc<Mutex<ChannelManager>>| -> Box<dyn OperatorExecutorT> {
-            let mut channel_manager = channel_manager.lock().unwrap();
-
-            let read_stream = channel_manager
-                .take_read_stream(default_graph::resolve_stream_id(&read_stream_id).unwrap())
-                .unwrap();
-            let write_stream = channel_manager.write_stream(write_stream_id).unwrap();
-
-            Box::new(OneInExecutor::new(
-                config_copy.clone(),
-                Box::new(OneInOneOutMessageProcessor::new(
-                    config_copy.clone(),
-                    operator_fn.clone(),
-                    state_fn.clone(),
-                    write_stream,
-                )),
-                read_stream,
-            ))
-        };
-
-    default_graph::add_operator::<_, T, (), U, ()>(
-        config,
-        op_runner,
-        Some(read_stream),
-        None,
-        Some(&write_stream),
-        None,
-    );
-
-    write_stream
+    default_graph::get().connect_one_in_one_out(operator_fn, state_fn, config, read_stream)
 }
 
 /// Adds a [`ParallelTwoInOneOut`] operator that has two input read streams and one output
 /// write stream.
+///
+/// The operator is added to the default graph (see [`Graph::connect_parallel_two_in_one_out`]).
 pub fn connect_parallel_two_in_one_out<O, S, T, U, V, W>(
     operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
     // Add state as an explicit argument to support future features such as state sharing.
     state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
-    mut config: OperatorConfig,
+    config: OperatorConfig,
     left_read_stream: &dyn Stream<T>,
     right_read_stream: &dyn Stream<U>,
 ) -> OperatorStream<V>
@@ -274,56 +621,23 @@ where
     V: Data + for<'a> Deserialize<'a>,
     W: 'static + Send + Sync,
 {
-    config.id = OperatorId::new_deterministic();
-    let write_stream = OperatorStream::new();
-
-    let config_copy = config.clone();
-    let left_read_stream_id = left_read_stream.id();
-    let right_read_stream_id = right_read_stream.id();
-    let write_stream_id = write_stream.id();
-    let op_runner =
-        move |channel_manager: Arc<Mutex<ChannelManager>>| -> Box<dyn OperatorExecutorT> {
-            let mut channel_manager = channel_manager.lock().unwrap();
-
-            let left_read_stream = channel_manager
-                .take_read_stream(default_graph::resolve_stream_id(&left_read_stream_id).unwrap())
-                .unwrap();
-            let right_read_stream = channel_manager
-                .take_read_stream(default_graph::resolve_stream_id(&right_read_stream_id).unwrap())
-                .unwrap();
-            let write_stream = channel_manager.write_stream(write_stream_id).unwrap();
-
-            Box::new(TwoInExecutor::new(
-                config_copy.clone(),
-                Box::new(ParallelTwoInOneOutMessageProcessor::new(
-                    config_copy.clone(),
-                    operator_fn.clone(),
-                    state_fn.clone(),
-                    write_stream,
-                )),
-                left_read_stream,
-                right_read_stream,
-            ))
-        };
-
-    default_graph::add_operator::<_, T, U, V, ()>(
+    default_graph::get().connect_parallel_two_in_one_out(
+        operator_fn,
+        state_fn,
         config,
-        op_runner,
-        Some(left_read_stream),
-        Some(right_read_stream),
-        Some(&write_stream),
-        None,
-    );
-
-    write_stream
+        left_read_stream,
+        right_read_stream,
+    )
 }
 
 /// Adds a [`TwoInOneOut`] operator that has two input read streams and one output write stream.
+///
+/// The operator is added to the default graph (see [`Graph::connect_two_in_one_out`]).
 pub fn connect_two_in_one_out<O, S, T, U, V>(
     operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
     // Add state as an explicit argument to support future features such as state sharing.
     state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
-    mut config: OperatorConfig,
+    config: OperatorConfig,
     left_read_stream: &dyn Stream<T>,
     right_read_stream: &dyn Stream<U>,
 ) -> OperatorStream<V>
@@ -334,57 +648,24 @@ where
     U: Data + for<'a> Deserialize<'a>,
     V: Data + for<'a> Deserialize<'a>,
 {
-    config.id = OperatorId::new_deterministic();
-    let write_stream = OperatorStream::new();
-
-    let config_copy = config.clone();
-    let left_read_stream_id = left_read_stream.id();
-    let right_read_stream_id = right_read_stream.id();
-    let write_stream_id = write_stream.id();
-    let op_runner =
-        move |channel_manager: Arc<Mutex<ChannelManager>>| -> Box<dyn OperatorExecutorT> {
-            let mut channel_manager = channel_manager.lock().unwrap();
-
-            let left_read_stream = channel_manager
-                .take_read_stream(default_graph::resolve_stream_id(&left_read_stream_id).unwrap())
-                .unwrap();
-            let right_read_stream = channel_manager
-                .take_read_stream(default_graph::resolve_stream_id(&right_read_stream_id).unwrap())
-                .unwrap();
-            let write_stream = channel_manager.write_stream(write_stream_id).unwrap();
-
-            Box::new(TwoInExecutor::new(
-                config_copy.clone(),
-                Box::new(TwoInOneOutMessageProcessor::new(
-                    config_copy.clone(),
-                    operator_fn.clone(),
-                    state_fn.clone(),
-                    write_stream,
-                )),
-                left_read_stream,
-                right_read_stream,
-            ))
-        };
-
-    default_graph::add_operator::<_, T, U, V, ()>(
+    default_graph::get().connect_two_in_one_out(
+        operator_fn,
+        state_fn,
         config,
-        op_runner,
-        Some(left_read_stream),
-        Some(right_read_stream),
-        Some(&write_stream),
-        None,
-    );
-
-    write_stream
+        left_read_stream,
+        right_read_stream,
+    )
 }
 
 /// Adds a [`ParallelOneInTwoOut`] operator that has one input read stream and two output
 /// write streams.
+///
+/// The operator is added to the default graph (see [`Graph::connect_parallel_one_in_two_out`]).
 pub fn connect_parallel_one_in_two_out<O, S, T, U, V, W>(
     operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
     // Add state as an explicit argument to support future features such as state sharing.
     state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
-    mut config: OperatorConfig,
+    config: OperatorConfig,
     read_stream: &dyn Stream<T>,
 ) -> (OperatorStream<U>, OperatorStream<V>)
 where
@@ -395,55 +676,17 @@ where
     V: Data + for<'a> Deserialize<'a>,
     W: 'static + Send + Sync,
 {
-    config.id = OperatorId::new_deterministic();
-    let left_write_stream = OperatorStream::new();
-    let right_write_stream = OperatorStream::new();
-
-    let config_copy = config.clone();
-    let read_stream_id = read_stream.id();
-    let left_write_stream_id = left_write_stream.id();
-    let right_write_stream_id = right_write_stream.id();
-    let op_runner =
-        move |channel_manager: Arc<Mutex<ChannelManager>>| -> Box<dyn OperatorExecutorT> {
-            let mut channel_manager = channel_manager.lock().unwrap();
-
-            let read_stream = channel_manager
-                .take_read_stream(default_graph::resolve_stream_id(&read_stream_id).unwrap())
-                .unwrap();
-            let left_write_stream = channel_manager.write_stream(left_write_stream_id).unwrap();
-            let right_write_stream = channel_manager.write_stream(right_write_stream_id).unwrap();
-
-            Box::new(OneInExecutor::new(
-                config_copy.clone(),
-                Box::new(ParallelOneInTwoOutMessageProcessor::new(
-                    config_copy.clone(),
-                    operator_fn.clone(),
-                    state_fn.clone(),
-                    left_write_stream,
-                    right_write_stream,
-                )),
-                read_stream,
-            ))
-        };
-
-    default_graph::add_operator::<_, T, (), U, V>(
-        config,
-        op_runner,
-        Some(read_stream),
-        None,
-        Some(&left_write_stream),
-        Some(&right_write_stream),
-    );
-
-    (left_write_stream, right_write_stream)
+    default_graph::get().connect_parallel_one_in_two_out(operator_fn, state_fn, config, read_stream)
 }
 
 /// Adds a [`OneInTwoOut`] operator that has one input read stream and two output write streams.
+///
+/// The operator is added to the default graph (see [`Graph::connect_one_in_two_out`]).
 pub fn connect_one_in_two_out<O, S, T, U, V>(
     operator_fn: impl Fn() -> O + Clone + Send + Sync + 'static,
     // Add state as an explicit argument to support future features such as state sharing.
     state_fn: impl Fn() -> S + Clone + Send + Sync + 'static,
-    mut config: OperatorConfig,
+    config: OperatorConfig,
     read_stream: &dyn Stream<T>,
 ) -> (OperatorStream<U>, OperatorStream<V>)
 where
@@ -453,45 +696,13 @@ where
     U: Data + for<'a> Deserialize<'a>,
     V: Data + for<'a> Deserialize<'a>,
 {
-    config.id = OperatorId::new_deterministic();
-    let left_write_stream = OperatorStream::new();
-    let right_write_stream = OperatorStream::new();
-
-    let config_copy = config.clone();
-    let read_stream_id = read_stream.id();
-    let left_write_stream_id = left_write_stream.id();
-    let right_write_stream_id = right_write_stream.id();
-    let op_runner =
-        move |channel_manager: Arc<Mutex<ChannelManager>>| -> Box<dyn OperatorExecutorT> {
-            let mut channel_manager = channel_manager.lock().unwrap();
-
-            let read_stream = channel_manager
-                .take_read_stream(default_graph::resolve_stream_id(&read_stream_id).unwrap())
-                .unwrap();
-            let left_write_stream = channel_manager.write_stream(left_write_stream_id).unwrap();
-            let right_write_stream = channel_manager.write_stream(right_write_stream_id).unwrap();
-
-            Box::new(OneInExecutor::new(
-                config_copy.clone(),
-                Box::new(OneInTwoOutMessageProcessor::new(
-                    config_copy.clone(),
-                    operator_fn.clone(),
-                    state_fn.clone(),
-                    left_write_stream,
-                    right_write_stream,
-                )),
-                read_stream,
-            ))
-        };
+    default_graph::get().connect_one_in_two_out(operator_fn, state_fn, config, read_stream)
+}
 
-    default_graph::add_operator::<_, T, (), U, V>(
-        config,
-        op_runner,
-        Some(read_stream),
-        None,
-        Some(&left_write_stream),
-        Some(&right_write_stream),
-    );
-
-    (left_write_stream, right_write_stream)
+/// Returns the configuration of the operator which was added to the default graph most
+/// recently, including the ID and name assigned by the graph.
+///
+/// See [`Graph::last_operator_config`].
+pub fn last_operator_config() -> Option<OperatorConfig> {
+    default_graph::get().last_operator_config()
 }
@@ -40,6 +40,11 @@ impl<S> SetupContext<S> {
         self.deadlines.insert(deadline_id, Arc::new(deadline));
     }
 
+    /// Returns the number of deadlines registered in this context.
+    pub(crate) fn num_deadlines(&self) -> usize {
+        self.deadlines.len()
+    }
+
     /// Get the deadlines registered in this context.
     pub(crate) fn deadlines(&mut self) -> impl Iterator<Item = &mut Arc<dyn DeadlineT<S>>> {
         self.deadlines.values_mut()
@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
+use petgraph::{algo::tarjan_scc, graph::DiGraph};
 use serde::Deserialize;
 
 use crate::{
+    communication::{Compression, Delivery, Priority},
     dataflow::{
         stream::{ExtractStream, IngestStream, OperatorStream, Stream, StreamId},
         Data, LoopStream,
@@ -11,8 +13,9 @@ use crate::{
 };
 
 use super::{
-    job_graph::JobGraph, AbstractOperator, AbstractStream, AbstractStreamT, OperatorRunner,
-    StreamSetupHook,
+    job_graph::JobGraph, AbstractOperator, AbstractStream, AbstractStreamT, GraphProblem,
+    GraphValidationError, OperatorRunner, PlacementGraph, PlacementOperator, PlacementReport,
+    PlacementStrategy, StreamSetupHook,
 };
 
 /// The abstract graph representation of an ERDOS program defined in the driver.
@@ -29,6 +32,8 @@ pub struct AbstractGraph {
     extract_streams: HashMap<StreamId, Box<dyn StreamSetupHook>>,
     /// Collection of loop streams and the streams to which they connect.
     loop_streams: HashMap<StreamId, Option<StreamId>>,
+    /// The operator which was added most recently.
+    last_operator_id: Option<OperatorId>,
 }
 
 impl AbstractGraph {
@@ -39,6 +44,7 @@ impl AbstractGraph {
             ingest_streams: HashMap::new(),
             extract_streams: HashMap::new(),
             loop_streams: HashMap::new(),
+            last_operator_id: None,
         }
     }
 
@@ -80,12 +86,14 @@ impl AbstractGraph {
             } else {
                 format!("{}-write-left-stream", config.get_name())
             };
-            let abstract_stream = AbstractStream::<V>::new(ls.id(), stream_name);
+            let abstract_stream =
+                AbstractStream::<V>::new(ls.id(), stream_name).with_compression(config.compression);
             self.streams.insert(ls.id(), Box::new(abstract_stream));
         }
         if let Some(rs) = right_write_stream {
             let stream_name = format!("{}-right-write-stream", config.get_name());
-            let abstract_stream = AbstractStream::<W>::new(rs.id(), stream_name);
+            let abstract_stream =
+                AbstractStream::<W>::new(rs.id(), stream_name).with_compression(config.compression);
             self.streams.insert(rs.id(), Box::new(abstract_stream));
         }
 
@@ -98,6 +106,7 @@ impl AbstractGraph {
             write_streams,
         };
         self.operators.insert(operator_id, abstract_operator);
+        self.last_operator_id = Some(operator_id);
     }
 
     /// Adds an [`IngestStream`] to the graph.
@@ -163,6 +172,20 @@ impl AbstractGraph {
         }
     }
 
+    pub(crate) fn contains_operator(&self, operator_id: &OperatorId) -> bool {
+        self.operators.contains_key(operator_id)
+    }
+
+    pub(crate) fn last_operator_config(&self) -> Option<OperatorConfig> {
+        self.last_operator_id
+            .and_then(|operator_id| self.operators.get(&operator_id))
+            .map(|operator| operator.config.clone())
+    }
+
+    pub(crate) fn contains_stream(&self, stream_id: &StreamId) -> bool {
+        self.streams.contains_key(stream_id)
+    }
+
     pub(crate) fn get_stream_name(&self, stream_id: &StreamId) -> String {
         self.streams.get(stream_id).unwrap().name()
     }
@@ -171,6 +194,45 @@ impl AbstractGraph {
         self.streams.get_mut(stream_id).unwrap().set_name(name);
     }
 
+    pub(crate) fn set_stream_schema_version(&mut self, stream_id: &StreamId, version: u32) {
+        self.streams
+            .get_mut(stream_id)
+            .unwrap()
+            .set_schema_version(version);
+    }
+
+    pub(crate) fn set_stream_compression(
+        &mut self,
+        stream_id: &StreamId,
+        compression: Compression,
+    ) {
+        self.streams
+            .get_mut(stream_id)
+            .unwrap()
+            .set_compression(compression);
+    }
+
+    pub(crate) fn set_stream_low_latency(&mut self, stream_id: &StreamId, low_latency: bool) {
+        self.streams
+            .get_mut(stream_id)
+            .unwrap()
+            .set_low_latency(low_latency);
+    }
+
+    pub(crate) fn set_stream_priority(&mut self, stream_id: &StreamId, priority: Priority) {
+        self.streams
+            .get_mut(stream_id)
+            .unwrap()
+            .set_priority(priority);
+    }
+
+    pub(crate) fn set_stream_delivery(&mut self, stream_id: &StreamId, delivery: Delivery) {
+        self.streams
+            .get_mut(stream_id)
+            .unwrap()
+            .set_delivery(delivery);
+    }
+
     /// If `stream_id` corresponds to a [`LoopStream`], returns the [`StreamId`] of the
     /// [`Stream`] to which it is connected. Returns [`None`] if unconnected.
     /// Otherwise, returns `stream_id`.
@@ -181,16 +243,272 @@ impl AbstractGraph {
         }
     }
 
+    /// Checks the graph for mistakes which would cause it to fail or misbehave once it is
+    /// executed by `num_nodes` nodes.
+    pub(crate) fn validate(&self, num_nodes: usize) -> Vec<GraphProblem> {
+        let mut problems = Vec::new();
+
+        let mut operators: Vec<_> = self.operators.values().collect();
+        operators.sort_by_key(|operator| operator.id);
+        let stream_name = |stream_id: &StreamId| {
+            self.streams
+                .get(stream_id)
+                .map(|stream| stream.name())
+                .unwrap_or_else(|| stream_id.to_string())
+        };
+
+        // Maps each stream to the name of the job which writes to it.
+        let mut stream_sources: BTreeMap<StreamId, String> = BTreeMap::new();
+        let mut stream_writers: HashMap<StreamId, OperatorId> = HashMap::new();
+        for operator in operators.iter() {
+            for write_stream_id in operator.write_streams.iter() {
+                stream_sources.insert(*write_stream_id, operator.config.get_name());
+                stream_writers.insert(*write_stream_id, operator.id);
+            }
+        }
+        for ingest_stream_id in self.ingest_streams.keys() {
+            stream_sources.insert(*ingest_stream_id, "driver".to_string());
+        }
+
+        // Check that every stream which is read is written by a job in the graph.
+        let mut consumed_streams = HashSet::new();
+        for operator in operators.iter() {
+            for read_stream_id in operator.read_streams.iter() {
+                match self.loop_streams.get(read_stream_id) {
+                    Some(Some(connected_stream_id)) => {
+                        consumed_streams.insert(*connected_stream_id);
+                    }
+                    // Reported with the unconnected loops below.
+                    Some(None) => (),
+                    None => {
+                        consumed_streams.insert(*read_stream_id);
+                        if !stream_sources.contains_key(read_stream_id) {
+                            problems.push(GraphProblem::DanglingStream {
+                                stream_id: *read_stream_id,
+                                consumer_name: format!("operator {}", operator.config.get_name()),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        let mut extract_stream_ids: Vec<_> = self.extract_streams.keys().collect();
+        extract_stream_ids.sort();
+        for extract_stream_id in extract_stream_ids {
+            consumed_streams.insert(*extract_stream_id);
+            if !stream_sources.contains_key(extract_stream_id) {
+                problems.push(GraphProblem::DanglingStream {
+                    stream_id: *extract_stream_id,
+                    consumer_name: "an ExtractStream".to_string(),
+                });
+            }
+        }
+
+        // Check that all loops are closed.
+        let mut loop_streams: Vec<_> = self.loop_streams.iter().collect();
+        loop_streams.sort();
+        for (loop_stream_id, connected_stream_id) in loop_streams {
+            match connected_stream_id {
+                Some(connected_stream_id) => {
+                    if !stream_sources.contains_key(connected_stream_id) {
+                        problems.push(GraphProblem::DanglingStream {
+                            stream_id: *connected_stream_id,
+                            consumer_name: format!("loop stream {}", stream_name(loop_stream_id)),
+                        });
+                    }
+                }
+                None => problems.push(GraphProblem::UnconnectedLoop {
+                    stream_id: *loop_stream_id,
+                    stream_name: stream_name(loop_stream_id),
+                    consumer_names: operators
+                        .iter()
+                        .filter(|operator| operator.read_streams.contains(loop_stream_id))
+                        .map(|operator| operator.config.get_name())
+                        .collect(),
+                }),
+            }
+        }
+
+        // Check for cycles. Edges through loop streams are omitted, as they break cycles.
+        let mut operator_graph = DiGraph::new();
+        let operator_indices: HashMap<_, _> = operators
+            .iter()
+            .map(|operator| (operator.id, operator_graph.add_node(*operator)))
+            .collect();
+        for operator in operators.iter() {
+            for read_stream_id in operator.read_streams.iter() {
+                if let Some(writer_id) = stream_writers.get(read_stream_id) {
+                    operator_graph.add_edge(
+                        operator_indices[writer_id],
+                        operator_indices[&operator.id],
+                        *read_stream_id,
+                    );
+                }
+            }
+        }
+        for component in tarjan_scc(&operator_graph) {
+            let is_cycle = component.len() > 1
+                || operator_graph
+                    .find_edge(component[0], component[0])
+                    .is_some();
+            if is_cycle {
+                let stream_names = operator_graph
+                    .edge_indices()
+                    .filter(|&edge| {
+                        let (source, destination) = operator_graph.edge_endpoints(edge).unwrap();
+                        component.contains(&source) && component.contains(&destination)
+                    })
+                    .map(|edge| stream_name(&operator_graph[edge]))
+                    .collect();
+                problems.push(GraphProblem::CycleWithoutLoopStream {
+                    operator_names: component
+                        .iter()
+                        .map(|&index| operator_graph[index].config.get_name())
+                        .collect(),
+                    stream_names,
+                });
+            }
+        }
+
+        // Check that operators are placed on existing nodes.
+        for operator in operators.iter() {
+            if operator.config.node_id >= num_nodes {
+                problems.push(GraphProblem::InvalidNodeId {
+                    operator_id: operator.id,
+                    operator_name: operator.config.get_name(),
+                    node_id: operator.config.node_id,
+                    num_nodes,
+                });
+            }
+        }
+
+        // Check that operator names, from which operator IDs are derived, are unique. Unnamed
+        // operators are named after their IDs.
+        let mut operators_by_name: BTreeMap<&str, Vec<OperatorId>> = BTreeMap::new();
+        for operator in operators.iter() {
+            if let Some(name) = &operator.config.name {
+                operators_by_name
+                    .entry(name.as_str())
+                    .or_default()
+                    .push(operator.id);
+            }
+        }
+        for (operator_name, operator_ids) in operators_by_name {
+            if operator_ids.len() > 1 {
+                problems.push(GraphProblem::DuplicateOperatorName {
+                    operator_name: operator_name.to_string(),
+                    operator_ids,
+                });
+            }
+        }
+
+        // Check that every stream is read.
+        for (stream_id, source_name) in stream_sources {
+            if !consumed_streams.contains(&stream_id) {
+                problems.push(GraphProblem::StreamWithoutConsumers {
+                    stream_id,
+                    stream_name: stream_name(&stream_id),
+                    source_name,
+                });
+            }
+        }
+
+        problems
+    }
+
+    /// Returns the operators to place on `num_nodes` nodes, and the streams connecting them.
+    fn placement_graph(&self, num_nodes: usize) -> PlacementGraph {
+        let mut operators: Vec<_> = self.operators.values().collect();
+        operators.sort_by_key(|o| (o.config.get_name(), o.id));
+
+        let mut writers = HashMap::new();
+        for (index, operator) in operators.iter().enumerate() {
+            for write_stream_id in operator.write_streams.iter() {
+                writers.insert(*write_stream_id, index);
+            }
+        }
+        let mut edges = Vec::new();
+        for (index, operator) in operators.iter().enumerate() {
+            for read_stream_id in operator.read_streams.iter() {
+                let writer = self
+                    .resolve_stream_id(read_stream_id)
+                    .and_then(|stream_id| writers.get(&stream_id));
+                if let Some(&writer) = writer {
+                    edges.push((writer, index));
+                }
+            }
+        }
+
+        PlacementGraph {
+            num_nodes,
+            operators: operators
+                .iter()
+                .map(|o| PlacementOperator {
+                    id: o.id,
+                    name: o.config.get_name(),
+                    pinned_node: if o.config.pinned {
+                        Some(o.config.node_id)
+                    } else {
+                        None
+                    },
+                    estimated_cost: o.config.estimated_cost,
+                })
+                .collect(),
+            edges,
+        }
+    }
+
+    /// Assigns the operators which are not pinned to one of `num_nodes` nodes using `strategy`,
+    /// and reports the resulting placement.
+    pub(crate) fn place(
+        &mut self,
+        num_nodes: usize,
+        strategy: &dyn PlacementStrategy,
+    ) -> PlacementReport {
+        let graph = self.placement_graph(num_nodes);
+        let mut nodes: Vec<_> = graph
+            .operators
+            .iter()
+            .map(|o| self.operators[&o.id].config.node_id)
+            .collect();
+        if num_nodes > 0 {
+            for (index, node_id) in strategy.place(&graph).into_iter().enumerate() {
+                match graph.operators.get(index) {
+                    Some(operator) if operator.pinned_node.is_none() => nodes[index] = node_id,
+                    _ => (),
+                }
+            }
+        }
+        for (operator, &node_id) in graph.operators.iter().zip(nodes.iter()) {
+            self.operators.get_mut(&operator.id).unwrap().config.node_id = node_id;
+        }
+        PlacementReport::new(&graph, &nodes)
+    }
+
     /// Compiles the abstract graph defined into a physical plan
     /// consisting of jobs and typed communication channels connecting jobs.
-    /// The compilation step checks that all [`LoopStream`]s are connected,
+    /// The compilation step places operators on nodes using `placement_strategy`, if any,
+    /// [validates](AbstractGraph::validate) the graph,
     /// and arranges jobs and channels in a directed graph.
-    pub(crate) fn compile(&mut self) -> JobGraph {
-        // Check that all loops are closed.
-        for (loop_stream_id, connected_stream_id) in self.loop_streams.iter() {
-            if connected_stream_id.is_none() {
-                panic!("LoopStream {} is not connected to another loop. Call `LoopStream::connect_loop` to fix.", loop_stream_id);
-            }
+    /// Problems which do not prevent the graph from running are logged as warnings.
+    pub(crate) fn compile(
+        &mut self,
+        num_nodes: usize,
+        placement_strategy: Option<&dyn PlacementStrategy>,
+    ) -> Result<JobGraph, GraphValidationError> {
+        if let Some(strategy) = placement_strategy {
+            let report = self.place(num_nodes, strategy);
+            tracing::info!("Dataflow graph: placed operators on nodes\n{}", report);
+        }
+        let (errors, warnings): (Vec<_>, Vec<_>) = self
+            .validate(num_nodes)
+            .into_iter()
+            .partition(GraphProblem::is_error);
+        for warning in warnings {
+            tracing::warn!("Dataflow graph: {}", warning);
+        }
+        if !errors.is_empty() {
+            return Err(GraphValidationError::new(errors));
         }
 
         // Get all streams except loop streams.
@@ -221,17 +539,35 @@ impl AbstractGraph {
 
         let mut operators: Vec<_> = self.operators.values().cloned().collect();
 
-        // Replace loop stream IDs with connected stream IDs.
+        // Replace loop stream IDs with connected stream IDs, and remember which reads close loops.
+        let mut loop_reads = HashSet::new();
         for o in operators.iter_mut() {
             for i in 0..o.read_streams.len() {
                 if self.loop_streams.contains_key(&o.read_streams[i]) {
                     let resolved_id = self.resolve_stream_id(&o.read_streams[i]).unwrap();
                     o.read_streams[i] = resolved_id;
+                    loop_reads.insert((resolved_id, o.id));
                 }
             }
         }
 
-        JobGraph::new(operators, streams, ingest_streams, extract_streams)
+        let loop_streams = self
+            .loop_streams
+            .keys()
+            .map(|loop_stream_id| {
+                let resolved_id = self.resolve_stream_id(loop_stream_id).unwrap();
+                (*loop_stream_id, resolved_id)
+            })
+            .collect();
+
+        Ok(JobGraph::new(
+            operators,
+            streams,
+            ingest_streams,
+            extract_streams,
+            loop_streams,
+            loop_reads,
+        ))
     }
 
     // TODO: implement this using the Clone trait.
@@ -266,6 +602,7 @@ impl AbstractGraph {
             ingest_streams,
             extract_streams,
             loop_streams: self.loop_streams.clone(),
+            last_operator_id: self.last_operator_id,
         }
     }
 }
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{communication::Compression, node::NodeId};
+
+/// A machine-readable description of a dataflow graph, exported by
+/// [`Configuration::export_dataflow_graph`](crate::Configuration::export_dataflow_graph).
+///
+/// The graph is exported once all operators are set up, so the description includes the
+/// deadlines which operators registered in `setup`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GraphDescription {
+    pub operators: Vec<OperatorDescription>,
+    pub streams: Vec<StreamDescription>,
+}
+
+/// Describes an operator and its [configuration](crate::dataflow::OperatorConfig).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OperatorDescription {
+    pub id: String,
+    pub name: String,
+    /// The node on which the operator runs.
+    pub node_id: NodeId,
+    pub flow_watermarks: bool,
+    pub close_streams_on_node_failure: bool,
+    /// The number of deadlines the operator registered.
+    pub deadlines: usize,
+    /// IDs of the streams from which the operator reads.
+    pub read_streams: Vec<String>,
+    /// IDs of the streams to which the operator writes.
+    pub write_streams: Vec<String>,
+}
+
+/// Describes a stream and the type of data sent on it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StreamDescription {
+    pub id: String,
+    pub name: String,
+    /// The name of the type of data sent on the stream.
+    pub data_type: String,
+    /// The schema version set with [`Stream::set_schema_version`](crate::dataflow::Stream::set_schema_version).
+    pub schema_version: u32,
+    /// The compression of the messages sent on the stream to other nodes.
+    #[serde(default)]
+    pub compression: Compression,
+    /// The job which writes to the stream.
+    pub source: JobDescription,
+    /// The jobs which read from the stream.
+    pub destinations: Vec<JobDescription>,
+}
+
+/// Identifies a job which reads from or writes to a stream.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobDescription {
+    /// The driver, which interacts with the dataflow through
+    /// [`IngestStream`](crate::dataflow::stream::IngestStream)s and
+    /// [`ExtractStream`](crate::dataflow::stream::ExtractStream)s.
+    Driver,
+    /// The operator with the given ID.
+    Operator(String),
+}
@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{dataflow::stream::StreamId, OperatorId, Uuid};
+
+/// A summary of the operators and streams in a dataflow graph.
+///
+/// Nodes exchange fingerprints when they connect, and refuse to start unless all nodes execute
+/// the same graph.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphFingerprint {
+    /// Maps the ID of each operator to a description of the operator.
+    operators: BTreeMap<OperatorId, String>,
+    /// Maps the ID of each stream to a description of the stream.
+    streams: BTreeMap<StreamId, String>,
+}
+
+impl GraphFingerprint {
+    pub(crate) fn new(
+        operators: BTreeMap<OperatorId, String>,
+        streams: BTreeMap<StreamId, String>,
+    ) -> Self {
+        Self { operators, streams }
+    }
+
+    /// Returns a line for each operator and stream which differs from `other`.
+    ///
+    /// Lines starting with `-` describe operators and streams only in `self`, lines starting
+    /// with `+` describe those only in `other`, and lines starting with `~` describe those which
+    /// are in both graphs but differ.
+    pub fn diff(&self, other: &Self) -> Vec<String> {
+        let mut lines = Self::diff_entries("operator", &self.operators, &other.operators);
+        lines.extend(Self::diff_entries("stream", &self.streams, &other.streams));
+        lines
+    }
+
+    fn diff_entries(
+        kind: &str,
+        entries: &BTreeMap<Uuid, String>,
+        other_entries: &BTreeMap<Uuid, String>,
+    ) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (id, description) in entries.iter() {
+            match other_entries.get(id) {
+                Some(other_description) if other_description != description => lines.push(format!(
+                    "~ {} {}: {} != {}",
+                    kind, id, description, other_description
+                )),
+                Some(_) => (),
+                None => lines.push(format!("- {} {}: {}", kind, id, description)),
+            }
+        }
+        for (id, other_description) in other_entries.iter() {
+            if !entries.contains_key(id) {
+                lines.push(format!("+ {} {}: {}", kind, id, other_description));
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint(
+        operators: &[(OperatorId, &str)],
+        streams: &[(StreamId, &str)],
+    ) -> GraphFingerprint {
+        let entries = |entries: &[(Uuid, &str)]| {
+            entries
+                .iter()
+                .map(|&(id, description)| (id, description.to_string()))
+                .collect()
+        };
+        GraphFingerprint::new(entries(operators), entries(streams))
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let operator_id = OperatorId::new_deterministic();
+        let stream_id = StreamId::new_deterministic();
+        let a = fingerprint(&[(operator_id, "map on node 0")], &[(stream_id, "s")]);
+        let b = fingerprint(&[(operator_id, "map on node 0")], &[(stream_id, "s")]);
+        assert_eq!(a, b);
+        assert!(a.diff(&b).is_empty());
+    }
+
+    #[test]
+    fn test_diff() {
+        let changed_id = OperatorId::new_deterministic();
+        let removed_id = OperatorId::new_deterministic();
+        let added_id = StreamId::new_deterministic();
+        let a = fingerprint(
+            &[
+                (changed_id, "map on node 0"),
+                (removed_id, "filter on node 0"),
+            ],
+            &[],
+        );
+        let b = fingerprint(&[(changed_id, "map on node 1")], &[(added_id, "s")]);
+        let mut diff = a.diff(&b);
+        // Streams are listed after operators, which are ordered by ID.
+        assert_eq!(diff.pop(), Some(format!("+ stream {}: s", added_id)));
+        diff.sort();
+        let mut expected = vec![
+            format!("~ operator {}: map on node 0 != map on node 1", changed_id),
+            format!("- operator {}: filter on node 0", removed_id),
+        ];
+        expected.sort();
+        assert_eq!(diff, expected);
+    }
+}
@@ -0,0 +1,568 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::Deserialize;
+
+use crate::{
+    communication::{Compression, Delivery, Priority},
+    dataflow::{
+        stream::{ExtractStream, IngestStream, LoopStream, OperatorStream, StreamId},
+        Data,
+    },
+    node::NodeId,
+    OperatorConfig, OperatorId, Uuid,
+};
+
+use super::{AbstractGraph, GraphProblem, PlacementReport, PlacementStrategy};
+
+/// A dataflow graph to which operators and streams are added.
+///
+/// Operators are added to the graph using the `connect_*` methods, which mirror the
+/// [connect functions](crate::dataflow::connect). The free functions add operators to a
+/// process-wide default graph, whereas each [`Graph`] is independent: several graphs can be
+/// built in the same process, or from different threads. The graph is executed by passing it to
+/// [`Node::with_graph`](crate::node::Node::with_graph).
+///
+/// Cloning a [`Graph`] returns a handle to the same graph. Streams created by the graph belong
+/// to it, so the [operators](crate::dataflow::operators) invoked on those streams (e.g.
+/// [`map`](crate::dataflow::operators::Map::map)) are also added to it.
+///
+/// Reusable parts of a graph can be built in a [subgraph](Graph::subgraph), which prefixes the
+/// names of the operators added to it with the subgraph's name.
+///
+/// # Example
+/// ```no_run
+/// # use erdos::{
+/// #     dataflow::{operators::Map, stream::ExtractStream, Graph},
+/// #     node::Node,
+/// #     Configuration,
+/// # };
+/// #
+/// let graph = Graph::new();
+/// let ingest_stream = graph.ingest_stream::<usize>();
+/// let mapped_stream = ingest_stream.map(|x: &usize| 2 * x);
+/// let extract_stream = ExtractStream::new(&mapped_stream);
+///
+/// let args = erdos::new_app("ERDOS").get_matches();
+/// let node = Node::with_graph(Configuration::from_args(&args), graph);
+/// node.run_async();
+/// ```
+#[derive(Clone)]
+pub struct Graph {
+    abstract_graph: Arc<Mutex<AbstractGraph>>,
+    /// The subgraphs which are currently being built, from outermost to innermost.
+    scopes: Arc<Mutex<Vec<Scope>>>,
+}
+
+/// A subgraph which is being built.
+struct Scope {
+    /// The hierarchical name of the subgraph, e.g. `front_cam/tracking`.
+    name: String,
+    /// The node on which the subgraph's operators run, if the subgraph is placed on a node.
+    node_id: Option<NodeId>,
+}
+
+impl Graph {
+    /// Creates an empty graph.
+    ///
+    /// Named operators and their write streams receive IDs derived from the operators' names.
+    /// Unnamed operators, as well as ingest and loop streams, receive IDs derived from the
+    /// subgraph in which they are built and from the streams they read, so graphs built in
+    /// different processes assign the same IDs to their operators and streams.
+    pub fn new() -> Self {
+        Self {
+            abstract_graph: Arc::new(Mutex::new(AbstractGraph::new())),
+            scopes: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Creates an [`IngestStream`] which belongs to this graph.
+    pub fn ingest_stream<D>(&self) -> IngestStream<D>
+    where
+        for<'a> D: Data + Deserialize<'a>,
+    {
+        IngestStream::new_in_graph(self)
+    }
+
+    /// Creates an [`ExtractStream`] which reads from a stream of this graph.
+    pub fn extract_stream<D>(&self, stream: &OperatorStream<D>) -> ExtractStream<D>
+    where
+        for<'a> D: Data + Deserialize<'a>,
+    {
+        ExtractStream::new(stream)
+    }
+
+    /// Creates a [`LoopStream`] which belongs to this graph.
+    pub fn loop_stream<D>(&self) -> LoopStream<D>
+    where
+        for<'a> D: Data + Deserialize<'a>,
+    {
+        LoopStream::new_in_graph(self)
+    }
+
+    /// Builds a reusable part of the graph as a subgraph named `name`.
+    ///
+    /// `build` receives the graph, typically connects operators to the streams which the caller
+    /// passes into the subgraph, and returns the streams which the subgraph outputs. The names of
+    /// the operators added while `build` runs are prefixed with `name`, e.g. an operator named
+    /// `detector` in the subgraph `front_cam` is named `front_cam/detector`; unnamed operators
+    /// are named after their IDs within the subgraph. Subgraphs may be nested, in which case
+    /// names are prefixed with each enclosing subgraph's name. The write streams of the
+    /// operators, as well as loop and ingest streams created in the subgraph, are named
+    /// accordingly.
+    ///
+    /// Subgraphs are tracked by the graph rather than by the handle passed to `build`, so
+    /// operators invoked on streams (e.g. [`map`](crate::dataflow::operators::Map::map)) are
+    /// also added to the subgraph. Subgraphs of the same graph should therefore not be built
+    /// concurrently from different threads.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use erdos::dataflow::{operators::Map, stream::OperatorStream, Graph};
+    /// #
+    /// fn scale(input: &OperatorStream<usize>) -> OperatorStream<usize> {
+    ///     // Named `front_cam/<ID>` when built in the subgraph `front_cam`.
+    ///     input.map(|x: &usize| 2 * x)
+    /// }
+    ///
+    /// let graph = Graph::new();
+    /// let ingest_stream = graph.ingest_stream::<usize>();
+    /// let camera_stream = ingest_stream.map(|x: &usize| *x);
+    /// let scaled_stream = graph.subgraph("front_cam", |_| scale(&camera_stream));
+    /// ```
+    pub fn subgraph<R>(&self, name: &str, build: impl FnOnce(&Graph) -> R) -> R {
+        self.build_in_scope(name, None, build)
+    }
+
+    /// Builds a subgraph whose operators all run on the node `node_id`, regardless of the
+    /// nodes set in their [`OperatorConfig`]s.
+    ///
+    /// See [`Graph::subgraph`].
+    pub fn subgraph_on_node<R>(
+        &self,
+        name: &str,
+        node_id: NodeId,
+        build: impl FnOnce(&Graph) -> R,
+    ) -> R {
+        self.build_in_scope(name, Some(node_id), build)
+    }
+
+    fn build_in_scope<R>(
+        &self,
+        name: &str,
+        node_id: Option<NodeId>,
+        build: impl FnOnce(&Graph) -> R,
+    ) -> R {
+        {
+            let mut scopes = self.scopes.lock().unwrap();
+            let (name, node_id) = match scopes.last() {
+                Some(parent) => (
+                    format!("{}/{}", parent.name, name),
+                    node_id.or(parent.node_id),
+                ),
+                None => (name.to_string(), node_id),
+            };
+            scopes.push(Scope { name, node_id });
+        }
+        let result = build(self);
+        self.scopes.lock().unwrap().pop();
+        result
+    }
+
+    /// Prefixes `name` with the name of the subgraph which is being built, if any.
+    pub(crate) fn scoped_name(&self, name: &str) -> String {
+        match self.scopes.lock().unwrap().last() {
+            Some(scope) => format!("{}/{}", scope.name, name),
+            None => name.to_string(),
+        }
+    }
+
+    /// Checks the graph for mistakes before it is executed by `num_nodes` nodes.
+    ///
+    /// The same checks run when a [`Node`](crate::node::Node) starts, which refuses to run the
+    /// graph if any of the returned problems [is an error](GraphProblem::is_error).
+    pub fn validate(&self, num_nodes: usize) -> Vec<GraphProblem> {
+        self.lock().validate(num_nodes)
+    }
+
+    /// Sets the ID of the operator configured by `config`, and applies the subgraph which is
+    /// being built, if any, to the operator's name and node.
+    ///
+    /// `kind` is the connect function which adds the operator (e.g. `one_in_one_out`), and
+    /// `read_stream_ids` are the streams from which the operator reads. Together with the
+    /// subgraph, they identify unnamed operators.
+    pub(crate) fn configure_operator(
+        &self,
+        config: &mut OperatorConfig,
+        kind: &str,
+        read_stream_ids: &[StreamId],
+    ) {
+        let scope = self
+            .scopes
+            .lock()
+            .unwrap()
+            .last()
+            .map(|scope| (scope.name.clone(), scope.node_id));
+        if let Some((_, Some(node_id))) = scope {
+            config.node_id = node_id;
+            config.pinned = true;
+        }
+        let scope_name = scope.map(|(name, _)| name);
+        match config.name.take() {
+            Some(name) => {
+                let name = match &scope_name {
+                    Some(scope_name) => format!("{}/{}", scope_name, name),
+                    None => name,
+                };
+                config.id = self.named_operator_id(&name);
+                config.name = Some(name);
+            }
+            None => {
+                let read_stream_ids: Vec<_> =
+                    read_stream_ids.iter().map(|id| id.to_string()).collect();
+                let key = format!("{}({})", kind, read_stream_ids.join(", "));
+                config.id = self.structural_id("unnamed-operator", &key, |graph, id| {
+                    graph.contains_operator(id)
+                });
+                if let Some(scope_name) = scope_name {
+                    config.name = Some(format!("{}/{}", scope_name, config.id));
+                }
+            }
+        }
+    }
+
+    /// Returns the configuration of the operator which was added to the graph most recently,
+    /// including the ID and name assigned by the graph.
+    pub fn last_operator_config(&self) -> Option<OperatorConfig> {
+        self.lock().last_operator_config()
+    }
+
+    /// Returns the placement of the graph's operators on `num_nodes` nodes by `strategy`,
+    /// without modifying the graph.
+    ///
+    /// The same placement is applied when a [`Node`](crate::node::Node) configured with the
+    /// strategy starts.
+    pub fn plan_placement(
+        &self,
+        num_nodes: usize,
+        strategy: &dyn PlacementStrategy,
+    ) -> PlacementReport {
+        self.clone_abstract_graph().place(num_nodes, strategy)
+    }
+
+    /// Returns the ID of the operator named `name`.
+    ///
+    /// The ID is derived from the name, so it does not depend on the order in which operators
+    /// are added to the graph. If another operator already has the name, the operator receives
+    /// a different ID so that both remain in the graph, which then fails
+    /// [validation](Graph::validate).
+    fn named_operator_id(&self, name: &str) -> OperatorId {
+        let abstract_graph = self.lock();
+        let mut id = Self::operator_id_from_name(name);
+        let mut occurrence = 1;
+        while abstract_graph.contains_operator(&id) {
+            occurrence += 1;
+            id = OperatorId::new_v5(
+                OperatorId::nil(),
+                &format!("operator/{}#{}", name, occurrence),
+            );
+        }
+        id
+    }
+
+    fn operator_id_from_name(name: &str) -> OperatorId {
+        OperatorId::new_v5(OperatorId::nil(), &format!("operator/{}", name))
+    }
+
+    /// Returns a name for an operator which an extension trait such as
+    /// [`Map`](crate::dataflow::operators::Map) adds to the graph.
+    ///
+    /// The name is `name` unless another operator in the subgraph which is being built already
+    /// has it, in which case the first free occurrence number is appended, so that applying
+    /// the same extension to a stream several times does not fail [validation](Graph::validate).
+    pub(crate) fn unique_operator_name(&self, name: &str) -> String {
+        (1..)
+            .map(|occurrence| match occurrence {
+                1 => name.to_string(),
+                _ => format!("{}_{}", name, occurrence),
+            })
+            .find(|candidate| {
+                let id = Self::operator_id_from_name(&self.scoped_name(candidate));
+                !self.lock().contains_operator(&id)
+            })
+            .unwrap()
+    }
+
+    /// Returns an ID derived from the subgraph which is being built and `key`, which describes
+    /// the structure of an unnamed operator or stream.
+    ///
+    /// Adding an operator or stream elsewhere in the graph does not change the ID. Several
+    /// operators or streams with the same key in the same subgraph are told apart by the order
+    /// in which they are added; `is_taken` returns whether an ID is already in use.
+    pub(crate) fn structural_id(
+        &self,
+        kind: &str,
+        key: &str,
+        is_taken: impl Fn(&AbstractGraph, &Uuid) -> bool,
+    ) -> Uuid {
+        let key = self.scoped_name(key);
+        let abstract_graph = self.lock();
+        (0..)
+            .map(|occurrence| {
+                Uuid::new_v5(Uuid::nil(), &format!("{}/{}#{}", kind, key, occurrence))
+            })
+            .find(|id| !is_taken(&abstract_graph, id))
+            .unwrap()
+    }
+
+    /// Locks the graph for modification.
+    pub(crate) fn lock(&self) -> MutexGuard<AbstractGraph> {
+        self.abstract_graph.lock().unwrap()
+    }
+
+    pub(crate) fn get_stream_name(&self, stream_id: &StreamId) -> String {
+        self.lock().get_stream_name(stream_id)
+    }
+
+    pub(crate) fn set_stream_name(&self, stream_id: &StreamId, name: &str) {
+        self.lock().set_stream_name(stream_id, name.to_string());
+    }
+
+    pub(crate) fn set_stream_schema_version(&self, stream_id: &StreamId, version: u32) {
+        self.lock().set_stream_schema_version(stream_id, version);
+    }
+
+    pub(crate) fn set_stream_compression(&self, stream_id: &StreamId, compression: Compression) {
+        self.lock().set_stream_compression(stream_id, compression);
+    }
+
+    pub(crate) fn set_stream_low_latency(&self, stream_id: &StreamId, low_latency: bool) {
+        self.lock().set_stream_low_latency(stream_id, low_latency);
+    }
+
+    pub(crate) fn set_stream_priority(&self, stream_id: &StreamId, priority: Priority) {
+        self.lock().set_stream_priority(stream_id, priority);
+    }
+
+    pub(crate) fn set_stream_delivery(&self, stream_id: &StreamId, delivery: Delivery) {
+        self.lock().set_stream_delivery(stream_id, delivery);
+    }
+
+    /// Returns a copy of the graph's current operators and streams.
+    pub(crate) fn clone_abstract_graph(&self) -> AbstractGraph {
+        self.lock().clone()
+    }
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dataflow::{
+        context::SinkContext,
+        graph::GraphFingerprint,
+        operator::{Sink, Source},
+        operators::Map,
+        Stream,
+    };
+
+    use super::*;
+
+    struct TestSource;
+
+    impl Source<usize> for TestSource {}
+
+    struct TestSink;
+
+    impl Sink<(), usize> for TestSink {
+        fn on_data(&mut self, _ctx: &mut SinkContext<()>, _data: &usize) {}
+
+        fn on_watermark(&mut self, _ctx: &mut SinkContext<()>) {}
+    }
+
+    fn errors(graph: &Graph, num_nodes: usize) -> Vec<GraphProblem> {
+        graph
+            .validate(num_nodes)
+            .into_iter()
+            .filter(GraphProblem::is_error)
+            .collect()
+    }
+
+    fn fingerprint(graph: &Graph, num_nodes: usize) -> GraphFingerprint {
+        graph
+            .clone_abstract_graph()
+            .compile(num_nodes, None)
+            .unwrap()
+            .fingerprint()
+    }
+
+    #[test]
+    fn test_valid_graph() {
+        let graph = Graph::new();
+        let ingest_stream = graph.ingest_stream::<usize>();
+        let mapped_stream = ingest_stream.map(|x: &usize| *x);
+        graph.extract_stream(&mapped_stream);
+        assert_eq!(graph.validate(1), vec![]);
+    }
+
+    #[test]
+    fn test_stream_without_consumers_is_warning() {
+        let graph = Graph::new();
+        let ingest_stream = graph.ingest_stream::<usize>();
+        let mapped_stream = ingest_stream.map(|x: &usize| *x);
+
+        let problems = graph.validate(1);
+        assert_eq!(problems.len(), 1);
+        assert!(matches!(
+            &problems[0],
+            GraphProblem::StreamWithoutConsumers { stream_id, .. } if *stream_id == mapped_stream.id()
+        ));
+        assert!(!problems[0].is_error());
+    }
+
+    #[test]
+    fn test_dangling_stream() {
+        let other_graph = Graph::new();
+        let ingest_stream = other_graph.ingest_stream::<usize>();
+
+        let graph = Graph::new();
+        graph.connect_sink(
+            || TestSink,
+            || (),
+            OperatorConfig::new().name("sink"),
+            &ingest_stream,
+        );
+        assert_eq!(
+            errors(&graph, 1),
+            vec![GraphProblem::DanglingStream {
+                stream_id: ingest_stream.id(),
+                consumer_name: "operator sink".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unconnected_loop() {
+        let graph = Graph::new();
+        let loop_stream = graph.loop_stream::<usize>();
+        let mapped_stream = loop_stream.map(|x: &usize| *x);
+        graph.extract_stream(&mapped_stream);
+
+        let errors_before = errors(&graph, 1);
+        assert_eq!(errors_before.len(), 1);
+        assert!(matches!(
+            &errors_before[0],
+            GraphProblem::UnconnectedLoop { stream_id, consumer_names, .. }
+                if *stream_id == loop_stream.id() && consumer_names.len() == 1
+        ));
+
+        loop_stream.connect_loop(&mapped_stream);
+        assert_eq!(errors(&graph, 1), vec![]);
+    }
+
+    #[test]
+    fn test_invalid_node_id() {
+        let graph = Graph::new();
+        graph.connect_source::<_, usize>(
+            || TestSource,
+            OperatorConfig::new().name("source").node(2),
+        );
+        assert_eq!(errors(&graph, 3), vec![]);
+        assert!(matches!(
+            errors(&graph, 2).as_slice(),
+            [GraphProblem::InvalidNodeId {
+                node_id: 2,
+                num_nodes: 2,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let build = |node_id: NodeId| {
+            let graph = Graph::new();
+            let source_stream = graph.connect_source::<_, usize>(
+                || TestSource,
+                OperatorConfig::new().name("source").node(node_id),
+            );
+            graph.extract_stream(&source_stream.map(|x: &usize| *x));
+            graph
+        };
+
+        // Nodes building the same graph agree on its fingerprint.
+        assert_eq!(fingerprint(&build(0), 2), fingerprint(&build(0), 2));
+
+        let diff = fingerprint(&build(0), 2).diff(&fingerprint(&build(1), 2));
+        assert_eq!(diff.len(), 1, "{:?}", diff);
+        assert!(diff[0].starts_with("~ operator"));
+        assert!(diff[0].contains("source on node 0"));
+        assert!(diff[0].contains("source on node 1"));
+    }
+
+    #[test]
+    fn test_duplicate_operator_names_are_errors() {
+        let graph = Graph::new();
+        graph.connect_source::<_, usize>(|| TestSource, OperatorConfig::new().name("source"));
+        graph.connect_source::<_, usize>(|| TestSource, OperatorConfig::new().name("source"));
+
+        let problems = graph.validate(1);
+        assert!(problems.iter().any(|problem| {
+            matches!(problem, GraphProblem::DuplicateOperatorName { .. }) && problem.is_error()
+        }));
+    }
+
+    #[test]
+    fn test_unnamed_ids_do_not_depend_on_other_operators() {
+        let build = |add_source: bool| {
+            let graph = Graph::new();
+            if add_source {
+                graph.connect_source::<_, usize>(|| TestSource, OperatorConfig::new());
+            }
+            let ingest_stream = graph.ingest_stream::<usize>();
+            graph.connect_sink(|| TestSink, || (), OperatorConfig::new(), &ingest_stream);
+            (ingest_stream.id(), graph.last_operator_config().unwrap().id)
+        };
+        assert_eq!(build(false), build(true));
+    }
+
+    #[test]
+    fn test_unnamed_operators_in_subgraph() {
+        let graph = Graph::new();
+        let ingest_stream = graph.ingest_stream::<usize>();
+        let connect_sink = |graph: &Graph| {
+            graph.connect_sink(|| TestSink, || (), OperatorConfig::new(), &ingest_stream)
+        };
+        graph.subgraph("sinks", connect_sink);
+        let config = graph.last_operator_config().unwrap();
+        assert_eq!(config.name, Some(format!("sinks/{}", config.id)));
+
+        // The same operator outside of the subgraph receives a different ID.
+        connect_sink(&graph);
+        assert_ne!(graph.last_operator_config().unwrap().id, config.id);
+    }
+
+    #[test]
+    fn test_extension_operator_names() {
+        let graph = Graph::new();
+        let ingest_stream = graph.ingest_stream::<usize>();
+        let doubled_stream = ingest_stream.map(|x: &usize| 2 * x);
+        assert_eq!(
+            graph.last_operator_config().unwrap().name,
+            Some("MapOp_ingest-stream-0".to_string())
+        );
+
+        // Mapping the same stream again yields another name instead of a duplicate.
+        let tripled_stream = ingest_stream.map(|x: &usize| 3 * x);
+        assert_eq!(
+            graph.last_operator_config().unwrap().name,
+            Some("MapOp_ingest-stream-0_2".to_string())
+        );
+        graph.extract_stream(&doubled_stream);
+        graph.extract_stream(&tripled_stream);
+        assert_eq!(graph.validate(1), vec![]);
+    }
+}
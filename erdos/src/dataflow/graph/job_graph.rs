@@ -1,8 +1,14 @@
-use std::{collections::HashMap, fs::File, io::prelude::*};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::File,
+    io::{self, prelude::*},
+    path::Path,
+};
 
 use crate::{dataflow::stream::StreamId, OperatorId};
 
 use super::{
+    GraphDescription, GraphFingerprint, JobDescription, OperatorDescription, StreamDescription,
     StreamSetupHook, {AbstractOperator, AbstractStreamT, Job},
 };
 
@@ -12,6 +18,10 @@ pub(crate) struct JobGraph {
     stream_sources: HashMap<StreamId, Job>,
     stream_destinations: HashMap<StreamId, Vec<Job>>,
     driver_setup_hooks: Vec<Box<dyn StreamSetupHook>>,
+    /// Maps each [`LoopStream`](crate::dataflow::LoopStream) to the stream to which it connects.
+    loop_streams: HashMap<StreamId, StreamId>,
+    /// Pairs of streams and operators which read from the streams through loop streams.
+    loop_reads: HashSet<(StreamId, OperatorId)>,
 }
 
 impl JobGraph {
@@ -20,6 +30,8 @@ impl JobGraph {
         streams: Vec<Box<dyn AbstractStreamT>>,
         ingest_streams: HashMap<StreamId, Box<dyn StreamSetupHook>>,
         extract_streams: HashMap<StreamId, Box<dyn StreamSetupHook>>,
+        loop_streams: HashMap<StreamId, StreamId>,
+        loop_reads: HashSet<(StreamId, OperatorId)>,
     ) -> Self {
         let mut stream_sources = HashMap::new();
         let mut stream_destinations: HashMap<StreamId, Vec<Job>> = HashMap::new();
@@ -64,6 +76,8 @@ impl JobGraph {
             stream_sources,
             stream_destinations,
             driver_setup_hooks,
+            loop_streams,
+            loop_reads,
         }
     }
 
@@ -100,6 +114,12 @@ impl JobGraph {
             .collect()
     }
 
+    /// Returns a map from each [`LoopStream`](crate::dataflow::LoopStream) to the stream to which
+    /// it connects.
+    pub fn loop_streams(&self) -> HashMap<StreamId, StreamId> {
+        self.loop_streams.clone()
+    }
+
     /// Returns the hooks used to set up ingest and extract streams.
     pub fn get_driver_setup_hooks(&self) -> Vec<Box<dyn StreamSetupHook>> {
         let mut driver_setup_hooks = Vec::new();
@@ -109,51 +129,339 @@ impl JobGraph {
         driver_setup_hooks
     }
 
-    /// Exports the job graph to a Graphviz file (*.gv, *.dot).
-    pub fn to_graph_viz(&self, filename: &str) -> std::io::Result<()> {
-        let mut file = File::create(filename)?;
-        writeln!(file, "digraph erdos_dataflow {{")?;
+    /// Summarizes the operators and streams in the graph, including their names, placement,
+    /// and connections.
+    pub fn fingerprint(&self) -> GraphFingerprint {
+        let stream_name = |stream_id: &StreamId| {
+            self.streams
+                .get(stream_id)
+                .map(|stream| stream.name())
+                .unwrap_or_else(|| stream_id.to_string())
+        };
+        let operator_names: HashMap<_, _> = self
+            .operators
+            .iter()
+            .map(|operator| (operator.id, operator.config.get_name()))
+            .collect();
+        let job_name = |job: &Job| match job {
+            Job::Driver => "driver".to_string(),
+            Job::Operator(operator_id) => operator_names[operator_id].clone(),
+        };
+
+        let operators = self
+            .operators
+            .iter()
+            .map(|operator| {
+                let read_streams: Vec<_> = operator.read_streams.iter().map(stream_name).collect();
+                let write_streams: Vec<_> =
+                    operator.write_streams.iter().map(stream_name).collect();
+                let description = format!(
+                    "{} on node {} reads [{}] writes [{}]",
+                    operator.config.get_name(),
+                    operator.config.node_id,
+                    read_streams.join(", "),
+                    write_streams.join(", ")
+                );
+                (operator.id, description)
+            })
+            .collect();
+
+        let streams: BTreeMap<_, _> = self
+            .streams
+            .values()
+            .map(|stream| {
+                let source = self
+                    .stream_sources
+                    .get(&stream.id())
+                    .map(job_name)
+                    .unwrap_or_else(|| "nothing".to_string());
+                let mut destinations: Vec<_> = self
+                    .stream_destinations
+                    .get(&stream.id())
+                    .map(|jobs| jobs.iter().map(job_name).collect())
+                    .unwrap_or_default();
+                // Sort because destinations are listed in the order in which they were added.
+                destinations.sort();
+                let description = format!(
+                    "{} written by {} read by [{}]",
+                    stream.name(),
+                    source,
+                    destinations.join(", ")
+                );
+                (stream.id(), description)
+            })
+            .collect();
+
+        GraphFingerprint::new(operators, streams)
+    }
 
-        let driver_id = OperatorId::new_deterministic();
-        if !self.driver_setup_hooks.is_empty() {
-            writeln!(file, "   // Driver")?;
-            writeln!(file, "   \"{}\" [label=\"Driver\"];", driver_id)?;
+    /// Returns a machine-readable description of the graph, given the number of deadlines
+    /// registered by each operator.
+    pub fn to_description(&self, num_deadlines: &HashMap<OperatorId, usize>) -> GraphDescription {
+        let job_description = |job: &Job| match job {
+            Job::Driver => JobDescription::Driver,
+            Job::Operator(operator_id) => JobDescription::Operator(operator_id.to_string()),
+        };
+        let to_strings = |stream_ids: &Vec<StreamId>| -> Vec<String> {
+            stream_ids.iter().map(|id| id.to_string()).collect()
+        };
+
+        let mut operators: Vec<_> = self
+            .operators
+            .iter()
+            .map(|operator| OperatorDescription {
+                id: operator.id.to_string(),
+                name: operator.config.get_name(),
+                node_id: operator.config.node_id,
+                flow_watermarks: operator.config.flow_watermarks,
+                close_streams_on_node_failure: operator.config.close_streams_on_node_failure,
+                deadlines: num_deadlines.get(&operator.id).cloned().unwrap_or(0),
+                read_streams: to_strings(&operator.read_streams),
+                write_streams: to_strings(&operator.write_streams),
+            })
+            .collect();
+        operators.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut streams: Vec<_> = self
+            .streams
+            .values()
+            .filter_map(|stream| {
+                let source = self.stream_sources.get(&stream.id())?;
+                let destinations = self
+                    .stream_destinations
+                    .get(&stream.id())
+                    .map(|jobs| jobs.iter().map(job_description).collect())
+                    .unwrap_or_default();
+                Some(StreamDescription {
+                    id: stream.id().to_string(),
+                    name: stream.name(),
+                    data_type: stream.data_type().to_string(),
+                    schema_version: stream.schema_version(),
+                    compression: stream.compression(),
+                    source: job_description(source),
+                    destinations,
+                })
+            })
+            .collect();
+        streams.sort_by(|a, b| a.name.cmp(&b.name));
+
+        GraphDescription { operators, streams }
+    }
+
+    /// Exports the job graph to a file whose format is chosen based on the extension of
+    /// `filename`: JSON (*.json), YAML (*.yaml, *.yml), or Graphviz otherwise.
+    ///
+    /// `num_deadlines` maps operators to the number of deadlines they registered.
+    /// If `collapse_composite_operators` is set, Graphviz exports draw each composite operator
+    /// as a single operator.
+    pub fn export(
+        &self,
+        filename: &str,
+        num_deadlines: &HashMap<OperatorId, usize>,
+        collapse_composite_operators: bool,
+    ) -> io::Result<()> {
+        match Path::new(filename).extension().and_then(|e| e.to_str()) {
+            Some("json") => self.to_json(filename, num_deadlines),
+            Some("yaml") | Some("yml") => self.to_yaml(filename, num_deadlines),
+            _ => self.to_graph_viz(filename, num_deadlines, collapse_composite_operators),
         }
+    }
 
-        writeln!(file, "   // Operators")?;
+    /// Exports the job graph to a JSON file.
+    pub fn to_json(
+        &self,
+        filename: &str,
+        num_deadlines: &HashMap<OperatorId, usize>,
+    ) -> io::Result<()> {
+        let file = File::create(filename)?;
+        serde_json::to_writer_pretty(file, &self.to_description(num_deadlines))
+            .map_err(io::Error::from)
+    }
+
+    /// Exports the job graph to a YAML file.
+    pub fn to_yaml(
+        &self,
+        filename: &str,
+        num_deadlines: &HashMap<OperatorId, usize>,
+    ) -> io::Result<()> {
+        let file = File::create(filename)?;
+        serde_yaml::to_writer(file, &self.to_description(num_deadlines))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+
+    /// Exports the job graph to a Graphviz file (*.gv, *.dot).
+    ///
+    /// Operators are grouped into a cluster per node. Edges are labeled with the stream's name
+    /// and data type, and edges which close loops are dashed. Ingest and extract streams are
+    /// drawn as separate endpoints. Operators which registered deadlines are red, and operators
+    /// which do not flow watermarks are dashed.
+    ///
+    /// If `collapse_composite_operators` is set, the operators of each top-level
+    /// [subgraph](crate::dataflow::Graph::subgraph), i.e. the operators whose names share the
+    /// prefix before the first `/`, are drawn as a single composite operator, and streams
+    /// between them are omitted.
+    pub fn to_graph_viz(
+        &self,
+        filename: &str,
+        num_deadlines: &HashMap<OperatorId, usize>,
+        collapse_composite_operators: bool,
+    ) -> std::io::Result<()> {
+        // Maps each operator to the ID of the vertex which represents it.
+        let mut vertices: HashMap<OperatorId, String> = HashMap::new();
+        // Maps each node to the lines describing the vertices placed on the node.
+        let mut clusters: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        // Lines describing vertices which are not placed on a single node.
+        let mut unclustered = Vec::new();
+
+        // Maps the name of each composite operator to its operators.
+        let mut composites: BTreeMap<String, Vec<&AbstractOperator>> = BTreeMap::new();
         for operator in self.operators.iter() {
-            writeln!(
-                file,
-                "   \"{}\" [label=\"{}\n(Node {})\"];",
-                operator.id,
-                operator.config.get_name(),
-                operator.config.node_id,
-            )?;
+            let name = operator.config.get_name();
+            match name.split_once('/') {
+                Some((prefix, _)) if collapse_composite_operators => composites
+                    .entry(prefix.to_string())
+                    .or_default()
+                    .push(operator),
+                _ => {
+                    let mut label =
+                        format!("{}\\n(Node {})", escape(&name), operator.config.node_id);
+                    let mut attributes = Vec::new();
+                    let deadlines = num_deadlines.get(&operator.id).cloned().unwrap_or(0);
+                    if deadlines > 0 {
+                        label.push_str(&format!("\\n({} deadlines)", deadlines));
+                        attributes.push("color=red".to_string());
+                    }
+                    if !operator.config.flow_watermarks {
+                        label.push_str("\\n(no watermark flow)");
+                        attributes.push("style=dashed".to_string());
+                    }
+                    attributes.insert(0, format!("label=\"{}\"", label));
+                    clusters
+                        .entry(operator.config.node_id)
+                        .or_default()
+                        .push(format!("\"{}\" [{}];", operator.id, attributes.join(", ")));
+                    vertices.insert(operator.id, operator.id.to_string());
+                }
+            }
+        }
+        for (name, operators) in composites.iter() {
+            let vertex = format!("composite/{}", name);
+            let nodes: BTreeSet<_> = operators.iter().map(|o| o.config.node_id).collect();
+            let nodes: Vec<_> = nodes.iter().map(|n| n.to_string()).collect();
+            let deadlines: usize = operators
+                .iter()
+                .map(|o| num_deadlines.get(&o.id).cloned().unwrap_or(0))
+                .sum();
+            let mut label = format!(
+                "{}\\n({} operators on node {})",
+                escape(name),
+                operators.len(),
+                nodes.join(", ")
+            );
+            let mut attributes = vec!["shape=box3d".to_string()];
+            if deadlines > 0 {
+                label.push_str(&format!("\\n({} deadlines)", deadlines));
+                attributes.push("color=red".to_string());
+            }
+            attributes.insert(0, format!("label=\"{}\"", label));
+            let line = format!("\"{}\" [{}];", escape(&vertex), attributes.join(", "));
+            if nodes.len() == 1 {
+                clusters
+                    .entry(operators[0].config.node_id)
+                    .or_default()
+                    .push(line);
+            } else {
+                unclustered.push(line);
+            }
+            for operator in operators.iter() {
+                vertices.insert(operator.id, escape(&vertex));
+            }
         }
 
-        writeln!(file, "   // Streams")?;
-        for (stream_id, source) in self.stream_sources.iter() {
-            let source_id = match source {
-                Job::Driver => driver_id,
-                Job::Operator(id) => *id,
+        // Sort streams by name to produce the same file across runs.
+        let mut streams: Vec<_> = self.streams.values().collect();
+        streams.sort_by_key(|s| (s.name(), s.id()));
+
+        let mut edges = BTreeSet::new();
+        for stream in streams.iter() {
+            let label = format!(
+                "{}\\n{}",
+                escape(&stream.name()),
+                escape(stream.data_type())
+            );
+            let source = match self.stream_sources.get(&stream.id()) {
+                Some(Job::Driver) => {
+                    let vertex = format!("ingest/{}", stream.id());
+                    unclustered.push(format!(
+                        "\"{}\" [label=\"Ingest\\n{}\", shape=invhouse];",
+                        vertex,
+                        escape(&stream.name())
+                    ));
+                    vertex
+                }
+                Some(Job::Operator(id)) => vertices[id].clone(),
+                None => continue,
             };
-            if let Some(dests) = self.stream_destinations.get(stream_id) {
-                for dest in dests.iter() {
-                    let dest_id = match dest {
-                        Job::Driver => driver_id,
-                        Job::Operator(id) => *id,
-                    };
-                    let stream_name = self.streams.get(stream_id).unwrap().name();
-                    writeln!(
-                        file,
-                        "   \"{}\" -> \"{}\" [label=\"{}\"];",
-                        source_id, dest_id, stream_name
-                    )?;
+            for destination in self
+                .stream_destinations
+                .get(&stream.id())
+                .into_iter()
+                .flatten()
+            {
+                let (destination, is_loop) = match destination {
+                    Job::Driver => {
+                        let vertex = format!("extract/{}", stream.id());
+                        unclustered.push(format!(
+                            "\"{}\" [label=\"Extract\\n{}\", shape=house];",
+                            vertex,
+                            escape(&stream.name())
+                        ));
+                        (vertex, false)
+                    }
+                    Job::Operator(id) => (
+                        vertices[id].clone(),
+                        self.loop_reads.contains(&(stream.id(), *id)),
+                    ),
+                };
+                // Streams within a collapsed composite operator are omitted.
+                if source == destination {
+                    continue;
                 }
+                let style = if is_loop { ", style=dashed" } else { "" };
+                edges.insert(format!(
+                    "\"{}\" -> \"{}\" [label=\"{}\"{}];",
+                    source, destination, label, style
+                ));
             }
         }
 
+        let mut file = File::create(filename)?;
+        writeln!(file, "digraph erdos_dataflow {{")?;
+        writeln!(file, "   // Operators")?;
+        for (node_id, lines) in clusters.iter() {
+            writeln!(file, "   subgraph cluster_{} {{", node_id)?;
+            writeln!(file, "      label=\"Node {}\";", node_id)?;
+            for line in lines.iter() {
+                writeln!(file, "      {}", line)?;
+            }
+            writeln!(file, "   }}")?;
+        }
+        if !unclustered.is_empty() {
+            writeln!(file, "   // Ingest and extract streams")?;
+            for line in unclustered.iter() {
+                writeln!(file, "   {}", line)?;
+            }
+        }
+        writeln!(file, "   // Streams")?;
+        for edge in edges.iter() {
+            writeln!(file, "   {}", edge)?;
+        }
         writeln!(file, "}}")?;
         file.flush()
     }
 }
+
+/// Escapes quotes and backslashes in a Graphviz label.
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
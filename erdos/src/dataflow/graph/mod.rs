@@ -1,9 +1,11 @@
 use std::{
+    any::type_name,
     marker::PhantomData,
     sync::{Arc, Mutex},
 };
 
 use crate::{
+    communication::{Compression, Delivery, Priority, SendOptions},
     node::operator_executors::OperatorExecutorT,
     scheduler::channel_manager::{ChannelManager, StreamEndpoints, StreamEndpointsT},
     OperatorConfig, OperatorId,
@@ -11,11 +13,27 @@ use crate::{
 
 // Private submodules
 mod abstract_graph;
+mod description;
+mod fingerprint;
+#[allow(clippy::module_inception)]
+mod graph;
 mod job_graph;
+mod placement;
+mod validation;
 
 // Public submodules
 pub(crate) mod default_graph;
 
+// Public exports
+pub use description::{GraphDescription, JobDescription, OperatorDescription, StreamDescription};
+pub use fingerprint::GraphFingerprint;
+pub use graph::Graph;
+pub use placement::{
+    BalanceCost, MinimizeCrossNodeStreams, PlacedOperator, PlacementGraph, PlacementOperator,
+    PlacementReport, PlacementStrategy, RoundRobin,
+};
+pub use validation::{GraphProblem, GraphValidationError};
+
 // Crate-wide exports
 pub(crate) use abstract_graph::AbstractGraph;
 pub(crate) use job_graph::JobGraph;
@@ -24,15 +42,21 @@ use serde::Deserialize;
 use super::{stream::StreamId, Data};
 
 /// Trait for functions that set up operator execution.
+///
+/// The functions receive the operator's configuration, whose node may have been assigned by a
+/// [`PlacementStrategy`] when the graph was compiled.
 pub(crate) trait OperatorRunner:
-    'static + (Fn(Arc<Mutex<ChannelManager>>) -> Box<dyn OperatorExecutorT>) + Sync + Send
+    'static
+    + (Fn(OperatorConfig, Arc<Mutex<ChannelManager>>) -> Box<dyn OperatorExecutorT>)
+    + Sync
+    + Send
 {
     fn box_clone(&self) -> Box<dyn OperatorRunner>;
 }
 
 impl<
         T: 'static
-            + (Fn(Arc<Mutex<ChannelManager>>) -> Box<dyn OperatorExecutorT>)
+            + (Fn(OperatorConfig, Arc<Mutex<ChannelManager>>) -> Box<dyn OperatorExecutorT>)
             + Sync
             + Send
             + Clone,
@@ -76,6 +100,16 @@ where
 {
     id: StreamId,
     name: String,
+    /// Version of the schema of the data sent on the stream, set by the user.
+    schema_version: u32,
+    /// Compression of the messages sent to other nodes.
+    compression: Compression,
+    /// Whether messages sent to other nodes are flushed without waiting to be batched.
+    low_latency: bool,
+    /// Priority with which messages are sent to other nodes.
+    priority: Priority,
+    /// How messages are delivered to other nodes.
+    delivery: Delivery,
     phantom: PhantomData<D>,
 }
 
@@ -87,9 +121,19 @@ where
         Self {
             id,
             name,
+            schema_version: 0,
+            compression: Compression::None,
+            low_latency: false,
+            priority: Priority::Normal,
+            delivery: Delivery::Reliable,
             phantom: PhantomData,
         }
     }
+
+    fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
 }
 
 /// A trait implemented over [`AbstractStream`]s used to preserve
@@ -98,6 +142,25 @@ pub(crate) trait AbstractStreamT: Send + Sync {
     fn id(&self) -> StreamId;
     fn name(&self) -> String;
     fn set_name(&mut self, name: String);
+    fn set_schema_version(&mut self, version: u32);
+    fn schema_version(&self) -> u32;
+    fn set_compression(&mut self, compression: Compression);
+    fn compression(&self) -> Compression;
+    fn set_low_latency(&mut self, low_latency: bool);
+    fn set_priority(&mut self, priority: Priority);
+    fn set_delivery(&mut self, delivery: Delivery);
+    fn delivery(&self) -> Delivery;
+    /// Returns the name of the type of data sent on the stream.
+    fn data_type(&self) -> &'static str;
+    /// Describes the type of the data sent on the stream. Streams whose data is serialized
+    /// differently have different fingerprints.
+    fn type_fingerprint(&self) -> String {
+        format!(
+            "{} (schema version {})",
+            self.data_type(),
+            self.schema_version()
+        )
+    }
     fn box_clone(&self) -> Box<dyn AbstractStreamT>;
     fn to_stream_endpoints_t(&self) -> Box<dyn StreamEndpointsT>;
 }
@@ -118,12 +181,57 @@ where
         self.name = name;
     }
 
+    fn set_schema_version(&mut self, version: u32) {
+        self.schema_version = version;
+    }
+
+    fn schema_version(&self) -> u32 {
+        self.schema_version
+    }
+
+    fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    fn set_low_latency(&mut self, low_latency: bool) {
+        self.low_latency = low_latency;
+    }
+
+    fn set_priority(&mut self, priority: Priority) {
+        self.priority = priority;
+    }
+
+    fn set_delivery(&mut self, delivery: Delivery) {
+        self.delivery = delivery;
+    }
+
+    fn delivery(&self) -> Delivery {
+        self.delivery
+    }
+
+    fn data_type(&self) -> &'static str {
+        type_name::<D>()
+    }
+
     fn box_clone(&self) -> Box<dyn AbstractStreamT> {
         Box::new(self.clone())
     }
 
     fn to_stream_endpoints_t(&self) -> Box<dyn StreamEndpointsT> {
-        Box::new(StreamEndpoints::<D>::new(self.id, self.name()))
+        Box::new(StreamEndpoints::<D>::new(
+            self.id,
+            self.name(),
+            SendOptions {
+                compression: self.compression,
+                low_latency: self.low_latency,
+                priority: self.priority,
+                delivery: self.delivery,
+            },
+        ))
     }
 }
 
@@ -0,0 +1,391 @@
+use std::{collections::VecDeque, fmt};
+
+use crate::{node::NodeId, OperatorId};
+
+/// Assigns operators which are not [pinned](crate::dataflow::OperatorConfig::pinned) to nodes.
+///
+/// A strategy is set with
+/// [`Configuration::with_placement_strategy`](crate::Configuration::with_placement_strategy), and
+/// runs when the dataflow graph is compiled. All nodes compile the graph independently, so
+/// strategies must be deterministic.
+pub trait PlacementStrategy: Send + Sync {
+    /// Returns the node of each operator in `graph.operators`, in the same order.
+    ///
+    /// Nodes returned for pinned operators are ignored, and operators without a returned node
+    /// keep their configured node.
+    fn place(&self, graph: &PlacementGraph) -> Vec<NodeId>;
+}
+
+/// The operators to place, and the streams connecting them.
+#[derive(Clone, Debug)]
+pub struct PlacementGraph {
+    /// The number of nodes executing the graph.
+    pub num_nodes: usize,
+    /// The operators, ordered by name.
+    pub operators: Vec<PlacementOperator>,
+    /// A `(writer, reader)` pair of indices into `operators` for each stream an operator reads
+    /// from another operator.
+    pub edges: Vec<(usize, usize)>,
+}
+
+/// An operator to place.
+#[derive(Clone, Debug)]
+pub struct PlacementOperator {
+    pub id: OperatorId,
+    pub name: String,
+    /// The node to which the operator is pinned, if any.
+    pub pinned_node: Option<NodeId>,
+    /// See [`OperatorConfig::estimated_cost`](crate::dataflow::OperatorConfig::estimated_cost).
+    pub estimated_cost: f64,
+}
+
+impl PlacementGraph {
+    /// Returns the indices of the operators adjacent to each operator.
+    fn neighbors(&self) -> Vec<Vec<usize>> {
+        let mut neighbors = vec![Vec::new(); self.operators.len()];
+        for &(writer, reader) in self.edges.iter() {
+            neighbors[writer].push(reader);
+            neighbors[reader].push(writer);
+        }
+        neighbors
+    }
+}
+
+/// Assigns unpinned operators to nodes in turn, ordered by name.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RoundRobin;
+
+impl PlacementStrategy for RoundRobin {
+    fn place(&self, graph: &PlacementGraph) -> Vec<NodeId> {
+        let mut next_node = 0;
+        graph
+            .operators
+            .iter()
+            .map(|operator| {
+                operator.pinned_node.unwrap_or_else(|| {
+                    let node_id = next_node;
+                    next_node = (next_node + 1) % graph.num_nodes;
+                    node_id
+                })
+            })
+            .collect()
+    }
+}
+
+/// Places connected operators on the same node in order to minimize the number of streams which
+/// cross nodes, while placing at most `ceil(#operators / #nodes)` operators on each node.
+///
+/// Operators are visited breadth-first from the pinned operators, and each is placed on the node
+/// which runs most of its already placed neighbors.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MinimizeCrossNodeStreams;
+
+impl PlacementStrategy for MinimizeCrossNodeStreams {
+    fn place(&self, graph: &PlacementGraph) -> Vec<NodeId> {
+        let num_operators = graph.operators.len();
+        let capacity = (num_operators + graph.num_nodes - 1) / graph.num_nodes;
+        let neighbors = graph.neighbors();
+
+        let mut nodes: Vec<Option<NodeId>> =
+            graph.operators.iter().map(|o| o.pinned_node).collect();
+        let mut load = vec![0; graph.num_nodes];
+        for node_id in nodes.iter().flatten() {
+            if let Some(l) = load.get_mut(*node_id) {
+                *l += 1;
+            }
+        }
+
+        let mut visited: Vec<bool> = nodes.iter().map(Option::is_some).collect();
+        let mut queue: VecDeque<usize> = (0..num_operators).filter(|&i| visited[i]).collect();
+        // Operators which are not connected to pinned operators start new searches.
+        let mut starts = 0..num_operators;
+        loop {
+            let index = match queue.pop_front() {
+                Some(index) => index,
+                None => match starts.find(|&i| !visited[i]) {
+                    Some(index) => {
+                        visited[index] = true;
+                        index
+                    }
+                    None => break,
+                },
+            };
+            if nodes[index].is_none() {
+                let mut affinity = vec![0; graph.num_nodes];
+                for &neighbor in neighbors[index].iter() {
+                    if let Some(node_id) = nodes[neighbor] {
+                        if node_id < graph.num_nodes {
+                            affinity[node_id] += 1;
+                        }
+                    }
+                }
+                // Prefer the node with the most placed neighbors, then the least loaded node.
+                let node_id = (0..graph.num_nodes)
+                    .filter(|&n| load[n] < capacity)
+                    .max_by_key(|&n| (affinity[n], std::cmp::Reverse((load[n], n))))
+                    .unwrap_or_else(|| {
+                        // Pinned operators exceed the capacity of all nodes.
+                        (0..graph.num_nodes).min_by_key(|&n| load[n]).unwrap()
+                    });
+                nodes[index] = Some(node_id);
+                load[node_id] += 1;
+            }
+            for &neighbor in neighbors[index].iter() {
+                if !visited[neighbor] {
+                    visited[neighbor] = true;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        nodes.into_iter().map(|n| n.unwrap_or(0)).collect()
+    }
+}
+
+/// Balances the [estimated cost](crate::dataflow::OperatorConfig::estimated_cost) of the
+/// operators on each node by placing the most costly unpinned operators first, each on the node
+/// with the lowest total cost.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BalanceCost;
+
+impl PlacementStrategy for BalanceCost {
+    fn place(&self, graph: &PlacementGraph) -> Vec<NodeId> {
+        let mut costs = vec![0.0; graph.num_nodes];
+        let mut nodes: Vec<Option<NodeId>> =
+            graph.operators.iter().map(|o| o.pinned_node).collect();
+        for (operator, node_id) in graph.operators.iter().zip(nodes.iter()) {
+            if let Some(cost) = node_id.and_then(|n| costs.get_mut(n)) {
+                *cost += operator.estimated_cost;
+            }
+        }
+
+        let mut unpinned: Vec<usize> = (0..nodes.len()).filter(|&i| nodes[i].is_none()).collect();
+        // The sort is stable, so operators with the same cost remain ordered by name.
+        unpinned.sort_by(|&a, &b| {
+            let (a, b) = (&graph.operators[a], &graph.operators[b]);
+            b.estimated_cost
+                .partial_cmp(&a.estimated_cost)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        for index in unpinned {
+            let node_id = (0..graph.num_nodes)
+                .min_by(|&a, &b| {
+                    costs[a]
+                        .partial_cmp(&costs[b])
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .unwrap();
+            costs[node_id] += graph.operators[index].estimated_cost;
+            nodes[index] = Some(node_id);
+        }
+        nodes.into_iter().map(|n| n.unwrap_or(0)).collect()
+    }
+}
+
+/// The placement of the operators in a dataflow graph.
+#[derive(Clone, Debug)]
+pub struct PlacementReport {
+    /// The operators, ordered by name.
+    pub operators: Vec<PlacedOperator>,
+    /// The number of streams between operators which cross nodes.
+    pub cross_node_streams: usize,
+    /// The number of operators on each node.
+    pub operators_per_node: Vec<usize>,
+    /// The total estimated cost of the operators on each node.
+    pub cost_per_node: Vec<f64>,
+}
+
+/// An operator and the node on which it runs.
+#[derive(Clone, Debug)]
+pub struct PlacedOperator {
+    pub id: OperatorId,
+    pub name: String,
+    pub node_id: NodeId,
+    /// Whether the operator was pinned to the node instead of placed by the strategy.
+    pub pinned: bool,
+    pub estimated_cost: f64,
+}
+
+impl PlacementReport {
+    pub(crate) fn new(graph: &PlacementGraph, nodes: &[NodeId]) -> Self {
+        let mut operators_per_node = vec![0; graph.num_nodes];
+        let mut cost_per_node = vec![0.0; graph.num_nodes];
+        let operators = graph
+            .operators
+            .iter()
+            .zip(nodes.iter())
+            .map(|(operator, &node_id)| {
+                if node_id < graph.num_nodes {
+                    operators_per_node[node_id] += 1;
+                    cost_per_node[node_id] += operator.estimated_cost;
+                }
+                PlacedOperator {
+                    id: operator.id,
+                    name: operator.name.clone(),
+                    node_id,
+                    pinned: operator.pinned_node.is_some(),
+                    estimated_cost: operator.estimated_cost,
+                }
+            })
+            .collect();
+        let cross_node_streams = graph
+            .edges
+            .iter()
+            .filter(|&&(writer, reader)| nodes[writer] != nodes[reader])
+            .count();
+        Self {
+            operators,
+            cross_node_streams,
+            operators_per_node,
+            cost_per_node,
+        }
+    }
+}
+
+impl fmt::Display for PlacementReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for operator in self.operators.iter() {
+            writeln!(
+                f,
+                "{} on node {}{} (cost {})",
+                operator.name,
+                operator.node_id,
+                if operator.pinned { ", pinned" } else { "" },
+                operator.estimated_cost
+            )?;
+        }
+        for (node_id, (num_operators, cost)) in self
+            .operators_per_node
+            .iter()
+            .zip(self.cost_per_node.iter())
+            .enumerate()
+        {
+            writeln!(
+                f,
+                "node {}: {} operators with total cost {}",
+                node_id, num_operators, cost
+            )?;
+        }
+        write!(
+            f,
+            "{} streams between operators cross nodes",
+            self.cross_node_streams
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a graph of operators with the given pinned nodes and estimated costs.
+    fn graph(
+        num_nodes: usize,
+        operators: &[(Option<NodeId>, f64)],
+        edges: &[(usize, usize)],
+    ) -> PlacementGraph {
+        PlacementGraph {
+            num_nodes,
+            operators: operators
+                .iter()
+                .enumerate()
+                .map(|(i, &(pinned_node, estimated_cost))| PlacementOperator {
+                    id: OperatorId::new_deterministic(),
+                    name: format!("operator_{}", i),
+                    pinned_node,
+                    estimated_cost,
+                })
+                .collect(),
+            edges: edges.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let graph = graph(
+            2,
+            &[
+                (None, 1.0),
+                (Some(1), 1.0),
+                (None, 1.0),
+                (None, 1.0),
+                (None, 1.0),
+            ],
+            &[],
+        );
+        assert_eq!(RoundRobin.place(&graph), vec![0, 1, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_minimize_cross_node_streams() {
+        // Two chains of three operators.
+        let graph = graph(2, &[(None, 1.0); 6], &[(0, 1), (1, 2), (3, 4), (4, 5)]);
+        let nodes = MinimizeCrossNodeStreams.place(&graph);
+        assert_eq!(nodes, vec![0, 0, 0, 1, 1, 1]);
+
+        let report = PlacementReport::new(&graph, &nodes);
+        assert_eq!(report.cross_node_streams, 0);
+        assert_eq!(report.operators_per_node, vec![3, 3]);
+    }
+
+    #[test]
+    fn test_minimize_cross_node_streams_from_pinned() {
+        // A chain whose last operator is pinned. Operators are placed next to it until the node
+        // is full.
+        let graph = graph(
+            2,
+            &[(None, 1.0), (None, 1.0), (None, 1.0), (Some(1), 1.0)],
+            &[(0, 1), (1, 2), (2, 3)],
+        );
+        let nodes = MinimizeCrossNodeStreams.place(&graph);
+        assert_eq!(nodes, vec![0, 0, 1, 1]);
+        assert_eq!(PlacementReport::new(&graph, &nodes).cross_node_streams, 1);
+    }
+
+    #[test]
+    fn test_balance_cost() {
+        let graph = graph(
+            2,
+            &[(None, 1.0), (None, 5.0), (None, 3.0), (None, 3.0)],
+            &[],
+        );
+        let nodes = BalanceCost.place(&graph);
+        assert_eq!(nodes, vec![0, 0, 1, 1]);
+        assert_eq!(
+            PlacementReport::new(&graph, &nodes).cost_per_node,
+            vec![6.0, 6.0]
+        );
+    }
+
+    #[test]
+    fn test_balance_cost_with_pinned() {
+        let graph = graph(
+            2,
+            &[(Some(1), 4.0), (None, 2.0), (None, 2.0), (None, 2.0)],
+            &[],
+        );
+        let nodes = BalanceCost.place(&graph);
+        assert_eq!(nodes, vec![1, 0, 0, 0]);
+        assert_eq!(
+            PlacementReport::new(&graph, &nodes).cost_per_node,
+            vec![6.0, 4.0]
+        );
+    }
+
+    #[test]
+    fn test_placement_report() {
+        let graph = graph(2, &[(Some(1), 2.0), (None, 1.5)], &[(0, 1)]);
+        let report = PlacementReport::new(&graph, &[1, 0]);
+        assert!(report.operators[0].pinned);
+        assert!(!report.operators[1].pinned);
+        assert_eq!(report.operators[1].node_id, 0);
+        assert_eq!(report.cross_node_streams, 1);
+        assert_eq!(
+            report.to_string(),
+            "operator_0 on node 1, pinned (cost 2)\n\
+             operator_1 on node 0 (cost 1.5)\n\
+             node 0: 1 operators with total cost 1.5\n\
+             node 1: 1 operators with total cost 2\n\
+             1 streams between operators cross nodes"
+        );
+    }
+}
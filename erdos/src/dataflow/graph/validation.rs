@@ -0,0 +1,159 @@
+use std::fmt;
+
+use crate::{dataflow::stream::StreamId, node::NodeId, OperatorId};
+
+/// A problem found when validating a dataflow graph before it executes.
+///
+/// Problems for which [`GraphProblem::is_error`] returns `true` prevent the graph from running.
+/// The remaining problems are likely mistakes, and are logged as warnings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GraphProblem {
+    /// A stream is read but is not part of the graph, so nothing ever writes to it.
+    /// This happens when a stream created in another [`Graph`](crate::dataflow::Graph), or
+    /// before a call to [`reset`](crate::reset), is passed to an operator.
+    DanglingStream {
+        stream_id: StreamId,
+        /// The operator, [`ExtractStream`](crate::dataflow::stream::ExtractStream), or
+        /// [`LoopStream`](crate::dataflow::LoopStream) which reads from the stream.
+        consumer_name: String,
+    },
+    /// A [`LoopStream`](crate::dataflow::LoopStream) was never connected using
+    /// [`LoopStream::connect_loop`](crate::dataflow::LoopStream::connect_loop).
+    UnconnectedLoop {
+        stream_id: StreamId,
+        stream_name: String,
+        /// The operators which read from the loop stream.
+        consumer_names: Vec<String>,
+    },
+    /// The operators form a cycle which does not pass through a
+    /// [`LoopStream`](crate::dataflow::LoopStream).
+    CycleWithoutLoopStream {
+        operator_names: Vec<String>,
+        stream_names: Vec<String>,
+    },
+    /// An operator is placed on a node which does not exist.
+    InvalidNodeId {
+        operator_id: OperatorId,
+        operator_name: String,
+        node_id: NodeId,
+        num_nodes: usize,
+    },
+    /// Several operators share the same name. Operator IDs are derived from names, so the IDs
+    /// would depend on the order in which the operators are added to the graph.
+    DuplicateOperatorName {
+        operator_name: String,
+        operator_ids: Vec<OperatorId>,
+    },
+    /// Nothing reads from a stream, so the messages sent on it are dropped.
+    StreamWithoutConsumers {
+        stream_id: StreamId,
+        stream_name: String,
+        /// The operator which writes to the stream, or `"driver"` for
+        /// [`IngestStream`](crate::dataflow::stream::IngestStream)s.
+        source_name: String,
+    },
+}
+
+impl GraphProblem {
+    /// Returns `true` if the problem prevents the graph from running.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Self::StreamWithoutConsumers { .. })
+    }
+}
+
+impl fmt::Display for GraphProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DanglingStream {
+                stream_id,
+                consumer_name,
+            } => write!(
+                f,
+                "{} reads from stream {} which is not part of the graph",
+                consumer_name, stream_id
+            ),
+            Self::UnconnectedLoop {
+                stream_id,
+                stream_name,
+                consumer_names,
+            } => write!(
+                f,
+                "loop stream {} (ID: {}) read by [{}] is not connected; call `LoopStream::connect_loop` to fix",
+                stream_name,
+                stream_id,
+                consumer_names.join(", ")
+            ),
+            Self::CycleWithoutLoopStream {
+                operator_names,
+                stream_names,
+            } => write!(
+                f,
+                "operators [{}] form a cycle over streams [{}] which does not pass through a LoopStream",
+                operator_names.join(", "),
+                stream_names.join(", ")
+            ),
+            Self::InvalidNodeId {
+                operator_id,
+                operator_name,
+                node_id,
+                num_nodes,
+            } => write!(
+                f,
+                "operator {} (ID: {}) is placed on node {}, but there are only {} nodes",
+                operator_name, operator_id, node_id, num_nodes
+            ),
+            Self::DuplicateOperatorName {
+                operator_name,
+                operator_ids,
+            } => write!(
+                f,
+                "{} operators are named {} (IDs: {})",
+                operator_ids.len(),
+                operator_name,
+                operator_ids
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::StreamWithoutConsumers {
+                stream_id,
+                stream_name,
+                source_name,
+            } => write!(
+                f,
+                "stream {} (ID: {}) written by {} has no consumers",
+                stream_name, stream_id, source_name
+            ),
+        }
+    }
+}
+
+/// Error returned when a dataflow graph is not valid.
+#[derive(Clone, Debug)]
+pub struct GraphValidationError {
+    problems: Vec<GraphProblem>,
+}
+
+impl GraphValidationError {
+    pub(crate) fn new(problems: Vec<GraphProblem>) -> Self {
+        Self { problems }
+    }
+
+    /// Returns the problems that prevent the graph from running.
+    pub fn problems(&self) -> &[GraphProblem] {
+        &self.problems
+    }
+}
+
+impl fmt::Display for GraphValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the dataflow graph is not valid:")?;
+        for problem in self.problems.iter() {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GraphValidationError {}
@@ -1,5 +1,8 @@
 //! Functions and structures for building an ERDOS application.
 
+// Private submodules
+mod cancellation;
+
 // Public submodules
 pub mod connect;
 pub mod context;
@@ -8,12 +11,15 @@ pub mod graph;
 pub mod message;
 pub mod operator;
 pub mod operators;
+pub mod pipeline;
 pub mod state;
 pub mod stream;
 pub mod time;
 
 // Public exports
+pub use cancellation::CancellationToken;
 pub use deadlines::TimestampDeadline;
+pub use graph::Graph;
 pub use message::{Data, Message, TimestampedData};
 pub use operator::OperatorConfig;
 pub use state::{AppendableState, State};
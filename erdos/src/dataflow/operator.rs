@@ -1,7 +1,10 @@
 use serde::Deserialize;
 
 use crate::{
-    dataflow::{context::*, AppendableState, Data, ReadStream, State, WriteStream},
+    communication::Compression,
+    dataflow::{
+        context::*, AppendableState, CancellationToken, Data, ReadStream, State, WriteStream,
+    },
     node::NodeId,
     OperatorId,
 };
@@ -321,6 +324,9 @@ where
 #[derive(Clone)]
 pub struct OperatorConfig {
     /// A human-readable name for the [operator](self) used in logging.
+    /// The operator's ID and the IDs of its write streams are derived from the name, so naming
+    /// operators keeps their IDs stable across nodes even if the graphs are built in a
+    /// different order.
     pub name: Option<String>,
     /// A unique identifier for the [operator](self).
     /// ERDOS sets this value when the dataflow graph executes.
@@ -338,7 +344,30 @@ pub struct OperatorConfig {
     /// Defaults to `true`.
     pub flow_watermarks: bool,
     /// The ID of the node on which the operator should run. Defaults to `0`.
+    ///
+    /// Unless the operator is [pinned](OperatorConfig::pinned), the node is chosen by the
+    /// [`PlacementStrategy`](crate::dataflow::graph::PlacementStrategy) if the graph has one.
     pub node_id: NodeId,
+    /// Whether the [operator](self) must run on `node_id`. Set by [`OperatorConfig::node`].
+    /// Defaults to `false`.
+    pub pinned: bool,
+    /// An estimate of the relative cost of running the [operator](self), used by
+    /// [`PlacementStrategy`](crate::dataflow::graph::PlacementStrategy)s to balance load
+    /// across nodes. Defaults to `1.0`.
+    pub estimated_cost: f64,
+    /// The [`Compression`] of the messages the [operator](self) sends to other nodes on its
+    /// [`WriteStream`](crate::dataflow::WriteStream)s. Can be overridden for a stream with
+    /// [`Stream::set_compression`](crate::dataflow::Stream::set_compression).
+    /// Defaults to [`Compression::None`].
+    pub compression: Compression,
+    /// Whether the [operator](self)'s [`ReadStream`](crate::dataflow::ReadStream)s from
+    /// another node should be closed with a
+    /// [top watermark](crate::dataflow::Message::new_watermark) if that node fails.
+    /// Defaults to `false`.
+    pub close_streams_on_node_failure: bool,
+    /// Cancelled by ERDOS when the node shuts down. Operators which override `run` should
+    /// return soon after the token is cancelled.
+    pub cancellation_token: CancellationToken,
 }
 
 impl OperatorConfig {
@@ -348,6 +377,11 @@ impl OperatorConfig {
             name: None,
             flow_watermarks: true,
             node_id: 0,
+            pinned: false,
+            estimated_cost: 1.0,
+            compression: Compression::None,
+            close_streams_on_node_failure: false,
+            cancellation_token: CancellationToken::new(),
         }
     }
 
@@ -363,9 +397,29 @@ impl OperatorConfig {
         self
     }
 
-    /// Set the node on which the [operator](self) runs.
+    /// Set the node on which the [operator](self) runs, and pin the operator to the node.
     pub fn node(mut self, node_id: NodeId) -> Self {
         self.node_id = node_id;
+        self.pinned = true;
+        self
+    }
+
+    /// Set the estimated relative cost of running the [operator](self).
+    pub fn estimated_cost(mut self, estimated_cost: f64) -> Self {
+        self.estimated_cost = estimated_cost;
+        self
+    }
+
+    /// Set the compression of the messages the [operator](self) sends to other nodes.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Set whether the [operator](self)'s read streams should be closed if the node
+    /// sending on them fails.
+    pub fn close_streams_on_node_failure(mut self, close_streams_on_node_failure: bool) -> Self {
+        self.close_streams_on_node_failure = close_streams_on_node_failure;
         self
     }
 
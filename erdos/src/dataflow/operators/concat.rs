@@ -81,8 +81,12 @@ where
     D: Data + for<'a> Deserialize<'a>,
 {
     fn concat(&self, other: &dyn Stream<D>) -> OperatorStream<D> {
-        let name = format!("ConcatOp_{}_{}", self.name(), other.name());
-        crate::connect_two_in_one_out(
+        let name = self.graph().unique_operator_name(&format!(
+            "ConcatOp_{}_{}",
+            self.name(),
+            other.name()
+        ));
+        self.graph().connect_two_in_one_out(
             ConcatOperator::new,
             || {},
             OperatorConfig::new().name(&name),
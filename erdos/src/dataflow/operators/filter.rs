@@ -91,9 +91,11 @@ where
     where
         F: 'static + Fn(&D) -> bool + Send + Sync + Clone,
     {
-        let op_name = format!("FilterOp_{}", self.id());
+        let op_name = self
+            .graph()
+            .unique_operator_name(&format!("FilterOp_{}", self.name()));
 
-        crate::connect_one_in_one_out(
+        self.graph().connect_one_in_one_out(
             move || -> FilterOperator<D> { FilterOperator::new(filter_fn.clone()) },
             || {},
             OperatorConfig::new().name(&op_name),
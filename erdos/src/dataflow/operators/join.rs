@@ -134,8 +134,12 @@ where
     /// let joined_stream = left_stream.timestamp_join(&right_stream);
     /// ```
     fn timestamp_join(&self, other: &dyn Stream<U>) -> OperatorStream<(T, U)> {
-        let name = format!("TimestampJoinOp_{}_{}", self.name(), other.name());
-        crate::connect_two_in_one_out(
+        let name = self.graph().unique_operator_name(&format!(
+            "TimestampJoinOp_{}_{}",
+            self.name(),
+            other.name()
+        ));
+        self.graph().connect_two_in_one_out(
             TimestampJoinOperator::new,
             TimeVersionedState::new,
             OperatorConfig::new().name(&name),
@@ -123,9 +123,11 @@ where
     where
         F: 'static + Fn(&D1) -> D2 + Send + Sync + Clone,
     {
-        let op_name = format!("MapOp_{}", self.id());
+        let op_name = self
+            .graph()
+            .unique_operator_name(&format!("MapOp_{}", self.name()));
 
-        crate::connect_one_in_one_out(
+        self.graph().connect_one_in_one_out(
             move || -> FlatMapOperator<D1, _> {
                 let map_fn = map_fn.clone();
                 FlatMapOperator::new(move |x| std::iter::once(map_fn(x)))
@@ -141,9 +143,11 @@ where
         F: 'static + Fn(&D1) -> I + Send + Sync + Clone,
         I: 'static + IntoIterator<Item = D2>,
     {
-        let op_name = format!("FlatMapOp_{}", self.id());
+        let op_name = self
+            .graph()
+            .unique_operator_name(&format!("FlatMapOp_{}", self.name()));
 
-        crate::connect_one_in_one_out(
+        self.graph().connect_one_in_one_out(
             move || -> FlatMapOperator<D1, _> { FlatMapOperator::new(flat_map_fn.clone()) },
             || {},
             OperatorConfig::new().name(&op_name),
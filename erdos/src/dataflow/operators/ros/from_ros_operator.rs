@@ -5,7 +5,10 @@ use crate::dataflow::{
     Data, Message,
 };
 use serde::Deserialize;
-use std::sync::{Arc, Mutex};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 /// Subscribes to a ROS topic and outputs incoming messages to an ERDOS stream using the
 /// provided message conversion function.
@@ -113,6 +116,11 @@ where
             })
             .unwrap();
 
-        rosrust::spin();
+        // Receive messages until ROS or the node shuts down.
+        while rosrust::is_ok()
+            && !config
+                .cancellation_token
+                .wait_timeout(Duration::from_millis(100))
+        {}
     }
 }
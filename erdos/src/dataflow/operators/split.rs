@@ -101,9 +101,11 @@ where
     where
         F: 'static + Fn(&D1) -> bool + Send + Sync + Clone,
     {
-        let op_name = format!("SplitOp_{}", self.id());
+        let op_name = self
+            .graph()
+            .unique_operator_name(&format!("SplitOp_{}", self.name()));
 
-        crate::connect_one_in_two_out(
+        self.graph().connect_one_in_two_out(
             move || -> SplitOperator<D1> { SplitOperator::new(split_fn.clone()) },
             || {},
             OperatorConfig::new().name(&op_name),
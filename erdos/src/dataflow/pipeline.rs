@@ -0,0 +1,392 @@
+//! Declarative pipelines, which describe a dataflow graph in a file instead of in the driver.
+//!
+//! Operators are made available to pipelines by registering a constructor for each operator type
+//! in an [`OperatorRegistry`]. A [`Pipeline`] lists the operators to instantiate along with
+//! their types, names, placement, parameters, and the streams from which they read. Changing
+//! the topology or the placement of a pipeline does not require recompiling the application.
+//!
+//! An operator reads from the first stream written by another operator by naming that operator
+//! in its `inputs`. Further write streams are referenced as `name:index` (e.g. `splitter:1`).
+//!
+//! # Example
+//! ```no_run
+//! # use erdos::dataflow::{
+//! #     operators::FlatMapOperator,
+//! #     pipeline::{OperatorRegistry, Pipeline, PipelineStream},
+//! # };
+//! # use erdos::{node::Node, Configuration};
+//! #
+//! let mut registry = OperatorRegistry::new();
+//! // The source operator "Camera" is registered similarly.
+//! registry.register("Scale", |graph, config, parameters, inputs| {
+//!     let factor = parameters["factor"].as_u64().unwrap_or(1) as usize;
+//!     let output = graph.connect_one_in_one_out(
+//!         move || -> FlatMapOperator<usize, _> {
+//!             FlatMapOperator::new(move |x: &usize| vec![factor * x])
+//!         },
+//!         || {},
+//!         config,
+//!         inputs.get::<usize>(0)?,
+//!     );
+//!     Ok(vec![PipelineStream::new(output)])
+//! });
+//!
+//! let pipeline = Pipeline::from_yaml(
+//!     r#"
+//! operators:
+//!   - name: camera
+//!     type: Camera
+//!   - name: scale
+//!     type: Scale
+//!     node: 1
+//!     inputs: [camera]
+//!     parameters:
+//!       factor: 3
+//! "#,
+//! )
+//! .unwrap();
+//!
+//! let args = erdos::new_app("ERDOS").get_matches();
+//! let node = Node::from_pipeline(Configuration::from_args(&args), &pipeline, &registry).unwrap();
+//! node.run_async();
+//! ```
+use std::{
+    any::{type_name, Any},
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    communication::Compression,
+    dataflow::{stream::OperatorStream, Data, Graph, OperatorConfig},
+    node::NodeId,
+};
+
+/// A trait that defines the functions which add an operator of a registered type to a graph.
+///
+/// The function receives the graph, the operator's configuration, the parameters listed in the
+/// [`Pipeline`], and the streams from which the operator reads. It returns the streams to which
+/// the operator writes.
+pub trait OperatorConstructor:
+    Fn(&Graph, OperatorConfig, &Value, &OperatorInputs) -> Result<Vec<PipelineStream>, String>
+    + Send
+    + Sync
+{
+}
+impl<
+        F: Fn(
+                &Graph,
+                OperatorConfig,
+                &Value,
+                &OperatorInputs,
+            ) -> Result<Vec<PipelineStream>, String>
+            + Send
+            + Sync,
+    > OperatorConstructor for F
+{
+}
+
+/// Maps operator types used in [`Pipeline`]s to the functions which construct the operators.
+#[derive(Default)]
+pub struct OperatorRegistry {
+    constructors: HashMap<String, Box<dyn OperatorConstructor>>,
+}
+
+impl OperatorRegistry {
+    pub fn new() -> Self {
+        Self {
+            constructors: HashMap::new(),
+        }
+    }
+
+    /// Registers the constructor for operators of type `operator_type`, replacing any
+    /// constructor previously registered for the type.
+    pub fn register(
+        &mut self,
+        operator_type: &str,
+        constructor: impl Fn(
+                &Graph,
+                OperatorConfig,
+                &Value,
+                &OperatorInputs,
+            ) -> Result<Vec<PipelineStream>, String>
+            + Send
+            + Sync
+            + 'static,
+    ) -> &mut Self {
+        self.constructors
+            .insert(operator_type.to_string(), Box::new(constructor));
+        self
+    }
+
+    /// Returns `true` if a constructor is registered for `operator_type`.
+    pub fn contains(&self, operator_type: &str) -> bool {
+        self.constructors.contains_key(operator_type)
+    }
+}
+
+/// A stream written by an operator in a [`Pipeline`].
+///
+/// The type of data sent on the stream is checked when the stream is passed to another operator.
+#[derive(Clone)]
+pub struct PipelineStream {
+    data_type: &'static str,
+    stream: Arc<dyn Any + Send + Sync>,
+}
+
+impl PipelineStream {
+    pub fn new<D>(stream: OperatorStream<D>) -> Self
+    where
+        for<'a> D: Data + Deserialize<'a>,
+    {
+        Self {
+            data_type: type_name::<D>(),
+            stream: Arc::new(stream),
+        }
+    }
+
+    /// Returns the name of the type of data sent on the stream.
+    pub fn data_type(&self) -> &'static str {
+        self.data_type
+    }
+
+    /// Returns the stream if it carries data of type `D`.
+    pub fn downcast<D>(&self) -> Result<&OperatorStream<D>, String>
+    where
+        for<'a> D: Data + Deserialize<'a>,
+    {
+        self.stream.downcast_ref().ok_or_else(|| {
+            format!(
+                "expected a stream of {}, but found a stream of {}",
+                type_name::<D>(),
+                self.data_type
+            )
+        })
+    }
+}
+
+/// The streams from which an operator in a [`Pipeline`] reads, in the order listed in the
+/// operator's `inputs`.
+pub struct OperatorInputs {
+    streams: Vec<PipelineStream>,
+}
+
+impl OperatorInputs {
+    /// Returns the `index`-th input stream if it carries data of type `D`.
+    pub fn get<D>(&self, index: usize) -> Result<&OperatorStream<D>, String>
+    where
+        for<'a> D: Data + Deserialize<'a>,
+    {
+        self.streams
+            .get(index)
+            .ok_or_else(|| {
+                format!(
+                    "expected at least {} inputs, but found {}",
+                    index + 1,
+                    self.streams.len()
+                )
+            })?
+            .downcast()
+            .map_err(|e| format!("input {}: {}", index, e))
+    }
+
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+}
+
+/// A dataflow graph described declaratively, e.g. in a JSON or YAML file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub operators: Vec<PipelineOperator>,
+}
+
+/// An operator in a [`Pipeline`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PipelineOperator {
+    /// The unique name of the operator.
+    pub name: String,
+    /// The type under which the operator's constructor is registered in the
+    /// [`OperatorRegistry`].
+    #[serde(rename = "type")]
+    pub operator_type: String,
+    /// The node to which the operator is pinned. If omitted, the operator is placed by the
+    /// [`PlacementStrategy`](crate::dataflow::graph::PlacementStrategy) set in the
+    /// [`Configuration`](crate::Configuration), or runs on node `0`.
+    #[serde(default)]
+    pub node: Option<NodeId>,
+    /// The streams from which the operator reads, referenced as `name` for the first stream
+    /// written by the operator `name`, or as `name:index`.
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    /// Parameters passed to the operator's constructor.
+    #[serde(default)]
+    pub parameters: Value,
+    /// See [`OperatorConfig::flow_watermarks`]. Defaults to `true`.
+    #[serde(default = "default_flow_watermarks")]
+    pub flow_watermarks: bool,
+    /// See [`OperatorConfig::close_streams_on_node_failure`]. Defaults to `false`.
+    #[serde(default)]
+    pub close_streams_on_node_failure: bool,
+    /// See [`OperatorConfig::estimated_cost`]. Defaults to `1.0`.
+    #[serde(default = "default_estimated_cost")]
+    pub estimated_cost: f64,
+    /// See [`OperatorConfig::compression`], e.g. `lz4` or `{"zstd": 3}`. Defaults to `none`.
+    #[serde(default)]
+    pub compression: Compression,
+}
+
+fn default_flow_watermarks() -> bool {
+    true
+}
+
+fn default_estimated_cost() -> f64 {
+    1.0
+}
+
+impl Pipeline {
+    /// Parses a pipeline from JSON.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Error parsing pipeline: {}", e))
+    }
+
+    /// Parses a pipeline from YAML.
+    pub fn from_yaml(yaml: &str) -> Result<Self, String> {
+        serde_yaml::from_str(yaml).map_err(|e| format!("Error parsing pipeline: {}", e))
+    }
+
+    /// Reads a pipeline from a JSON (*.json) or YAML (*.yaml, *.yml) file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading pipeline {}: {}", path.display(), e))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json(&contents),
+            Some("yaml") | Some("yml") => Self::from_yaml(&contents),
+            _ => Err(format!(
+                "Error reading pipeline {}: expected a .json, .yaml, or .yml file",
+                path.display()
+            )),
+        }
+    }
+
+    /// Builds a [`Graph`] containing the operators in the pipeline.
+    ///
+    /// Operators may be listed in any order, but may not form cycles.
+    pub fn build(&self, registry: &OperatorRegistry) -> Result<Graph, String> {
+        let mut names = HashSet::new();
+        for operator in self.operators.iter() {
+            if !names.insert(operator.name.as_str()) {
+                return Err(format!(
+                    "Pipeline has several operators named {}",
+                    operator.name
+                ));
+            }
+        }
+
+        let graph = Graph::new();
+        // Streams written by the operators which were added to the graph.
+        let mut write_streams: HashMap<&str, Vec<PipelineStream>> = HashMap::new();
+        let mut pending: Vec<_> = self.operators.iter().collect();
+        while !pending.is_empty() {
+            let mut remaining = Vec::new();
+            for &operator in pending.iter() {
+                match Self::resolve_inputs(operator, &names, &write_streams)? {
+                    Some(streams) => {
+                        let streams = Self::add_operator(&graph, registry, operator, streams)
+                            .map_err(|e| {
+                                format!("Error adding operator {}: {}", operator.name, e)
+                            })?;
+                        write_streams.insert(&operator.name, streams);
+                    }
+                    None => remaining.push(operator),
+                }
+            }
+            if remaining.len() == pending.len() {
+                let cycle: Vec<_> = remaining.iter().map(|o| o.name.as_str()).collect();
+                return Err(format!(
+                    "Pipeline operators [{}] form a cycle",
+                    cycle.join(", ")
+                ));
+            }
+            pending = remaining;
+        }
+        Ok(graph)
+    }
+
+    /// Returns the operator's input streams, or `None` if an operator which writes to one of the
+    /// input streams was not added to the graph yet.
+    fn resolve_inputs(
+        operator: &PipelineOperator,
+        names: &HashSet<&str>,
+        write_streams: &HashMap<&str, Vec<PipelineStream>>,
+    ) -> Result<Option<Vec<PipelineStream>>, String> {
+        let mut streams = Vec::with_capacity(operator.inputs.len());
+        for input in operator.inputs.iter() {
+            let (name, index) = match input.rsplit_once(':') {
+                Some((name, index)) => {
+                    let index = index.parse().map_err(|_| {
+                        format!("Operator {}: invalid input {}", operator.name, input)
+                    })?;
+                    (name, index)
+                }
+                None => (input.as_str(), 0),
+            };
+            if !names.contains(name) {
+                return Err(format!(
+                    "Operator {}: input {} refers to an unknown operator",
+                    operator.name, input
+                ));
+            }
+            match write_streams.get(name) {
+                Some(outputs) => streams.push(outputs.get(index).cloned().ok_or_else(|| {
+                    format!(
+                        "Operator {}: input {} refers to a missing stream, as {} writes {} streams",
+                        operator.name,
+                        input,
+                        name,
+                        outputs.len()
+                    )
+                })?),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(streams))
+    }
+
+    fn add_operator(
+        graph: &Graph,
+        registry: &OperatorRegistry,
+        operator: &PipelineOperator,
+        streams: Vec<PipelineStream>,
+    ) -> Result<Vec<PipelineStream>, String> {
+        let constructor = registry
+            .constructors
+            .get(&operator.operator_type)
+            .ok_or_else(|| format!("unknown operator type {}", operator.operator_type))?;
+        let mut config = OperatorConfig::new()
+            .name(&operator.name)
+            .flow_watermarks(operator.flow_watermarks)
+            .close_streams_on_node_failure(operator.close_streams_on_node_failure)
+            .estimated_cost(operator.estimated_cost)
+            .compression(operator.compression);
+        if let Some(node_id) = operator.node {
+            config = config.node(node_id);
+        }
+        (constructor)(
+            graph,
+            config,
+            &operator.parameters,
+            &OperatorInputs { streams },
+        )
+    }
+}
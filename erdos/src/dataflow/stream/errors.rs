@@ -41,6 +41,8 @@ impl From<TryRecvError> for TryReadError {
 pub enum SendError {
     /// Message serialization failed.
     SerializationError,
+    /// Message compression failed.
+    CompressionError,
     /// There was a network or a `mpsc::channel` error.
     IOError,
     /// Timestamp or watermark is smaller or equal to the low watermark.
@@ -70,6 +72,10 @@ impl From<CommunicationError> for SendError {
                 eprintln!("Got write stream IOError {}", io_error);
                 SendError::IOError
             }
+            CommunicationError::CompressionError(error) => {
+                tracing::error!("Compression error {}", error);
+                SendError::CompressionError
+            }
         }
     }
 }
@@ -8,7 +8,7 @@ use serde::Deserialize;
 
 use crate::{
     dataflow::{
-        graph::{default_graph, AbstractGraph},
+        graph::{AbstractGraph, Graph},
         Data, Message,
     },
     scheduler::channel_manager::ChannelManager,
@@ -72,6 +72,8 @@ where
 {
     /// The unique ID of the stream (automatically generated by the constructor)
     id: StreamId,
+    /// The graph to which the stream belongs.
+    graph: Graph,
     /// The ReadStream associated with the ExtractStream.
     read_stream_option: Arc<Mutex<Option<ReadStream<D>>>>,
 }
@@ -97,6 +99,7 @@ where
         // Create the ExtractStream structure.
         let extract_stream = Self {
             id,
+            graph: stream.graph(),
             read_stream_option: Arc::new(Mutex::new(None)),
         };
 
@@ -112,7 +115,10 @@ where
             }
         };
 
-        default_graph::add_extract_stream(&extract_stream, hook);
+        extract_stream
+            .graph
+            .lock()
+            .add_extract_stream(&extract_stream, hook);
         extract_stream
     }
 
@@ -159,7 +165,7 @@ where
     }
 
     pub fn name(&self) -> String {
-        default_graph::get_stream_name(&self.id)
+        self.graph.get_stream_name(&self.id)
     }
 }
 
@@ -8,8 +8,8 @@ use serde::Deserialize;
 
 use crate::{
     dataflow::{
-        graph::{default_graph, AbstractGraph},
-        Data, Message,
+        graph::{default_graph, AbstractGraph, Graph},
+        Data, Message, Timestamp,
     },
     scheduler::channel_manager::ChannelManager,
 };
@@ -69,6 +69,8 @@ where
 {
     // The unique ID of the stream (automatically generated by the constructor)
     id: StreamId,
+    // The graph to which the stream belongs.
+    graph: Graph,
     // Use a std mutex because the driver doesn't run on the tokio runtime.
     write_stream_option: Arc<Mutex<Option<WriteStream<D>>>>,
 }
@@ -79,10 +81,18 @@ where
 {
     /// Returns a new instance of the [`IngestStream`].
     pub fn new() -> Self {
+        Self::new_in_graph(&default_graph::get())
+    }
+
+    /// Returns a new instance of the [`IngestStream`] in the given graph.
+    pub(crate) fn new_in_graph(graph: &Graph) -> Self {
         tracing::debug!("Initializing an IngestStream");
-        let id = StreamId::new_deterministic();
+        let id = graph.structural_id("stream", "ingest-stream", |graph, id| {
+            graph.contains_stream(id)
+        });
         let ingest_stream = Self {
             id,
+            graph: graph.clone(),
             write_stream_option: Arc::new(Mutex::new(None)),
         };
 
@@ -98,13 +108,16 @@ where
                         .lock()
                         .unwrap()
                         .replace(write_stream);
+                    // Allow the node to wait for the driver to close the stream.
+                    channel_manager
+                        .add_ingest_stream(Box::new(Arc::clone(&write_stream_option_copy)));
                 }
                 Err(msg) => panic!("Unable to set up IngestStream {}: {}", id, msg),
             }
         };
 
-        default_graph::add_ingest_stream(&ingest_stream, setup_hook);
-        default_graph::set_stream_name(&id, &format!("ingest_stream_{}", id));
+        graph.lock().add_ingest_stream(&ingest_stream, setup_hook);
+        graph.set_stream_name(&id, &graph.scoped_name(&format!("ingest_stream_{}", id)));
 
         ingest_stream
     }
@@ -138,7 +151,7 @@ where
         } else {
             tracing::warn!(
                 "Trying to send messages on a closed IngestStream {} (ID: {})",
-                default_graph::get_stream_name(&self.id()),
+                self.name(),
                 self.id(),
             );
             Err(SendError::Closed)
@@ -162,6 +175,10 @@ where
     fn id(&self) -> StreamId {
         self.id
     }
+
+    fn graph(&self) -> Graph {
+        self.graph.clone()
+    }
 }
 
 impl<D> WriteStreamT<D> for IngestStream<D>
@@ -173,3 +190,38 @@ where
         self.send(msg)
     }
 }
+
+/// Allows a [`Node`](crate::node::Node) to check whether the driver closed an [`IngestStream`],
+/// and to close the stream when draining the dataflow.
+pub(crate) trait IngestStreamT: Send {
+    /// Returns `true` if a top watermark was sent on the stream.
+    fn is_closed(&self) -> bool;
+
+    /// Sends a top watermark on the stream if it is still open.
+    fn close(&mut self) -> Result<(), SendError>;
+}
+
+// Implemented over the write stream shared with the driver's [`IngestStream`], because the
+// node must not hold handles to the graph it executes.
+impl<D> IngestStreamT for Arc<Mutex<Option<WriteStream<D>>>>
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    fn is_closed(&self) -> bool {
+        self.lock()
+            .unwrap()
+            .as_ref()
+            .map(WriteStream::is_closed)
+            .unwrap_or(true)
+    }
+
+    fn close(&mut self) -> Result<(), SendError> {
+        match self.lock().unwrap().as_mut() {
+            Some(write_stream) if !write_stream.is_closed() => {
+                write_stream.send(Message::new_watermark(Timestamp::Top))
+            }
+            // The driver closed the stream, or the stream failed to set up.
+            _ => Ok(()),
+        }
+    }
+}
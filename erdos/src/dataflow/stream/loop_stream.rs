@@ -2,7 +2,10 @@ use std::marker::PhantomData;
 
 use serde::Deserialize;
 
-use crate::dataflow::{graph::default_graph, Data};
+use crate::dataflow::{
+    graph::{default_graph, Graph},
+    Data,
+};
 
 use super::{OperatorStream, Stream, StreamId};
 
@@ -26,6 +29,8 @@ where
     for<'a> D: Data + Deserialize<'a>,
 {
     id: StreamId,
+    /// The graph to which the stream belongs.
+    graph: Graph,
     phantom: PhantomData<D>,
 }
 
@@ -34,17 +39,27 @@ where
     for<'a> D: Data + Deserialize<'a>,
 {
     pub fn new() -> Self {
-        let id = StreamId::new_deterministic();
+        Self::new_in_graph(&default_graph::get())
+    }
+
+    /// Creates a new [`LoopStream`] in the given graph.
+    pub(crate) fn new_in_graph(graph: &Graph) -> Self {
+        let id = graph.structural_id("stream", "loop-stream", |graph, id| {
+            graph.contains_stream(id)
+        });
         let loop_stream = Self {
             id,
+            graph: graph.clone(),
             phantom: PhantomData,
         };
-        default_graph::add_loop_stream(&loop_stream);
+        graph.lock().add_loop_stream(&loop_stream);
+        let name = graph.get_stream_name(&id);
+        graph.set_stream_name(&id, &graph.scoped_name(&name));
         loop_stream
     }
 
     pub fn connect_loop(&self, stream: &OperatorStream<D>) {
-        default_graph::connect_loop(self, stream);
+        self.graph.lock().connect_loop(self, stream);
     }
 }
 
@@ -64,4 +79,8 @@ where
     fn id(&self) -> StreamId {
         self.id
     }
+
+    fn graph(&self) -> Graph {
+        self.graph.clone()
+    }
 }
@@ -17,10 +17,14 @@
 //! using zero-copy communication for operators on the same node.
 //! Messages sent across nodes are serialized using
 //! [abomonation](https://github.com/TimelyDataflow/abomonation) if possible,
-//! before falling back to [bincode](https://github.com/servo/bincode).
+//! before falling back to [bincode](https://github.com/servo/bincode), and may be
+//! [compressed](crate::communication::Compression).
 use std::marker::PhantomData;
 
-use crate::dataflow::{Data, Message};
+use crate::{
+    communication::{Compression, Delivery, Priority},
+    dataflow::{Data, Message},
+};
 
 // Private submodules
 mod extract_stream;
@@ -43,7 +47,10 @@ pub use loop_stream::LoopStream;
 pub use read_stream::ReadStream;
 pub use write_stream::WriteStream;
 
-use super::graph::default_graph;
+// Crate-wide exports
+pub(crate) use ingest_stream::IngestStreamT;
+
+use super::graph::{default_graph, Graph};
 
 pub type StreamId = crate::Uuid;
 
@@ -56,29 +63,81 @@ pub trait WriteStreamT<D: Data> {
 
 pub trait Stream<D: Data> {
     fn name(&self) -> String {
-        default_graph::get_stream_name(&self.id())
+        self.graph().get_stream_name(&self.id())
     }
     fn set_name(&mut self, name: &str) {
-        default_graph::set_stream_name(&self.id(), name);
+        self.graph().set_stream_name(&self.id(), name);
+    }
+    /// Sets the version of the schema of the data sent on the stream (defaults to 0).
+    ///
+    /// Nodes check that each stream which crosses process boundaries carries the same data type
+    /// and schema version on both ends. Bump the version when changing the serialized layout of
+    /// the data type, so that nodes running outdated binaries fail to start instead of
+    /// misinterpreting messages.
+    fn set_schema_version(&mut self, version: u32) {
+        self.graph().set_stream_schema_version(&self.id(), version);
+    }
+    /// Sets the compression of the messages sent on the stream to other nodes, overriding the
+    /// [`OperatorConfig::compression`](crate::dataflow::OperatorConfig::compression) of the
+    /// operator writing to the stream.
+    fn set_compression(&mut self, compression: Compression) {
+        self.graph().set_stream_compression(&self.id(), compression);
+    }
+    /// Sets whether messages sent on the stream to other nodes are flushed immediately
+    /// (defaults to `false`).
+    ///
+    /// Otherwise, messages are batched with other pending messages to the same node, as
+    /// configured by [`Configuration::with_max_batch_size`](crate::Configuration::with_max_batch_size)
+    /// and [`Configuration::with_max_batch_linger`](crate::Configuration::with_max_batch_linger).
+    /// Enable this for latency-critical streams, such as control commands.
+    fn set_low_latency(&mut self, low_latency: bool) {
+        self.graph().set_stream_low_latency(&self.id(), low_latency);
+    }
+    /// Sets the priority with which messages on the stream are sent to other nodes
+    /// (defaults to [`Priority::Normal`]).
+    ///
+    /// Pending messages of higher priority are sent first, so that, e.g., a stream of control
+    /// commands set to [`Priority::High`] does not wait for the images pending on a stream set
+    /// to [`Priority::Bulk`].
+    fn set_priority(&mut self, priority: Priority) {
+        self.graph().set_stream_priority(&self.id(), priority);
+    }
+    /// Sets how messages on the stream are delivered to other nodes
+    /// (defaults to [`Delivery::Reliable`]).
+    ///
+    /// [`Delivery::BestEffort`] and [`Delivery::Multicast`] send messages as UDP datagrams,
+    /// which avoids the latency spikes of TCP retransmissions for high-rate sensor data where
+    /// the newest message matters most. Messages may then be lost, which is reported in the
+    /// [`StreamStatistics`](crate::communication::StreamStatistics) of the receiving node.
+    /// The stream's compression and priority do not apply to datagrams.
+    fn set_delivery(&mut self, delivery: Delivery) {
+        self.graph().set_stream_delivery(&self.id(), delivery);
     }
     fn id(&self) -> StreamId;
+    /// Returns the [`Graph`] to which the stream belongs.
+    ///
+    /// Defaults to the graph used by the free [connect functions](crate::dataflow::connect).
+    fn graph(&self) -> Graph {
+        default_graph::get()
+    }
 }
 
 #[derive(Clone)]
 pub struct OperatorStream<D: Data> {
     /// The unique ID of the stream (automatically generated by the constructor)
     id: StreamId,
+    /// The graph to which the stream belongs.
+    graph: Graph,
     phantom: PhantomData<D>,
 }
 
 #[allow(dead_code)]
 impl<D: Data> OperatorStream<D> {
-    /// Creates a new stream.
-    pub(crate) fn new() -> Self {
-        let id = StreamId::new_deterministic();
-
+    /// Creates a new stream with the given ID in the given graph.
+    pub(crate) fn new(graph: &Graph, id: StreamId) -> Self {
         Self {
             id,
+            graph: graph.clone(),
             phantom: PhantomData,
         }
     }
@@ -88,4 +147,8 @@ impl<D: Data> Stream<D> for OperatorStream<D> {
     fn id(&self) -> StreamId {
         self.id
     }
+
+    fn graph(&self) -> Graph {
+        self.graph.clone()
+    }
 }
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::SystemTime};
 
 use crate::{
     communication::{RecvEndpoint, TryRecvError},
@@ -123,7 +123,11 @@ impl<D: Data> ReadStream<D> {
         result
     }
 
-    pub(crate) async fn async_read(&mut self) -> Result<Arc<Message<D>>, ReadError> {
+    /// Reads the next message, along with the time at which another node sent it on the clock
+    /// of this node if the message was received from another node.
+    pub(crate) async fn async_read(
+        &mut self,
+    ) -> Result<(Arc<Message<D>>, Option<SystemTime>), ReadError> {
         if self.is_closed {
             return Err(ReadError::Closed);
         }
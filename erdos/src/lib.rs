@@ -153,16 +153,20 @@ pub mod dataflow;
 pub mod node;
 #[doc(hidden)]
 pub mod scheduler;
+pub mod testing;
 
 // Public exports
 pub use configuration::Configuration;
-pub use dataflow::{connect::*, OperatorConfig};
+pub use dataflow::{connect::*, Graph, OperatorConfig};
 
 /// A unique identifier for an operator.
 pub type OperatorId = Uuid;
 
+/// Seed of the random number generators used to produce deterministic IDs.
+pub(crate) const RNG_SEED: &[usize] = &[1913, 3, 26];
+
 // Random number generator which should be the same accross threads and processes.
-thread_local!(static RNG: RefCell<StdRng>= RefCell::new(StdRng::from_seed(&[1913, 3, 26])));
+thread_local!(static RNG: RefCell<StdRng>= RefCell::new(StdRng::from_seed(RNG_SEED)));
 
 /// Produces a deterministic, unique ID.
 pub fn generate_id() -> Uuid {
@@ -191,6 +195,14 @@ impl Uuid {
     pub fn nil() -> Uuid {
         Uuid([0; 16])
     }
+
+    /// Derives an ID from a name within a namespace (UUID version 5).
+    ///
+    /// The same name and namespace always produce the same ID.
+    pub fn new_v5(namespace: Uuid, name: &str) -> Self {
+        let namespace = uuid::Uuid::from_bytes(namespace.0);
+        Self(*uuid::Uuid::new_v5(&namespace, name.as_bytes()).as_bytes())
+    }
 }
 
 impl fmt::Debug for Uuid {
@@ -213,7 +225,7 @@ impl fmt::Display for Uuid {
 pub fn reset() {
     // All global variables should be reset here.
     RNG.with(|rng| {
-        *rng.borrow_mut() = StdRng::from_seed(&[1913, 3, 26]);
+        *rng.borrow_mut() = StdRng::from_seed(RNG_SEED);
     });
     dataflow::graph::default_graph::set(dataflow::graph::AbstractGraph::new());
 }
@@ -249,12 +261,42 @@ pub fn new_app(name: &str) -> clap::App {
                 .default_value("0")
                 .help("Current node index"),
         )
+        .arg(
+            Arg::with_name("coordinator")
+                .long("coordinator")
+                .takes_value(true)
+                .requires("num-nodes")
+                .help(
+                    "Address of the coordinator from which the node learns the addresses of the \
+                    other nodes. Node 0 is the coordinator. If set, the data and control \
+                    addresses are only the addresses of the current node",
+                ),
+        )
+        .arg(
+            Arg::with_name("num-nodes")
+                .long("num-nodes")
+                .takes_value(true)
+                .help("Number of nodes which join through the coordinator"),
+        )
+        .arg(
+            Arg::with_name("min-nodes")
+                .long("min-nodes")
+                .takes_value(true)
+                .requires("coordinator")
+                .help(
+                    "Number of nodes which must join the coordinator before the dataflow \
+                    starts. The other nodes join the running dataflow. Defaults to all nodes",
+                ),
+        )
         .arg(
             Arg::with_name("graph-filename")
                 .short("g")
                 .long("graph-filename")
                 .default_value("")
-                .help("Exports the dataflow graph as a DOT file to the provided filename"),
+                .help(
+                    "Exports the dataflow graph to the provided filename as JSON (*.json), \
+                    YAML (*.yaml, *.yml), or DOT",
+                ),
         )
         .arg(
             Arg::with_name("verbose")
@@ -0,0 +1,336 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use tokio::sync::Notify;
+
+/// The source of time for deadlines on a [`Node`](crate::node::Node).
+///
+/// By default, nodes use the [`Clock::System`] clock. Tests which exercise
+/// [deadlines](crate::dataflow::deadlines) can instead run a node on a [`VirtualClock`] which
+/// only advances when [`VirtualClock::advance`] is called, so that deadline handlers are invoked
+/// at exactly the same points in every run.
+#[derive(Clone, Debug)]
+pub enum Clock {
+    /// Time is read from the operating system.
+    System,
+    /// Time is simulated, and advances only when requested.
+    Virtual(VirtualClock),
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::System
+    }
+}
+
+impl Clock {
+    /// Returns the current time.
+    pub fn now(&self) -> Instant {
+        match self {
+            Self::System => Instant::now(),
+            Self::Virtual(clock) => clock.now(),
+        }
+    }
+
+    /// Returns the current wall-clock time, e.g. to timestamp messages sent to other nodes.
+    pub fn system_time(&self) -> SystemTime {
+        match self {
+            Self::System => SystemTime::now(),
+            Self::Virtual(clock) => clock.system_time(),
+        }
+    }
+
+    /// Completes once the clock reaches `deadline`.
+    pub async fn sleep_until(&self, deadline: Instant) {
+        match self {
+            Self::System => tokio::time::sleep_until(deadline.into()).await,
+            Self::Virtual(clock) => clock.sleep_until(deadline).await,
+        }
+    }
+
+    /// Completes once `duration` elapses on the clock.
+    pub async fn sleep(&self, duration: Duration) {
+        self.sleep_until(self.now() + duration).await
+    }
+
+    /// Runs `future` until it completes or `duration` elapses on the clock.
+    ///
+    /// Returns `None` if the timeout elapsed.
+    pub async fn timeout<F: Future>(&self, duration: Duration, future: F) -> Option<F::Output> {
+        self.timeout_at(self.now() + duration, future).await
+    }
+
+    /// Runs `future` until it completes or the clock reaches `deadline`.
+    ///
+    /// Returns `None` if the deadline was reached.
+    pub async fn timeout_at<F: Future>(&self, deadline: Instant, future: F) -> Option<F::Output> {
+        tokio::select! {
+            output = future => Some(output),
+            _ = self.sleep_until(deadline) => None,
+        }
+    }
+
+    /// Returns an [`Interval`] which ticks every `period`, starting immediately.
+    pub(crate) fn interval(&self, period: Duration) -> Interval {
+        Interval {
+            clock: self.clone(),
+            next_tick: self.now(),
+            period,
+        }
+    }
+}
+
+/// A simulated clock which advances only when [`VirtualClock::advance`] is called.
+///
+/// Clones of a [`VirtualClock`] share the same time, so a test can keep a clone to drive the
+/// clock of a running node.
+///
+/// # Example
+/// ```no_run
+/// # use std::time::Duration;
+/// # use erdos::{node::{Node, VirtualClock}, Configuration};
+/// #
+/// let clock = VirtualClock::new();
+/// let config = Configuration::new(
+///     0,
+///     vec!["127.0.0.1:9000".parse().unwrap()],
+///     vec!["127.0.0.1:9001".parse().unwrap()],
+///     4,
+/// )
+/// .with_virtual_clock(clock.clone());
+/// let handle = Node::new(config).run_async();
+/// // Fire every deadline that expires within the next 100 ms.
+/// clock.advance(Duration::from_millis(100));
+/// ```
+#[derive(Clone)]
+pub struct VirtualClock {
+    inner: Arc<VirtualClockInner>,
+}
+
+struct VirtualClockInner {
+    /// The time at which the clock was created.
+    start: Instant,
+    /// The wall-clock time at which the clock was created.
+    start_time: SystemTime,
+    /// Time elapsed on the clock since `start`.
+    elapsed: Mutex<Duration>,
+    /// Wakes up tasks sleeping on the clock.
+    notify: Notify,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(VirtualClockInner {
+                start: Instant::now(),
+                start_time: SystemTime::now(),
+                elapsed: Mutex::new(Duration::from_secs(0)),
+                notify: Notify::new(),
+            }),
+        }
+    }
+
+    /// Returns the current time on the clock.
+    pub fn now(&self) -> Instant {
+        self.inner.start + self.elapsed()
+    }
+
+    /// Returns the current wall-clock time on the clock.
+    pub fn system_time(&self) -> SystemTime {
+        self.inner.start_time + self.elapsed()
+    }
+
+    /// Returns the time that elapsed on the clock since it was created.
+    pub fn elapsed(&self) -> Duration {
+        *self.inner.elapsed.lock().unwrap()
+    }
+
+    /// Advances the clock, and wakes up all tasks sleeping until a time which was reached.
+    pub fn advance(&self, duration: Duration) {
+        *self.inner.elapsed.lock().unwrap() += duration;
+        self.inner.notify.notify_waiters();
+    }
+
+    async fn sleep_until(&self, deadline: Instant) {
+        loop {
+            let notified = self.inner.notify.notified();
+            if self.now() >= deadline {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for VirtualClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "VirtualClock {{ elapsed: {:?} }}", self.elapsed())
+    }
+}
+
+/// Ticks with a fixed period on a [`Clock`].
+pub(crate) struct Interval {
+    clock: Clock,
+    next_tick: Instant,
+    period: Duration,
+}
+
+impl Interval {
+    /// Completes at the next tick. Ticks which were missed complete immediately.
+    pub(crate) async fn tick(&mut self) {
+        self.clock.sleep_until(self.next_tick).await;
+        self.next_tick += self.period;
+    }
+}
+
+/// Yields items once their delay elapses on a [`Clock`].
+pub(crate) struct DelayQueue<T> {
+    clock: Clock,
+    /// Queued items along with the time at which they expire, in order of insertion.
+    items: Vec<(Instant, T)>,
+}
+
+impl<T> DelayQueue<T> {
+    pub(crate) fn new(clock: Clock) -> Self {
+        Self {
+            clock,
+            items: Vec::new(),
+        }
+    }
+
+    pub(crate) fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    /// Inserts an item which expires once `delay` elapses.
+    pub(crate) fn insert(&mut self, item: T, delay: Duration) {
+        let expiry = self.clock.now() + delay;
+        self.items.push((expiry, item));
+    }
+
+    /// Waits for the item which expires first, and removes it from the queue.
+    ///
+    /// Returns `None` immediately if the queue is empty. Items which expire at the same time
+    /// are returned in order of insertion.
+    pub(crate) async fn receive(&mut self) -> Option<T> {
+        let (index, expiry) = self
+            .items
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, (expiry, _))| *expiry)
+            .map(|(index, (expiry, _))| (index, *expiry))?;
+        self.clock.sleep_until(expiry).await;
+        Some(self.items.remove(index).1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::{executor::block_on, future, poll, FutureExt};
+
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn test_virtual_clock_advance() {
+        let clock = VirtualClock::new();
+        let (start, start_time) = (clock.now(), clock.system_time());
+        assert_eq!(clock.elapsed(), Duration::from_secs(0));
+
+        // Clones share the same time.
+        clock.clone().advance(10 * MS);
+        assert_eq!(clock.elapsed(), 10 * MS);
+        assert_eq!(clock.now(), start + 10 * MS);
+        assert_eq!(clock.system_time(), start_time + 10 * MS);
+    }
+
+    #[test]
+    fn test_virtual_sleep() {
+        let virtual_clock = VirtualClock::new();
+        let clock = Clock::Virtual(virtual_clock.clone());
+        block_on(async {
+            let mut sleep = Box::pin(clock.sleep(10 * MS));
+            assert!(poll!(&mut sleep).is_pending());
+            virtual_clock.advance(5 * MS);
+            assert!(poll!(&mut sleep).is_pending());
+            virtual_clock.advance(5 * MS);
+            assert!(poll!(&mut sleep).is_ready());
+        });
+    }
+
+    #[test]
+    fn test_virtual_timeout() {
+        let virtual_clock = VirtualClock::new();
+        let clock = Clock::Virtual(virtual_clock.clone());
+        block_on(async {
+            assert_eq!(clock.timeout(10 * MS, future::ready(1)).await, Some(1));
+
+            let mut timeout = Box::pin(clock.timeout(10 * MS, future::pending::<()>()));
+            assert!(poll!(&mut timeout).is_pending());
+            virtual_clock.advance(10 * MS);
+            assert_eq!(timeout.await, None);
+        });
+    }
+
+    #[test]
+    fn test_virtual_interval() {
+        let virtual_clock = VirtualClock::new();
+        let clock = Clock::Virtual(virtual_clock.clone());
+        let mut interval = clock.interval(10 * MS);
+        block_on(async {
+            // The first tick completes immediately.
+            interval.tick().await;
+            assert!(poll!(Box::pin(interval.tick())).is_pending());
+
+            // Missed ticks complete immediately.
+            virtual_clock.advance(30 * MS);
+            for _ in 0..3 {
+                assert!(poll!(Box::pin(interval.tick())).is_ready());
+            }
+            assert!(poll!(Box::pin(interval.tick())).is_pending());
+        });
+    }
+
+    #[test]
+    fn test_delay_queue() {
+        let virtual_clock = VirtualClock::new();
+        let mut queue = DelayQueue::new(Clock::Virtual(virtual_clock.clone()));
+        assert_eq!(queue.receive().now_or_never(), Some(None));
+
+        queue.insert("a", 30 * MS);
+        queue.insert("b", 10 * MS);
+        queue.insert("c", 10 * MS);
+        block_on(async {
+            assert!(poll!(Box::pin(queue.receive())).is_pending());
+            virtual_clock.advance(10 * MS);
+            // Items which expire at the same time are returned in order of insertion.
+            assert_eq!(queue.receive().await, Some("b"));
+            assert_eq!(queue.receive().await, Some("c"));
+            assert!(poll!(Box::pin(queue.receive())).is_pending());
+            virtual_clock.advance(20 * MS);
+            assert_eq!(queue.receive().await, Some("a"));
+            assert_eq!(queue.receive().await, None);
+        });
+    }
+
+    #[test]
+    fn test_delay_queue_set_clock() {
+        let mut queue = DelayQueue::new(Clock::System);
+        let virtual_clock = VirtualClock::new();
+        queue.set_clock(Clock::Virtual(virtual_clock.clone()));
+        queue.insert(1, Duration::from_secs(3600));
+        assert_eq!(queue.receive().now_or_never(), None);
+        virtual_clock.advance(Duration::from_secs(3600));
+        assert_eq!(queue.receive().now_or_never(), Some(Some(1)));
+    }
+}
@@ -12,6 +12,7 @@ use petgraph::{
     visit::{DfsPostOrder, Reversed},
     Direction,
 };
+use tokio::sync::Notify;
 
 use crate::{dataflow::Timestamp, node::operator_event::OperatorEvent};
 
@@ -167,6 +168,8 @@ pub struct ExecutionLattice {
     /// The `run_queue` is the queue that maintains the events to be executed next. Note that this
     /// is different from the `leaves` because a leaf is only removed once its marked as complete.
     run_queue: Arc<Mutex<BinaryHeap<RunnableEvent>>>,
+    /// Notifies tasks waiting for the lattice to become empty once the last event completes.
+    empty_notify: Arc<Notify>,
 }
 
 impl ExecutionLattice {
@@ -176,6 +179,24 @@ impl ExecutionLattice {
             forest: Arc::new(Mutex::new(StableGraph::new())),
             leaves: Arc::new(Mutex::new(Vec::new())),
             run_queue: Arc::new(Mutex::new(BinaryHeap::new())),
+            empty_notify: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Returns `true` if all events added to the lattice have completed.
+    pub async fn is_empty(&self) -> bool {
+        self.forest.lock().await.node_count() == 0
+    }
+
+    /// Waits until all events added to the lattice have completed.
+    pub async fn wait_until_empty(&self) {
+        loop {
+            // Register for notifications before checking to avoid missing a wakeup.
+            let notified = self.empty_notify.notified();
+            if self.is_empty().await {
+                return;
+            }
+            notified.await;
         }
     }
 
@@ -422,6 +443,10 @@ impl ExecutionLattice {
                 run_queue.push(parent);
             }
         }
+
+        if forest.node_count() == 0 {
+            self.empty_notify.notify_waiters();
+        }
     }
 
     /// Convert graph to string in DOT format.
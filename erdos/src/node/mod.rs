@@ -1,20 +1,23 @@
 //! Data structures for executing and ERDOS application.
 //!
-//! ERDOS applications may run across one or several nodes connected via TCP,
-//! as set in the [`Configuration`](crate::Configuration).
+//! ERDOS applications may run across one or several nodes connected via TCP
+//! or another [`Transport`](crate::communication::Transport), as set in the
+//! [`Configuration`](crate::Configuration).
 //! The [`new_app`](crate::new_app) helper function may be useful in scaling
 //! from one node to many via command line arguments.
 //!
-//! Currently, operators are manually scheduled to a [`Node`] via the
-//! [`OperatorConfig`](crate::dataflow::OperatorConfig). By default, they are
-//! scheduled on node 0. We are looking into more elegant solutions for
-//! scheduling operators, and hope to provide a versatile solution.
+//! Operators are pinned to a [`Node`] via the
+//! [`OperatorConfig`](crate::dataflow::OperatorConfig). Operators which are not
+//! pinned are placed by the
+//! [`PlacementStrategy`](crate::dataflow::graph::PlacementStrategy) set in the
+//! [`Configuration`](crate::Configuration), or scheduled on node 0 by default.
 
 // Private submodules
 #[allow(clippy::module_inception)]
 mod node;
 
 // Crate-wide visible submodules
+pub(crate) mod clock;
 pub(crate) mod lattice;
 pub(crate) mod operator_event;
 pub(crate) mod worker;
@@ -24,4 +27,5 @@ pub(crate) mod worker;
 pub mod operator_executors;
 
 // Public exports
-pub use node::{Node, NodeHandle, NodeId};
+pub use clock::{Clock, VirtualClock};
+pub use node::{Node, NodeEvent, NodeHandle, NodeId};
@@ -1,40 +1,60 @@
-use std::{collections::HashSet, sync::Arc, thread};
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    sync::{mpsc as std_mpsc, Arc},
+    thread,
+    time::{Duration, SystemTime},
+};
 
 use futures_util::stream::StreamExt;
 use tokio::{
-    net::TcpStream,
     runtime::Builder,
     sync::{
-        mpsc::{self, Receiver, Sender, UnboundedReceiver},
+        mpsc::{self, Receiver, Sender, UnboundedReceiver, UnboundedSender},
         Mutex,
     },
+    task::JoinHandle,
 };
 use tokio_util::codec::Framed;
 use tracing::Level;
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::fmt::format::FmtSpan;
 
-use crate::dataflow::graph::{default_graph, JobGraph};
+use crate::dataflow::graph::{default_graph, Graph, JobGraph};
+use crate::dataflow::pipeline::{OperatorRegistry, Pipeline};
 use crate::scheduler::{
     channel_manager::ChannelManager,
     endpoints_manager::{ChannelsToReceivers, ChannelsToSenders},
 };
-use crate::Configuration;
 use crate::{
     communication::{
         self,
         receivers::{self, ControlReceiver, DataReceiver},
         senders::{self, ControlSender, DataSender},
-        ControlMessage, ControlMessageCodec, ControlMessageHandler, MessageCodec,
+        ClockOffset, ClockOffsets, ClockSynchronizer, CommunicationError, Connection,
+        ControlMessage, ControlMessageCodec, ControlMessageHandler, Delivery, FailureDetector,
+        MessageCodec, StreamStatistics, StreamStatisticsMap, UdpReceiver,
+    },
+    dataflow::{
+        graph::{AbstractGraph, Job},
+        stream::StreamId,
+        CancellationToken,
     },
-    dataflow::graph::AbstractGraph,
 };
+use crate::{Configuration, OperatorId};
 
 use super::worker::Worker;
 
 /// Unique index for a [`Node`].
 pub type NodeId = usize;
 
+/// Events which a [`Node`] reports to the driver through its [`NodeHandle`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NodeEvent {
+    /// The node with the given ID stopped sending heartbeats or closed its control connection.
+    NodeFailure(NodeId),
+}
+
 /// Structure which executes a portion of an ERDOS application.
 ///
 /// The [`Node`] contains a runtime which executes operators and manages
@@ -45,10 +65,22 @@ pub struct Node {
     config: Configuration,
     /// Unique node id.
     id: NodeId,
+    /// The dataflow graph built by the driver.
+    graph: Graph,
     /// Queryable, uncompiled version of the JobGraph which stores metadata (e.g. stream names).
     abstract_graph: Option<AbstractGraph>,
     /// Dataflow graph which the node will execute.
     job_graph: Option<JobGraph>,
+    /// The nodes which joined before this node or along with it, in the order they joined. The
+    /// node sets up with these nodes before running its operators, and the other nodes join
+    /// later.
+    joined_nodes: Vec<NodeId>,
+    /// Connections from the nodes which join after this node.
+    late_connections: Option<LateConnections>,
+    /// Channel used to hand the tasks which communicate with nodes that joined later to the
+    /// node, which waits for them to complete.
+    late_tasks_tx: Option<UnboundedSender<JoinHandle<Result<(), CommunicationError>>>>,
+    late_tasks_rx: Option<UnboundedReceiver<JoinHandle<Result<(), CommunicationError>>>>,
     /// Structure to be used to send `Sender` updates to receiver threads.
     channels_to_receivers: Arc<Mutex<ChannelsToReceivers>>,
     /// Structure to be used to send messages to sender threads.
@@ -60,14 +92,49 @@ pub struct Node {
     /// Channel used to shut down the node.
     shutdown_tx: Sender<()>,
     shutdown_rx: Option<Receiver<()>>,
+    /// Channel used to drain the dataflow within the given timeout.
+    drain_tx: Sender<Duration>,
+    drain_rx: Option<Receiver<Duration>>,
+    /// Channel used to report [`NodeEvent`]s to the driver.
+    events_tx: std_mpsc::Sender<NodeEvent>,
+    events_rx: Option<std_mpsc::Receiver<NodeEvent>>,
+    /// Channel used by control receivers to forward heartbeats to the failure detector.
+    failure_detector_tx: UnboundedSender<ControlMessage>,
+    failure_detector_rx: Option<UnboundedReceiver<ControlMessage>>,
+    /// Channel used by control receivers to forward clock probes to the clock synchronizer.
+    clock_sync_tx: UnboundedSender<(ControlMessage, SystemTime)>,
+    clock_sync_rx: Option<UnboundedReceiver<(ControlMessage, SystemTime)>>,
+    /// The estimated offsets of the clocks of other nodes.
+    clock_offsets: ClockOffsets,
+    /// Statistics on the messages sent and received on each stream over the data connections.
+    stream_statistics: StreamStatisticsMap,
     // Flushes buffered logs when dropped.
     logger_guard: Option<WorkerGuard>,
 }
 
 #[allow(dead_code)]
 impl Node {
-    /// Creates a new node.
+    /// Creates a new node which executes the default dataflow graph built using the
+    /// [connect functions](crate::dataflow::connect).
     pub fn new(config: Configuration) -> Self {
+        Self::with_graph(config, default_graph::get())
+    }
+
+    /// Creates a new node which executes the dataflow graph described by `pipeline`, using the
+    /// operators registered in `registry`.
+    pub fn from_pipeline(
+        config: Configuration,
+        pipeline: &Pipeline,
+        registry: &OperatorRegistry,
+    ) -> Result<Self, String> {
+        let graph = pipeline.build(registry)?;
+        Ok(Self::with_graph(config, graph))
+    }
+
+    /// Creates a new node which executes the given dataflow graph.
+    ///
+    /// Operators and streams may still be added to the graph until the node runs.
+    pub fn with_graph(config: Configuration, graph: Graph) -> Self {
         // Set up the logger.
         let logger_guard = if let Some(logging_level) = config.logging_level {
             let display_thread_ids = logging_level >= Level::TRACE;
@@ -80,7 +147,8 @@ impl Node {
                 .with_span_events(FmtSpan::FULL)
                 .with_target(display_target)
                 .with_max_level(logging_level);
-            subscriber.init();
+            // Another node running in the same process may have already set the logger.
+            subscriber.try_init().ok();
 
             Some(guard)
         } else {
@@ -94,30 +162,53 @@ impl Node {
         rosrust::init(&format!("erdos_node_{}", id));
 
         let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
+        let (drain_tx, drain_rx) = mpsc::channel(1);
+        let (events_tx, events_rx) = std_mpsc::channel();
+        let (failure_detector_tx, failure_detector_rx) = mpsc::unbounded_channel();
+        let (clock_sync_tx, clock_sync_rx) = mpsc::unbounded_channel();
+        let (late_tasks_tx, late_tasks_rx) = mpsc::unbounded_channel();
+        let joined_nodes = (0..config.data_addresses.len()).collect();
         Self {
             config,
             id,
+            graph,
             abstract_graph: None,
             job_graph: None,
+            joined_nodes,
+            late_connections: None,
+            late_tasks_tx: Some(late_tasks_tx),
+            late_tasks_rx: Some(late_tasks_rx),
             channels_to_receivers: Arc::new(Mutex::new(ChannelsToReceivers::new())),
             channels_to_senders: Arc::new(Mutex::new(ChannelsToSenders::new())),
             control_handler: ControlMessageHandler::new(),
             initialized: Arc::new((std::sync::Mutex::new(false), std::sync::Condvar::new())),
             shutdown_tx,
             shutdown_rx: Some(shutdown_rx),
+            drain_tx,
+            drain_rx: Some(drain_rx),
+            events_tx,
+            events_rx: Some(events_rx),
+            failure_detector_tx,
+            failure_detector_rx: Some(failure_detector_rx),
+            clock_sync_tx,
+            clock_sync_rx: Some(clock_sync_rx),
+            clock_offsets: ClockOffsets::default(),
+            stream_statistics: StreamStatisticsMap::default(),
             logger_guard,
         }
     }
 
     /// Runs an ERDOS node.
     ///
-    /// The method never returns.
+    /// The method returns once the dataflow completes: the driver closed all
+    /// [`IngestStream`](crate::dataflow::stream::IngestStream)s, all operators on all nodes were
+    /// destroyed, and all connections to other nodes were closed.
     pub fn run(&mut self) {
         tracing::debug!("Node {}: running", self.id);
         // Set the dataflow graph if it hasn't been set already.
         if self.job_graph.is_none() {
-            let mut abstract_graph = default_graph::clone();
-            self.job_graph = Some(abstract_graph.compile());
+            let mut abstract_graph = self.graph.clone_abstract_graph();
+            self.job_graph = Some(self.compile(&mut abstract_graph));
             self.abstract_graph = Some(abstract_graph);
         }
         // Build a runtime with n threads.
@@ -128,36 +219,68 @@ impl Node {
             .build()
             .unwrap();
         runtime.block_on(self.async_run());
+        // Release threads waiting for the node to initialize if it stopped early (e.g. because
+        // the dataflow graph differs from the graph on other nodes).
+        let (lock, cvar) = &*self.initialized;
+        *lock.lock().unwrap() = true;
+        cvar.notify_all();
+        // Operators blocked in `run` may not exit after being cancelled.
+        runtime.shutdown_timeout(self.config.shutdown_grace_period);
         tracing::debug!("Node {}: finished running", self.id);
     }
 
     /// Runs an ERDOS node in a seperate OS thread.
     ///
     /// The method immediately returns.
-    pub fn run_async(mut self) -> NodeHandle {
+    pub fn run_async(self) -> NodeHandle {
+        let handle = self.spawn();
+        // Wait for ERDOS to start up.
+        handle.wait_until_initialized();
+        handle
+    }
+
+    /// Runs an ERDOS node in a separate OS thread without waiting for it to initialize.
+    ///
+    /// Nodes only finish initializing once they connect to all other nodes which join before the
+    /// dataflow starts, so nodes that run in the same process must all be spawned before waiting
+    /// for any of them.
+    pub(crate) fn spawn(mut self) -> NodeHandle {
         // Clone to avoid move to other thread.
         let shutdown_tx = self.shutdown_tx.clone();
+        let drain_tx = self.drain_tx.clone();
+        let events_rx = self.events_rx.take().unwrap();
         // Copy dataflow graph to the other thread
-        let mut abstract_graph = default_graph::clone();
-        self.job_graph = Some(abstract_graph.compile());
+        let mut abstract_graph = self.graph.clone_abstract_graph();
+        self.job_graph = Some(self.compile(&mut abstract_graph));
         self.abstract_graph = Some(abstract_graph);
         let initialized = self.initialized.clone();
+        let stream_statistics = Arc::clone(&self.stream_statistics);
+        let clock_offsets = Arc::clone(&self.clock_offsets);
         let thread_handle = thread::spawn(move || {
             self.run();
         });
-        // Wait for ERDOS to start up.
-        let (lock, cvar) = &*initialized;
-        let mut started = lock.lock().unwrap();
-        while !*started {
-            started = cvar.wait(started).unwrap();
-        }
 
         NodeHandle {
             thread_handle,
             shutdown_tx,
+            drain_tx,
+            events_rx,
+            initialized,
+            stream_statistics,
+            clock_offsets,
         }
     }
 
+    /// Compiles the dataflow graph, and panics if it is not valid.
+    fn compile(&self, abstract_graph: &mut AbstractGraph) -> JobGraph {
+        abstract_graph
+            .compile(
+                self.config.data_addresses.len(),
+                self.config.placement_strategy.as_deref(),
+            )
+            .unwrap_or_else(|e| panic!("Node {}: {}", self.id, e))
+    }
+
     fn set_node_initialized(&mut self) {
         let (lock, cvar) = &*self.initialized;
         let mut started = lock.lock().unwrap();
@@ -167,16 +290,21 @@ impl Node {
         tracing::debug!("Node {}: done initializing.", self.id);
     }
 
-    /// Splits a vector of TCPStreams into `DataSender`s and `DataReceiver`s.
+    /// Splits a vector of connections into `DataSender`s and `DataReceiver`s.
     async fn split_data_streams(
         &mut self,
-        mut streams: Vec<(NodeId, TcpStream)>,
+        mut streams: Vec<(NodeId, Box<dyn Connection>)>,
     ) -> (Vec<DataSender>, Vec<DataReceiver>) {
         let mut sink_halves = Vec::new();
         let mut stream_halves = Vec::new();
         while let Some((node_id, stream)) = streams.pop() {
-            // Use the message codec to divide the TCP stream data into messages.
-            let framed = Framed::new(stream, MessageCodec::new());
+            // Use the message codec to divide the connection's data into messages.
+            let codec = MessageCodec::new()
+                .with_frame_options(self.config.frame_options)
+                .with_statistics(Arc::clone(&self.stream_statistics))
+                .with_clock_offsets(node_id, Arc::clone(&self.clock_offsets))
+                .with_clock(self.config.clock.clone());
+            let framed = Framed::new(stream, codec);
             let (split_sink, split_stream) = framed.split();
             // Create an ERDOS receiver for the stream half.
             stream_halves.push(
@@ -185,6 +313,7 @@ impl Node {
                     split_stream,
                     self.channels_to_receivers.clone(),
                     &mut self.control_handler,
+                    Arc::clone(&self.clock_offsets),
                 )
                 .await,
             );
@@ -196,6 +325,8 @@ impl Node {
                     split_sink,
                     self.channels_to_senders.clone(),
                     &mut self.control_handler,
+                    self.config.max_batch_size,
+                    self.config.max_batch_linger,
                 )
                 .await,
             );
@@ -203,16 +334,16 @@ impl Node {
         (sink_halves, stream_halves)
     }
 
-    /// Splits a vector of TCPStreams into `ControlMessageHandler`, `ControlSender`s and `ControlReceiver`s.
+    /// Splits a vector of connections into `ControlSender`s and `ControlReceiver`s.
     async fn split_control_streams(
         &mut self,
-        streams: Vec<(NodeId, TcpStream)>,
+        streams: Vec<(NodeId, Box<dyn Connection>)>,
     ) -> (Vec<ControlSender>, Vec<ControlReceiver>) {
         let mut control_receivers = Vec::new();
         let mut control_senders = Vec::new();
 
         for (node_id, stream) in streams {
-            // Use the message codec to divide the TCP stream data into messages.
+            // Use the message codec to divide the connection's data into messages.
             let framed = Framed::new(stream, ControlMessageCodec::new());
             let (split_sink, split_stream) = framed.split();
             // Create an control receiver for the stream half.
@@ -220,6 +351,9 @@ impl Node {
                 node_id,
                 split_stream,
                 &mut self.control_handler,
+                self.failure_detector_tx.clone(),
+                self.clock_sync_tx.clone(),
+                self.config.clock.clone(),
             ));
             // Create an control sender for the sink half.
             control_senders.push(ControlSender::new(
@@ -232,8 +366,24 @@ impl Node {
         (control_senders, control_receivers)
     }
 
+    /// Waits until connections to the nodes which joined before this node or along with it are
+    /// set up, and checks that these nodes execute the same dataflow graph.
     async fn wait_for_communication_layer_initialized(&mut self) -> Result<(), String> {
-        let num_nodes = self.config.data_addresses.len();
+        let num_nodes = self.joined_nodes.len();
+
+        let fingerprint = self
+            .job_graph
+            .as_ref()
+            .unwrap_or_else(|| panic!("Node {}: dataflow graph must be set.", self.id))
+            .fingerprint();
+        self.control_handler
+            .broadcast_to_nodes(ControlMessage::GraphFingerprint(
+                self.id,
+                fingerprint.clone(),
+            ))
+            .map_err(|e| format!("Error broadcasting graph fingerprint: {:?}", e))?;
+        let mut fingerprints_received = HashSet::new();
+        fingerprints_received.insert(self.id);
 
         let mut control_senders_initialized = HashSet::new();
         control_senders_initialized.insert(self.id);
@@ -248,6 +398,7 @@ impl Node {
             || control_receivers_initialized.len() < num_nodes
             || data_senders_initialized.len() < num_nodes
             || data_receivers_initialized.len() < num_nodes
+            || fingerprints_received.len() < num_nodes
         {
             let msg = self
                 .control_handler
@@ -267,35 +418,77 @@ impl Node {
                 ControlMessage::DataReceiverInitialized(node_id) => {
                     data_receivers_initialized.insert(node_id);
                 }
+                ControlMessage::GraphFingerprint(node_id, other_fingerprint) => {
+                    if other_fingerprint != fingerprint {
+                        return Err(format!(
+                            "node {} executes a different dataflow graph than node {} \
+                            (- only on node {}, + only on node {}):\n{}",
+                            node_id,
+                            self.id,
+                            self.id,
+                            node_id,
+                            fingerprint.diff(&other_fingerprint).join("\n")
+                        ));
+                    }
+                    fingerprints_received.insert(node_id);
+                }
                 _ => unreachable!(),
             };
         }
         Ok(())
     }
 
+    /// Waits until the operators on this node are set up, and returns the number of deadlines
+    /// each operator registered.
     async fn wait_for_local_operators_initialized(
         &mut self,
-        mut rx_from_operators: UnboundedReceiver<ControlMessage>,
         num_local_operators: usize,
-    ) {
-        let mut initialized_operators = HashSet::new();
+    ) -> Result<HashMap<OperatorId, usize>, String> {
+        let mut initialized_operators = HashMap::new();
         while initialized_operators.len() < num_local_operators {
-            if let Some(ControlMessage::OperatorInitialized(op_id)) = rx_from_operators.recv().await
-            {
-                initialized_operators.insert(op_id);
+            match self.control_handler.read_operator_initialized_msg().await {
+                Ok((operator_id, num_deadlines)) => {
+                    initialized_operators.insert(operator_id, num_deadlines);
+                }
+                Err(e) => {
+                    return Err(format!("Error waiting for operators to set up: {:?}", e));
+                }
             }
         }
+        Ok(initialized_operators)
+    }
+
+    /// Signals the operators on this node to start processing.
+    fn run_local_operators(&mut self, operator_ids: Vec<OperatorId>) -> Result<(), String> {
+        for operator_id in operator_ids {
+            self.control_handler
+                .send_to_operator(operator_id, ControlMessage::RunOperator(operator_id))
+                .map_err(|e| format!("Error sending control message: {:?}", e))?;
+        }
+        Ok(())
     }
 
-    async fn broadcast_local_operators_initialized(&mut self) -> Result<(), String> {
+    async fn broadcast_local_operators_initialized(
+        &mut self,
+        num_deadlines: HashMap<OperatorId, usize>,
+    ) -> Result<(), String> {
         tracing::debug!("Node {}: initialized all operators on this node.", self.id);
         self.control_handler
-            .broadcast_to_nodes(ControlMessage::AllOperatorsInitializedOnNode(self.id))
+            .broadcast_to_nodes(ControlMessage::AllOperatorsInitializedOnNode(
+                self.id,
+                num_deadlines,
+            ))
             .map_err(|e| format!("Error broadcasting control message: {:?}", e))
     }
 
-    async fn wait_for_all_operators_initialized(&mut self) -> Result<(), String> {
-        let num_nodes = self.config.data_addresses.len();
+    /// Waits until the operators on the nodes which joined before this node or along with it are
+    /// set up. Returns the number of deadlines registered by each operator on these nodes, given
+    /// those of the local operators.
+    async fn wait_for_all_operators_initialized(
+        &mut self,
+        mut num_deadlines: HashMap<OperatorId, usize>,
+    ) -> Result<HashMap<OperatorId, usize>, String> {
+        let num_nodes = self.joined_nodes.len();
         let mut initialized_nodes = HashSet::new();
         initialized_nodes.insert(self.id);
         while initialized_nodes.len() < num_nodes {
@@ -304,18 +497,52 @@ impl Node {
                 .read_all_operators_initialized_on_node_msg()
                 .await
             {
-                Ok(node_id) => {
+                Ok((node_id, node_num_deadlines)) => {
                     initialized_nodes.insert(node_id);
+                    num_deadlines.extend(node_num_deadlines);
                 }
                 Err(e) => {
                     return Err(format!("Error waiting for other nodes to set up: {:?}", e));
                 }
             }
         }
-        Ok(())
+        Ok(num_deadlines)
     }
 
-    async fn run_operators(&mut self) -> Result<(), String> {
+    /// Runs the operators on this node until the dataflow completes, or until `shutdown_token`
+    /// is cancelled. Upon shutdown, the worker gives the operators the shutdown grace period to
+    /// exit, and reports those which did not.
+    async fn run_operators(&mut self, shutdown_token: CancellationToken) -> Result<(), String> {
+        let mut worker = Worker::new(self.num_event_runners(), self.config.shutdown_grace_period);
+        tokio::select! {
+            res = self.execute_operators(&mut worker) => res,
+            _ = shutdown_token.cancelled() => {
+                tracing::debug!("Node {}: shutting down operators", self.id);
+                worker.shutdown().await;
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the number of tasks which execute the events of the operators on this node.
+    fn num_event_runners(&self) -> usize {
+        let num_local_operators = self
+            .job_graph
+            .iter()
+            .flat_map(|job_graph| job_graph.operators())
+            .filter(|op| op.config.node_id == self.id)
+            .count();
+        // TODO: choose a better value.
+        std::cmp::max(
+            self.config
+                .num_threads
+                .checked_sub(num_local_operators)
+                .unwrap_or(1),
+            num_local_operators,
+        )
+    }
+
+    async fn execute_operators(&mut self, worker: &mut Worker) -> Result<(), String> {
         self.wait_for_communication_layer_initialized().await?;
 
         let job_graph = self
@@ -323,19 +550,14 @@ impl Node {
             .as_ref()
             .unwrap_or_else(|| panic!("Node {}: dataflow graph must be set.", self.id));
 
-        if let Some(filename) = &self.config.graph_filename {
-            job_graph
-                .to_graph_viz(filename.as_str())
-                .map_err(|e| e.to_string())?;
-        }
-
         let channel_manager = ChannelManager::new(
             job_graph,
             self.id,
             Arc::clone(&self.channels_to_receivers),
             Arc::clone(&self.channels_to_senders),
+            &mut self.control_handler,
         )
-        .await;
+        .await?;
         // Execute operators scheduled on the current node.
         let channel_manager = Arc::new(std::sync::Mutex::new(channel_manager));
         let num_operators = job_graph.operators().len();
@@ -349,17 +571,8 @@ impl Node {
         let num_local_operators = local_operators.len();
         tracing::debug!("{} local operators", num_local_operators);
 
-        // TODO: choose a better value.
-        let num_event_runners = std::cmp::max(
-            self.config
-                .num_threads
-                .checked_sub(num_local_operators)
-                .unwrap_or(1),
-            num_local_operators,
-        );
-        let mut worker = Worker::new(num_event_runners);
-
         let mut operator_executors = Vec::with_capacity(num_local_operators);
+        let mut operator_ids = Vec::with_capacity(num_local_operators);
 
         for operator_info in local_operators {
             let name = operator_info
@@ -370,12 +583,21 @@ impl Node {
             tracing::debug!("Node {}: starting operator {}", self.id, name);
             let channel_manager_copy = Arc::clone(&channel_manager);
             // Launch the operator as a separate async task.
-            let operator_executor = (operator_info.runner)(channel_manager_copy);
+            let mut operator_executor =
+                (operator_info.runner)(operator_info.config.clone(), channel_manager_copy);
+            // Set up channels to synchronize the operator with the rest of the dataflow.
+            let (tx, rx) = mpsc::unbounded_channel();
+            let operator_id = operator_executor.operator_id();
+            self.control_handler
+                .add_channel_to_operator(operator_id, tx);
+            operator_executor
+                .set_control_channels(self.control_handler.get_channel_to_handler(), rx);
+            operator_executor.set_clock(self.config.clock.clone());
             operator_executors.push(operator_executor);
+            operator_ids.push(operator_id);
         }
 
         worker.spawn_tasks(operator_executors).await;
-        // TODO: Wait for all operators to finish setting up.
 
         // Setup driver on the current node.
         if self.id == 0 {
@@ -387,77 +609,513 @@ impl Node {
                 );
             }
         }
+        // Nodes which join later set up with this node using the messages it sent while setting up.
+        let mut setup_msgs = vec![
+            ControlMessage::GraphFingerprint(self.id, job_graph.fingerprint()),
+            ControlMessage::StreamTypes(
+                self.id,
+                channel_manager.lock().unwrap().stream_types().clone(),
+            ),
+        ];
+        // Wait for all operators to finish setting up.
+        let local_num_deadlines = self
+            .wait_for_local_operators_initialized(num_local_operators)
+            .await?;
+        setup_msgs.push(ControlMessage::AllOperatorsInitializedOnNode(
+            self.id,
+            local_num_deadlines.clone(),
+        ));
         // Broadcast all operators initialized on current node.
-        self.broadcast_local_operators_initialized().await?;
+        self.broadcast_local_operators_initialized(local_num_deadlines.clone())
+            .await?;
         // Wait for all other nodes to finish setting up.
-        self.wait_for_all_operators_initialized().await?;
+        let num_deadlines = self
+            .wait_for_all_operators_initialized(local_num_deadlines)
+            .await?;
+        // Export the graph once the deadlines registered by operators are known.
+        if let Some(filename) = &self.config.graph_filename {
+            self.job_graph
+                .as_ref()
+                .unwrap()
+                .export(
+                    filename.as_str(),
+                    &num_deadlines,
+                    self.config.collapse_composite_operators,
+                )
+                .map_err(|e| e.to_string())?;
+        }
+        // Tell all operators to run.
+        self.run_local_operators(operator_ids)?;
         // Tell driver to run.
         self.set_node_initialized();
-        // TODO: Tell all operators to run.
-        // Wait for all operators to finish running.
-        worker.execute().await;
+        // Wait for all operators to finish running, or for the driver to drain the dataflow,
+        // while setting up with the nodes which join.
+        let mut drain_rx = self.drain_rx.take().unwrap();
+        let mut late_connections = self.late_connections.take().unwrap();
+        let drain_timeout = {
+            let completed = Self::wait_for_dataflow_completed(worker, &channel_manager);
+            tokio::pin!(completed);
+            loop {
+                tokio::select! {
+                    _ = &mut completed => break None,
+                    Some(timeout) = drain_rx.recv() => break Some(timeout),
+                    Some((node_id, control_stream, data_stream)) = late_connections.next() => {
+                        self.add_late_node(node_id, control_stream, data_stream, &setup_msgs)
+                            .await?;
+                    }
+                }
+            }
+        };
+        match drain_timeout {
+            Some(timeout) => self.drain(worker, &channel_manager, timeout).await,
+            None => {
+                // Messages to the nodes which have not joined yet are sent once they join.
+                if late_connections.num_pending() > 0 {
+                    tracing::info!(
+                        "Node {}: waiting for {} more nodes to join before finishing",
+                        self.id,
+                        late_connections.num_pending()
+                    );
+                }
+                while let Some((node_id, control_stream, data_stream)) =
+                    late_connections.next().await
+                {
+                    self.add_late_node(node_id, control_stream, data_stream, &setup_msgs)
+                        .await?;
+                }
+            }
+        }
+        self.close_connections()
+    }
+
+    /// Sets up the connections to a node which joined after this node started running its
+    /// operators, and sends the node the messages with which this node set up, so that it can
+    /// check that both nodes execute the same dataflow graph and start its operators.
+    async fn add_late_node(
+        &mut self,
+        node_id: NodeId,
+        control_stream: Box<dyn Connection>,
+        data_stream: Box<dyn Connection>,
+        setup_msgs: &[ControlMessage],
+    ) -> Result<(), String> {
+        tracing::info!("Node {}: node {} joined", self.id, node_id);
+        let (control_senders, control_receivers) = self
+            .split_control_streams(vec![(node_id, control_stream)])
+            .await;
+        let (senders, receivers) = self.split_data_streams(vec![(node_id, data_stream)]).await;
+        let task = tokio::spawn(async move {
+            tokio::try_join!(
+                senders::run_senders(senders),
+                receivers::run_receivers(receivers),
+                senders::run_control_senders(control_senders),
+                receivers::run_control_receivers(control_receivers)
+            )
+            .map(|_| ())
+        });
+        if let Some(late_tasks_tx) = &self.late_tasks_tx {
+            late_tasks_tx.send(task).ok();
+        }
+        for msg in setup_msgs {
+            self.control_handler
+                .send_to_node(node_id, msg.clone())
+                .map_err(|e| format!("Error sending control message: {:?}", e))?;
+        }
         Ok(())
     }
 
+    /// Waits until all operators on this node are destroyed, and the driver closed all
+    /// ingest streams.
+    async fn wait_for_dataflow_completed(
+        worker: &mut Worker,
+        channel_manager: &std::sync::Mutex<ChannelManager>,
+    ) {
+        worker.execute().await;
+        let mut interval = tokio::time::interval(Duration::from_millis(100));
+        while !channel_manager.lock().unwrap().ingest_streams_closed() {
+            interval.tick().await;
+        }
+    }
+
+    /// Closes all ingest streams, and waits up to `timeout` for the operators to complete
+    /// before shutting them down.
+    async fn drain(
+        &mut self,
+        worker: &mut Worker,
+        channel_manager: &std::sync::Mutex<ChannelManager>,
+        timeout: Duration,
+    ) {
+        tracing::info!("Node {}: draining the dataflow", self.id);
+        if let Err(e) = channel_manager.lock().unwrap().close_ingest_streams() {
+            tracing::error!("Node {}: error closing ingest streams: {:?}", self.id, e);
+        }
+        if tokio::time::timeout(timeout, worker.execute())
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                "Node {}: operators did not complete within {:?}; shutting them down",
+                self.id,
+                timeout
+            );
+            worker.shutdown().await;
+        }
+    }
+
+    /// Notifies the other nodes that this node finished, and closes the connections to them
+    /// once all pending messages are sent.
+    fn close_connections(&mut self) -> Result<(), String> {
+        tracing::debug!("Node {}: finished running operators", self.id);
+        // No more nodes join, so the tasks which communicate with them are all known.
+        self.late_tasks_tx.take();
+        // Data senders flush pending messages before closing their connections.
+        self.control_handler
+            .broadcast_to_data_senders(ControlMessage::CloseConnection)
+            .map_err(|e| format!("Error broadcasting control message: {:?}", e))?;
+        self.control_handler
+            .broadcast_to_nodes(ControlMessage::NodeFinished(self.id))
+            .map_err(|e| format!("Error broadcasting control message: {:?}", e))?;
+        self.control_handler
+            .broadcast_to_control_senders(ControlMessage::CloseConnection)
+            .map_err(|e| format!("Error broadcasting control message: {:?}", e))
+    }
+
+    /// Registers with the coordinator, and waits until enough nodes joined to learn their
+    /// addresses. The node keeps listening on the addresses it is configured with.
+    async fn discover_nodes(&mut self, coordinator_address: SocketAddr) {
+        let num_nodes = self.config.data_addresses.len();
+        let data_address = self.config.data_addresses[self.id];
+        let control_address = self.config.control_addresses[self.id];
+        let (mut data_addresses, mut control_addresses, joined_nodes) =
+            communication::discover_nodes(
+                coordinator_address,
+                self.id,
+                num_nodes,
+                self.config
+                    .min_nodes
+                    .unwrap_or(num_nodes)
+                    .clamp(1, num_nodes),
+                data_address,
+                control_address,
+                self.config.tls.as_ref(),
+                self.config.frame_options,
+            )
+            .await
+            .unwrap_or_else(|e| panic!("Node {}: failed to join the cluster: {}", self.id, e));
+        tracing::info!(
+            "Node {}: joined the cluster along with {} of {} nodes",
+            self.id,
+            joined_nodes.len(),
+            num_nodes
+        );
+        data_addresses[self.id] = data_address;
+        control_addresses[self.id] = control_address;
+        self.config.data_addresses = data_addresses;
+        self.config.control_addresses = control_addresses;
+        self.joined_nodes = joined_nodes;
+    }
+
+    /// Sets up the sending of best-effort streams to other nodes, and binds the UDP sockets on
+    /// which this node receives best-effort streams.
+    ///
+    /// Must run before the channel manager is created, so that the receivers learn the pushers
+    /// of the streams.
+    async fn start_udp_receivers(&mut self) {
+        // The addresses of the nodes which join later are unknown.
+        let udp_addresses = self
+            .joined_nodes
+            .iter()
+            .map(|&node_id| (node_id, self.config.data_addresses[node_id]))
+            .collect();
+        self.channels_to_senders.lock().await.set_udp_addresses(
+            udp_addresses,
+            Arc::clone(&self.stream_statistics),
+            self.config.clock.clone(),
+        );
+
+        let job_graph = match self.job_graph.as_ref() {
+            Some(job_graph) => job_graph,
+            None => return,
+        };
+        let operator_nodes: HashMap<OperatorId, NodeId> = job_graph
+            .operators()
+            .into_iter()
+            .map(|o| (o.id, o.config.node_id))
+            .collect();
+        // TODO: change this when ERDOS programs are submitted to a cluster.
+        let job_node = |job: &Job| match job {
+            Job::Operator(operator_id) => operator_nodes[operator_id],
+            Job::Driver => 0,
+        };
+        let mut addresses = HashSet::new();
+        for (stream, source, destinations) in job_graph.get_streams() {
+            let address = match stream.delivery() {
+                Delivery::Reliable => continue,
+                Delivery::BestEffort => self.config.data_addresses[self.id],
+                Delivery::Multicast(group) => group,
+            };
+            if job_node(&source) != self.id
+                && destinations.iter().any(|job| job_node(job) == self.id)
+            {
+                addresses.insert(address);
+            }
+        }
+
+        // Datagrams are only received from the other nodes.
+        let sources: HashSet<IpAddr> = self
+            .joined_nodes
+            .iter()
+            .filter(|&&node_id| node_id != self.id)
+            .map(|&node_id| self.config.data_addresses[node_id].ip())
+            .collect();
+        for address in addresses {
+            let node_id = self.id;
+            match UdpReceiver::bind(
+                node_id,
+                address,
+                sources.clone(),
+                Arc::clone(&self.channels_to_receivers),
+                Arc::clone(&self.stream_statistics),
+            )
+            .await
+            {
+                Ok(mut receiver) => {
+                    tokio::spawn(async move {
+                        if let Err(e) = receiver.run().await {
+                            tracing::error!(
+                                "Node {}: stopped receiving datagrams at {}; error {:?}",
+                                node_id,
+                                address,
+                                e
+                            );
+                        }
+                    });
+                }
+                Err(e) => tracing::error!(
+                    "Node {}: could not receive datagrams at {}; error {}",
+                    node_id,
+                    address,
+                    e
+                ),
+            }
+        }
+    }
+
     async fn async_run(&mut self) {
         // Assign values used later to avoid lifetime errors.
-        let num_nodes = self.config.data_addresses.len();
-        // Create TCPStreams between all node pairs.
-        let control_streams =
-            communication::create_tcp_streams(self.config.control_addresses.clone(), self.id).await;
-        let data_streams =
-            communication::create_tcp_streams(self.config.data_addresses.clone(), self.id).await;
+        let node_id = self.id;
+        if let Some(coordinator_address) = self.config.coordinator_address {
+            self.discover_nodes(coordinator_address).await;
+        }
+        // Create connections between all pairs of nodes which joined, and queue the messages to
+        // the nodes which join later until they connect.
+        {
+            let mut channels_to_senders = self.channels_to_senders.lock().await;
+            for other_node_id in 0..self.config.data_addresses.len() {
+                if !self.joined_nodes.contains(&other_node_id) {
+                    channels_to_senders.add_pending_node(other_node_id);
+                }
+            }
+        }
+        let (control_streams, late_control_streams) = communication::create_connections(
+            self.config.control_addresses.clone(),
+            self.id,
+            &self.joined_nodes,
+            &self.config.transports,
+            self.config.shared_memory,
+            self.config.tls.as_ref(),
+            self.config.frame_options,
+        )
+        .await;
+        let (data_streams, late_data_streams) = communication::create_connections(
+            self.config.data_addresses.clone(),
+            self.id,
+            &self.joined_nodes,
+            &self.config.transports,
+            self.config.shared_memory,
+            self.config.tls.as_ref(),
+            self.config.frame_options,
+        )
+        .await;
+        self.late_connections = Some(LateConnections::new(
+            late_control_streams,
+            late_data_streams,
+            self.config.data_addresses.len() - self.joined_nodes.len(),
+        ));
         let (control_senders, control_receivers) =
             self.split_control_streams(control_streams).await;
         let (senders, receivers) = self.split_data_streams(data_streams).await;
-        // Listen for shutdown message.
+        self.start_udp_receivers().await;
+        // Detect failures of other nodes via heartbeats.
+        let mut failure_detector = FailureDetector::new(
+            self.id,
+            self.config.heartbeat_interval,
+            self.config.heartbeat_timeout,
+            self.failure_detector_rx.take().unwrap(),
+            self.events_tx.clone(),
+            &self.control_handler,
+        );
+        tokio::spawn(async move { failure_detector.run().await });
+        // Estimate the offsets of the clocks of other nodes.
+        let mut clock_synchronizer = ClockSynchronizer::new(
+            self.id,
+            self.config.clock_sync_interval,
+            self.config.clock.clone(),
+            Arc::clone(&self.clock_offsets),
+            self.clock_sync_rx.take().unwrap(),
+            &self.control_handler,
+        );
+        tokio::spawn(async move { clock_synchronizer.run().await });
+        // Listen for shutdown message, and wait for the operators to shut down.
+        let shutdown_token = CancellationToken::new();
+        let operators_stopped = CancellationToken::new();
         let mut shutdown_rx = self.shutdown_rx.take().unwrap();
-        let shutdown_fut = shutdown_rx.recv();
+        let shutdown_fut = async {
+            shutdown_rx.recv().await;
+            tracing::debug!("Node {}: shutting down", node_id);
+            shutdown_token.cancel();
+            operators_stopped.cancelled().await;
+        };
         // Execute threads that send data to other nodes.
         let control_senders_fut = senders::run_control_senders(control_senders);
         let senders_fut = senders::run_senders(senders);
         // Execute threads that receive data from other nodes.
         let control_recvs_fut = receivers::run_control_receivers(control_receivers);
         let recvs_fut = receivers::run_receivers(receivers);
-        // Execute operators.
-        let ops_fut = self.run_operators();
-        // These threads only complete when a failure happens.
-        if num_nodes <= 1 {
-            // Senders and Receivers should return if there's only 1 node.
-            if let Err(e) = tokio::try_join!(
+        // Completes once all connections to other nodes are closed.
+        let mut late_tasks_rx = self.late_tasks_rx.take().unwrap();
+        let comms_fut = async {
+            tokio::try_join!(
                 senders_fut,
                 recvs_fut,
                 control_senders_fut,
                 control_recvs_fut
-            ) {
-                tracing::error!(
-                    "Non-fatal network communication error; this should not happen! {:?}",
-                    e
-                );
+            )
+            .map_err(|e| format!("Network communication error: {:?}", e))?;
+            // The node stops handing over tasks once it finished.
+            while let Some(task) = late_tasks_rx.recv().await {
+                match task.await {
+                    Ok(res) => res.map_err(|e| format!("Network communication error: {:?}", e))?,
+                    Err(e) => return Err(format!("Network communication error: {:?}", e)),
+                }
             }
-            tokio::select! {
-                Err(e) = ops_fut => tracing::error!(
+            Ok::<(), String>(())
+        };
+        // Execute operators.
+        let ops_fut = async {
+            let res = self.run_operators(shutdown_token.clone()).await;
+            operators_stopped.cancel();
+            res
+        };
+        // Completes once the dataflow completes on all nodes.
+        let run_fut = async { tokio::try_join!(ops_fut, comms_fut) };
+        tokio::select! {
+            res = run_fut => match res {
+                Ok(_) => tracing::debug!("Node {}: dataflow completed", node_id),
+                Err(e) => tracing::error!("Error running node {:?}: {}", node_id, e),
+            },
+            _ = shutdown_fut => tracing::debug!("Node {}: shut down", node_id),
+        }
+        self.log_stream_statistics();
+    }
 
-                    "Error running operators on node {:?}: {:?}", self.id, e
-                ),
-                _ = shutdown_fut => tracing::debug!("Node {}: shutting down", self.id),
+    /// Logs the amount of data sent and received on each stream, and the compression ratios.
+    fn log_stream_statistics(&self) {
+        let stream_names: HashMap<StreamId, String> = self
+            .job_graph
+            .iter()
+            .flat_map(|job_graph| job_graph.get_streams())
+            .map(|(stream, _, _)| (stream.id(), stream.name()))
+            .collect();
+        for (stream_id, stats) in self.stream_statistics.lock().unwrap().iter() {
+            let name = stream_names
+                .get(stream_id)
+                .cloned()
+                .unwrap_or_else(|| stream_id.to_string());
+            tracing::info!(
+                "Node {}: stream {} sent {} messages ({} bytes, compression ratio {:.2}), \
+                received {} messages ({} bytes, compression ratio {:.2})",
+                self.id,
+                name,
+                stats.messages_sent,
+                stats.compressed_bytes_sent,
+                stats.send_compression_ratio().unwrap_or(1.0),
+                stats.messages_received,
+                stats.compressed_bytes_received,
+                stats.receive_compression_ratio().unwrap_or(1.0)
+            );
+            if let Some(mean_latency) = stats.mean_latency() {
+                tracing::info!(
+                    "Node {}: messages on stream {} took {:?} on average and at most {:?} to \
+                    arrive from other nodes",
+                    self.id,
+                    name,
+                    mean_latency,
+                    stats.max_latency
+                );
             }
-        } else {
-            tokio::select! {
-                Err(e) = senders_fut => tracing::error!("Error with data senders: {:?}", e),
-                Err(e) = recvs_fut => tracing::error!("Error with data receivers: {:?}", e),
-                Err(e) = control_senders_fut => tracing::error!("Error with control senders: {:?}", e),
-                Err(e) = control_recvs_fut => tracing::error!(
+        }
+    }
+}
 
-                    "Error with control receivers: {:?}", e
-                ),
-                Err(e) = ops_fut => tracing::error!(
+/// Pairs the control and data connections of the nodes which join after a node.
+struct LateConnections {
+    control_streams_rx: UnboundedReceiver<(NodeId, Box<dyn Connection>)>,
+    data_streams_rx: UnboundedReceiver<(NodeId, Box<dyn Connection>)>,
+    /// Connections of the nodes whose other connection is not established yet.
+    control_streams: HashMap<NodeId, Box<dyn Connection>>,
+    data_streams: HashMap<NodeId, Box<dyn Connection>>,
+    /// The number of nodes which have not joined yet.
+    num_pending: usize,
+}
 
-                    "Error running operators on node {:?}: {:?}", self.id, e
-                ),
-                _ = shutdown_fut => tracing::debug!("Node {}: shutting down", self.id),
+impl LateConnections {
+    fn new(
+        control_streams_rx: UnboundedReceiver<(NodeId, Box<dyn Connection>)>,
+        data_streams_rx: UnboundedReceiver<(NodeId, Box<dyn Connection>)>,
+        num_pending: usize,
+    ) -> Self {
+        Self {
+            control_streams_rx,
+            data_streams_rx,
+            control_streams: HashMap::new(),
+            data_streams: HashMap::new(),
+            num_pending,
+        }
+    }
+
+    fn num_pending(&self) -> usize {
+        self.num_pending
+    }
+
+    /// Waits until the next node established both its control and data connection. Returns
+    /// `None` once all nodes joined, or if the node stopped accepting connections.
+    ///
+    /// Cancellation safe, so no connection is lost if the future is dropped.
+    async fn next(&mut self) -> Option<(NodeId, Box<dyn Connection>, Box<dyn Connection>)> {
+        while self.num_pending > 0 {
+            let node_id = tokio::select! {
+                Some((node_id, stream)) = self.control_streams_rx.recv() => {
+                    self.control_streams.insert(node_id, stream);
+                    node_id
+                }
+                Some((node_id, stream)) = self.data_streams_rx.recv() => {
+                    self.data_streams.insert(node_id, stream);
+                    node_id
+                }
+                else => return None,
+            };
+            if self.control_streams.contains_key(&node_id)
+                && self.data_streams.contains_key(&node_id)
+            {
+                self.num_pending -= 1;
+                return Some((
+                    node_id,
+                    self.control_streams.remove(&node_id).unwrap(),
+                    self.data_streams.remove(&node_id).unwrap(),
+                ));
             }
         }
+        None
     }
 }
 
@@ -465,18 +1123,77 @@ impl Node {
 pub struct NodeHandle {
     thread_handle: thread::JoinHandle<()>,
     shutdown_tx: Sender<()>,
+    drain_tx: Sender<Duration>,
+    events_rx: std_mpsc::Receiver<NodeEvent>,
+    initialized: Arc<(std::sync::Mutex<bool>, std::sync::Condvar)>,
+    stream_statistics: StreamStatisticsMap,
+    clock_offsets: ClockOffsets,
 }
 
-// TODO: distinguish between shutting down the dataflow and shutting down the node.
 impl NodeHandle {
+    /// Blocks until the [`Node`] connected to all other nodes and set up its operators.
+    pub(crate) fn wait_until_initialized(&self) {
+        let (lock, cvar) = &*self.initialized;
+        let mut started = lock.lock().unwrap();
+        while !*started {
+            started = cvar.wait(started).unwrap();
+        }
+    }
+
     /// Waits for the associated [`Node`] to finish.
+    ///
+    /// Returns once the dataflow completes (see [`Node::run`]).
     pub fn join(self) -> Result<(), String> {
         self.thread_handle.join().map_err(|e| format!("{:?}", e))
     }
+
+    /// Closes all [`IngestStream`](crate::dataflow::stream::IngestStream)s on the node by
+    /// sending top watermarks, and blocks until the dataflow completes.
+    ///
+    /// Operators which are still running `timeout` after draining started are shut down.
+    pub fn drain(self, timeout: Duration) -> Result<(), String> {
+        self.start_drain(timeout);
+        self.join()
+    }
+
+    /// Signals the [`Node`] to drain without waiting for it to finish.
+    pub(crate) fn start_drain(&self, timeout: Duration) {
+        // Error indicates node is already draining.
+        self.drain_tx.try_send(timeout).ok();
+    }
+
+    /// Returns the next [`NodeEvent`] if one is available, without blocking.
+    pub fn try_recv_event(&self) -> Option<NodeEvent> {
+        self.events_rx.try_recv().ok()
+    }
+
+    /// Blocks for up to `timeout` until the next [`NodeEvent`] is available.
+    pub fn recv_event_timeout(&self, timeout: Duration) -> Option<NodeEvent> {
+        self.events_rx.recv_timeout(timeout).ok()
+    }
+
+    /// Returns statistics on the messages sent and received on each stream over connections to
+    /// other nodes, including the compression ratios.
+    pub fn stream_statistics(&self) -> HashMap<StreamId, StreamStatistics> {
+        self.stream_statistics.lock().unwrap().clone()
+    }
+
+    /// Returns the estimated offset of the clock of each other node from the clock of this node.
+    ///
+    /// Nodes are missing until the first clock probe to them is answered.
+    pub fn clock_offsets(&self) -> HashMap<NodeId, ClockOffset> {
+        self.clock_offsets.lock().unwrap().clone()
+    }
+
     /// Blocks until the [`Node`] shuts down.
     pub fn shutdown(self) -> Result<(), String> {
+        self.start_shutdown();
+        self.join()
+    }
+
+    /// Signals the [`Node`] to shut down without waiting for it to finish.
+    pub(crate) fn start_shutdown(&self) {
         // Error indicates node is already shutting down.
         self.shutdown_tx.try_send(()).ok();
-        self.thread_handle.join().map_err(|e| format!("{:?}", e))
     }
 }
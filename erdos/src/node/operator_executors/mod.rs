@@ -22,10 +22,8 @@ pub use two_in_one_out_executor::*;
 /* ***********************************************************************************************
  * Imports for the traits.
  * ***********************************************************************************************/
-use std::{cmp, collections::HashMap, future::Future, pin::Pin, sync::Arc, time::Duration};
+use std::{cmp, collections::HashSet, future::Future, pin::Pin, sync::Arc, time::SystemTime};
 
-use futures_delay_queue::{delay_queue, DelayHandle, DelayQueue, Receiver};
-use futures_intrusive::buffer::GrowingHeapBuf;
 use serde::Deserialize;
 use tokio::{
     self,
@@ -33,6 +31,7 @@ use tokio::{
 };
 
 use crate::{
+    communication::ControlMessage,
     dataflow::{
         context::SetupContext,
         deadlines::{ConditionContext, DeadlineEvent, DeadlineId},
@@ -41,6 +40,7 @@ use crate::{
         Data, Message, ReadStream, Timestamp,
     },
     node::{
+        clock::{Clock, DelayQueue},
         lattice::ExecutionLattice,
         operator_event::OperatorEvent,
         worker::{EventNotification, OperatorExecutorNotification, WorkerNotification},
@@ -68,6 +68,19 @@ pub(crate) trait OperatorExecutorT: Send {
 
     /// Returns the operator ID.
     fn operator_id(&self) -> OperatorId;
+
+    /// Returns the operator's configuration.
+    fn config(&self) -> &OperatorConfig;
+
+    /// Sets the channels used to synchronize the operator with the rest of the dataflow graph.
+    fn set_control_channels(
+        &mut self,
+        control_tx: mpsc::UnboundedSender<ControlMessage>,
+        control_rx: mpsc::UnboundedReceiver<ControlMessage>,
+    );
+
+    /// Sets the clock on which the operator's deadlines expire.
+    fn set_clock(&mut self, clock: Clock);
 }
 
 /// Trait that needs to be defined by the executors for an operator that processes a single message
@@ -217,14 +230,14 @@ where
         channel_to_worker: mpsc::UnboundedSender<WorkerNotification>,
         channel_to_event_runners: broadcast::Sender<EventNotification>,
     ) {
-        // Synchronize the operator with the rest of the dataflow graph.
-        self.helper.synchronize().await;
-
         // Run the `setup` method.
         let mut read_stream: ReadStream<T> = self.read_stream.take().unwrap();
         let mut setup_context =
             tokio::task::block_in_place(|| self.processor.execute_setup(&mut read_stream));
 
+        // Synchronize the operator with the rest of the dataflow graph.
+        self.helper.synchronize(setup_context.num_deadlines()).await;
+
         // Execute the `run` method.
         tracing::debug!(
             "Node {}: Running Operator {}",
@@ -244,9 +257,13 @@ where
         );
 
         // Shutdown.
+        let mut streams_closed = false;
         loop {
             tokio::select! {
-                _ = process_stream_fut => break,
+                _ = process_stream_fut => {
+                    streams_closed = true;
+                    break;
+                }
                 notification_result = channel_from_worker.recv() => {
                     match notification_result {
                         Ok(notification) => {
@@ -268,6 +285,11 @@ where
             }
         }
 
+        // Wait for in-flight callbacks to complete before destroying the operator.
+        if streams_closed {
+            self.helper.lattice.wait_until_empty().await;
+        }
+
         // Invoke the `destroy` method.
         tokio::task::block_in_place(|| self.processor.execute_destroy());
 
@@ -303,6 +325,22 @@ where
     fn operator_id(&self) -> OperatorId {
         self.config.id
     }
+
+    fn config(&self) -> &OperatorConfig {
+        &self.config
+    }
+
+    fn set_control_channels(
+        &mut self,
+        control_tx: mpsc::UnboundedSender<ControlMessage>,
+        control_rx: mpsc::UnboundedReceiver<ControlMessage>,
+    ) {
+        self.helper.set_control_channels(control_tx, control_rx);
+    }
+
+    fn set_clock(&mut self, clock: Clock) {
+        self.helper.set_clock(clock);
+    }
 }
 
 /// Executor that executes operators that process messages on two read streams of type T and U.
@@ -345,9 +383,6 @@ where
         channel_to_worker: mpsc::UnboundedSender<WorkerNotification>,
         channel_to_event_runners: broadcast::Sender<EventNotification>,
     ) {
-        // Synchronize the operator with the rest of the dataflow graph.
-        self.helper.synchronize().await;
-
         // Run the `setup` method.
         let mut left_read_stream: ReadStream<T> = self.left_read_stream.take().unwrap();
         let mut right_read_stream: ReadStream<U> = self.right_read_stream.take().unwrap();
@@ -356,6 +391,9 @@ where
                 .execute_setup(&mut left_read_stream, &mut right_read_stream)
         });
 
+        // Synchronize the operator with the rest of the dataflow graph.
+        self.helper.synchronize(setup_context.num_deadlines()).await;
+
         // Execute the `run` method.
         tracing::debug!(
             "Node {}: Running Operator {}",
@@ -377,9 +415,13 @@ where
         );
 
         // Shutdown.
+        let mut streams_closed = false;
         loop {
             tokio::select! {
-                _ = process_stream_fut => break,
+                _ = process_stream_fut => {
+                    streams_closed = true;
+                    break;
+                }
                 notification_result = channel_from_worker.recv() => {
                     match notification_result {
                         Ok(notification) => {
@@ -401,6 +443,11 @@ where
             }
         }
 
+        // Wait for in-flight callbacks to complete before destroying the operator.
+        if streams_closed {
+            self.helper.lattice.wait_until_empty().await;
+        }
+
         // Invoke the `destroy` method.
         tokio::task::block_in_place(|| self.processor.execute_destroy());
 
@@ -437,6 +484,22 @@ where
     fn operator_id(&self) -> OperatorId {
         self.config.id
     }
+
+    fn config(&self) -> &OperatorConfig {
+        &self.config
+    }
+
+    fn set_control_channels(
+        &mut self,
+        control_tx: mpsc::UnboundedSender<ControlMessage>,
+        control_rx: mpsc::UnboundedReceiver<ControlMessage>,
+    ) {
+        self.helper.set_control_channels(control_tx, control_rx);
+    }
+
+    fn set_clock(&mut self, clock: Clock) {
+        self.helper.set_clock(clock);
+    }
 }
 
 /* ***********************************************************************************************
@@ -446,21 +509,27 @@ where
 pub struct OperatorExecutorHelper {
     operator_id: OperatorId,
     lattice: Arc<ExecutionLattice>,
-    deadline_queue: DelayQueue<DeadlineEvent, GrowingHeapBuf<DeadlineEvent>>,
-    deadline_queue_rx: Receiver<DeadlineEvent>,
+    deadline_queue: DelayQueue<DeadlineEvent>,
+    // Clock on which deadlines expire.
+    clock: Clock,
     // For active deadlines.
-    deadline_to_key_map: HashMap<DeadlineId, DelayHandle>,
+    armed_deadlines: HashSet<DeadlineId>,
+    // Used to notify the node that the operator is initialized.
+    control_tx: Option<mpsc::UnboundedSender<ControlMessage>>,
+    // Used to receive the signal to run the operator.
+    control_rx: Option<mpsc::UnboundedReceiver<ControlMessage>>,
 }
 
 impl OperatorExecutorHelper {
     pub(crate) fn new(operator_id: OperatorId) -> Self {
-        let (deadline_queue, deadline_queue_rx) = delay_queue();
         OperatorExecutorHelper {
             operator_id,
             lattice: Arc::new(ExecutionLattice::new()),
-            deadline_queue,
-            deadline_queue_rx,
-            deadline_to_key_map: HashMap::new(),
+            deadline_queue: DelayQueue::new(Clock::System),
+            clock: Clock::System,
+            armed_deadlines: HashSet::new(),
+            control_tx: None,
+            control_rx: None,
         }
     }
 
@@ -468,27 +537,83 @@ impl OperatorExecutorHelper {
         Arc::clone(&self.lattice)
     }
 
-    pub(crate) async fn synchronize(&self) {
-        // TODO: replace this with a synchronization step
-        // that ensures all operators are ready to run.
-        tokio::time::sleep(Duration::from_secs(1)).await;
+    pub(crate) fn set_control_channels(
+        &mut self,
+        control_tx: mpsc::UnboundedSender<ControlMessage>,
+        control_rx: mpsc::UnboundedReceiver<ControlMessage>,
+    ) {
+        self.control_tx = Some(control_tx);
+        self.control_rx = Some(control_rx);
+    }
+
+    /// Sets the clock on which deadlines expire.
+    pub(crate) fn set_clock(&mut self, clock: Clock) {
+        self.deadline_queue.set_clock(clock.clone());
+        self.clock = clock;
     }
 
-    // Arms the given `DeadlineEvents` by installing them into a DeadlineQueue.
-    fn manage_deadlines(&mut self, deadlines: Vec<DeadlineEvent>) {
+    /// Notifies the node that the operator is initialized and registered `num_deadlines`
+    /// deadlines, and waits until the node signals that all operators in the dataflow graph are
+    /// initialized.
+    ///
+    /// Returns immediately if the control channels are not set.
+    pub(crate) async fn synchronize(&mut self, num_deadlines: usize) {
+        let (control_tx, control_rx) = match (&self.control_tx, &mut self.control_rx) {
+            (Some(control_tx), Some(control_rx)) => (control_tx, control_rx),
+            _ => return,
+        };
+        if control_tx
+            .send(ControlMessage::OperatorInitialized(
+                self.operator_id,
+                num_deadlines,
+            ))
+            .is_err()
+        {
+            tracing::error!(
+                "Operator {}: unable to notify the node that the operator is initialized",
+                self.operator_id
+            );
+            return;
+        }
+        loop {
+            match control_rx.recv().await {
+                Some(ControlMessage::RunOperator(operator_id))
+                    if operator_id == self.operator_id =>
+                {
+                    return
+                }
+                Some(msg) => tracing::warn!(
+                    "Operator {}: received unexpected control message {:?}",
+                    self.operator_id,
+                    msg
+                ),
+                // The node is shutting down.
+                None => return,
+            }
+        }
+    }
+
+    // Arms the given `DeadlineEvents` by installing them into a DeadlineQueue. Deadlines armed
+    // by a message which was sent from another node at `sent_at` expire relative to that time.
+    fn manage_deadlines(&mut self, deadlines: Vec<DeadlineEvent>, sent_at: Option<SystemTime>) {
+        // The time since the message was sent, which is already corrected for the offset of the
+        // sending node's clock.
+        let elapsed = sent_at
+            .and_then(|sent_at| self.clock.system_time().duration_since(sent_at).ok())
+            .unwrap_or_default();
         for event in deadlines {
-            if !self.deadline_to_key_map.contains_key(&event.id) {
+            if !self.armed_deadlines.contains(&event.id) {
                 // Install the handler onto the queue with the given duration.
-                let event_duration = event.duration;
+                let event_duration = event.duration.saturating_sub(elapsed);
                 let deadline_id = event.id;
-                let queue_key: DelayHandle = self.deadline_queue.insert(event, event_duration);
+                self.deadline_queue.insert(event, event_duration);
                 tracing::debug!(
-                    "Installed a deadline handler for the Deadline {} with the DelayHandle: {:?}",
+                    "Installed a deadline handler for the Deadline {} with a duration of {:?}",
                     deadline_id,
-                    queue_key,
+                    event_duration,
                 );
 
-                self.deadline_to_key_map.insert(deadline_id, queue_key);
+                self.armed_deadlines.insert(deadline_id);
             }
         }
     }
@@ -506,11 +631,9 @@ impl OperatorExecutorHelper {
         let mut condition_context = ConditionContext::new();
         loop {
             tokio::select! {
-                // DelayQueue returns `None` if the queue is empty. This means that if there are no
-                // deadlines installed, the queue will always be ready and return `None` thus
-                // wasting resources. We can potentially fix this by inserting a Deadline for the
-                // future and maintaining it so that the queue is not empty.
-                Some(deadline_event) = self.deadline_queue_rx.receive() => {
+                // DelayQueue returns `None` if the queue is empty, which disables this branch
+                // until the next iteration of the loop.
+                Some(deadline_event) = self.deadline_queue.receive() => {
                     // Missed a deadline. Check if the end condition is satisfied and invoke the
                     // handler if not so.
                     // TODO (Sukrit): The handler is invoked in the thread of the OperatorExecutor.
@@ -525,24 +648,18 @@ impl OperatorExecutorHelper {
                         );
                     }
 
-                    // Remove the key from the hashmap and clear the state in the ConditionContext.
-                    match self.deadline_to_key_map.remove(&deadline_event.id) {
-                        None => {
-                            tracing::warn!(
-
-                                "Could not find a key corresponding to the Deadline ID: {}",
-                                deadline_event.id,
-                            );
-                        }
-                        Some(key) => {
-                            tracing::debug!(
-
-                                "Finished invoking the deadline handler for the DelayHandle: {:?} \
-                                corresponding to the Deadline ID: {}",
-                                key,
-                                deadline_event.id,
-                            );
-                        }
+                    // Remove the deadline from the armed set and clear the state in the
+                    // ConditionContext.
+                    if self.armed_deadlines.remove(&deadline_event.id) {
+                        tracing::debug!(
+                            "Finished invoking the deadline handler for the Deadline ID: {}",
+                            deadline_event.id,
+                        );
+                    } else {
+                        tracing::warn!(
+                            "Could not find an armed deadline corresponding to the Deadline ID: {}",
+                            deadline_event.id,
+                        );
                     }
 
                     // Clean the state.
@@ -556,7 +673,7 @@ impl OperatorExecutorHelper {
                 // TODO (Sukrit) : The start and end conditions are evaluated in the thread of the
                 // OperatorExecutor, and can be moved to a separate task if they become a
                 // bottleneck.
-                Ok(msg) = read_stream.async_read() => {
+                Ok((msg, sent_at)) = read_stream.async_read() => {
                     let events = match msg.data() {
                         // Data message
                         Some(_) => {
@@ -597,7 +714,7 @@ impl OperatorExecutorHelper {
                         &condition_context,
                         msg.timestamp().clone()
                     );
-                    self.manage_deadlines(deadline_events);
+                    self.manage_deadlines(deadline_events, sent_at);
 
                     self.lattice.add_events(events).await;
                     notifier_tx
@@ -629,11 +746,9 @@ impl OperatorExecutorHelper {
         let mut min_watermark = cmp::min(&left_watermark, &right_watermark).clone();
         loop {
             tokio::select! {
-                // DelayQueue returns `None` if the queue is empty. This means that if there are no
-                // deadlines installed, the queue will always be ready and return `None` thus
-                // wasting resources. We can potentially fix this by inserting a Deadline for the
-                // future and maintaining it so that the queue is not empty.
-                Some(deadline_event) = self.deadline_queue_rx.receive() => {
+                // DelayQueue returns `None` if the queue is empty, which disables this branch
+                // until the next iteration of the loop.
+                Some(deadline_event) = self.deadline_queue.receive() => {
                     // Missed a deadline. Check if the end condition is satisfied and invoke the
                     // handler if not so.
                     // TODO (Sukrit): The handler is invoked in the thread of the OperatorExecutor.
@@ -648,24 +763,18 @@ impl OperatorExecutorHelper {
                         );
                     }
 
-                    // Remove the key from the hashmap and clear the state in the ConditionContext.
-                    match self.deadline_to_key_map.remove(&deadline_event.id) {
-                        None => {
-                            tracing::warn!(
-
-                                "Could not find a key corresponding to the Deadline ID: {}",
-                                deadline_event.id,
-                            );
-                        }
-                        Some(key) => {
-                            tracing::debug!(
-
-                                "Finished invoking the deadline handler for the DelayHandle: {:?} \
-                                corresponding to the Deadline ID: {}",
-                                key,
-                                deadline_event.id,
-                            );
-                        }
+                    // Remove the deadline from the armed set and clear the state in the
+                    // ConditionContext.
+                    if self.armed_deadlines.remove(&deadline_event.id) {
+                        tracing::debug!(
+                            "Finished invoking the deadline handler for the Deadline ID: {}",
+                            deadline_event.id,
+                        );
+                    } else {
+                        tracing::warn!(
+                            "Could not find an armed deadline corresponding to the Deadline ID: {}",
+                            deadline_event.id,
+                        );
                     }
 
                     // Clean the state.
@@ -679,7 +788,7 @@ impl OperatorExecutorHelper {
                 // TODO(Sukrit): The start and end conditions are evaluated in the thread of the
                 // OperatorExecutor, and can be moved to a separate task if they become a
                 // bottleneck.
-                Ok(left_msg) = left_read_stream.async_read() => {
+                Ok((left_msg, sent_at)) = left_read_stream.async_read() => {
                     let events = match left_msg.data() {
                         // Data message
                         Some(_) => {
@@ -727,7 +836,7 @@ impl OperatorExecutorHelper {
                         &condition_context,
                         left_msg.timestamp().clone()
                     );
-                    self.manage_deadlines(deadline_events);
+                    self.manage_deadlines(deadline_events, sent_at);
 
                     // Add the events to the lattice.
                     self.lattice.add_events(events).await;
@@ -741,7 +850,7 @@ impl OperatorExecutorHelper {
                 // TODO(Sukrit): The start and end conditions are evaluated in the thread of the
                 // OperatorExecutor, and can be moved to a separate task if they become a
                 // bottleneck.
-                Ok(right_msg) = right_read_stream.async_read() => {
+                Ok((right_msg, sent_at)) = right_read_stream.async_read() => {
                     let events = match right_msg.data() {
                         // Data message
                         Some(_) => {
@@ -789,7 +898,7 @@ impl OperatorExecutorHelper {
                         &condition_context,
                         right_msg.timestamp().clone()
                     );
-                    self.manage_deadlines(deadline_events);
+                    self.manage_deadlines(deadline_events, sent_at);
 
                     // Add the events to the lattice.
                     self.lattice.add_events(events).await;
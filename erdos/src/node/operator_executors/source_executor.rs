@@ -6,12 +6,14 @@ use tokio::{
 };
 
 use crate::{
+    communication::ControlMessage,
     dataflow::{
         operator::{OperatorConfig, Source},
         stream::WriteStreamT,
         Data, Message, Timestamp, WriteStream,
     },
     node::{
+        clock::Clock,
         lattice::ExecutionLattice,
         operator_executors::{OperatorExecutorHelper, OperatorExecutorT},
         worker::{EventNotification, OperatorExecutorNotification, WorkerNotification},
@@ -55,7 +57,8 @@ where
         channel_to_worker: mpsc::UnboundedSender<WorkerNotification>,
         _channel_to_event_runners: broadcast::Sender<EventNotification>,
     ) {
-        self.helper.synchronize().await;
+        // Sources cannot register deadlines.
+        self.helper.synchronize(0).await;
 
         tracing::debug!(
             "Node {}: running operator {}",
@@ -104,4 +107,20 @@ where
     fn operator_id(&self) -> OperatorId {
         self.config.id
     }
+
+    fn config(&self) -> &OperatorConfig {
+        &self.config
+    }
+
+    fn set_control_channels(
+        &mut self,
+        control_tx: mpsc::UnboundedSender<ControlMessage>,
+        control_rx: mpsc::UnboundedReceiver<ControlMessage>,
+    ) {
+        self.helper.set_control_channels(control_tx, control_rx);
+    }
+
+    fn set_clock(&mut self, clock: Clock) {
+        self.helper.set_clock(clock);
+    }
 }
@@ -1,11 +1,12 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use tokio::{
     sync::{broadcast, mpsc},
     task::JoinHandle,
+    time::Instant,
 };
 
-use crate::OperatorId;
+use crate::{dataflow::OperatorConfig, OperatorId};
 
 use super::{lattice::ExecutionLattice, operator_executors::OperatorExecutorT};
 
@@ -100,6 +101,10 @@ async fn event_runner(
 pub(crate) struct Worker {
     /// Number of tasks that execute events generate by operators.
     num_event_runners: usize,
+    /// Time operators are given to exit after being cancelled during shutdown.
+    shutdown_grace_period: Duration,
+    /// Configurations of the operators, used to cancel them during shutdown.
+    operator_configs: HashMap<OperatorId, OperatorConfig>,
     // Lattices of events for each operator.
     lattices: HashMap<OperatorId, Arc<ExecutionLattice>>,
     // Arc of the above lattices which is shared with the event runners.
@@ -121,7 +126,7 @@ pub(crate) struct Worker {
 }
 
 impl Worker {
-    pub fn new(num_event_runners: usize) -> Self {
+    pub fn new(num_event_runners: usize, shutdown_grace_period: Duration) -> Self {
         let (events_channel, _) = broadcast::channel(16);
         // Only need to store most recent update to lattices or shutdown.
         let (event_runner_notifications, _) = broadcast::channel(1);
@@ -131,6 +136,8 @@ impl Worker {
         let (worker_notifications_tx, worker_notifications_rx) = mpsc::unbounded_channel();
         Self {
             num_event_runners,
+            shutdown_grace_period,
+            operator_configs: HashMap::new(),
             lattices: HashMap::new(),
             lattices_arc: Arc::new(HashMap::new()),
             operator_executor_tasks: HashMap::new(),
@@ -154,35 +161,63 @@ impl Worker {
         }
     }
 
+    /// Runs until all operators have been destroyed, and then shuts down the event runners.
     pub async fn execute(&mut self) {
         // Manage destruction of operators.
         // TODO: in the future, scale up/down event runners, spawn new operators.
-        while let Some(notification) = self.worker_notifications_rx.recv().await {
-            match notification {
-                WorkerNotification::DestroyedOperator(operator_id) => {
+        while !self.operator_executor_tasks.is_empty() {
+            match self.worker_notifications_rx.recv().await {
+                Some(WorkerNotification::DestroyedOperator(operator_id)) => {
                     self.on_destroyed_operator(operator_id).await
                 }
+                None => break,
             }
         }
         self.shutdown().await;
     }
 
-    async fn shutdown(&mut self) {
+    /// Notifies all operators to shut down, and waits for the operators and event runners to exit.
+    ///
+    /// Operators which do not exit within the shutdown grace period are reported and detached.
+    pub async fn shutdown(&mut self) {
         tracing::info!("Worker: shutting down");
+        // Signal operators with long-running `run` methods to return.
+        for config in self.operator_configs.values() {
+            config.cancellation_token.cancel();
+        }
         // Shutdown operator executors.
+        // Error indicates that all operator executors have already shut down.
         self.operator_executor_notifications
             .send(OperatorExecutorNotification::Shutdown)
-            .unwrap();
-        let operator_ids: Vec<_> = self.operator_executor_tasks.keys().cloned().collect();
-        for operator_id in operator_ids {
-            self.on_destroyed_operator(operator_id).await;
+            .ok();
+        let deadline = Instant::now() + self.shutdown_grace_period;
+        let tasks: Vec<_> = self.operator_executor_tasks.drain().collect();
+        for (operator_id, task) in tasks {
+            match tokio::time::timeout_at(deadline, task).await {
+                Ok(Ok(_)) => tracing::debug!(
+                    "Worker: shut down task for operator executor with ID {}",
+                    operator_id
+                ),
+                Ok(Err(e)) => tracing::error!(
+                    "Worker: error during shut down of task for operator executor with ID {}: {:?}",
+                    operator_id,
+                    e
+                ),
+                // The task cannot be aborted while it executes blocking operator code.
+                Err(_) => tracing::error!(
+                    "Worker: operator {} did not exit within the shutdown grace period of {:?}",
+                    self.operator_configs[&operator_id].get_name(),
+                    self.shutdown_grace_period
+                ),
+            }
         }
         tracing::debug!("[Worker] shut down all operator executors");
 
         // Shutdown event runners.
+        // Error indicates that all event runners have already shut down.
         self.event_runner_notifications
             .send(EventRunnerNotification::Shutdown)
-            .unwrap();
+            .ok();
         for (i, event_runner_task) in self.event_runner_tasks.drain(..).enumerate() {
             match event_runner_task.await {
                 Ok(_) => (),
@@ -200,6 +235,8 @@ impl Worker {
     async fn spawn_operator(&mut self, mut operator_executor: Box<dyn OperatorExecutorT>) {
         let operator_id = operator_executor.operator_id();
         tracing::debug!("Worker: spawning operator with ID {}", operator_id);
+        self.operator_configs
+            .insert(operator_id, operator_executor.config().clone());
         // Get lattice and share with event runners.
         self.lattices
             .insert(operator_id, operator_executor.lattice());
@@ -233,7 +270,7 @@ impl Worker {
                     operator_id
                 ),
                 Err(e) => tracing::error!(
-                    "Worker: error duing shut down of task for operator executor with ID {}: {:?}",
+                    "Worker: error during shut down of task for operator executor with ID {}: {:?}",
                     operator_id,
                     e
                 ),
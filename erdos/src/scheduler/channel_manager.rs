@@ -1,14 +1,21 @@
 use async_trait::async_trait;
 use serde::Deserialize;
-use std::{any::Any, collections::HashMap, sync::Arc};
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 use tokio::sync::{mpsc, Mutex};
 
 use crate::{
-    communication::{Pusher, PusherT, RecvEndpoint, SendEndpoint},
+    communication::{
+        ControlMessage, ControlMessageHandler, Delivery, Pusher, PusherT, RecvEndpoint,
+        SendEndpoint, SendOptions, UdpSender,
+    },
     dataflow::{
         graph::{Job, JobGraph},
-        stream::StreamId,
-        Data, Message, ReadStream, WriteStream,
+        stream::{errors::SendError, IngestStreamT, StreamId},
+        Data, Message, ReadStream, Timestamp, WriteStream,
     },
     node::NodeId,
     scheduler::endpoints_manager::{ChannelsToReceivers, ChannelsToSenders},
@@ -36,9 +43,14 @@ pub trait StreamEndpointsT: Send {
         other_node_id: NodeId,
         channels_to_senders: Arc<Mutex<ChannelsToSenders>>,
     ) -> Result<(), String>;
+    /// Adds a `RecvEndpoint` which receives messages sent from another node.
+    ///
+    /// If `close_on_failure` is set, the endpoint receives a top watermark if the other
+    /// node fails.
     fn add_inter_node_recv_endpoint(
         &mut self,
         receiver_pushers: &mut HashMap<StreamId, Box<dyn PusherT>>,
+        close_on_failure: bool,
     ) -> Result<(), String>;
 }
 
@@ -50,22 +62,29 @@ where
     stream_id: StreamId,
     /// The name of the stream.
     stream_name: String,
+    /// Options for sending messages to other nodes.
+    send_options: SendOptions,
     /// The receive endpoints of the stream.
     recv_endpoints: Vec<RecvEndpoint<Arc<Message<D>>>>,
     /// The send endpoints of the stream.
     send_endpoints: Vec<SendEndpoint<Arc<Message<D>>>>,
+    /// Whether the endpoint which sends to the multicast group was added. All nodes receive
+    /// from the same endpoint.
+    multicast_endpoint_added: bool,
 }
 
 impl<D> StreamEndpoints<D>
 where
     for<'a> D: Data + Deserialize<'a>,
 {
-    pub fn new(stream_id: StreamId, stream_name: String) -> Self {
+    pub fn new(stream_id: StreamId, stream_name: String, send_options: SendOptions) -> Self {
         Self {
             stream_id,
             stream_name,
+            send_options,
             recv_endpoints: Vec::new(),
             send_endpoints: Vec::new(),
+            multicast_endpoint_added: false,
         }
     }
 
@@ -118,24 +137,68 @@ where
         channels_to_senders: Arc<Mutex<ChannelsToSenders>>,
     ) -> Result<(), String> {
         let channels_to_senders = channels_to_senders.lock().await;
-        if let Some(tx) = channels_to_senders.clone_channel(other_node_id) {
-            self.add_send_endpoint(SendEndpoint::InterProcess(self.stream_id, tx));
-            Ok(())
-        } else {
-            Err(format!("Unable to clone channel to node {}", other_node_id))
-        }
+        let destination = match self.send_options.delivery {
+            Delivery::Reliable => {
+                return if let Some(tx) =
+                    channels_to_senders.clone_channel(other_node_id, self.send_options.priority)
+                {
+                    self.add_send_endpoint(SendEndpoint::InterProcess(
+                        self.stream_id,
+                        self.send_options,
+                        tx,
+                    ));
+                    Ok(())
+                } else {
+                    Err(format!("Unable to clone channel to node {}", other_node_id))
+                };
+            }
+            Delivery::BestEffort => {
+                channels_to_senders
+                    .udp_address(other_node_id)
+                    .ok_or_else(|| {
+                        format!("Unable to find the UDP address of node {}", other_node_id)
+                    })?
+            }
+            Delivery::Multicast(_) if self.multicast_endpoint_added => return Ok(()),
+            Delivery::Multicast(group) => {
+                self.multicast_endpoint_added = true;
+                group
+            }
+        };
+        let sender = UdpSender::new(
+            self.stream_id,
+            destination,
+            channels_to_senders.udp_statistics(),
+            channels_to_senders.udp_clock(),
+        )
+        .map_err(|e| {
+            format!(
+                "Unable to create a UDP sender of stream {} to {}: {}",
+                self.stream_id, destination, e
+            )
+        })?;
+        self.add_send_endpoint(SendEndpoint::BestEffort(sender));
+        Ok(())
     }
 
     fn add_inter_node_recv_endpoint(
         &mut self,
         receiver_pushers: &mut HashMap<StreamId, Box<dyn PusherT>>,
+        close_on_failure: bool,
     ) -> Result<(), String> {
         let pusher: &mut Box<dyn PusherT> = receiver_pushers
             .entry(self.stream_id)
             .or_insert_with(|| Box::new(Pusher::<Arc<Message<D>>>::new()));
         if let Some(pusher) = pusher.as_any().downcast_mut::<Pusher<Arc<Message<D>>>>() {
             let (tx, rx) = mpsc::unbounded_channel();
-            pusher.add_endpoint(SendEndpoint::InterThread(tx));
+            if close_on_failure {
+                pusher.add_endpoint_closed_on_failure(
+                    SendEndpoint::InterThread(tx),
+                    Arc::new(Message::new_watermark(Timestamp::Top)),
+                );
+            } else {
+                pusher.add_endpoint(SendEndpoint::InterThread(tx));
+            }
             self.add_recv_endpoint(RecvEndpoint::InterThread(rx));
             Ok(())
         } else {
@@ -154,6 +217,13 @@ pub(crate) struct ChannelManager {
     node_id: NodeId,
     /// Stores a `StreamEndpoints` for each stream id.
     stream_entries: HashMap<StreamId, Box<dyn StreamEndpointsT>>,
+    /// Ingest streams which the driver set up on this node.
+    ingest_streams: Vec<Box<dyn IngestStreamT>>,
+    /// Maps each [`LoopStream`](crate::dataflow::LoopStream) to the stream to which it connects.
+    loop_streams: HashMap<StreamId, StreamId>,
+    /// Maps each stream which crosses process boundaries at this node to a fingerprint of the
+    /// type of data sent on the stream.
+    stream_types: BTreeMap<StreamId, String>,
 }
 
 #[allow(dead_code)]
@@ -162,19 +232,28 @@ impl ChannelManager {
     /// for operators with streams containing dataflow channels to other nodes, and transport
     /// channels from TCP receivers to operators that are connected to streams originating on
     /// other nodes.
+    ///
+    /// Returns an error if another node sends or receives a different type of data on a stream
+    /// which connects it to this node.
     #[allow(clippy::needless_collect)]
     pub async fn new(
         job_graph: &JobGraph,
         node_id: NodeId,
         channels_to_receivers: Arc<Mutex<ChannelsToReceivers>>,
         channels_to_senders: Arc<Mutex<ChannelsToSenders>>,
-    ) -> Self {
+        control_handler: &mut ControlMessageHandler,
+    ) -> Result<Self, String> {
         let mut channel_manager = Self {
             node_id,
             stream_entries: HashMap::new(),
+            ingest_streams: Vec::new(),
+            loop_streams: job_graph.loop_streams(),
+            stream_types: BTreeMap::new(),
         };
 
         let mut receiver_pushers: HashMap<StreamId, Box<dyn PusherT>> = HashMap::new();
+        // Names and type fingerprints of the streams which cross process boundaries.
+        let mut inter_node_streams: BTreeMap<StreamId, (String, String)> = BTreeMap::new();
 
         let local_operator_ids: Vec<OperatorId> = job_graph
             .operators()
@@ -230,49 +309,131 @@ impl ChannelManager {
                                 destination_node_id,
                                 channels_to_senders.clone(),
                             )
-                            .await
-                            .unwrap();
+                            .await?;
+                        inter_node_streams
+                            .insert(stream.id(), (stream.name(), stream.type_fingerprint()));
                     }
                 }
             } else {
                 // The stream originates on another node.
-                let num_local_destinations = destinations
-                    .iter()
-                    .filter(|destination| {
-                        let destination_node_id = match destination {
-                            Job::Operator(operator_id) => {
-                                operators.get(operator_id).unwrap().config.node_id
-                            }
-                            // TODO: change this when ERDOS programs are submitted to a cluster.
-                            Job::Driver => 0,
-                        };
-                        node_id == destination_node_id
-                    })
-                    .count();
-                for _ in 0..num_local_destinations {
+                for destination in destinations {
+                    // Whether the destination is on this node, and whether it should close the
+                    // stream if the source node fails.
+                    let (destination_node_id, close_on_failure) = match destination {
+                        Job::Operator(operator_id) => {
+                            let config = &operators.get(&operator_id).unwrap().config;
+                            (config.node_id, config.close_streams_on_node_failure)
+                        }
+                        // TODO: change this when ERDOS programs are submitted to a cluster.
+                        Job::Driver => (0, false),
+                    };
+                    if node_id != destination_node_id {
+                        continue;
+                    }
                     let stream_endpoint_t = channel_manager
                         .stream_entries
                         .entry(stream.id())
                         .or_insert_with(|| stream.to_stream_endpoints_t());
                     stream_endpoint_t
-                        .add_inter_node_recv_endpoint(&mut receiver_pushers)
+                        .add_inter_node_recv_endpoint(&mut receiver_pushers, close_on_failure)
                         .unwrap();
+                    inter_node_streams
+                        .insert(stream.id(), (stream.name(), stream.type_fingerprint()));
                 }
             }
         }
 
+        channel_manager.stream_types = inter_node_streams
+            .iter()
+            .map(|(stream_id, (_, type_fingerprint))| (*stream_id, type_fingerprint.clone()))
+            .collect();
+        Self::check_stream_types(
+            node_id,
+            &inter_node_streams,
+            &channel_manager.stream_types,
+            control_handler,
+        )
+        .await?;
+
         // Send pushers to the DataReceiver which publishes received messages from TCP
         // on the proper transport channel.
         for (k, v) in receiver_pushers.into_iter() {
             channels_to_receivers.lock().await.send(k, v);
         }
-        channel_manager
+        Ok(channel_manager)
+    }
+
+    /// Exchanges the type fingerprints of streams which cross process boundaries with all other
+    /// nodes, and checks that both ends of each stream agree on the type of data sent.
+    ///
+    /// Otherwise, messages would be deserialized as the wrong type.
+    async fn check_stream_types(
+        node_id: NodeId,
+        inter_node_streams: &BTreeMap<StreamId, (String, String)>,
+        stream_types: &BTreeMap<StreamId, String>,
+        control_handler: &mut ControlMessageHandler,
+    ) -> Result<(), String> {
+        control_handler
+            .broadcast_to_nodes(ControlMessage::StreamTypes(node_id, stream_types.clone()))
+            .map_err(|e| format!("Error broadcasting stream types: {:?}", e))?;
+
+        let num_other_nodes = control_handler
+            .get_channels_to_nodes()
+            .lock()
+            .unwrap()
+            .len();
+        for _ in 0..num_other_nodes {
+            let (other_node_id, other_stream_types) = control_handler
+                .read_stream_types_msg()
+                .await
+                .map_err(|e| format!("Error receiving stream types: {:?}", e))?;
+            for (stream_id, other_type_fingerprint) in other_stream_types.iter() {
+                if let Some((name, type_fingerprint)) = inter_node_streams.get(stream_id) {
+                    if type_fingerprint != other_type_fingerprint {
+                        return Err(format!(
+                            "stream {} (ID: {}) carries {} on node {}, but {} on node {}",
+                            name,
+                            stream_id,
+                            type_fingerprint,
+                            node_id,
+                            other_type_fingerprint,
+                            other_node_id
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
     }
 
     pub fn node_id(&self) -> NodeId {
         self.node_id
     }
 
+    /// Returns the type fingerprints of the streams which cross process boundaries at this
+    /// node, which are also sent to the nodes that join later.
+    pub(crate) fn stream_types(&self) -> &BTreeMap<StreamId, String> {
+        &self.stream_types
+    }
+
+    /// Registers an ingest stream set up by the driver.
+    pub fn add_ingest_stream(&mut self, ingest_stream: Box<dyn IngestStreamT>) {
+        self.ingest_streams.push(ingest_stream);
+    }
+
+    /// Returns `true` if the driver closed all ingest streams.
+    pub fn ingest_streams_closed(&self) -> bool {
+        self.ingest_streams.iter().all(|s| s.is_closed())
+    }
+
+    /// Sends a top watermark on all ingest streams which are still open.
+    pub fn close_ingest_streams(&mut self) -> Result<(), SendError> {
+        for ingest_stream in self.ingest_streams.iter_mut() {
+            ingest_stream.close()?;
+        }
+        Ok(())
+    }
+
     /// Takes a `RecvEnvpoint` from a given stream.
     pub fn take_recv_endpoint<D>(
         &mut self,
@@ -332,10 +493,18 @@ impl ChannelManager {
     }
 
     /// This function can only be called once successfully.
+    ///
+    /// If `stream_id` corresponds to a [`LoopStream`](crate::dataflow::LoopStream), returns a
+    /// [`ReadStream`] for the stream to which the loop is connected.
     pub fn take_read_stream<D>(&mut self, stream_id: StreamId) -> Result<ReadStream<D>, String>
     where
         D: Data + for<'a> Deserialize<'a>,
     {
+        let stream_id = self
+            .loop_streams
+            .get(&stream_id)
+            .cloned()
+            .unwrap_or(stream_id);
         self.take_recv_endpoint(stream_id)
             .map(|endpoint| ReadStream::new(stream_id, &stream_id.to_string(), endpoint))
     }
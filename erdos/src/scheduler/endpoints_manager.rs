@@ -1,10 +1,10 @@
-use std::collections::HashMap;
-use tokio::sync::mpsc::UnboundedSender;
+use std::{collections::HashMap, net::SocketAddr};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
 use crate::{
-    communication::{InterProcessMessage, PusherT},
+    communication::{InterProcessMessage, Priority, PusherT, StreamStatisticsMap},
     dataflow::stream::StreamId,
-    node::NodeId,
+    node::{Clock, NodeId},
 };
 
 /// Wrapper used to update pushers in the TCP receiving.
@@ -16,17 +16,23 @@ pub struct ChannelsToReceivers {
     // It does not allow us to just check if the channel has a new message. We need this API in
     // the receivers, which regularly check if there are new pushers available.
     senders: Vec<UnboundedSender<(StreamId, Box<dyn PusherT>)>>,
+    /// The pushers sent so far, which are also sent to the receivers of nodes that join later.
+    pushers: Vec<(StreamId, Box<dyn PusherT>)>,
 }
 
 impl ChannelsToReceivers {
     pub fn new() -> Self {
         ChannelsToReceivers {
             senders: Vec::new(),
+            pushers: Vec::new(),
         }
     }
 
-    /// Adds a `mpsc::Sender` to a new receiver thread.
+    /// Adds a `mpsc::Sender` to a new receiver thread, and sends it the pushers sent so far.
     pub fn add_sender(&mut self, sender: UnboundedSender<(StreamId, Box<dyn PusherT>)>) {
+        for (stream_id, pusher) in self.pushers.iter() {
+            sender.send((*stream_id, pusher.clone())).unwrap();
+        }
         self.senders.push(sender);
     }
 
@@ -38,37 +44,110 @@ impl ChannelsToReceivers {
             let msg = (stream_id, pusher.clone());
             sender.send(msg).unwrap();
         }
+        self.pushers.push((stream_id, pusher));
     }
 }
 
 /// Wrapper used to store mappings between node ids and `mpsc::UnboundedSender` to sender threads.
 #[derive(Default)]
 pub struct ChannelsToSenders {
-    /// The ith sender corresponds to a TCP connection to the ith node.
-    senders: HashMap<NodeId, UnboundedSender<InterProcessMessage>>,
+    /// The senders for a node correspond to the queues of each priority of the connection to
+    /// the node.
+    senders: HashMap<(NodeId, Priority), UnboundedSender<InterProcessMessage>>,
+    /// The receiving ends of the queues to nodes which have not connected yet, from the highest
+    /// to the lowest priority.
+    pending_receivers: HashMap<NodeId, Vec<UnboundedReceiver<InterProcessMessage>>>,
+    /// The addresses at which nodes receive the datagrams of best-effort streams.
+    udp_addresses: HashMap<NodeId, SocketAddr>,
+    /// Statistics on the messages sent on best-effort streams.
+    udp_statistics: StreamStatisticsMap,
+    /// Clock on which best-effort streams send watermarks again.
+    udp_clock: Clock,
 }
 
 impl ChannelsToSenders {
     pub fn new() -> Self {
         ChannelsToSenders {
             senders: HashMap::new(),
+            pending_receivers: HashMap::new(),
+            udp_addresses: HashMap::new(),
+            udp_statistics: StreamStatisticsMap::default(),
+            udp_clock: Clock::System,
         }
     }
 
-    /// Adds a `mpsc::UnboundedSender` to a node.
+    /// Adds a `mpsc::UnboundedSender` to the queue of messages of `priority` to a node.
     pub fn add_sender(
         &mut self,
         node_id: NodeId,
+        priority: Priority,
         sender: tokio::sync::mpsc::UnboundedSender<InterProcessMessage>,
     ) {
-        self.senders.insert(node_id, sender);
+        self.senders.insert((node_id, priority), sender);
+    }
+
+    /// Creates the queues of messages to a node which has not connected yet, so that messages
+    /// to the node are queued until it connects.
+    pub fn add_pending_node(&mut self, node_id: NodeId) {
+        let rxs = self.create_queues(node_id);
+        self.pending_receivers.insert(node_id, rxs);
+    }
+
+    /// Returns the receiving ends of the queues of messages to a node from the highest to the
+    /// lowest priority, and creates the queues unless the node was pending.
+    pub fn take_receivers(
+        &mut self,
+        node_id: NodeId,
+    ) -> Vec<UnboundedReceiver<InterProcessMessage>> {
+        match self.pending_receivers.remove(&node_id) {
+            Some(rxs) => rxs,
+            None => self.create_queues(node_id),
+        }
+    }
+
+    fn create_queues(&mut self, node_id: NodeId) -> Vec<UnboundedReceiver<InterProcessMessage>> {
+        Priority::ALL
+            .iter()
+            .map(|&priority| {
+                let (tx, rx) = mpsc::unbounded_channel();
+                self.add_sender(node_id, priority, tx);
+                rx
+            })
+            .collect()
     }
 
-    /// Returns the associated `mpsc::UnboundedSender` for a given node.
+    /// Returns the associated `mpsc::UnboundedSender` for a given node and priority.
     pub fn clone_channel(
         &self,
         node_id: NodeId,
+        priority: Priority,
     ) -> Option<tokio::sync::mpsc::UnboundedSender<InterProcessMessage>> {
-        self.senders.get(&node_id).cloned()
+        self.senders.get(&(node_id, priority)).cloned()
+    }
+
+    /// Sets the addresses at which nodes receive best-effort streams, where to record the
+    /// statistics of the messages sent on them, and the clock on which they are timed.
+    pub fn set_udp_addresses(
+        &mut self,
+        udp_addresses: HashMap<NodeId, SocketAddr>,
+        udp_statistics: StreamStatisticsMap,
+        udp_clock: Clock,
+    ) {
+        self.udp_addresses = udp_addresses;
+        self.udp_statistics = udp_statistics;
+        self.udp_clock = udp_clock;
+    }
+
+    /// Returns the address at which a node receives best-effort streams.
+    pub fn udp_address(&self, node_id: NodeId) -> Option<SocketAddr> {
+        self.udp_addresses.get(&node_id).copied()
+    }
+
+    pub fn udp_statistics(&self) -> StreamStatisticsMap {
+        self.udp_statistics.clone()
+    }
+
+    pub fn udp_clock(&self) -> Clock {
+        self.udp_clock.clone()
     }
 }
@@ -0,0 +1,1135 @@
+//! Harnesses for testing a single operator without running a [`Node`](crate::node::Node).
+//!
+//! A harness instantiates an operator, invokes its `setup` method, and executes its callbacks in
+//! response to scripted messages and watermarks. Callbacks are ordered by an execution lattice
+//! as they are on a [`Node`](crate::node::Node), but run one at a time on the calling thread, so
+//! tests are deterministic. Deadlines are driven by a virtual clock which only advances when
+//! `advance_time` is called.
+//!
+//! [`LocalCluster`] runs several [`Node`](crate::node::Node)s within the test process, which
+//! allows testing how operators behave when placed on different nodes.
+//!
+//! # Example
+//! The following example checks that an operator doubles the data it receives.
+//! ```
+//! use erdos::{
+//!     dataflow::{
+//!         context::OneInOneOutContext, operator::OneInOneOut, stream::WriteStreamT, Message,
+//!         Timestamp,
+//!     },
+//!     testing::OneInOneOutHarness,
+//!     OperatorConfig,
+//! };
+//!
+//! struct DoubleOperator {}
+//!
+//! impl OneInOneOut<(), usize, usize> for DoubleOperator {
+//!     fn on_data(&mut self, ctx: &mut OneInOneOutContext<(), usize>, data: &usize) {
+//!         let timestamp = ctx.timestamp().clone();
+//!         ctx.write_stream()
+//!             .send(Message::new_message(timestamp, 2 * data))
+//!             .unwrap();
+//!     }
+//!
+//!     fn on_watermark(&mut self, _ctx: &mut OneInOneOutContext<(), usize>) {}
+//! }
+//!
+//! let mut harness =
+//!     OneInOneOutHarness::new(|| DoubleOperator {}, || (), OperatorConfig::new());
+//! harness.send(Message::new_message(Timestamp::Time(vec![1]), 21));
+//! harness.send(Message::new_watermark(Timestamp::Time(vec![1])));
+//! harness.process();
+//!
+//! let output = harness.output();
+//! assert_eq!(output[0].data(), Some(&42));
+//! assert_eq!(output[1], Message::new_watermark(Timestamp::Time(vec![1])));
+//! ```
+use std::{
+    cmp,
+    collections::{HashSet, VecDeque},
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::{executor::block_on, FutureExt};
+use rand::{Rng, SeedableRng, StdRng};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::{
+    communication::{RecvEndpoint, SendEndpoint},
+    dataflow::{
+        context::SetupContext,
+        deadlines::{ConditionContext, DeadlineEvent, DeadlineId},
+        operator::*,
+        stream::WriteStreamT,
+        AppendableState, CancellationToken, Data, Message, ReadStream, State, Timestamp,
+        WriteStream,
+    },
+    node::{
+        clock::DelayQueue,
+        lattice::ExecutionLattice,
+        operator_event::OperatorEvent,
+        operator_executors::{
+            OneInMessageProcessorT, OneInOneOutMessageProcessor, OneInTwoOutMessageProcessor,
+            ParallelOneInOneOutMessageProcessor, ParallelOneInTwoOutMessageProcessor,
+            ParallelSinkMessageProcessor, ParallelTwoInOneOutMessageProcessor,
+            SinkMessageProcessor, TwoInMessageProcessorT, TwoInOneOutMessageProcessor,
+        },
+        Clock, Node, NodeHandle, NodeId, VirtualClock,
+    },
+    Configuration, Uuid,
+};
+
+/// Generates the IDs of a harness's operator and streams.
+///
+/// Each harness has its own generator, so the IDs do not depend on the harnesses and graphs
+/// created before.
+struct IdGenerator(StdRng);
+
+impl IdGenerator {
+    fn new() -> Self {
+        Self(StdRng::from_seed(crate::RNG_SEED))
+    }
+
+    fn next_id(&mut self) -> Uuid {
+        let mut bytes = [0u8; 16];
+        self.0.fill_bytes(&mut bytes);
+        Uuid(bytes)
+    }
+}
+
+/// Creates a [`ReadStream`] along with the endpoint used to send messages on it.
+fn input_stream<D>(
+    name: &str,
+    ids: &mut IdGenerator,
+) -> (SendEndpoint<Arc<Message<D>>>, ReadStream<D>)
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    let (tx, rx) = mpsc::unbounded_channel();
+    let read_stream = ReadStream::new(ids.next_id(), name, RecvEndpoint::InterThread(rx));
+    (SendEndpoint::InterThread(tx), read_stream)
+}
+
+/// Creates a [`WriteStream`] along with the endpoint used to collect the messages sent on it.
+fn output_stream<D>(
+    name: &str,
+    ids: &mut IdGenerator,
+) -> (WriteStream<D>, RecvEndpoint<Arc<Message<D>>>)
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    let (tx, rx) = mpsc::unbounded_channel();
+    let write_stream = WriteStream::new(ids.next_id(), name, vec![SendEndpoint::InterThread(tx)]);
+    (write_stream, RecvEndpoint::InterThread(rx))
+}
+
+/// Returns all messages sent on an output stream since the last call.
+fn collect_output<D>(endpoint: &mut RecvEndpoint<Arc<Message<D>>>) -> Vec<Message<D>>
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    let mut messages = Vec::new();
+    while let Ok(msg) = endpoint.try_read() {
+        messages.push(Message::clone(&msg));
+    }
+    messages
+}
+
+/// Assigns an ID to the operator, as connecting the operator to a graph would.
+fn prepare_config(mut config: OperatorConfig, ids: &mut IdGenerator) -> OperatorConfig {
+    config.id = ids.next_id();
+    config
+}
+
+/// Executes all runnable events in the lattice on the calling thread.
+fn execute_events(lattice: &ExecutionLattice, events: Vec<OperatorEvent>) {
+    block_on(async {
+        lattice.add_events(events).await;
+        while let Some((event, event_id)) = lattice.get_event().await {
+            (event.callback)();
+            lattice.mark_as_completed(event_id).await;
+        }
+    });
+}
+
+/// The message processors of operators with one or two read streams, as driven by harnesses.
+trait HarnessProcessorT<S> {
+    /// Invokes the handler of a deadline which expired, unless the deadline is disarmed.
+    ///
+    /// Returns whether the handler was invoked.
+    fn handle_deadline(&self, setup_context: &mut SetupContext<S>, event: &DeadlineEvent) -> bool;
+
+    /// Invokes the operator's `destroy` method, and closes the write streams.
+    fn destroy(&mut self);
+}
+
+impl<S, T> HarnessProcessorT<S> for dyn OneInMessageProcessorT<S, T>
+where
+    for<'a> T: Data + Deserialize<'a>,
+{
+    fn handle_deadline(&self, setup_context: &mut SetupContext<S>, event: &DeadlineEvent) -> bool {
+        if self.disarm_deadline(event) {
+            return false;
+        }
+        self.invoke_handler(setup_context, event.id, event.timestamp.clone());
+        true
+    }
+
+    fn destroy(&mut self) {
+        self.execute_destroy();
+        self.cleanup();
+    }
+}
+
+impl<S, T, U> HarnessProcessorT<S> for dyn TwoInMessageProcessorT<S, T, U>
+where
+    for<'a> T: Data + Deserialize<'a>,
+    for<'a> U: Data + Deserialize<'a>,
+{
+    fn handle_deadline(&self, setup_context: &mut SetupContext<S>, event: &DeadlineEvent) -> bool {
+        if self.disarm_deadline(event) {
+            return false;
+        }
+        self.invoke_handler(setup_context, event.id, event.timestamp.clone());
+        true
+    }
+
+    fn destroy(&mut self) {
+        self.execute_destroy();
+        self.cleanup();
+    }
+}
+
+/// Arms deadlines on a [`VirtualClock`], using the same queue as the operator executors.
+struct VirtualDeadlines {
+    clock: VirtualClock,
+    queue: DelayQueue<DeadlineEvent>,
+    /// Deadlines which are armed.
+    armed: HashSet<DeadlineId>,
+    /// Deadlines whose handlers were invoked.
+    missed: Vec<(DeadlineId, Timestamp)>,
+}
+
+impl VirtualDeadlines {
+    fn new() -> Self {
+        let clock = VirtualClock::new();
+        Self {
+            queue: DelayQueue::new(Clock::Virtual(clock.clone())),
+            clock,
+            armed: HashSet::new(),
+            missed: Vec::new(),
+        }
+    }
+
+    fn arm(&mut self, deadline_events: Vec<DeadlineEvent>) {
+        for event in deadline_events {
+            // Only one instance of each deadline may be armed, as in the operator executors.
+            if self.armed.insert(event.id) {
+                let duration = event.duration;
+                self.queue.insert(event, duration);
+            }
+        }
+    }
+
+    /// Advances the virtual clock, and handles the deadlines which expired in order of expiry.
+    fn advance<S, P>(
+        &mut self,
+        duration: Duration,
+        processor: &P,
+        setup_context: &mut SetupContext<S>,
+        condition_context: &mut ConditionContext,
+    ) where
+        P: HarnessProcessorT<S> + ?Sized,
+    {
+        self.clock.advance(duration);
+        // Deadlines which expired are received immediately.
+        while let Some(Some(event)) = self.queue.receive().now_or_never() {
+            self.armed.remove(&event.id);
+            if processor.handle_deadline(setup_context, &event) {
+                self.missed.push((event.id, event.timestamp.clone()));
+            }
+            for stream_id in event.read_stream_ids {
+                condition_context.clear_state(stream_id, event.timestamp.clone());
+            }
+        }
+    }
+}
+
+/// Drives an operator with a single read stream.
+struct OneInHarness<S, T>
+where
+    for<'a> T: Data + Deserialize<'a>,
+{
+    processor: Box<dyn OneInMessageProcessorT<S, T>>,
+    setup_context: SetupContext<S>,
+    input: SendEndpoint<Arc<Message<T>>>,
+    read_stream: ReadStream<T>,
+    lattice: ExecutionLattice,
+    condition_context: ConditionContext,
+    deadlines: VirtualDeadlines,
+}
+
+impl<S, T> OneInHarness<S, T>
+where
+    for<'a> T: Data + Deserialize<'a>,
+{
+    fn new(mut processor: Box<dyn OneInMessageProcessorT<S, T>>, ids: &mut IdGenerator) -> Self {
+        let (input, mut read_stream) = input_stream("input", ids);
+        let setup_context = processor.execute_setup(&mut read_stream);
+        Self {
+            processor,
+            setup_context,
+            input,
+            read_stream,
+            lattice: ExecutionLattice::new(),
+            condition_context: ConditionContext::new(),
+            deadlines: VirtualDeadlines::new(),
+        }
+    }
+
+    fn send(&mut self, msg: Message<T>) {
+        self.input.send(Arc::new(msg)).unwrap();
+    }
+
+    fn run(&mut self) {
+        self.processor.execute_run(&mut self.read_stream);
+    }
+
+    fn process(&mut self) {
+        while let Ok(msg) = self.read_stream.try_read() {
+            let stream_id = self.read_stream.id();
+            let timestamp = msg.timestamp().clone();
+            let event = if msg.data().is_some() {
+                self.condition_context
+                    .increment_msg_count(stream_id, timestamp.clone());
+                self.processor.message_cb_event(Arc::new(msg))
+            } else {
+                self.condition_context
+                    .notify_watermark_arrival(stream_id, timestamp.clone());
+                self.processor.watermark_cb_event(&timestamp)
+            };
+            let deadline_events = self.processor.arm_deadlines(
+                &mut self.setup_context,
+                vec![stream_id],
+                &self.condition_context,
+                timestamp,
+            );
+            self.deadlines.arm(deadline_events);
+            execute_events(&self.lattice, vec![event]);
+        }
+    }
+
+    fn advance_time(&mut self, duration: Duration) {
+        self.deadlines.advance(
+            duration,
+            &*self.processor,
+            &mut self.setup_context,
+            &mut self.condition_context,
+        );
+    }
+
+    fn destroy(&mut self) {
+        self.processor.destroy();
+    }
+}
+
+/// Drives an operator with two read streams.
+struct TwoInHarness<S, T, U>
+where
+    for<'a> T: Data + Deserialize<'a>,
+    for<'a> U: Data + Deserialize<'a>,
+{
+    processor: Box<dyn TwoInMessageProcessorT<S, T, U>>,
+    setup_context: SetupContext<S>,
+    left_input: SendEndpoint<Arc<Message<T>>>,
+    left_read_stream: ReadStream<T>,
+    right_input: SendEndpoint<Arc<Message<U>>>,
+    right_read_stream: ReadStream<U>,
+    /// Whether each pending message was sent on the left stream, in the order the messages
+    /// were sent.
+    pending: VecDeque<bool>,
+    left_watermark: Timestamp,
+    right_watermark: Timestamp,
+    min_watermark: Timestamp,
+    lattice: ExecutionLattice,
+    condition_context: ConditionContext,
+    deadlines: VirtualDeadlines,
+}
+
+impl<S, T, U> TwoInHarness<S, T, U>
+where
+    for<'a> T: Data + Deserialize<'a>,
+    for<'a> U: Data + Deserialize<'a>,
+{
+    fn new(mut processor: Box<dyn TwoInMessageProcessorT<S, T, U>>, ids: &mut IdGenerator) -> Self {
+        let (left_input, mut left_read_stream) = input_stream("left_input", ids);
+        let (right_input, mut right_read_stream) = input_stream("right_input", ids);
+        let setup_context = processor.execute_setup(&mut left_read_stream, &mut right_read_stream);
+        Self {
+            processor,
+            setup_context,
+            left_input,
+            left_read_stream,
+            right_input,
+            right_read_stream,
+            pending: VecDeque::new(),
+            left_watermark: Timestamp::Bottom,
+            right_watermark: Timestamp::Bottom,
+            min_watermark: Timestamp::Bottom,
+            lattice: ExecutionLattice::new(),
+            condition_context: ConditionContext::new(),
+            deadlines: VirtualDeadlines::new(),
+        }
+    }
+
+    fn send_left(&mut self, msg: Message<T>) {
+        self.left_input.send(Arc::new(msg)).unwrap();
+        self.pending.push_back(true);
+    }
+
+    fn send_right(&mut self, msg: Message<U>) {
+        self.right_input.send(Arc::new(msg)).unwrap();
+        self.pending.push_back(false);
+    }
+
+    fn run(&mut self) {
+        self.processor
+            .execute_run(&mut self.left_read_stream, &mut self.right_read_stream);
+    }
+
+    /// Returns a watermark callback if the minimum watermark across both streams advanced.
+    fn advance_watermark(&mut self) -> Vec<OperatorEvent> {
+        let min_watermark = cmp::min(&self.left_watermark, &self.right_watermark).clone();
+        if min_watermark > self.min_watermark {
+            self.min_watermark = min_watermark;
+            vec![self.processor.watermark_cb_event(&self.min_watermark)]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn process(&mut self) {
+        while let Some(is_left) = self.pending.pop_front() {
+            let (stream_id, timestamp, events) = if is_left {
+                let msg = match self.left_read_stream.try_read() {
+                    Ok(msg) => msg,
+                    // The message was consumed by `run`.
+                    Err(_) => continue,
+                };
+                let stream_id = self.left_read_stream.id();
+                let timestamp = msg.timestamp().clone();
+                let events = if msg.data().is_some() {
+                    self.condition_context
+                        .increment_msg_count(stream_id, timestamp.clone());
+                    vec![self.processor.left_message_cb_event(Arc::new(msg))]
+                } else {
+                    self.condition_context
+                        .notify_watermark_arrival(stream_id, timestamp.clone());
+                    self.left_watermark = timestamp.clone();
+                    self.advance_watermark()
+                };
+                (stream_id, timestamp, events)
+            } else {
+                let msg = match self.right_read_stream.try_read() {
+                    Ok(msg) => msg,
+                    // The message was consumed by `run`.
+                    Err(_) => continue,
+                };
+                let stream_id = self.right_read_stream.id();
+                let timestamp = msg.timestamp().clone();
+                let events = if msg.data().is_some() {
+                    self.condition_context
+                        .increment_msg_count(stream_id, timestamp.clone());
+                    vec![self.processor.right_message_cb_event(Arc::new(msg))]
+                } else {
+                    self.condition_context
+                        .notify_watermark_arrival(stream_id, timestamp.clone());
+                    self.right_watermark = timestamp.clone();
+                    self.advance_watermark()
+                };
+                (stream_id, timestamp, events)
+            };
+            let deadline_events = self.processor.arm_deadlines(
+                &mut self.setup_context,
+                vec![self.left_read_stream.id(), self.right_read_stream.id()],
+                &self.condition_context,
+                timestamp,
+            );
+            self.deadlines.arm(deadline_events);
+            execute_events(&self.lattice, events);
+        }
+    }
+
+    fn advance_time(&mut self, duration: Duration) {
+        self.deadlines.advance(
+            duration,
+            &*self.processor,
+            &mut self.setup_context,
+            &mut self.condition_context,
+        );
+    }
+
+    fn destroy(&mut self) {
+        self.processor.destroy();
+    }
+}
+
+/*************************************************************************************************
+ * Source                                                                                        *
+ ************************************************************************************************/
+
+/// Tests a [`Source`] operator.
+///
+/// [`SourceHarness::run`] invokes the operator's `run` method on the calling thread. Operators
+/// which run until cancelled can be stopped by cancelling the token returned by
+/// [`SourceHarness::cancellation_token`] from another thread.
+pub struct SourceHarness<O, T>
+where
+    O: Source<T>,
+    for<'a> T: Data + Deserialize<'a>,
+{
+    config: OperatorConfig,
+    operator: O,
+    write_stream: WriteStream<T>,
+    output: RecvEndpoint<Arc<Message<T>>>,
+}
+
+impl<O, T> SourceHarness<O, T>
+where
+    O: Source<T>,
+    for<'a> T: Data + Deserialize<'a>,
+{
+    pub fn new(operator_fn: impl Fn() -> O, config: OperatorConfig) -> Self {
+        let mut ids = IdGenerator::new();
+        let (write_stream, output) = output_stream("output", &mut ids);
+        Self {
+            config: prepare_config(config, &mut ids),
+            operator: operator_fn(),
+            write_stream,
+            output,
+        }
+    }
+
+    /// Invokes the operator's `run` method.
+    pub fn run(&mut self) {
+        self.operator.run(&self.config, &mut self.write_stream);
+    }
+
+    /// Returns the token which signals the operator to return from `run`.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.config.cancellation_token.clone()
+    }
+
+    /// Invokes the operator's `destroy` method, and closes the write stream.
+    pub fn destroy(&mut self) {
+        self.operator.destroy();
+        if !self.write_stream.is_closed() {
+            self.write_stream
+                .send(Message::new_watermark(Timestamp::Top))
+                .unwrap();
+        }
+    }
+
+    /// Returns the messages sent on the write stream since the last call.
+    pub fn output(&mut self) -> Vec<Message<T>> {
+        collect_output(&mut self.output)
+    }
+}
+
+/*************************************************************************************************
+ * Sink                                                                                          *
+ ************************************************************************************************/
+
+/// Tests a [`Sink`] or [`ParallelSink`] operator.
+pub struct SinkHarness<S, T>
+where
+    for<'a> T: Data + Deserialize<'a>,
+{
+    inner: OneInHarness<S, T>,
+}
+
+impl<S, T> SinkHarness<S, T>
+where
+    for<'a> T: Data + Deserialize<'a>,
+{
+    /// Instantiates a [`Sink`] operator and invokes its `setup` method.
+    pub fn new<O>(
+        operator_fn: impl Fn() -> O + Send,
+        state_fn: impl Fn() -> S + Send,
+        config: OperatorConfig,
+    ) -> Self
+    where
+        O: 'static + Sink<S, T>,
+        S: State,
+    {
+        let mut ids = IdGenerator::new();
+        let processor =
+            SinkMessageProcessor::new(prepare_config(config, &mut ids), operator_fn, state_fn);
+        Self {
+            inner: OneInHarness::new(Box::new(processor), &mut ids),
+        }
+    }
+
+    /// Instantiates a [`ParallelSink`] operator and invokes its `setup` method.
+    pub fn new_parallel<O, U>(
+        operator_fn: impl Fn() -> O + Send,
+        state_fn: impl Fn() -> S + Send,
+        config: OperatorConfig,
+    ) -> Self
+    where
+        O: 'static + ParallelSink<S, T, U>,
+        S: AppendableState<U>,
+        U: 'static + Send + Sync,
+    {
+        let mut ids = IdGenerator::new();
+        let processor = ParallelSinkMessageProcessor::new(
+            prepare_config(config, &mut ids),
+            operator_fn,
+            state_fn,
+        );
+        Self {
+            inner: OneInHarness::new(Box::new(processor), &mut ids),
+        }
+    }
+
+    /// Sends a message on the read stream. The message is processed by [`SinkHarness::run`] or
+    /// [`SinkHarness::process`].
+    pub fn send(&mut self, msg: Message<T>) {
+        self.inner.send(msg)
+    }
+
+    /// Invokes the operator's `run` method.
+    pub fn run(&mut self) {
+        self.inner.run()
+    }
+
+    /// Invokes the callbacks for all messages sent on the read stream.
+    pub fn process(&mut self) {
+        self.inner.process()
+    }
+
+    /// Advances the virtual clock, and invokes the handlers of deadlines which were missed.
+    pub fn advance_time(&mut self, duration: Duration) {
+        self.inner.advance_time(duration)
+    }
+
+    /// Returns the IDs and timestamps of the deadlines whose handlers were invoked.
+    pub fn missed_deadlines(&self) -> &[(DeadlineId, Timestamp)] {
+        &self.inner.deadlines.missed
+    }
+
+    /// Invokes the operator's `destroy` method.
+    pub fn destroy(&mut self) {
+        self.inner.destroy()
+    }
+}
+
+/*************************************************************************************************
+ * OneInOneOut                                                                                   *
+ ************************************************************************************************/
+
+/// Tests a [`OneInOneOut`] or [`ParallelOneInOneOut`] operator.
+pub struct OneInOneOutHarness<S, T, U>
+where
+    for<'a> T: Data + Deserialize<'a>,
+    for<'a> U: Data + Deserialize<'a>,
+{
+    inner: OneInHarness<S, T>,
+    output: RecvEndpoint<Arc<Message<U>>>,
+}
+
+impl<S, T, U> OneInOneOutHarness<S, T, U>
+where
+    for<'a> T: Data + Deserialize<'a>,
+    for<'a> U: Data + Deserialize<'a>,
+{
+    /// Instantiates a [`OneInOneOut`] operator and invokes its `setup` method.
+    pub fn new<O>(
+        operator_fn: impl Fn() -> O + Send,
+        state_fn: impl Fn() -> S + Send,
+        config: OperatorConfig,
+    ) -> Self
+    where
+        O: 'static + OneInOneOut<S, T, U>,
+        S: State,
+    {
+        let mut ids = IdGenerator::new();
+        let (write_stream, output) = output_stream("output", &mut ids);
+        let processor = OneInOneOutMessageProcessor::new(
+            prepare_config(config, &mut ids),
+            operator_fn,
+            state_fn,
+            write_stream,
+        );
+        Self {
+            inner: OneInHarness::new(Box::new(processor), &mut ids),
+            output,
+        }
+    }
+
+    /// Instantiates a [`ParallelOneInOneOut`] operator and invokes its `setup` method.
+    pub fn new_parallel<O, V>(
+        operator_fn: impl Fn() -> O + Send,
+        state_fn: impl Fn() -> S + Send,
+        config: OperatorConfig,
+    ) -> Self
+    where
+        O: 'static + ParallelOneInOneOut<S, T, U, V>,
+        S: AppendableState<V>,
+        V: 'static + Send + Sync,
+    {
+        let mut ids = IdGenerator::new();
+        let (write_stream, output) = output_stream("output", &mut ids);
+        let processor = ParallelOneInOneOutMessageProcessor::new(
+            prepare_config(config, &mut ids),
+            operator_fn,
+            state_fn,
+            write_stream,
+        );
+        Self {
+            inner: OneInHarness::new(Box::new(processor), &mut ids),
+            output,
+        }
+    }
+
+    /// Sends a message on the read stream. The message is processed by
+    /// [`OneInOneOutHarness::run`] or [`OneInOneOutHarness::process`].
+    pub fn send(&mut self, msg: Message<T>) {
+        self.inner.send(msg)
+    }
+
+    /// Invokes the operator's `run` method.
+    pub fn run(&mut self) {
+        self.inner.run()
+    }
+
+    /// Invokes the callbacks for all messages sent on the read stream.
+    pub fn process(&mut self) {
+        self.inner.process()
+    }
+
+    /// Advances the virtual clock, and invokes the handlers of deadlines which were missed.
+    pub fn advance_time(&mut self, duration: Duration) {
+        self.inner.advance_time(duration)
+    }
+
+    /// Returns the IDs and timestamps of the deadlines whose handlers were invoked.
+    pub fn missed_deadlines(&self) -> &[(DeadlineId, Timestamp)] {
+        &self.inner.deadlines.missed
+    }
+
+    /// Invokes the operator's `destroy` method, and closes the write stream.
+    pub fn destroy(&mut self) {
+        self.inner.destroy()
+    }
+
+    /// Returns the messages sent on the write stream since the last call.
+    pub fn output(&mut self) -> Vec<Message<U>> {
+        collect_output(&mut self.output)
+    }
+}
+
+/*************************************************************************************************
+ * OneInTwoOut                                                                                   *
+ ************************************************************************************************/
+
+/// Tests a [`OneInTwoOut`] or [`ParallelOneInTwoOut`] operator.
+pub struct OneInTwoOutHarness<S, T, U, V>
+where
+    for<'a> T: Data + Deserialize<'a>,
+    for<'a> U: Data + Deserialize<'a>,
+    for<'a> V: Data + Deserialize<'a>,
+{
+    inner: OneInHarness<S, T>,
+    left_output: RecvEndpoint<Arc<Message<U>>>,
+    right_output: RecvEndpoint<Arc<Message<V>>>,
+}
+
+impl<S, T, U, V> OneInTwoOutHarness<S, T, U, V>
+where
+    for<'a> T: Data + Deserialize<'a>,
+    for<'a> U: Data + Deserialize<'a>,
+    for<'a> V: Data + Deserialize<'a>,
+{
+    /// Instantiates a [`OneInTwoOut`] operator and invokes its `setup` method.
+    pub fn new<O>(
+        operator_fn: impl Fn() -> O + Send,
+        state_fn: impl Fn() -> S + Send,
+        config: OperatorConfig,
+    ) -> Self
+    where
+        O: 'static + OneInTwoOut<S, T, U, V>,
+        S: State,
+    {
+        let mut ids = IdGenerator::new();
+        let (left_write_stream, left_output) = output_stream("left_output", &mut ids);
+        let (right_write_stream, right_output) = output_stream("right_output", &mut ids);
+        let processor = OneInTwoOutMessageProcessor::new(
+            prepare_config(config, &mut ids),
+            operator_fn,
+            state_fn,
+            left_write_stream,
+            right_write_stream,
+        );
+        Self {
+            inner: OneInHarness::new(Box::new(processor), &mut ids),
+            left_output,
+            right_output,
+        }
+    }
+
+    /// Instantiates a [`ParallelOneInTwoOut`] operator and invokes its `setup` method.
+    pub fn new_parallel<O, W>(
+        operator_fn: impl Fn() -> O + Send,
+        state_fn: impl Fn() -> S + Send,
+        config: OperatorConfig,
+    ) -> Self
+    where
+        O: 'static + ParallelOneInTwoOut<S, T, U, V, W>,
+        S: AppendableState<W>,
+        W: 'static + Send + Sync,
+    {
+        let mut ids = IdGenerator::new();
+        let (left_write_stream, left_output) = output_stream("left_output", &mut ids);
+        let (right_write_stream, right_output) = output_stream("right_output", &mut ids);
+        let processor = ParallelOneInTwoOutMessageProcessor::new(
+            prepare_config(config, &mut ids),
+            operator_fn,
+            state_fn,
+            left_write_stream,
+            right_write_stream,
+        );
+        Self {
+            inner: OneInHarness::new(Box::new(processor), &mut ids),
+            left_output,
+            right_output,
+        }
+    }
+
+    /// Sends a message on the read stream. The message is processed by
+    /// [`OneInTwoOutHarness::run`] or [`OneInTwoOutHarness::process`].
+    pub fn send(&mut self, msg: Message<T>) {
+        self.inner.send(msg)
+    }
+
+    /// Invokes the operator's `run` method.
+    pub fn run(&mut self) {
+        self.inner.run()
+    }
+
+    /// Invokes the callbacks for all messages sent on the read stream.
+    pub fn process(&mut self) {
+        self.inner.process()
+    }
+
+    /// Advances the virtual clock, and invokes the handlers of deadlines which were missed.
+    pub fn advance_time(&mut self, duration: Duration) {
+        self.inner.advance_time(duration)
+    }
+
+    /// Returns the IDs and timestamps of the deadlines whose handlers were invoked.
+    pub fn missed_deadlines(&self) -> &[(DeadlineId, Timestamp)] {
+        &self.inner.deadlines.missed
+    }
+
+    /// Invokes the operator's `destroy` method, and closes the write streams.
+    pub fn destroy(&mut self) {
+        self.inner.destroy()
+    }
+
+    /// Returns the messages sent on the left write stream since the last call.
+    pub fn left_output(&mut self) -> Vec<Message<U>> {
+        collect_output(&mut self.left_output)
+    }
+
+    /// Returns the messages sent on the right write stream since the last call.
+    pub fn right_output(&mut self) -> Vec<Message<V>> {
+        collect_output(&mut self.right_output)
+    }
+}
+
+/*************************************************************************************************
+ * TwoInOneOut                                                                                   *
+ ************************************************************************************************/
+
+/// Tests a [`TwoInOneOut`] or [`ParallelTwoInOneOut`] operator.
+///
+/// Messages are processed in the order in which they were sent across both read streams.
+pub struct TwoInOneOutHarness<S, T, U, V>
+where
+    for<'a> T: Data + Deserialize<'a>,
+    for<'a> U: Data + Deserialize<'a>,
+    for<'a> V: Data + Deserialize<'a>,
+{
+    inner: TwoInHarness<S, T, U>,
+    output: RecvEndpoint<Arc<Message<V>>>,
+}
+
+impl<S, T, U, V> TwoInOneOutHarness<S, T, U, V>
+where
+    for<'a> T: Data + Deserialize<'a>,
+    for<'a> U: Data + Deserialize<'a>,
+    for<'a> V: Data + Deserialize<'a>,
+{
+    /// Instantiates a [`TwoInOneOut`] operator and invokes its `setup` method.
+    pub fn new<O>(
+        operator_fn: impl Fn() -> O + Send,
+        state_fn: impl Fn() -> S + Send,
+        config: OperatorConfig,
+    ) -> Self
+    where
+        O: 'static + TwoInOneOut<S, T, U, V>,
+        S: State,
+    {
+        let mut ids = IdGenerator::new();
+        let (write_stream, output) = output_stream("output", &mut ids);
+        let processor = TwoInOneOutMessageProcessor::new(
+            prepare_config(config, &mut ids),
+            operator_fn,
+            state_fn,
+            write_stream,
+        );
+        Self {
+            inner: TwoInHarness::new(Box::new(processor), &mut ids),
+            output,
+        }
+    }
+
+    /// Instantiates a [`ParallelTwoInOneOut`] operator and invokes its `setup` method.
+    pub fn new_parallel<O, W>(
+        operator_fn: impl Fn() -> O + Send,
+        state_fn: impl Fn() -> S + Send,
+        config: OperatorConfig,
+    ) -> Self
+    where
+        O: 'static + ParallelTwoInOneOut<S, T, U, V, W>,
+        S: AppendableState<W>,
+        W: 'static + Send + Sync,
+    {
+        let mut ids = IdGenerator::new();
+        let (write_stream, output) = output_stream("output", &mut ids);
+        let processor = ParallelTwoInOneOutMessageProcessor::new(
+            prepare_config(config, &mut ids),
+            operator_fn,
+            state_fn,
+            write_stream,
+        );
+        Self {
+            inner: TwoInHarness::new(Box::new(processor), &mut ids),
+            output,
+        }
+    }
+
+    /// Sends a message on the left read stream. The message is processed by
+    /// [`TwoInOneOutHarness::run`] or [`TwoInOneOutHarness::process`].
+    pub fn send_left(&mut self, msg: Message<T>) {
+        self.inner.send_left(msg)
+    }
+
+    /// Sends a message on the right read stream. The message is processed by
+    /// [`TwoInOneOutHarness::run`] or [`TwoInOneOutHarness::process`].
+    pub fn send_right(&mut self, msg: Message<U>) {
+        self.inner.send_right(msg)
+    }
+
+    /// Invokes the operator's `run` method.
+    pub fn run(&mut self) {
+        self.inner.run()
+    }
+
+    /// Invokes the callbacks for all messages sent on the read streams.
+    pub fn process(&mut self) {
+        self.inner.process()
+    }
+
+    /// Advances the virtual clock, and invokes the handlers of deadlines which were missed.
+    pub fn advance_time(&mut self, duration: Duration) {
+        self.inner.advance_time(duration)
+    }
+
+    /// Returns the IDs and timestamps of the deadlines whose handlers were invoked.
+    pub fn missed_deadlines(&self) -> &[(DeadlineId, Timestamp)] {
+        &self.inner.deadlines.missed
+    }
+
+    /// Invokes the operator's `destroy` method, and closes the write stream.
+    pub fn destroy(&mut self) {
+        self.inner.destroy()
+    }
+
+    /// Returns the messages sent on the write stream since the last call.
+    pub fn output(&mut self) -> Vec<Message<V>> {
+        collect_output(&mut self.output)
+    }
+}
+
+/*************************************************************************************************
+ * LocalCluster                                                                                  *
+ ************************************************************************************************/
+
+/// Runs several [`Node`]s in the current process, which listen on loopback ports.
+///
+/// Each node executes its own copy of the dataflow graph built by the driver, and operators are
+/// placed on nodes according to [`OperatorConfig::node`] as they are when running on separate
+/// machines. The driver's [`IngestStream`](crate::dataflow::stream::IngestStream)s and
+/// [`ExtractStream`](crate::dataflow::stream::ExtractStream)s are set up on node 0.
+///
+/// # Example
+/// ```no_run
+/// # use erdos::testing::LocalCluster;
+/// #
+/// // Connect operators, placing some of them on node 1 with `OperatorConfig::node(1)`.
+/// let handle = LocalCluster::new(2).run_async();
+/// // Send data on ingest streams and read data from extract streams.
+/// handle.shutdown().unwrap();
+/// ```
+pub struct LocalCluster {
+    configs: Vec<Configuration>,
+}
+
+impl LocalCluster {
+    /// Creates the configurations for `num_nodes` nodes which listen on free loopback ports.
+    pub fn new(num_nodes: usize) -> Self {
+        let data_addresses = free_addresses(num_nodes);
+        let control_addresses = free_addresses(num_nodes);
+        let configs = (0..num_nodes)
+            .map(|index| {
+                Configuration::new(index, data_addresses.clone(), control_addresses.clone(), 4)
+            })
+            .collect();
+        Self { configs }
+    }
+
+    /// Modifies the configuration of each node, e.g. to set the number of threads or a
+    /// [`VirtualClock`](crate::node::VirtualClock).
+    pub fn configure(mut self, f: impl Fn(Configuration) -> Configuration) -> Self {
+        self.configs = self.configs.into_iter().map(f).collect();
+        self
+    }
+
+    /// Returns the configuration of each node.
+    pub fn configs(&self) -> &[Configuration] {
+        &self.configs
+    }
+
+    /// Runs all nodes in separate OS threads.
+    ///
+    /// The method returns once all nodes are initialized.
+    pub fn run_async(self) -> LocalClusterHandle {
+        let handles: Vec<NodeHandle> = self
+            .configs
+            .into_iter()
+            .map(|config| Node::new(config).spawn())
+            .collect();
+        for handle in handles.iter() {
+            handle.wait_until_initialized();
+        }
+        LocalClusterHandle { handles }
+    }
+}
+
+/// Returns loopback addresses on ports which are currently free.
+fn free_addresses(num_addresses: usize) -> Vec<SocketAddr> {
+    // Keep the listeners open until all ports are chosen to avoid duplicates.
+    let listeners: Vec<TcpListener> = (0..num_addresses)
+        .map(|_| TcpListener::bind("127.0.0.1:0").expect("Unable to find a free port"))
+        .collect();
+    listeners
+        .iter()
+        .map(|listener| listener.local_addr().unwrap())
+        .collect()
+}
+
+/// Handle to a [`LocalCluster`] running asynchronously.
+pub struct LocalClusterHandle {
+    handles: Vec<NodeHandle>,
+}
+
+impl LocalClusterHandle {
+    /// Returns the handle to the node with the given ID.
+    pub fn node(&self, node_id: NodeId) -> &NodeHandle {
+        &self.handles[node_id]
+    }
+
+    /// Waits for all nodes to finish.
+    ///
+    /// Returns the first error if any node failed.
+    pub fn join(self) -> Result<(), String> {
+        let mut result = Ok(());
+        for handle in self.handles {
+            let node_result = handle.join();
+            if result.is_ok() {
+                result = node_result;
+            }
+        }
+        result
+    }
+
+    /// Drains all nodes (see [`NodeHandle::drain`]), and blocks until they finish.
+    pub fn drain(self, timeout: Duration) -> Result<(), String> {
+        for handle in self.handles.iter() {
+            handle.start_drain(timeout);
+        }
+        self.join()
+    }
+
+    /// Shuts down all nodes, and blocks until they finish.
+    pub fn shutdown(self) -> Result<(), String> {
+        for handle in self.handles.iter() {
+            handle.start_shutdown();
+        }
+        self.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataflow::{context::SinkContext, Graph};
+
+    struct TestSink;
+
+    impl Sink<(), usize> for TestSink {
+        fn on_data(&mut self, _ctx: &mut SinkContext<()>, _data: &usize) {}
+
+        fn on_watermark(&mut self, _ctx: &mut SinkContext<()>) {}
+    }
+
+    #[test]
+    fn test_advancing_virtual_clock_does_not_fail_nodes() {
+        let clock = VirtualClock::new();
+        // The sink keeps node 1 running until the ingest stream on node 0 closes.
+        let graph = Graph::new();
+        let ingest_stream = graph.ingest_stream::<usize>();
+        graph.connect_sink(
+            || TestSink,
+            || (),
+            OperatorConfig::new().node(1),
+            &ingest_stream,
+        );
+        let cluster = LocalCluster::new(2).configure(|config| {
+            config
+                .with_heartbeat_interval(Duration::from_millis(10))
+                .with_heartbeat_timeout(Duration::from_millis(100))
+                .with_virtual_clock(clock.clone())
+                .disable_logger()
+        });
+        let handles: Vec<NodeHandle> = cluster
+            .configs
+            .into_iter()
+            .map(|config| Node::with_graph(config, graph.clone()).spawn())
+            .collect();
+        for handle in handles.iter() {
+            handle.wait_until_initialized();
+        }
+
+        clock.advance(Duration::from_secs(3600));
+        for handle in handles.iter() {
+            assert_eq!(handle.recv_event_timeout(Duration::from_millis(300)), None);
+        }
+        LocalClusterHandle { handles }.shutdown().unwrap();
+    }
+}
@@ -1,13 +1,13 @@
 #![feature(get_mut_unchecked)]
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use pyo3::{exceptions, prelude::*};
 
 use erdos::{
     dataflow::OperatorConfig,
     node::{Node, NodeHandle, NodeId},
-    Configuration, OperatorId,
+    Configuration,
 };
 
 // Private submodules
@@ -49,18 +49,16 @@ fn internal(_py: Python, m: &PyModule) -> PyResult<()> {
     ) -> PyResult<Py<PyOperatorStream>> {
         // Create the config.
         let operator_name: Option<String> = py_config.getattr(py, "name")?.extract(py)?;
-        let name = match &operator_name {
-            Some(n) => n.clone(),
-            None => String::from("SourceOperator"),
-        };
         let flow_watermarks: bool = py_config.getattr(py, "flow_watermarks")?.extract(py)?;
         let mut config = OperatorConfig::new()
-            .name(&name)
             .node(node_id)
             .flow_watermarks(flow_watermarks);
-        config.id = OperatorId::new_deterministic();
-        tracing::debug!("Assigning ID {} to {}.", config.id, name,);
-        let config_copy = config.clone();
+        if let Some(name) = &operator_name {
+            config = config.name(name);
+        }
+        // The graph assigns the operator's ID when the operator is connected.
+        let operator_config: Arc<Mutex<Option<OperatorConfig>>> = Arc::new(Mutex::new(None));
+        let operator_config_copy = Arc::clone(&operator_config);
 
         // Arc objects to pass to the executor.
         let py_type_arc = Arc::new(py_type);
@@ -75,11 +73,14 @@ fn internal(_py: Python, m: &PyModule) -> PyResult<()> {
                     Arc::clone(&args_arc),
                     Arc::clone(&kwargs_arc),
                     Arc::clone(&py_config_arc),
-                    config_copy.clone(),
+                    operator_config_copy.lock().unwrap().clone().unwrap(),
                 )
             },
             config,
         );
+        let config = erdos::last_operator_config().unwrap();
+        tracing::debug!("Assigning ID {} to {}.", config.id, config.get_name());
+        operator_config.lock().unwrap().replace(config);
 
         PyOperatorStream::new(py, write_stream)
     }
@@ -97,18 +98,16 @@ fn internal(_py: Python, m: &PyModule) -> PyResult<()> {
     ) -> PyResult<()> {
         // Create the config.
         let operator_name: Option<String> = py_config.getattr(py, "name")?.extract(py)?;
-        let name = match &operator_name {
-            Some(n) => n.clone(),
-            None => String::from("SinkOperator"),
-        };
         let flow_watermarks: bool = py_config.getattr(py, "flow_watermarks")?.extract(py)?;
         let mut config = OperatorConfig::new()
-            .name(&name)
             .node(node_id)
             .flow_watermarks(flow_watermarks);
-        config.id = OperatorId::new_deterministic();
-        tracing::debug!("Assigning ID {} to {}.", config.id, name,);
-        let config_copy = config.clone();
+        if let Some(name) = &operator_name {
+            config = config.name(name);
+        }
+        // The graph assigns the operator's ID when the operator is connected.
+        let operator_config: Arc<Mutex<Option<OperatorConfig>>> = Arc::new(Mutex::new(None));
+        let operator_config_copy = Arc::clone(&operator_config);
 
         // Arc objects to pass to the constructor.
         let py_type_arc = Arc::new(py_type);
@@ -123,13 +122,16 @@ fn internal(_py: Python, m: &PyModule) -> PyResult<()> {
                     Arc::clone(&args_arc),
                     Arc::clone(&kwargs_arc),
                     Arc::clone(&py_config_arc),
-                    config_copy.clone(),
+                    operator_config_copy.lock().unwrap().clone().unwrap(),
                 )
             },
             || {},
             config,
             read_stream,
         );
+        let config = erdos::last_operator_config().unwrap();
+        tracing::debug!("Assigning ID {} to {}.", config.id, config.get_name());
+        operator_config.lock().unwrap().replace(config);
         Ok(())
     }
 
@@ -146,18 +148,16 @@ fn internal(_py: Python, m: &PyModule) -> PyResult<()> {
     ) -> PyResult<Py<PyOperatorStream>> {
         // Create the config.
         let operator_name: Option<String> = py_config.getattr(py, "name")?.extract(py)?;
-        let name = match &operator_name {
-            Some(n) => n.clone(),
-            None => String::from("OneInOneOut"),
-        };
         let flow_watermarks: bool = py_config.getattr(py, "flow_watermarks")?.extract(py)?;
         let mut config = OperatorConfig::new()
-            .name(&name)
             .node(node_id)
             .flow_watermarks(flow_watermarks);
-        config.id = OperatorId::new_deterministic();
-        tracing::debug!("Assigning ID {} to {}.", config.id, name,);
-        let config_copy = config.clone();
+        if let Some(name) = &operator_name {
+            config = config.name(name);
+        }
+        // The graph assigns the operator's ID when the operator is connected.
+        let operator_config: Arc<Mutex<Option<OperatorConfig>>> = Arc::new(Mutex::new(None));
+        let operator_config_copy = Arc::clone(&operator_config);
 
         // Arc objects to pass to the executor.
         let py_type_arc = Arc::new(py_type);
@@ -172,13 +172,16 @@ fn internal(_py: Python, m: &PyModule) -> PyResult<()> {
                     Arc::clone(&args_arc),
                     Arc::clone(&kwargs_arc),
                     Arc::clone(&py_config_arc),
-                    config_copy.clone(),
+                    operator_config_copy.lock().unwrap().clone().unwrap(),
                 )
             },
             || {},
             config,
             read_stream,
         );
+        let config = erdos::last_operator_config().unwrap();
+        tracing::debug!("Assigning ID {} to {}.", config.id, config.get_name());
+        operator_config.lock().unwrap().replace(config);
 
         PyOperatorStream::new(py, write_stream)
     }
@@ -196,18 +199,16 @@ fn internal(_py: Python, m: &PyModule) -> PyResult<()> {
     ) -> PyResult<(Py<PyOperatorStream>, Py<PyOperatorStream>)> {
         // Create the config.
         let operator_name: Option<String> = py_config.getattr(py, "name")?.extract(py)?;
-        let name = match &operator_name {
-            Some(n) => n.clone(),
-            None => String::from("OneInTwoOut"),
-        };
         let flow_watermarks: bool = py_config.getattr(py, "flow_watermarks")?.extract(py)?;
         let mut config = OperatorConfig::new()
-            .name(&name)
             .node(node_id)
             .flow_watermarks(flow_watermarks);
-        config.id = OperatorId::new_deterministic();
-        tracing::debug!("Assigning ID {} to {}", config.id, name,);
-        let config_copy = config.clone();
+        if let Some(name) = &operator_name {
+            config = config.name(name);
+        }
+        // The graph assigns the operator's ID when the operator is connected.
+        let operator_config: Arc<Mutex<Option<OperatorConfig>>> = Arc::new(Mutex::new(None));
+        let operator_config_copy = Arc::clone(&operator_config);
 
         // Arc objects to pass to the executor.
         let py_type_arc = Arc::new(py_type);
@@ -222,13 +223,16 @@ fn internal(_py: Python, m: &PyModule) -> PyResult<()> {
                     Arc::clone(&args_arc),
                     Arc::clone(&kwargs_arc),
                     Arc::clone(&py_config_arc),
-                    config_copy.clone(),
+                    operator_config_copy.lock().unwrap().clone().unwrap(),
                 )
             },
             || {},
             config,
             read_stream,
         );
+        let config = erdos::last_operator_config().unwrap();
+        tracing::debug!("Assigning ID {} to {}.", config.id, config.get_name());
+        operator_config.lock().unwrap().replace(config);
 
         let py_left_write_stream = PyOperatorStream::new(py, left_write_stream)?;
         let py_right_write_stream = PyOperatorStream::new(py, right_write_stream)?;
@@ -251,18 +255,16 @@ fn internal(_py: Python, m: &PyModule) -> PyResult<()> {
     ) -> PyResult<Py<PyOperatorStream>> {
         // Create the config.
         let operator_name: Option<String> = py_config.getattr(py, "name")?.extract(py)?;
-        let name = match &operator_name {
-            Some(n) => n.clone(),
-            None => String::from("OneInOneOut"),
-        };
         let flow_watermarks: bool = py_config.getattr(py, "flow_watermarks")?.extract(py)?;
         let mut config = OperatorConfig::new()
-            .name(&name)
             .node(node_id)
             .flow_watermarks(flow_watermarks);
-        config.id = OperatorId::new_deterministic();
-        tracing::debug!("Assigning ID {} to {}.", config.id, name,);
-        let config_copy = config.clone();
+        if let Some(name) = &operator_name {
+            config = config.name(name);
+        }
+        // The graph assigns the operator's ID when the operator is connected.
+        let operator_config: Arc<Mutex<Option<OperatorConfig>>> = Arc::new(Mutex::new(None));
+        let operator_config_copy = Arc::clone(&operator_config);
 
         // Arc objects to pass to the executor.
         let py_type_arc = Arc::new(py_type);
@@ -277,7 +279,7 @@ fn internal(_py: Python, m: &PyModule) -> PyResult<()> {
                     Arc::clone(&args_arc),
                     Arc::clone(&kwargs_arc),
                     Arc::clone(&py_config_arc),
-                    config_copy.clone(),
+                    operator_config_copy.lock().unwrap().clone().unwrap(),
                 )
             },
             || {},
@@ -285,6 +287,9 @@ fn internal(_py: Python, m: &PyModule) -> PyResult<()> {
             left_read_stream,
             right_read_stream,
         );
+        let config = erdos::last_operator_config().unwrap();
+        tracing::debug!("Assigning ID {} to {}.", config.id, config.get_name());
+        operator_config.lock().unwrap().replace(config);
 
         PyOperatorStream::new(py, write_stream)
     }
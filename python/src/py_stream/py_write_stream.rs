@@ -11,6 +11,7 @@ create_exception!(SendError, TimestampError, exceptions::PyException);
 create_exception!(SendError, ClosedError, exceptions::PyException);
 create_exception!(SendError, IOError, exceptions::PyException);
 create_exception!(SendError, SerializationError, exceptions::PyException);
+create_exception!(SendError, CompressionError, exceptions::PyException);
 
 /// The internal Python abstraction over a `WriteStream`.
 ///
@@ -42,6 +43,7 @@ impl PyWriteStream {
                 SendError::Closed => ClosedError::new_err(error_str),
                 SendError::IOError => IOError::new_err(error_str),
                 SendError::SerializationError => SerializationError::new_err(error_str),
+                SendError::CompressionError => CompressionError::new_err(error_str),
             }
         })
     }